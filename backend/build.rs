@@ -0,0 +1,38 @@
+//! Compiles build details into the crate for `version::build_info`: the
+//! git commit, the target triple and the resolved pyo3 version.
+
+use std::path::Path;
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !text.trim().is_empty()).then(|| text.trim().to_string())
+}
+
+/// The pyo3 version Cargo.lock resolved, if the lock file is next to the manifest
+fn locked_version(manifest_dir: &str, package: &str) -> Option<String> {
+    let lock = std::fs::read_to_string(Path::new(manifest_dir).join("Cargo.lock")).ok()?;
+    let header = format!("name = \"{}\"\n", package);
+    let rest = &lock[lock.find(&header)? + header.len()..];
+    let version = rest.strip_prefix("version = \"")?;
+    Some(version[..version.find('"')?].to_string())
+}
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    // Rebuild when HEAD moves, whether by checkout or by commit
+    let branch = git(&["symbolic-ref", "-q", "HEAD"]).and_then(|reference| git(&["rev-parse", "--git-path", &reference]));
+    for path in [git(&["rev-parse", "--git-path", "HEAD"]), branch].into_iter().flatten() {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let git_hash = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    let pyo3_version = locked_version(&manifest_dir, "pyo3").unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MEMORIES_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=MEMORIES_BUILD_TARGET={}", target);
+    println!("cargo:rustc-env=MEMORIES_PYO3_VERSION={}", pyo3_version);
+}
@@ -0,0 +1,63 @@
+#!/usr/bin/env python
+"""
+Build the English reference Bloom filter used by detect_inside_jokes.
+
+Usage: build_english_reference.py OUTPUT WORDLIST [WORDLIST ...]
+
+Each word list holds one word per line. The shipped filter was built from the
+English Wikipedia and US TV/film subtitle frequency lists distributed with
+zxcvbn (MIT licensed), about 49,000 words.
+
+The layout and hashing must match src/core/parsing/reference.rs: the magic
+b"BLM1", the hash count (u8), the bit count (u32, little-endian), then the
+bits, least significant bit first. Each word is lowercased and hashed with
+64-bit FNV-1a; probe i sets bit (h1 + i * h2) mod m, where h2 is
+splitmix64(h1) with the low bit set.
+"""
+import math
+import struct
+import sys
+
+MASK = (1 << 64) - 1
+FALSE_POSITIVE_RATE = 0.01
+
+
+def fnv1a(data):
+    value = 0xCBF29CE484222325
+    for byte in data:
+        value = ((value ^ byte) * 0x100000001B3) & MASK
+    return value
+
+
+def splitmix64(value):
+    value = (value + 0x9E3779B97F4A7C15) & MASK
+    value = ((value ^ (value >> 30)) * 0xBF58476D1CE4E5B9) & MASK
+    value = ((value ^ (value >> 27)) * 0x94D049BB133111EB) & MASK
+    return value ^ (value >> 31)
+
+
+def main():
+    if len(sys.argv) < 3:
+        sys.exit(__doc__)
+    words = set()
+    for path in sys.argv[2:]:
+        with open(path, encoding="utf-8") as f:
+            words.update(line.strip().lower() for line in f if line.strip())
+
+    bits = math.ceil(-len(words) * math.log(FALSE_POSITIVE_RATE) / math.log(2) ** 2)
+    hashes = max(1, round(bits / len(words) * math.log(2)))
+    table = bytearray((bits + 7) // 8)
+    for word in words:
+        h1 = fnv1a(word.encode("utf-8"))
+        h2 = splitmix64(h1) | 1
+        for i in range(hashes):
+            bit = ((h1 + i * h2) & MASK) % bits
+            table[bit // 8] |= 1 << (bit % 8)
+
+    with open(sys.argv[1], "wb") as f:
+        f.write(b"BLM1" + struct.pack("<BI", hashes, bits) + bytes(table))
+    print(f"{len(words)} words, {bits} bits, {hashes} hashes")
+
+
+if __name__ == "__main__":
+    main()
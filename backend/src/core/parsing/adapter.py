@@ -66,8 +66,10 @@ def parse_with_python(file_path: str, user_identity: str) -> List[Message]:
     """
     Python fallback implementation for parsing WhatsApp chat exports.
     """
-    # Regex for WhatsApp timestamp and sender
-    pattern = r'^\[(\d{2}/\d{2}/\d{4}, \d{2}:\d{2}:\d{2})\] ([^:]+): (.+)$'
+    # Regex for WhatsApp timestamp and sender. Seconds are optional. Content
+    # may be empty ("Alice: "), in which case the line is kept as an empty
+    # text message, matching the Rust parser.
+    pattern = r'^\[(\d{2}/\d{2}/\d{4}, \d{2}:\d{2}(?::\d{2})?)\] ([^:]+):(?: (.*))?$'
     regex = re.compile(pattern)
     
     messages = []
@@ -86,6 +88,7 @@ def parse_with_python(file_path: str, user_identity: str) -> List[Message]:
                     
                     # Extract data from the new message
                     timestamp_str, sender, content = match.groups()
+                    content = content or ""
                     timestamp = parse_whatsapp_timestamp(timestamp_str)
                     
                     # Start building a new message
@@ -112,8 +115,11 @@ def parse_with_python(file_path: str, user_identity: str) -> List[Message]:
 
 def parse_whatsapp_timestamp(timestamp_str: str) -> datetime:
     """
-    Parse WhatsApp timestamp in format "DD/MM/YYYY, HH:MM:SS"
+    Parse WhatsApp timestamp in format "DD/MM/YYYY, HH:MM:SS". Timestamps
+    without seconds ("DD/MM/YYYY, HH:MM") are read as HH:MM:00.
     """
+    if timestamp_str.count(":") == 1:
+        timestamp_str += ":00"
     try:
         return datetime.strptime(timestamp_str, "%d/%m/%Y, %H:%M:%S")
     except ValueError as e:
@@ -0,0 +1,1729 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, Timelike};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::chat_parser::ChatParser;
+use crate::error::ParserError;
+use crate::events::{DateReferenceResolver, DetectedEvent};
+use crate::message::{Message, MessageType};
+use crate::reference::is_english_word;
+use crate::text::{
+    contains_laughter, extract_emojis, fold_case, is_emoji, looks_like_code, sender_key, STOP_WORDS, tokenize_words, tokenize_words_with, CHAT_ABBREVIATIONS,
+};
+
+/// Whether any message carries a synthetic (made-up) timestamp
+pub fn has_synthetic_timestamps(messages: &[Message]) -> bool {
+    messages.iter().any(|message| message.timestamp_synthetic)
+}
+
+/// Time-based stats refuse synthetic timestamps rather than report fiction
+pub fn ensure_real_timestamps(messages: &[Message]) -> Result<(), ParserError> {
+    if has_synthetic_timestamps(messages) {
+        return Err(ParserError::SyntheticTimestamps);
+    }
+    Ok(())
+}
+
+/// Chat analytics, exposed to Python as static methods on `ChatParser`
+impl ChatParser {
+    /// Total messages per ISO weekday, indexed 0=Monday … 6=Sunday (local time)
+    pub fn count_messages_by_weekday(messages: &[Message]) -> [usize; 7] {
+        let mut counts = [0usize; 7];
+        for message in messages {
+            counts[message.timestamp.weekday().num_days_from_monday() as usize] += 1;
+        }
+        counts
+    }
+
+    /// Weekday index with the highest message count; ties go to the earlier day
+    pub fn busiest_weekday(messages: &[Message]) -> u32 {
+        let counts = Self::count_messages_by_weekday(messages);
+        let mut busiest = 0;
+        for (day, &count) in counts.iter().enumerate() {
+            if count > counts[busiest] {
+                busiest = day;
+            }
+        }
+        busiest as u32
+    }
+
+    /// Weekday-by-hour activity grid (rows Monday … Sunday, columns 0–23,
+    /// local time), the data behind a GitHub-style heatmap
+    pub fn heatmap(messages: &[Message]) -> [[u32; 24]; 7] {
+        let mut grid = [[0u32; 24]; 7];
+        for message in messages {
+            let day = message.timestamp.weekday().num_days_from_monday() as usize;
+            grid[day][message.timestamp.hour() as usize] += 1;
+        }
+        grid
+    }
+
+    /// Mean sentiment of the scored text messages in each hour of day (local
+    /// time), the emotional counterpart to `heatmap`; hours without a scored
+    /// message are 0.0. Needs sentiment scores to be populated.
+    pub fn compute_sentiment_by_hour(messages: &[Message]) -> [f32; 24] {
+        let mut sums = [0f32; 24];
+        let mut counts = [0u32; 24];
+        for message in messages.iter().filter(|message| message.message_type == MessageType::Text) {
+            if let Some(score) = message.sentiment_score {
+                let hour = message.timestamp.hour() as usize;
+                sums[hour] += score;
+                counts[hour] += 1;
+            }
+        }
+        let mut averages = [0f32; 24];
+        for hour in 0..24 {
+            if counts[hour] > 0 {
+                averages[hour] = sums[hour] / counts[hour] as f32;
+            }
+        }
+        averages
+    }
+
+    /// Messages sent between `hour_start` and `hour_end` (local time, wrapping
+    /// past midnight when start > end) whose sentiment is more extreme than
+    /// ±`sentiment_threshold`. Needs sentiment scores to be populated.
+    pub fn detect_late_night_confessions(
+        messages: &[Message],
+        hour_start: u32,
+        hour_end: u32,
+        sentiment_threshold: f32,
+    ) -> Vec<&Message> {
+        messages
+            .iter()
+            .filter(|message| {
+                let hour = message.timestamp.hour();
+                if hour_start <= hour_end {
+                    hour >= hour_start && hour < hour_end
+                } else {
+                    hour >= hour_start || hour < hour_end
+                }
+            })
+            .filter(|message| {
+                message
+                    .sentiment_score
+                    .is_some_and(|score| score > sentiment_threshold || score < -sentiment_threshold)
+            })
+            .collect()
+    }
+
+    /// Messages containing a question mark (`?`, or the full-width and Arabic
+    /// forms) that no other sender replied to within `timeout_hours`.
+    /// Messages must be in time order; a question with nothing after it
+    /// counts as unanswered. System events are neither questions nor replies.
+    pub fn detect_unanswered_questions(messages: &[Message], timeout_hours: f64) -> Vec<&Message> {
+        let timeout = Duration::milliseconds((timeout_hours.max(0.0) * 3_600_000.0) as i64);
+        let is_event = |message: &Message| matches!(message.message_type, MessageType::SystemEvent(_));
+        messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| !is_event(message) && message.content.contains(['?', '？', '؟']))
+            .filter(|(index, question)| {
+                let deadline = question.timestamp + timeout;
+                !messages[index + 1..]
+                    .iter()
+                    .take_while(|message| message.timestamp <= deadline)
+                    .any(|message| !is_event(message) && message.sender != question.sender)
+            })
+            .map(|(_, question)| question)
+            .collect()
+    }
+
+    /// Messages sent within `window_days` of an anniversary of the chat's
+    /// first message (one year on, two years on, ...), for "this day X
+    /// years ago". A first message on 29 February has its anniversaries on
+    /// 28 February in other years. System events are ignored.
+    pub fn find_anniversary_messages(messages: &[Message], window_days: u32) -> Vec<&Message> {
+        let is_event = |message: &Message| matches!(message.message_type, MessageType::SystemEvent(_));
+        let Some(first) = messages.iter().filter(|message| !is_event(message)).map(|message| message.timestamp).min()
+        else {
+            return Vec::new();
+        };
+        let first = first.date_naive();
+        let anniversary = |year: i32| {
+            first.with_year(year).or_else(|| NaiveDate::from_ymd_opt(year, first.month(), first.day() - 1))
+        };
+        messages
+            .iter()
+            .filter(|message| !is_event(message))
+            .filter(|message| {
+                let date = message.timestamp.date_naive();
+                // The window may reach into the neighbouring year
+                (date.year() - 1..=date.year() + 1)
+                    .filter(|year| *year > first.year())
+                    .filter_map(anniversary)
+                    .any(|anniversary| (date - anniversary).num_days().abs() <= i64::from(window_days))
+            })
+            .collect()
+    }
+
+    /// Text messages that look like they contain code: a line combining at
+    /// least two of `{`, `}` and `;`, backtick-delimited code, or a line
+    /// indented by four or more spaces (or a tab)
+    pub fn detect_code_snippets(messages: &[Message]) -> Vec<&Message> {
+        messages
+            .iter()
+            .filter(|message| matches!(message.message_type, MessageType::Text))
+            .filter(|message| looks_like_code(&message.content))
+            .collect()
+    }
+
+    /// Indices of messages whose content contains `query`. Case-insensitive
+    /// matching lowercases both sides (full Unicode lowercasing).
+    pub fn find_messages_by_content(messages: &[Message], query: &str, case_sensitive: bool) -> Vec<usize> {
+        if case_sensitive {
+            let finder = memchr::memmem::Finder::new(query.as_bytes());
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| finder.find(message.content.as_bytes()).is_some())
+                .map(|(index, _)| index)
+                .collect()
+        } else {
+            let query = query.to_lowercase();
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| message.content.to_lowercase().contains(&query))
+                .map(|(index, _)| index)
+                .collect()
+        }
+    }
+
+    /// The text message with the most characters, the earliest on ties
+    pub fn longest_message(messages: &[Message]) -> Option<&Message> {
+        messages
+            .iter()
+            .filter(|message| matches!(message.message_type, MessageType::Text))
+            .map(|message| (message.content.chars().count(), message))
+            .reduce(|longest, candidate| if candidate.0 > longest.0 { candidate } else { longest })
+            .map(|(_, message)| message)
+    }
+
+    /// Distinct senders with their message counts, most active first (ties by
+    /// name). System messages are not counted.
+    pub fn sender_counts(messages: &[Message]) -> Vec<(String, u32)> {
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+                *counts.entry(message.sender.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut counts: Vec<(String, u32)> = counts
+            .into_iter()
+            .map(|(sender, count)| (sender.to_string(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Each sender's longest word, as written, measured in grapheme clusters
+    /// so combining marks and emoji sequences count once. Links and tokens
+    /// without a letter or digit are skipped; the earliest word wins ties.
+    pub fn compute_longest_word(messages: &[Message]) -> HashMap<String, String> {
+        let mut longest: HashMap<String, (usize, &str)> = HashMap::new();
+        for message in messages.iter().filter(|message| has_words(message)) {
+            for word in tokenize_words_with(&message.content, true) {
+                let word = word.trim_matches('\'');
+                if !word.chars().any(char::is_alphanumeric) {
+                    continue;
+                }
+                let length = word.graphemes(true).count();
+                let entry = longest.entry(message.sender.clone()).or_insert((0, ""));
+                if length > entry.0 {
+                    *entry = (length, word);
+                }
+            }
+        }
+        longest.into_iter().map(|(sender, (_, word))| (sender, word.to_string())).collect()
+    }
+
+    /// How many text messages each sender ended with a question mark (`?`,
+    /// or the full-width and Arabic forms), ignoring trailing whitespace,
+    /// emoji and `!` ("really?? 😅", "what?!"). A question mark earlier in
+    /// the message doesn't count, and a message counts once however many it
+    /// has. Every sender of a text message is listed, with 0 if they never
+    /// asked.
+    pub fn questions_asked(messages: &[Message]) -> HashMap<String, u32> {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for message in messages.iter().filter(|message| has_words(message)) {
+            *counts.entry(message.sender.clone()).or_insert(0) += u32::from(ends_with_question(&message.content));
+        }
+        counts
+    }
+
+    /// Likely inside jokes: phrases of up to `max_word_count` words, used in
+    /// at least `min_occurrences` messages, that contain a word missing from
+    /// the bundled English vocabulary (see `reference`). Participants' names
+    /// and numbers don't count as unusual, phrases of several words can't
+    /// start or end with a stop word, and a phrase only ever seen inside a
+    /// longer one is folded into it. Counts are messages, most used first
+    /// with ties alphabetical.
+    pub fn detect_inside_jokes(messages: &[Message], min_occurrences: usize, max_word_count: usize) -> Vec<(String, usize)> {
+        let names: HashSet<String> = messages
+            .iter()
+            .flat_map(|message| tokenize_words(&message.sender.to_lowercase()).map(str::to_string).collect::<Vec<_>>())
+            .collect();
+        let unusual = |word: &str| {
+            !names.contains(word) && !word.chars().all(|c| c.is_numeric() || c == '\'') && !is_english_word(word)
+        };
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for message in messages.iter().filter(|message| has_words(message)) {
+            let lowered = message.content.to_lowercase();
+            let words: Vec<&str> = tokenize_words_with(&lowered, true).into_iter().map(|word| word.trim_matches('\'')).collect();
+            let mut phrases: HashSet<String> = HashSet::new();
+            for length in 1..=max_word_count.max(1) {
+                for window in words.windows(length) {
+                    let edges_are_content = length == 1
+                        || !(STOP_WORDS.contains(&window[0]) || STOP_WORDS.contains(&window[length - 1]));
+                    if edges_are_content && window.iter().any(|word| unusual(word)) {
+                        phrases.insert(window.join(" "));
+                    }
+                }
+            }
+            for phrase in phrases {
+                *counts.entry(phrase).or_insert(0) += 1;
+            }
+        }
+
+        let frequent: Vec<(String, usize)> = counts.into_iter().filter(|(_, count)| *count >= min_occurrences).collect();
+        let mut jokes: Vec<(String, usize)> = frequent
+            .iter()
+            .filter(|(phrase, count)| {
+                let inner = format!(" {} ", phrase);
+                !frequent.iter().any(|(longer, longer_count)| {
+                    longer_count == count && longer.len() > phrase.len() && format!(" {} ", longer).contains(&inner)
+                })
+            })
+            .cloned()
+            .collect();
+        jokes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        jokes
+    }
+
+    /// Word counts across the chat (lowercased), most frequent first and ties
+    /// alphabetical. With `exclude_urls`, links don't contribute words.
+    /// System events and media placeholders are not counted.
+    pub fn word_frequency(messages: &[Message], exclude_urls: bool) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for message in messages.iter().filter(|message| has_words(message)) {
+            let lowered = message.content.to_lowercase();
+            for word in tokenize_words_with(&lowered, exclude_urls) {
+                *counts.entry(word.to_string()).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Mean number of words per message for each sender. With
+    /// `exclude_urls`, a message that is only a link counts as zero words.
+    /// System events and media placeholders are not counted.
+    pub fn avg_message_length(messages: &[Message], exclude_urls: bool) -> HashMap<String, f64> {
+        let mut totals: HashMap<&str, (usize, usize)> = HashMap::new();
+        for message in messages.iter().filter(|message| has_words(message)) {
+            let entry = totals.entry(message.sender.as_str()).or_insert((0, 0));
+            entry.0 += tokenize_words_with(&message.content, exclude_urls).len();
+            entry.1 += 1;
+        }
+        totals
+            .into_iter()
+            .map(|(sender, (words, count))| (sender.to_string(), words as f64 / count as f64))
+            .collect()
+    }
+
+    /// Each sender's share of all messages, for a "who talks more" chart.
+    /// Shares sum to 1; system events are not counted.
+    pub fn balance(messages: &[Message]) -> HashMap<String, f64> {
+        let counts = Self::sender_counts(messages);
+        let total: u32 = counts.iter().map(|(_, count)| count).sum();
+        counts
+            .into_iter()
+            .map(|(sender, count)| (sender, count as f64 / total as f64))
+            .collect()
+    }
+
+    /// Number of chats each sender appears in, for finding someone's most
+    /// frequent contacts across several exports. System events don't count.
+    pub fn detect_recurring_senders(chats: &[Vec<Message>]) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for chat in chats {
+            let senders: HashSet<&str> = chat
+                .iter()
+                .filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_)))
+                .map(|message| message.sender.as_str())
+                .collect();
+            for sender in senders {
+                *counts.entry(sender.to_string()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Index of the earliest message from `sender`, compared trimmed and
+    /// case-insensitively with full case folding (bidi marks ignored). Ties
+    /// on the timestamp go to the earlier index.
+    pub fn find_first_occurrence_of_sender(messages: &[Message], sender: &str) -> Option<usize> {
+        let wanted = fold_case(&sender_key(sender));
+        if wanted.is_empty() {
+            return None;
+        }
+        messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| !matches!(message.message_type, MessageType::SystemEvent(_)))
+            .filter(|(_, message)| fold_case(&message.sender) == wanted)
+            .min_by_key(|(index, message)| (message.timestamp, *index))
+            .map(|(index, _)| index)
+    }
+
+    /// Partition messages into sessions wherever the silence between two
+    /// consecutive messages exceeds `gap_hours`
+    pub fn split_by_conversation_gap(messages: &[Message], gap_hours: f64) -> Vec<Vec<Message>> {
+        let gap = Duration::milliseconds((gap_hours * 3_600_000.0) as i64);
+        let mut sessions: Vec<Vec<Message>> = Vec::new();
+        for message in messages {
+            match sessions.last_mut() {
+                Some(session) if message.timestamp - session.last().unwrap().timestamp <= gap => {
+                    session.push(message.clone());
+                }
+                _ => sessions.push(vec![message.clone()]),
+            }
+        }
+        sessions
+    }
+
+    /// Messages grouped by sender, each group in the original order, for
+    /// per-person analysis or export. System events are dropped.
+    pub fn by_sender(messages: Vec<Message>) -> HashMap<String, Vec<Message>> {
+        let mut partitions: HashMap<String, Vec<Message>> = HashMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+                partitions.entry(message.sender.clone()).or_default().push(message);
+            }
+        }
+        partitions
+    }
+
+    /// Formality per sender in [0, 1]: the mean of the fractions of text
+    /// messages that start with a capital letter, end with `.`, `!` or `?`,
+    /// and avoid chat abbreviations (`CHAT_ABBREVIATIONS`)
+    pub fn compute_formality_score(messages: &[Message]) -> HashMap<String, f64> {
+        let mut totals: HashMap<&str, [usize; 4]> = HashMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::Text) {
+                continue;
+            }
+            let content = message.content.trim();
+            let entry = totals.entry(message.sender.as_str()).or_insert([0; 4]);
+            entry[0] += 1;
+            if content.chars().next().is_some_and(|c| c.is_uppercase()) {
+                entry[1] += 1;
+            }
+            if content.ends_with(['.', '!', '?']) {
+                entry[2] += 1;
+            }
+            let lowered = content.to_lowercase();
+            if !tokenize_words(&lowered).any(|word| CHAT_ABBREVIATIONS.contains(&word)) {
+                entry[3] += 1;
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, [count, capitalized, punctuated, unabbreviated])| {
+                let count = count as f64;
+                let score = (capitalized as f64 / count + punctuated as f64 / count + unabbreviated as f64 / count) / 3.0;
+                (sender.to_string(), score)
+            })
+            .collect()
+    }
+
+    /// Mean length in characters of each sender's replies, where a reply is
+    /// the first message after one from a different sender. Follow-ups to
+    /// one's own message and system events are not counted.
+    pub fn compute_average_response_length(messages: &[Message]) -> HashMap<String, f64> {
+        let mut totals: HashMap<&str, (usize, usize)> = HashMap::new();
+        let mut previous_sender: Option<&str> = None;
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            if previous_sender.is_some_and(|previous| previous != message.sender) {
+                let entry = totals.entry(message.sender.as_str()).or_insert((0, 0));
+                entry.0 += message.content.chars().count();
+                entry.1 += 1;
+            }
+            previous_sender = Some(&message.sender);
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, (characters, replies))| (sender.to_string(), characters as f64 / replies as f64))
+            .collect()
+    }
+
+    /// Mean seconds each sender took to reply, where a reply is the first
+    /// message after one from a different sender (as for
+    /// `compute_average_response_length`). For interpolated timestamps (see
+    /// `ChatParser::set_interpolate_subminute`), `use_interpolated` takes
+    /// the spread-out values; otherwise the exported minutes are compared,
+    /// so a reply within the same minute counts as zero.
+    pub fn average_response_time(messages: &[Message], use_interpolated: bool) -> HashMap<String, f64> {
+        let timestamp = |message: &Message| if use_interpolated { message.timestamp } else { message.exported_timestamp() };
+        let mut totals: HashMap<&str, (f64, usize)> = HashMap::new();
+        let mut previous: Option<&Message> = None;
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) {
+                continue;
+            }
+            if let Some(previous) = previous.filter(|previous| previous.sender != message.sender) {
+                let seconds = (timestamp(message) - timestamp(previous)).num_milliseconds().max(0) as f64 / 1000.0;
+                let entry = totals.entry(message.sender.as_str()).or_insert((0.0, 0));
+                entry.0 += seconds;
+                entry.1 += 1;
+            }
+            previous = Some(message);
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, (seconds, replies))| (sender.to_string(), seconds / replies as f64))
+            .collect()
+    }
+
+    /// Directed reply counts for a social graph: (A, B) counts A's messages
+    /// that directly follow one of B's. Follow-ups to one's own message are
+    /// not counted, and system events are skipped. `export::export_graph`
+    /// renders the same edges, with @-mentions, as DOT or GEXF.
+    pub fn interaction_matrix(messages: &[Message]) -> HashMap<(String, String), u32> {
+        let mut matrix: HashMap<(String, String), u32> = HashMap::new();
+        let mut previous: Option<&Message> = None;
+        for message in messages.iter().filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_))) {
+            if let Some(previous) = previous.filter(|previous| previous.sender != message.sender) {
+                *matrix.entry((message.sender.clone(), previous.sender.clone())).or_insert(0) += 1;
+            }
+            previous = Some(message);
+        }
+        matrix
+    }
+
+    /// Number of distinct senders on each day that has messages, in date order.
+    /// System events are not counted.
+    pub fn compute_daily_active_users(messages: &[Message]) -> Vec<(NaiveDate, usize)> {
+        let mut days: BTreeMap<NaiveDate, HashSet<&str>> = BTreeMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+                days.entry(message.timestamp.date_naive()).or_default().insert(&message.sender);
+            }
+        }
+        days.into_iter().map(|(day, senders)| (day, senders.len())).collect()
+    }
+
+    /// Messages per active day for each sender: their message count divided
+    /// by the number of calendar days on which they sent anything, not by the
+    /// span of the whole chat. System events are not counted.
+    pub fn compute_average_daily_messages(messages: &[Message]) -> HashMap<String, f64> {
+        let mut totals: HashMap<&str, (usize, HashSet<NaiveDate>)> = HashMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+                let entry = totals.entry(message.sender.as_str()).or_default();
+                entry.0 += 1;
+                entry.1.insert(message.timestamp.date_naive());
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, (count, days))| (sender.to_string(), count as f64 / days.len() as f64))
+            .collect()
+    }
+
+    /// How irregularly each sender texts: the coefficient of variation
+    /// (standard deviation over mean) of the seconds between their
+    /// consecutive messages. Near 0 is clockwork, above 1 is bursty. Senders
+    /// with fewer than 3 messages, or whose messages share one timestamp,
+    /// get NaN. System events are not counted.
+    pub fn compute_timing_irregularity(messages: &[Message]) -> HashMap<String, f64> {
+        let mut timestamps: HashMap<&str, Vec<i64>> = HashMap::new();
+        for message in messages.iter().filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_))) {
+            timestamps.entry(message.sender.as_str()).or_default().push(message.timestamp.timestamp());
+        }
+        timestamps
+            .into_iter()
+            .map(|(sender, mut times)| {
+                if times.len() < 3 {
+                    return (sender.to_string(), f64::NAN);
+                }
+                times.sort_unstable();
+                let intervals: Vec<f64> = times.windows(2).map(|pair| (pair[1] - pair[0]) as f64).collect();
+                let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+                let variance = intervals.iter().map(|interval| (interval - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+                let irregularity = if mean > 0.0 { variance.sqrt() / mean } else { f64::NAN };
+                (sender.to_string(), irregularity)
+            })
+            .collect()
+    }
+
+    /// Each sender's daily rhythm, most active first: when they text on
+    /// average and how consistently, as a `Chronotype` archetype. Times of
+    /// day are read in `timezone`, or as exported when `None`. Hours are
+    /// treated as angles on a 24-hour clock, so the mean of 23:00 and 01:00
+    /// is midnight. System events are not counted.
+    pub fn chronotype(messages: &[Message], timezone: Option<FixedOffset>) -> Vec<Chronotype> {
+        // sender -> (hourly counts, sum of cosines, sum of sines)
+        let mut totals: HashMap<&str, ([u32; 24], f64, f64)> = HashMap::new();
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            let time = match timezone {
+                Some(offset) => message.timestamp.with_timezone(&offset).time(),
+                None => message.timestamp.time(),
+            };
+            let angle = (time.num_seconds_from_midnight() as f64 / 86_400.0) * std::f64::consts::TAU;
+            let entry = totals.entry(message.sender.as_str()).or_insert(([0; 24], 0.0, 0.0));
+            entry.0[time.hour() as usize] += 1;
+            entry.1 += angle.cos();
+            entry.2 += angle.sin();
+        }
+
+        let mut chronotypes: Vec<Chronotype> = totals
+            .into_iter()
+            .map(|(sender, (hourly, cos_sum, sin_sum))| {
+                let message_count: u32 = hourly.iter().sum();
+                let (mean_hour, concentration) = circular_mean_hour(cos_sum, sin_sum, message_count as usize);
+                Chronotype {
+                    sender: sender.to_string(),
+                    label: chronotype_label(message_count as usize, mean_hour, concentration).to_string(),
+                    mean_hour,
+                    concentration,
+                    message_count,
+                    hourly: hourly.to_vec(),
+                }
+            })
+            .collect();
+        chronotypes.sort_by(|a, b| b.message_count.cmp(&a.message_count).then_with(|| a.sender.cmp(&b.sender)));
+        chronotypes
+    }
+
+    /// Fraction of each sender's messages, in [0, 1], that belong to a burst:
+    /// a run of at least `BURST_MIN_MESSAGES` consecutive messages from that
+    /// sender, each within `BURST_WINDOW_SECONDS` of the one before. System
+    /// events break a run and are not counted.
+    pub fn compute_burst_score(messages: &[Message]) -> HashMap<String, f64> {
+        // sender -> (messages in bursts, messages)
+        let mut totals: HashMap<&str, (usize, usize)> = HashMap::new();
+        let mut run_start = 0;
+        for (index, message) in messages.iter().enumerate() {
+            let is_system = matches!(message.message_type, MessageType::SystemEvent(_));
+            if !is_system {
+                totals.entry(message.sender.as_str()).or_insert((0, 0)).1 += 1;
+            }
+            let continues = index > 0 && !is_system && {
+                let previous = &messages[index - 1];
+                !matches!(previous.message_type, MessageType::SystemEvent(_))
+                    && previous.sender == message.sender
+                    && (message.timestamp - previous.timestamp).num_seconds() <= BURST_WINDOW_SECONDS
+            };
+            if !continues {
+                // The run ending at the previous message
+                if index - run_start >= BURST_MIN_MESSAGES {
+                    totals.get_mut(messages[run_start].sender.as_str()).unwrap().0 += index - run_start;
+                }
+                run_start = if is_system { index + 1 } else { index };
+            }
+        }
+        if messages.len().saturating_sub(run_start) >= BURST_MIN_MESSAGES {
+            totals.get_mut(messages[run_start].sender.as_str()).unwrap().0 += messages.len() - run_start;
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, (in_bursts, total))| (sender.to_string(), in_bursts as f64 / total as f64))
+            .collect()
+    }
+
+    /// Mean number of emoji per message for each sender (see
+    /// `text::extract_emojis`; a ZWJ sequence counts once). Media captions
+    /// are included; system events are not counted.
+    pub fn emoji_rate(messages: &[Message]) -> HashMap<String, f64> {
+        // sender -> (emoji, messages)
+        let mut totals: HashMap<&str, (usize, usize)> = HashMap::new();
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            let entry = totals.entry(message.sender.as_str()).or_insert((0, 0));
+            entry.0 += message_emojis(message).len();
+            entry.1 += 1;
+        }
+
+        totals
+            .into_iter()
+            .map(|(sender, (emojis, count))| (sender.to_string(), emojis as f64 / count as f64))
+            .collect()
+    }
+
+    /// Senders ranked by an engagement score, highest first (ties by name),
+    /// with the default `EngagementWeights`
+    pub fn rank_senders_by_engagement(messages: &[Message]) -> Vec<(String, f64)> {
+        Self::rank_senders_by_engagement_with(messages, &EngagementWeights::default())
+    }
+
+    /// Senders ranked by a weighted sum of message count, mean words per
+    /// message (see `avg_message_length`), response rate and emoji per
+    /// message (see `emoji_rate`), each scaled by its chat-wide maximum so
+    /// it lies in [0, 1]. A sender's response rate is the share of the
+    /// turns others started that the sender's next turn answered, where a
+    /// turn is a run of messages from one sender. System events and
+    /// reactions are not counted.
+    pub fn rank_senders_by_engagement_with(messages: &[Message], weights: &EngagementWeights) -> Vec<(String, f64)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let mut turns: HashMap<&str, usize> = HashMap::new();
+        let mut responses: HashMap<&str, usize> = HashMap::new();
+        let mut previous: Option<&str> = None;
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) {
+                continue;
+            }
+            let sender = message.sender.as_str();
+            *counts.entry(sender).or_insert(0) += 1;
+            if previous != Some(sender) {
+                *turns.entry(sender).or_insert(0) += 1;
+                if previous.is_some() {
+                    *responses.entry(sender).or_insert(0) += 1;
+                }
+            }
+            previous = Some(sender);
+        }
+        let total_turns: usize = turns.values().sum();
+        let lengths = Self::avg_message_length(messages, false);
+        let emoji = Self::emoji_rate(messages);
+
+        let parts: Vec<(&str, [f64; 4])> = counts
+            .iter()
+            .map(|(&sender, &count)| {
+                let opportunities = total_turns - turns[sender];
+                let response_rate = if opportunities == 0 {
+                    0.0
+                } else {
+                    responses.get(sender).copied().unwrap_or(0) as f64 / opportunities as f64
+                };
+                let length = lengths.get(sender).copied().unwrap_or(0.0);
+                (sender, [count as f64, length, response_rate, emoji.get(sender).copied().unwrap_or(0.0)])
+            })
+            .collect();
+        let mut maxima = [0.0f64; 4];
+        for (_, values) in &parts {
+            for (max, value) in maxima.iter_mut().zip(values) {
+                *max = max.max(*value);
+            }
+        }
+        let weights = [weights.message_count, weights.message_length, weights.response_rate, weights.emoji_rate];
+        let mut ranked: Vec<(String, f64)> = parts
+            .into_iter()
+            .map(|(sender, values)| {
+                let score = values
+                    .iter()
+                    .zip(&maxima)
+                    .zip(&weights)
+                    .map(|((value, max), weight)| if *max > 0.0 { weight * value / max } else { 0.0 })
+                    .sum();
+                (sender.to_string(), score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked
+    }
+
+    /// How often each emoji is used across the chat, media captions
+    /// included. System events are not counted.
+    pub fn emoji_frequencies(messages: &[Message]) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for message in messages {
+            if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+                for emoji in message_emojis(message) {
+                    *counts.entry(emoji).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Mean `sentiment_score` of the messages containing each emoji used
+    /// more than `EMOJI_SENTIMENT_MIN_USES` times, showing which emoji go with
+    /// happy or unhappy messages in this chat. A message counts once per
+    /// emoji however often it repeats it; messages without a score (run
+    /// `compute_sentiment_scores` first) are skipped.
+    pub fn compute_emoji_sentiment(messages: &[Message]) -> HashMap<String, f32> {
+        let frequent: HashSet<String> = Self::emoji_frequencies(messages)
+            .into_iter()
+            .filter(|(_, count)| *count > EMOJI_SENTIMENT_MIN_USES)
+            .map(|(emoji, _)| emoji)
+            .collect();
+
+        let mut totals: HashMap<String, (f32, usize)> = HashMap::new();
+        for message in messages {
+            let Some(score) = message.sentiment_score else { continue };
+            let emojis: HashSet<String> = message_emojis(message).into_iter().collect();
+            for emoji in emojis.into_iter().filter(|emoji| frequent.contains(emoji)) {
+                let entry = totals.entry(emoji).or_insert((0.0, 0));
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(|(emoji, (sum, count))| (emoji, sum / count as f32))
+            .collect()
+    }
+
+    /// Separators for gaps of at least `min_gap_seconds` between consecutive
+    /// messages, labelled by `gap_label`. Gaps are measured from the
+    /// timestamps, so messages need not have been through `annotate_gaps`.
+    pub fn gap_markers(messages: &[Message], min_gap_seconds: u64) -> Vec<GapMarker> {
+        messages
+            .windows(2)
+            .enumerate()
+            .filter_map(|(index, pair)| {
+                let gap_seconds = (pair[1].timestamp - pair[0].timestamp).num_seconds().max(0) as u64;
+                (gap_seconds >= min_gap_seconds && gap_seconds > 0)
+                    .then(|| GapMarker { index: index + 1, gap_seconds, label: gap_label(gap_seconds) })
+            })
+            .collect()
+    }
+
+    /// Runs of at least `min_count` consecutive media messages from one
+    /// sender, each within `max_gap_seconds` of the previous, so a UI can
+    /// show 15 photos as one entry. Any other message in between, including
+    /// media from someone else, ends the run.
+    pub fn group_media_bursts(messages: &[Message], max_gap_seconds: u64, min_count: usize) -> Vec<MediaBurst> {
+        let mut bursts = Vec::new();
+        let mut run: Vec<usize> = Vec::new();
+        for (index, message) in messages.iter().enumerate() {
+            let continues = message.message_type == MessageType::Media
+                && run.last().is_some_and(|&last| {
+                    let gap = (message.timestamp - messages[last].timestamp).num_seconds();
+                    messages[last].sender == message.sender && (0..=max_gap_seconds as i64).contains(&gap)
+                });
+            if !continues {
+                if run.len() >= min_count.max(1) {
+                    bursts.push(MediaBurst::from_run(messages, &run));
+                }
+                run.clear();
+            }
+            if message.message_type == MessageType::Media {
+                run.push(index);
+            }
+        }
+        if run.len() >= min_count.max(1) {
+            bursts.push(MediaBurst::from_run(messages, &run));
+        }
+        bursts
+    }
+
+    /// `messages` with each burst's members replaced by one summary row at
+    /// the position of its first member: a media message from the burst's
+    /// sender reading "15 media files", carrying the combined caption.
+    /// `bursts` must come from `group_media_bursts` on the same messages.
+    pub fn collapse_media_bursts(messages: &[Message], bursts: &[MediaBurst]) -> Vec<Message> {
+        let starts: HashMap<usize, &MediaBurst> = bursts.iter().map(|burst| (burst.indices[0], burst)).collect();
+        let members: HashSet<usize> = bursts.iter().flat_map(|burst| burst.indices.iter().copied()).collect();
+        let mut collapsed = Vec::with_capacity(messages.len());
+        for (index, message) in messages.iter().enumerate() {
+            if let Some(burst) = starts.get(&index) {
+                let mut summary = message.clone();
+                summary.content = format!("{} media files", burst.count);
+                summary.caption = burst.caption.clone();
+                summary.media_filename = None;
+                collapsed.push(summary);
+            } else if !members.contains(&index) {
+                collapsed.push(message.clone());
+            }
+        }
+        collapsed
+    }
+
+    /// Percentage change in message volume for each month after the first,
+    /// as ("YYYY-MM", pct). Empty months in between are included; growth from
+    /// an empty month is `f64::INFINITY`.
+    pub fn month_over_month_growth(messages: &[Message]) -> Vec<(String, f64)> {
+        let mut months: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+        for message in messages {
+            *months.entry((message.timestamp.year(), message.timestamp.month())).or_insert(0) += 1;
+        }
+
+        let (Some(&first), Some(&last)) = (months.keys().next(), months.keys().next_back()) else {
+            return Vec::new();
+        };
+
+        let mut growth = Vec::new();
+        let (mut year, mut month) = first;
+        let mut previous = months[&first];
+        while (year, month) < last {
+            (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            let current = months.get(&(year, month)).copied().unwrap_or(0);
+            let pct = if previous == 0 {
+                f64::INFINITY
+            } else {
+                (current as f64 - previous as f64) / previous as f64 * 100.0
+            };
+            growth.push((format!("{:04}-{:02}", year, month), pct));
+            previous = current;
+        }
+        growth
+    }
+
+    /// Find messages that mention a date ("next Friday", "on the 15th",
+    /// "December 25") and group nearby ones by the date they refer to: a
+    /// mention joins the previous one of the same date when it follows
+    /// within `SHARED_EVENT_WINDOW_SECONDS`, otherwise it starts a new
+    /// event. Relative references are resolved against each message's own
+    /// date. This favours precision: anything more ambiguous is ignored.
+    /// Events are ordered by date, then by first mention.
+    pub fn detect_shared_events(messages: &[Message]) -> Vec<DetectedEvent> {
+        let resolver = DateReferenceResolver::new();
+        // Per date, the events so far with the time of their latest mention
+        let mut events: BTreeMap<NaiveDate, Vec<(DateTime<Local>, DetectedEvent)>> = BTreeMap::new();
+
+        for message in messages {
+            if !matches!(message.message_type, MessageType::Text) {
+                continue;
+            }
+            let sent = message.timestamp.date_naive();
+            let mut dates: Vec<NaiveDate> = resolver.resolve(&message.content, sent);
+            dates.sort();
+            dates.dedup();
+            for date in dates {
+                let mentions = events.entry(date).or_default();
+                let nearby = mentions.last().is_some_and(|(last, _)| {
+                    (message.timestamp - *last).num_seconds() <= SHARED_EVENT_WINDOW_SECONDS
+                });
+                if !nearby {
+                    let event = DetectedEvent { referenced_date: date, message_count: 0, participants: Vec::new() };
+                    mentions.push((message.timestamp, event));
+                }
+                let (last, event) = mentions.last_mut().unwrap();
+                *last = message.timestamp;
+                event.message_count += 1;
+                if !event.participants.contains(&message.sender) {
+                    event.participants.push(message.sender.clone());
+                }
+            }
+        }
+
+        events
+            .into_values()
+            .flatten()
+            .map(|(_, mut event)| {
+                event.participants.sort();
+                event
+            })
+            .collect()
+    }
+}
+
+/// Weights of the parts of the engagement score (see
+/// `ChatParser::rank_senders_by_engagement_with`). With weights summing to
+/// 1 the score lies in [0, 1].
+#[derive(Debug, Clone, Copy)]
+pub struct EngagementWeights {
+    pub message_count: f64,
+    pub message_length: f64,
+    pub response_rate: f64,
+    pub emoji_rate: f64,
+}
+
+impl Default for EngagementWeights {
+    fn default() -> Self {
+        EngagementWeights { message_count: 0.4, message_length: 0.2, response_rate: 0.2, emoji_rate: 0.2 }
+    }
+}
+
+/// A quick overview of a chat, for previews before a full analysis
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatProfile {
+    pub message_count: usize,
+    pub media_count: usize,
+    pub system_event_count: usize,
+    /// Senders with their message counts, most active first
+    pub participants: Vec<(String, u32)>,
+    pub first_message: Option<DateTime<Local>>,
+    pub last_message: Option<DateTime<Local>>,
+}
+
+/// A "3 hours later" separator to show before `messages[index]`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GapMarker {
+    pub index: usize,
+    pub gap_seconds: u64,
+    pub label: String,
+}
+
+/// Consecutive media messages from one sender (see
+/// `ChatParser::group_media_bursts`)
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaBurst {
+    pub sender: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub count: usize,
+    /// Indices of the member messages, in order
+    pub indices: Vec<usize>,
+    /// The members' captions joined by newlines, if any had one
+    pub caption: Option<String>,
+}
+
+impl MediaBurst {
+    fn from_run(messages: &[Message], run: &[usize]) -> MediaBurst {
+        let (first, last) = (&messages[run[0]], &messages[run[run.len() - 1]]);
+        let captions: Vec<&str> = run.iter().filter_map(|&index| messages[index].caption.as_deref()).collect();
+        MediaBurst {
+            sender: first.sender.clone(),
+            start: first.timestamp,
+            end: last.timestamp,
+            count: run.len(),
+            indices: run.to_vec(),
+            caption: (!captions.is_empty()).then(|| captions.join("\n")),
+        }
+    }
+}
+
+/// "5 minutes later", "3 hours later", "2 weeks later", "1 year later": the
+/// gap in the largest unit that fits at least once (months count as 30
+/// days, years as 365), rounded down
+pub fn gap_label(seconds: u64) -> String {
+    const UNITS: [(u64, &str); 6] = [
+        (365 * 86_400, "year"),
+        (30 * 86_400, "month"),
+        (7 * 86_400, "week"),
+        (86_400, "day"),
+        (3_600, "hour"),
+        (60, "minute"),
+    ];
+    let (size, name) = UNITS.iter().copied().find(|(size, _)| seconds >= *size).unwrap_or((1, "second"));
+    let count = seconds / size;
+    format!("{} {}{} later", count, name, if count == 1 { "" } else { "s" })
+}
+
+/// A sender's daily rhythm (see `ChatParser::chronotype`)
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Serialize)]
+pub struct Chronotype {
+    pub sender: String,
+    /// "night owl", "early bird", "morning person", "lunch-break texter",
+    /// "afternoon texter", "evening texter", "around the clock" (no clear
+    /// peak) or "not enough data" (under `CHRONOTYPE_MIN_MESSAGES` messages)
+    pub label: String,
+    /// Circular mean time of day in hours, [0, 24); `None` when the messages
+    /// are spread so evenly that there is no mean
+    pub mean_hour: Option<f64>,
+    /// Mean resultant length in [0, 1]: 1 when every message is sent at the
+    /// same time of day, near 0 when they are spread around the clock
+    pub concentration: f64,
+    pub message_count: u32,
+    /// Messages sent in each hour, 0–23
+    pub hourly: Vec<u32>,
+}
+
+/// Circular mean (in hours) and mean resultant length of `count` times of
+/// day given the sums of their angles' cosines and sines
+fn circular_mean_hour(cos_sum: f64, sin_sum: f64, count: usize) -> (Option<f64>, f64) {
+    if count == 0 {
+        return (None, 0.0);
+    }
+    let concentration = (cos_sum.hypot(sin_sum) / count as f64).min(1.0);
+    if concentration < 1e-9 {
+        return (None, 0.0);
+    }
+    let hour = (sin_sum.atan2(cos_sum) / std::f64::consts::TAU * 24.0).rem_euclid(24.0);
+    // rem_euclid can round a tiny negative angle up to exactly 24
+    (Some(if hour >= 24.0 { 0.0 } else { hour }), concentration)
+}
+
+fn chronotype_label(message_count: usize, mean_hour: Option<f64>, concentration: f64) -> &'static str {
+    if message_count < CHRONOTYPE_MIN_MESSAGES {
+        return "not enough data";
+    }
+    match mean_hour {
+        Some(_) if concentration < CHRONOTYPE_MIN_CONCENTRATION => "around the clock",
+        None => "around the clock",
+        Some(hour) if (5.0..9.0).contains(&hour) => "early bird",
+        Some(hour) if (9.0..11.5).contains(&hour) => "morning person",
+        Some(hour) if (11.5..14.0).contains(&hour) => "lunch-break texter",
+        Some(hour) if (14.0..18.0).contains(&hour) => "afternoon texter",
+        Some(hour) if (18.0..22.0).contains(&hour) => "evening texter",
+        Some(_) => "night owl",
+    }
+}
+
+/// Whether a message's content is something the sender wrote, rather than
+/// a system notice, media placeholder or call log line
+fn has_words(message: &Message) -> bool {
+    matches!(message.message_type, MessageType::Text | MessageType::Link | MessageType::Custom(_))
+}
+
+/// Whether `text` ends with a question mark once trailing whitespace, emoji
+/// (with their modifiers and joiners) and exclamation marks are dropped
+fn ends_with_question(text: &str) -> bool {
+    let trimmed = text.trim_end_matches(|c: char| {
+        c.is_whitespace() || c == '!' || is_emoji(c) || matches!(c, '\u{1F3FB}'..='\u{1F3FF}' | '\u{FE0F}' | '\u{200D}')
+    });
+    trimmed.ends_with(['?', '？', '؟'])
+}
+
+/// Emoji in a message's content and media caption
+fn message_emojis(message: &Message) -> Vec<String> {
+    let mut emojis = extract_emojis(&message.content);
+    if let Some(caption) = &message.caption {
+        emojis.extend(extract_emojis(caption));
+    }
+    emojis
+}
+
+/// Summarise parsed messages (system events included, if parsed) into a `ChatProfile`
+pub fn profile_chat(messages: &[Message]) -> ChatProfile {
+    let system_event_count = messages
+        .iter()
+        .filter(|message| matches!(message.message_type, MessageType::SystemEvent(_)))
+        .count();
+    ChatProfile {
+        message_count: messages.len() - system_event_count,
+        media_count: messages.iter().filter(|message| matches!(message.message_type, MessageType::Media)).count(),
+        system_event_count,
+        participants: ChatParser::sender_counts(messages),
+        first_message: messages.iter().map(|message| message.timestamp).min(),
+        last_message: messages.iter().map(|message| message.timestamp).max(),
+    }
+}
+
+/// Running totals behind `style_metrics`, for one sender or the whole chat
+#[derive(Default)]
+struct StyleAccumulator {
+    messages: usize,
+    words: usize,
+    vocabulary: HashSet<String>,
+    cased_messages: usize,
+    capitalized_messages: usize,
+    punctuation: usize,
+    visible_chars: usize,
+    emojis: usize,
+    laughing_messages: usize,
+    response_ratio_sum: f64,
+    responses: usize,
+}
+
+impl StyleAccumulator {
+    fn add(&mut self, content: &str, replied_to: Option<&str>) {
+        self.messages += 1;
+
+        let lowered = content.to_lowercase();
+        for word in tokenize_words(&lowered) {
+            self.words += 1;
+            if !self.vocabulary.contains(word) {
+                self.vocabulary.insert(word.to_string());
+            }
+        }
+
+        // Only messages whose first letter has case can be "capitalized"
+        if let Some(first) = content.chars().find(|c| c.is_alphabetic()) {
+            if first.is_uppercase() || first.is_lowercase() {
+                self.cased_messages += 1;
+                if first.is_uppercase() {
+                    self.capitalized_messages += 1;
+                }
+            }
+        }
+
+        for c in content.chars().filter(|c| !c.is_whitespace()) {
+            self.visible_chars += 1;
+            if c.is_ascii_punctuation() || matches!(c, '¿' | '¡' | '…' | '،' | '؟' | '。' | '！' | '？' | '、') {
+                self.punctuation += 1;
+            }
+        }
+
+        self.emojis += extract_emojis(content).len();
+        if contains_laughter(content) {
+            self.laughing_messages += 1;
+        }
+
+        if let Some(previous) = replied_to {
+            let previous_length = previous.chars().count().max(1);
+            self.response_ratio_sum += content.chars().count() as f64 / previous_length as f64;
+            self.responses += 1;
+        }
+    }
+
+    fn finish(&self) -> HashMap<String, f64> {
+        let ratio = |numerator: f64, denominator: usize| {
+            if denominator == 0 { 0.0 } else { numerator / denominator as f64 }
+        };
+        HashMap::from([
+            ("type_token_ratio".to_string(), ratio(self.vocabulary.len() as f64, self.words)),
+            ("avg_words_per_message".to_string(), ratio(self.words as f64, self.messages)),
+            ("capitalization_rate".to_string(), ratio(self.capitalized_messages as f64, self.cased_messages)),
+            ("punctuation_density".to_string(), ratio(self.punctuation as f64, self.visible_chars)),
+            ("emoji_per_message".to_string(), ratio(self.emojis as f64, self.messages)),
+            ("relative_response_length".to_string(), ratio(self.response_ratio_sum, self.responses)),
+            ("laughter_rate".to_string(), ratio(self.laughing_messages as f64 * 100.0, self.messages)),
+        ])
+    }
+}
+
+/// Per-sender writing style metrics over text messages, plus the chat-wide
+/// baseline computed the same way. Senders with fewer than `min_messages`
+/// text messages are left out (their ratios are too noisy) but still count
+/// towards the baseline. A message "replies" to the previous text message
+/// when that one came from someone else.
+pub fn style_metrics(
+    messages: &[Message],
+    min_messages: usize,
+) -> (HashMap<String, HashMap<String, f64>>, HashMap<String, f64>) {
+    let mut senders: HashMap<&str, StyleAccumulator> = HashMap::new();
+    let mut baseline = StyleAccumulator::default();
+    let mut previous: Option<&Message> = None;
+
+    for message in messages {
+        if !matches!(message.message_type, MessageType::Text) {
+            continue;
+        }
+        let replied_to = previous
+            .filter(|previous| previous.sender != message.sender)
+            .map(|previous| previous.content.as_str());
+        senders.entry(message.sender.as_str()).or_default().add(&message.content, replied_to);
+        baseline.add(&message.content, replied_to);
+        previous = Some(message);
+    }
+
+    let per_sender = senders
+        .into_iter()
+        .filter(|(_, accumulator)| accumulator.messages >= min_messages)
+        .map(|(sender, accumulator)| (sender.to_string(), accumulator.finish()))
+        .collect();
+    (per_sender, baseline.finish())
+}
+
+/// Default minimum length (in characters) for language detection; shorter
+/// messages ("ok", "jaja") are too ambiguous to classify
+pub const LANGUAGE_MIN_LENGTH: usize = 20;
+
+/// Fewest consecutive messages that count as a burst (see `compute_burst_score`)
+pub const BURST_MIN_MESSAGES: usize = 3;
+
+/// Default `min_gap_seconds` for `gap_markers`: an hour
+pub const GAP_MARKER_MIN_SECONDS: u64 = 3_600;
+
+/// Default `max_gap_seconds` for `group_media_bursts`
+pub const MEDIA_BURST_MAX_GAP_SECONDS: u64 = 120;
+
+/// Default `min_count` for `group_media_bursts`
+pub const MEDIA_BURST_MIN_COUNT: usize = 3;
+
+/// Senders with fewer messages get the "not enough data" chronotype
+pub const CHRONOTYPE_MIN_MESSAGES: usize = 50;
+
+/// Below this concentration a sender has no clear peak ("around the clock")
+pub const CHRONOTYPE_MIN_CONCENTRATION: f64 = 0.2;
+
+/// `compute_emoji_sentiment` skips emoji used this many times or fewer
+pub const EMOJI_SENTIMENT_MIN_USES: usize = 5;
+
+/// Longest gap in seconds between two messages of the same burst
+pub const BURST_WINDOW_SECONDS: i64 = 60;
+
+/// Longest gap in seconds between two mentions of a date in the same
+/// `detect_shared_events` event: three days
+pub const SHARED_EVENT_WINDOW_SECONDS: i64 = 3 * 24 * 3_600;
+
+/// Detect the language of a message with `whatlang`, or `None` when the
+/// message is shorter than `min_length` characters or undetectable
+pub fn detect_language(content: &str, min_length: usize) -> Option<whatlang::Lang> {
+    if content.trim().chars().count() < min_length {
+        return None;
+    }
+    whatlang::detect_lang(content)
+}
+
+/// Number of text messages per detected language (ISO 639-3 code); messages
+/// too short to classify are not counted
+pub fn language_breakdown(messages: &[Message], min_length: usize) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for message in messages {
+        if !matches!(message.message_type, MessageType::Text) {
+            continue;
+        }
+        if let Some(lang) = detect_language(&message.content, min_length) {
+            *counts.entry(lang.code().to_string()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::GroupEvent;
+
+    fn parse(text: &str) -> Vec<Message> {
+        ChatParser::new().parse_str(text, false).unwrap()
+    }
+
+    #[test]
+    fn sentiment_by_hour_averages_scored_text() {
+        let mut messages = parse(include_str!("../../../tests/fixtures/evening_chat.txt"));
+        for message in messages.iter_mut().filter(|message| message.message_type == MessageType::Text) {
+            message.sentiment_score = Some(crate::sentiment::score_sentiment(&message.content, 0.0));
+        }
+        let by_hour = ChatParser::compute_sentiment_by_hour(&messages);
+        for (hour, &score) in by_hour.iter().enumerate() {
+            if (18..=22).contains(&hour) {
+                assert!(score > 0.3, "hour {}: {}", hour, score);
+            } else {
+                assert_eq!(score, 0.0, "hour {}", hour);
+            }
+        }
+    }
+
+    #[test]
+    fn counts_by_weekday_and_hour() {
+        // 15/05/2023 was a Monday
+        let messages = parse("[15/05/2023, 09:00:00] John: a\n[15/05/2023, 09:30:00] Alice: b\n[20/05/2023, 23:00:00] John: c\n");
+        assert_eq!(ChatParser::count_messages_by_weekday(&messages), [2, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(ChatParser::busiest_weekday(&messages), 0);
+        let grid = ChatParser::heatmap(&messages);
+        assert_eq!(grid[0][9], 2);
+        assert_eq!(grid[5][23], 1);
+    }
+
+    #[test]
+    fn average_response_length_skips_self_replies() {
+        let messages = parse("[18/05/2023, 08:00:00] John: hello there\n[18/05/2023, 08:01:00] Alice: hi\n[18/05/2023, 08:02:00] Alice: a much longer follow-up\n[18/05/2023, 08:03:00] John: yes\n");
+        let lengths = ChatParser::compute_average_response_length(&messages);
+        assert_eq!(lengths["Alice"], 2.0);
+        assert_eq!(lengths["John"], 3.0);
+    }
+
+    #[test]
+    fn interaction_matrix_counts_replies() {
+        let mut text = String::new();
+        for (i, sender) in ["John", "Alice", "Bob", "John", "Alice", "Bob", "Bob", "John"].iter().enumerate() {
+            text.push_str(&format!("[18/05/2023, 08:{:02}:00] {}: m{}\n", i, sender, i));
+        }
+        let matrix = ChatParser::interaction_matrix(&parse(&text));
+        let edge = |from: &str, to: &str| matrix.get(&(from.to_string(), to.to_string())).copied();
+        assert_eq!((edge("Alice", "John"), edge("Bob", "Alice"), edge("John", "Bob")), (Some(2), Some(2), Some(2)));
+        assert_eq!((edge("John", "Alice"), edge("Bob", "Bob")), (None, None));
+        assert_eq!(matrix.len(), 3);
+    }
+
+    #[test]
+    fn daily_active_users_counts_distinct_senders() {
+        let mut text: String = (0..50).map(|i| format!("[18/05/2023, 08:{:02}:00] John: message {}\n", i, i)).collect();
+        text.push_str("[19/05/2023, 09:00:00] John: hi\n[19/05/2023, 09:01:00] Alice: hello\n");
+        let days = ChatParser::compute_daily_active_users(&parse(&text));
+        assert_eq!(days, [(NaiveDate::from_ymd_opt(2023, 5, 18).unwrap(), 1), (NaiveDate::from_ymd_opt(2023, 5, 19).unwrap(), 2)]);
+    }
+
+    #[test]
+    fn unanswered_questions_include_the_last_message() {
+        let messages = parse("[18/05/2023, 08:00:00] John: hi\n[18/05/2023, 08:01:00] Alice: are you coming?\n");
+        let unanswered = ChatParser::detect_unanswered_questions(&messages, 2.0);
+        assert_eq!(unanswered.iter().map(|message| message.id.as_str()).collect::<Vec<_>>(), ["msg_2"]);
+
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: lunch?\n[18/05/2023, 08:30:00] John: anyone?\n\
+             [18/05/2023, 09:15:00] Alice: yes\n[19/05/2023, 10:00:00] Alice: dinner？\n[19/05/2023, 13:00:00] John: sorry, missed it\n",
+        );
+        let unanswered = ChatParser::detect_unanswered_questions(&messages, 1.0);
+        assert_eq!(unanswered.iter().map(|message| message.id.as_str()).collect::<Vec<_>>(), ["msg_1", "msg_4"]);
+    }
+
+    #[test]
+    fn longest_word_counts_graphemes_and_skips_links() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: that is antidisestablishmentarianism, honestly\n\
+             [18/05/2023, 08:01:00] John: https://example.com/an-extraordinarily-long-path-segment\n\
+             [18/05/2023, 08:02:00] Alice: नमस्ते friend\n",
+        );
+        let longest = ChatParser::compute_longest_word(&messages);
+        assert_eq!(longest["John"], "antidisestablishmentarianism");
+        // 6 chars but 3 graphemes, so the 6-grapheme "friend" is longer
+        assert_eq!(longest["Alice"], "friend");
+    }
+
+    #[test]
+    fn questions_asked_counts_trailing_question_marks() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: lunch??\n[18/05/2023, 08:01:00] John: why? no idea\n\
+             [18/05/2023, 08:02:00] John: you coming?! 😅\n[18/05/2023, 08:03:00] Alice: sure\n\
+             [18/05/2023, 08:04:00] Alice: <Media omitted>\n",
+        );
+        let asked = ChatParser::questions_asked(&messages);
+        assert_eq!((asked["John"], asked["Alice"]), (2, 0));
+    }
+
+    #[test]
+    fn timing_irregularity_is_zero_for_clockwork_senders() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:10:00] John: b\n[18/05/2023, 08:20:00] John: c\n\
+             [18/05/2023, 08:00:30] Alice: a\n[18/05/2023, 08:01:00] Alice: b\n[18/05/2023, 09:00:00] Alice: c\n\
+             [18/05/2023, 09:01:00] Priya: hi\n",
+        );
+        let irregularity = ChatParser::compute_timing_irregularity(&messages);
+        assert_eq!(irregularity["John"], 0.0);
+        // Intervals of 30s and 3540s: mean 1785, standard deviation 1755
+        assert!((irregularity["Alice"] - 1755.0 / 1785.0).abs() < 1e-9);
+        assert!(irregularity["Priya"].is_nan());
+    }
+
+    #[test]
+    fn by_sender_keeps_order_within_partitions() {
+        let messages = ChatParser::new()
+            .parse_str(
+                "[18/05/2023, 08:00:00] John created group \"Trip\"\n[18/05/2023, 08:01:00] John: a\n\
+                 [18/05/2023, 08:02:00] Alice: b\n[18/05/2023, 08:03:00] John: c\n[18/05/2023, 08:04:00] Alice: d\n\
+                 [18/05/2023, 08:05:00] John: e\n",
+                true,
+            )
+            .unwrap();
+        let partitions = ChatParser::by_sender(messages);
+        let contents = |sender: &str| partitions[sender].iter().map(|message| message.content.as_str()).collect::<Vec<_>>();
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(contents("John"), ["a", "c", "e"]);
+        assert_eq!(contents("Alice"), ["b", "d"]);
+    }
+
+    #[test]
+    fn media_bursts_group_runs_from_one_sender() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: <Media omitted>\n[18/05/2023, 08:00:20] John: <Media omitted>\nbeach\n\
+             [18/05/2023, 08:01:00] John: <Media omitted>\n[18/05/2023, 08:01:30] John: <Media omitted>\nsunset\n\
+             [18/05/2023, 08:02:00] Alice: wow\n[18/05/2023, 08:02:10] John: <Media omitted>\n\
+             [18/05/2023, 08:02:20] John: <Media omitted>\n[18/05/2023, 09:00:00] John: <Media omitted>\n",
+        );
+        let bursts = ChatParser::group_media_bursts(&messages, 60, 2);
+        assert_eq!(bursts.len(), 2);
+        assert_eq!((bursts[0].count, bursts[0].indices.as_slice()), (4, &[0, 1, 2, 3][..]));
+        assert_eq!(bursts[0].caption.as_deref(), Some("beach\nsunset"));
+        assert_eq!((bursts[1].indices.as_slice(), bursts[1].caption.as_deref()), (&[5, 6][..], None));
+        assert!(ChatParser::group_media_bursts(&messages, 60, 5).is_empty());
+
+        let collapsed = ChatParser::collapse_media_bursts(&messages, &bursts);
+        let contents: Vec<&str> = collapsed.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["4 media files", "wow", "2 media files", "<Media omitted>"]);
+        assert_eq!(collapsed[0].caption.as_deref(), Some("beach\nsunset"));
+    }
+
+    #[test]
+    fn anniversary_messages_fall_near_each_anniversary() {
+        let messages = parse(
+            "[10/03/2021, 08:00:00] John: first!\n[20/03/2021, 08:00:00] Alice: same year\n\
+             [05/03/2022, 08:00:00] John: five days early\n[20/03/2022, 08:00:00] Alice: ten days late\n\
+             [17/03/2023, 08:00:00] John: seven days late\n[01/01/2024, 08:00:00] Alice: far off\n",
+        );
+        let found = ChatParser::find_anniversary_messages(&messages, 7);
+        let contents: Vec<&str> = found.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["five days early", "seven days late"]);
+
+        let leap = parse("[29/02/2020, 08:00:00] John: leap\n[28/02/2021, 08:00:00] John: anniversary\n");
+        assert_eq!(ChatParser::find_anniversary_messages(&leap, 0).len(), 1);
+    }
+
+    #[test]
+    fn anniversary_window_crosses_the_year_boundary() {
+        let messages = parse("[30/12/2021, 08:00:00] John: first\n[02/01/2023, 08:00:00] John: close\n");
+        assert_eq!(ChatParser::find_anniversary_messages(&messages, 7).len(), 1);
+    }
+
+    #[test]
+    fn inside_jokes_are_repeated_non_english_phrases() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: the snorfblat strikes again\n[18/05/2023, 08:01:00] Alice: classic snorfblat strikes\n\
+             [18/05/2023, 08:02:00] John: snorfblat strikes, every time\n[18/05/2023, 08:03:00] Alice: John John John strikes again\n\
+             [18/05/2023, 08:04:00] John: see you at 5\n[18/05/2023, 08:05:00] Alice: at 5 then\n",
+        );
+        let jokes = ChatParser::detect_inside_jokes(&messages, 3, 3);
+        assert_eq!(jokes, [("snorfblat strikes".to_string(), 3)]);
+        assert!(ChatParser::detect_inside_jokes(&messages, 4, 3).is_empty());
+    }
+
+    #[test]
+    fn longest_message_prefers_the_earliest_on_ties() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: short\n[18/05/2023, 08:01:00] Alice: ça va bien\n\
+             [18/05/2023, 08:02:00] John: https://example.com/a/very/long/link\n[18/05/2023, 08:03:00] John: all right!\n",
+        );
+        assert_eq!(ChatParser::longest_message(&messages).map(|message| message.id.as_str()), Some("msg_2"));
+        assert!(ChatParser::longest_message(&messages[2..3]).is_none());
+    }
+
+    #[test]
+    fn average_daily_messages_uses_active_days_only() {
+        let mut text = String::new();
+        // John: 100 messages on 10 days spread over 100 days; Alice: 3 on one day
+        for day in 0..10 {
+            let date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap() + Duration::days(day * 11);
+            for minute in 0..10 {
+                text.push_str(&format!("[{}, 08:{:02}:00] John: m\n", date.format("%d/%m/%Y"), minute));
+            }
+        }
+        text.push_str("[10/04/2023, 09:00:00] Alice: a\n[10/04/2023, 09:01:00] Alice: b\n[10/04/2023, 23:59:00] Alice: c\n");
+        let averages = ChatParser::compute_average_daily_messages(&parse(&text));
+        assert_eq!(averages["John"], 10.0);
+        assert_eq!(averages["Alice"], 3.0);
+    }
+
+    /// Distance between two times of day in hours, across midnight
+    fn hour_distance(a: f64, b: f64) -> f64 {
+        let difference = (a - b).rem_euclid(24.0);
+        difference.min(24.0 - difference)
+    }
+
+    #[test]
+    fn circular_mean_wraps_around_midnight() {
+        let angle = |hour: f64| hour / 24.0 * std::f64::consts::TAU;
+        let mean = |hours: &[f64]| {
+            let (cos_sum, sin_sum) = hours.iter().fold((0.0, 0.0), |(c, s), h| (c + angle(*h).cos(), s + angle(*h).sin()));
+            circular_mean_hour(cos_sum, sin_sum, hours.len())
+        };
+        let (hour, concentration) = mean(&[23.0, 1.0]);
+        assert!(hour_distance(hour.unwrap(), 0.0) < 1e-9, "{:?}", hour);
+        assert!((concentration - (std::f64::consts::PI / 12.0).cos()).abs() < 1e-9);
+        assert!(hour_distance(mean(&[22.0, 23.0, 0.0, 1.0, 2.0]).0.unwrap(), 0.0) < 1e-9);
+        assert!((mean(&[11.0, 13.0]).0.unwrap() - 12.0).abs() < 1e-9);
+        // Opposite hours cancel out
+        assert_eq!(mean(&[6.0, 18.0]), (None, 0.0));
+    }
+
+    #[test]
+    fn classifies_senders_by_time_of_day() {
+        let mut text = String::new();
+        for day in 1..=25 {
+            for (hour, sender) in [(23, "Owl"), (1, "Owl"), (6, "Bird"), (7, "Bird"), (12, "Lunch"), (12, "Lunch")] {
+                text.push_str(&format!("[{:02}/05/2023, {:02}:{:02}:00] {}: m\n", day, hour, day, sender));
+            }
+            text.push_str(&format!("[{:02}/05/2023, 10:00:00] Rare: m\n", day));
+            for hour in [0, 6, 12, 18] {
+                text.push_str(&format!("[{:02}/05/2023, {:02}:30:00] Anytime: m\n", day, hour));
+            }
+        }
+        let mut messages = parse(&text);
+        messages.sort_by_key(|message| message.timestamp);
+        let chronotypes = ChatParser::chronotype(&messages, None);
+        let labels: HashMap<&str, &str> =
+            chronotypes.iter().map(|chronotype| (chronotype.sender.as_str(), chronotype.label.as_str())).collect();
+        assert_eq!(labels["Owl"], "night owl");
+        assert_eq!(labels["Bird"], "early bird");
+        assert_eq!(labels["Lunch"], "lunch-break texter");
+        assert_eq!(labels["Rare"], "not enough data");
+        assert_eq!(labels["Anytime"], "around the clock");
+        let owl = chronotypes.iter().find(|chronotype| chronotype.sender == "Owl").unwrap();
+        assert!(hour_distance(owl.mean_hour.unwrap(), 0.2) < 0.1, "{:?}", owl.mean_hour);
+        assert_eq!((owl.hourly[23], owl.hourly[1], owl.message_count), (25, 25, 50));
+
+        // Read six hours ahead of the export's clock, the owl keeps early-bird hours
+        let local = messages[0].timestamp.offset().local_minus_utc();
+        let shifted = ChatParser::chronotype(&messages, FixedOffset::east_opt(local + 6 * 3600));
+        let owl = shifted.iter().find(|chronotype| chronotype.sender == "Owl").unwrap();
+        assert_eq!(owl.label, "early bird");
+    }
+
+    #[test]
+    fn url_only_messages_have_no_words_when_excluded() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: https://example.com/some/long/path\n\
+             [18/05/2023, 08:01:00] John: look at this\n[18/05/2023, 08:02:00] Alice: <Media omitted>\n",
+        );
+        assert_eq!(ChatParser::avg_message_length(&messages, true)["John"], 1.5);
+        assert_eq!(ChatParser::avg_message_length(&messages, false)["John"], 4.5);
+        assert!(!ChatParser::avg_message_length(&messages, true).contains_key("Alice"));
+
+        let words = ChatParser::word_frequency(&messages[..1], true);
+        assert!(words.is_empty());
+        assert_eq!(ChatParser::word_frequency(&messages, false)[0], ("at".to_string(), 1));
+    }
+
+    #[test]
+    fn balance_is_share_of_messages() {
+        let messages = parse("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:01:00] John: b\n[18/05/2023, 08:02:00] Alice: c\n[18/05/2023, 08:03:00] John: d\n");
+        let shares = ChatParser::balance(&messages);
+        assert_eq!(shares["John"], 0.75);
+        assert_eq!(shares["Alice"], 0.25);
+        assert!(ChatParser::balance(&[]).is_empty());
+    }
+
+    #[test]
+    fn emoji_rate_is_mean_emoji_per_message() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: 😂😂\n[18/05/2023, 08:01:00] John: 👨‍👩‍👧 nice 👍🏽\n\
+             [18/05/2023, 08:02:00] John: no emoji\n[18/05/2023, 08:03:00] Alice: ok\n\
+             [18/05/2023, 08:04:00] Alice: <Media omitted>\nsunset 🌅\n",
+        );
+        let rates = ChatParser::emoji_rate(&messages);
+        assert_eq!(rates["John"], 4.0 / 3.0);
+        assert_eq!(rates["Alice"], 0.5);
+        assert!(ChatParser::emoji_rate(&[]).is_empty());
+    }
+
+    #[test]
+    fn response_time_with_and_without_interpolation() {
+        let mut parser = ChatParser::new();
+        parser.set_interpolate_subminute(true);
+        let messages = parser
+            .parse_str(
+                "[18/05/2023, 08:00] John: are you coming\n[18/05/2023, 08:00] Alice: yes\n\
+                 [18/05/2023, 08:00] John: great\n[18/05/2023, 08:02] Alice: leaving now\n",
+                false,
+            )
+            .unwrap();
+        let interpolated = ChatParser::average_response_time(&messages, true);
+        assert_eq!((interpolated["Alice"], interpolated["John"]), ((20.0 + 80.0) / 2.0, 20.0));
+        let exported = ChatParser::average_response_time(&messages, false);
+        assert_eq!((exported["Alice"], exported["John"]), (60.0, 0.0));
+    }
+
+    #[test]
+    fn most_engaged_sender_ranks_first() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] Alice: how was the weekend away with everyone 😄\n\
+             [18/05/2023, 08:01:00] John: ok\n\
+             [18/05/2023, 08:02:00] Alice: we should plan the next one soon 🎉🎉\n\
+             [18/05/2023, 08:03:00] Tom: sure\n\
+             [18/05/2023, 08:04:00] Alice: I will start a list of places tonight 😄\n\
+             [18/05/2023, 08:05:00] John: fine\n\
+             [18/05/2023, 08:06:00] Alice: great, talk later 👋\n",
+        );
+        let ranked = ChatParser::rank_senders_by_engagement(&messages);
+        assert_eq!(ranked.iter().map(|(sender, _)| sender.as_str()).collect::<Vec<_>>(), ["Alice", "John", "Tom"]);
+        assert!((ranked[0].1 - 1.0).abs() < 1e-9);
+
+        // Alice answered all 3 turns others started, John 2 of 5, Tom 1 of 6
+        let weights = EngagementWeights { message_count: 0.0, message_length: 0.0, response_rate: 1.0, emoji_rate: 0.0 };
+        let by_response = ChatParser::rank_senders_by_engagement_with(&messages, &weights);
+        let scores: Vec<f64> = by_response.iter().map(|(_, score)| *score).collect();
+        assert_eq!(scores, [1.0, 0.4, 1.0 / 6.0]);
+    }
+
+    #[test]
+    fn emoji_sentiment_averages_scores_of_frequent_emoji() {
+        let mut text = String::new();
+        for i in 0..6 {
+            text.push_str(&format!("[18/05/2023, 08:{:02}:00] John: {}\n", i, if i < 3 { "great 😂😂" } else { "ugh 😂" }));
+        }
+        text.push_str("[18/05/2023, 09:00:00] Alice: 😭😭😭😭😭\n[18/05/2023, 09:01:00] Alice: 😭\n");
+        let mut messages = parse(&text);
+        for (index, message) in messages.iter_mut().enumerate() {
+            message.sentiment_score = Some(if index < 3 { 0.5 } else { -0.25 });
+        }
+        messages[7].sentiment_score = None;
+
+        let frequencies = ChatParser::emoji_frequencies(&messages);
+        assert_eq!((frequencies["😂"], frequencies["😭"]), (9, 6));
+        let sentiment = ChatParser::compute_emoji_sentiment(&messages);
+        assert_eq!(sentiment.len(), 2);
+        assert_eq!(sentiment["😂"], 0.125);
+        assert_eq!(sentiment["😭"], -0.25);
+    }
+
+    #[test]
+    fn labels_gaps_with_the_largest_unit() {
+        let labels: Vec<String> = [59, 60, 3 * 3_600, 86_400, 13 * 86_400, 45 * 86_400, 800 * 86_400]
+            .into_iter()
+            .map(gap_label)
+            .collect();
+        assert_eq!(
+            labels,
+            ["59 seconds later", "1 minute later", "3 hours later", "1 day later", "1 week later", "1 month later", "2 years later"]
+        );
+    }
+
+    #[test]
+    fn gap_markers_mark_long_silences() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:30:00] Alice: b\n\
+             [18/05/2023, 11:30:00] John: c\n[01/06/2023, 11:30:00] Alice: d\n",
+        );
+        let markers = ChatParser::gap_markers(&messages, GAP_MARKER_MIN_SECONDS);
+        let found: Vec<(usize, &str)> = markers.iter().map(|marker| (marker.index, marker.label.as_str())).collect();
+        assert_eq!(found, [(2, "3 hours later"), (3, "2 weeks later")]);
+        assert_eq!(markers[0].gap_seconds, messages[2].gap_before_seconds.unwrap());
+    }
+
+    #[test]
+    fn burst_score_is_share_of_messages_in_bursts() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:00:20] John: b\n[18/05/2023, 08:01:10] John: c\n\
+             [18/05/2023, 08:05:00] Alice: d\n[18/05/2023, 08:05:30] Alice: e\n\
+             [18/05/2023, 08:06:00] John: f\n[18/05/2023, 08:06:10] John: g\n[18/05/2023, 08:06:15] John: h\n\
+             [18/05/2023, 08:10:00] Alice: i\n[18/05/2023, 08:10:10] Alice: j\n[18/05/2023, 08:15:00] Alice: k\n",
+        );
+        let scores = ChatParser::compute_burst_score(&messages);
+        assert_eq!(scores["John"], 1.0);
+        assert_eq!(scores["Alice"], 0.0);
+
+        // A system event in the middle breaks the run
+        let mut messages = parse("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:00:10] John: b\n[18/05/2023, 08:00:20] John: c\n");
+        messages[1].message_type = MessageType::SystemEvent(GroupEvent::Other { text: "x".to_string() });
+        assert_eq!(ChatParser::compute_burst_score(&messages)["John"], 0.0);
+        assert!(ChatParser::compute_burst_score(&[]).is_empty());
+    }
+
+    #[test]
+    fn recurring_senders_count_chats_not_messages() {
+        let chats = [
+            parse("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:01:00] John: b\n[18/05/2023, 08:02:00] Alice: c\n"),
+            parse("[19/05/2023, 08:00:00] John: d\n[19/05/2023, 08:01:00] Bob: e\n"),
+            parse("[20/05/2023, 08:00:00] Carol: f\n[20/05/2023, 08:01:00] John: g\n"),
+        ];
+        let counts = ChatParser::detect_recurring_senders(&chats);
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts["John"], 3);
+        assert_eq!((counts["Alice"], counts["Bob"], counts["Carol"]), (1, 1, 1));
+    }
+
+    #[test]
+    fn finds_first_message_of_sender() {
+        let messages = parse("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:01:00] Alice: b\n[18/05/2023, 08:02:00] Alice: c\n");
+        assert_eq!(ChatParser::find_first_occurrence_of_sender(&messages, "  alice "), Some(1));
+        assert_eq!(ChatParser::find_first_occurrence_of_sender(&messages, "Bob"), None);
+    }
+
+    #[test]
+    fn splits_sessions_at_gaps() {
+        let messages = parse("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 09:00:00] Alice: b\n[18/05/2023, 20:00:00] John: c\n");
+        let sessions = ChatParser::split_by_conversation_gap(&messages, 4.0);
+        assert_eq!(sessions.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn synthetic_timestamps_are_rejected() {
+        let mut messages = parse("[18/05/2023, 08:00:00] John: a\n");
+        assert!(ensure_real_timestamps(&messages).is_ok());
+        messages[0].timestamp_synthetic = true;
+        assert!(matches!(ensure_real_timestamps(&messages), Err(ParserError::SyntheticTimestamps)));
+    }
+
+    #[test]
+    fn shared_events_group_only_nearby_mentions() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: party on May 26?\n\
+             [19/05/2023, 10:00:00] Alice: May 26 works\n\
+             [25/05/2023, 09:00:00] Bob: is May 26 still on?\n",
+        );
+        let events = ChatParser::detect_shared_events(&messages);
+        let may_26 = NaiveDate::from_ymd_opt(2023, 5, 26).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.referenced_date == may_26));
+        assert_eq!((events[0].message_count, events[0].participants.clone()), (2, vec!["Alice".to_string(), "John".to_string()]));
+        assert_eq!((events[1].message_count, events[1].participants.clone()), (1, vec!["Bob".to_string()]));
+    }
+}
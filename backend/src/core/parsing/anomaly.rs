@@ -0,0 +1,211 @@
+use chrono::NaiveDate;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::message::{Message, MessageType};
+
+/// Per-day aggregates that anomaly metrics are computed from
+#[derive(Debug, Default, Clone)]
+struct DayStats {
+    message_count: usize,
+    sentiment_sum: f64,
+    sentiment_count: usize,
+    /// Senders whose first message in the chat falls on this day
+    new_participants: usize,
+}
+
+/// A daily metric the anomaly detector can watch. New metrics only need to
+/// implement this and be added to `ANOMALY_METRICS`.
+trait DailyMetric: Sync {
+    fn name(&self) -> &'static str;
+    /// The metric's value for a day, or `None` when it is undefined that day
+    fn value(&self, day: &DayStats) -> Option<f64>;
+    /// Smallest standard deviation to judge a day against, given the
+    /// baseline mean; a flat baseline below this floor is skipped
+    fn std_floor(&self, _mean: f64) -> f64 {
+        0.0
+    }
+}
+
+/// Poisson-style floor for daily counts: a count's spread is about the
+/// square root of its mean, and at least 1, so a quiet stretch (all zeros)
+/// still makes a burst stand out instead of dividing by zero
+fn count_std_floor(mean: f64) -> f64 {
+    mean.sqrt().max(1.0)
+}
+
+struct MessageCountMetric;
+
+impl DailyMetric for MessageCountMetric {
+    fn name(&self) -> &'static str {
+        "message_count"
+    }
+
+    fn value(&self, day: &DayStats) -> Option<f64> {
+        Some(day.message_count as f64)
+    }
+
+    fn std_floor(&self, mean: f64) -> f64 {
+        count_std_floor(mean)
+    }
+}
+
+struct AverageSentimentMetric;
+
+impl DailyMetric for AverageSentimentMetric {
+    fn name(&self) -> &'static str {
+        "average_sentiment"
+    }
+
+    fn value(&self, day: &DayStats) -> Option<f64> {
+        (day.sentiment_count > 0).then(|| day.sentiment_sum / day.sentiment_count as f64)
+    }
+}
+
+struct NewParticipantsMetric;
+
+impl DailyMetric for NewParticipantsMetric {
+    fn name(&self) -> &'static str {
+        "new_participants"
+    }
+
+    fn value(&self, day: &DayStats) -> Option<f64> {
+        Some(day.new_participants as f64)
+    }
+
+    fn std_floor(&self, mean: f64) -> f64 {
+        count_std_floor(mean)
+    }
+}
+
+/// Metrics checked by `detect_anomalies`
+const ANOMALY_METRICS: &[&dyn DailyMetric] = &[&MessageCountMetric, &AverageSentimentMetric, &NewParticipantsMetric];
+
+/// Trailing window (in days) for the anomaly baseline
+const ANOMALY_BASELINE_DAYS: usize = 30;
+
+/// A day whose metric deviated from its trailing baseline
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone)]
+pub struct Anomaly {
+    pub date: String,
+    pub metric: String,
+    pub value: f64,
+    pub baseline_mean: f64,
+    /// The baseline's standard deviation, raised to the metric's floor for
+    /// counts (see `detect_anomalies`)
+    pub baseline_std: f64,
+    /// Signed z-score; its absolute value is the anomaly's magnitude
+    pub z_score: f64,
+}
+
+/// Aggregate messages into consecutive calendar days, including empty days
+fn daily_stats(messages: &[Message]) -> Vec<(NaiveDate, DayStats)> {
+    let mut days: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
+    let mut first_seen: HashMap<&str, NaiveDate> = HashMap::new();
+
+    for message in messages {
+        if matches!(message.message_type, MessageType::SystemEvent(_)) {
+            continue;
+        }
+        let date = message.timestamp.date_naive();
+        let day = days.entry(date).or_default();
+        day.message_count += 1;
+        if let Some(score) = message.sentiment_score {
+            day.sentiment_sum += score as f64;
+            day.sentiment_count += 1;
+        }
+        let first = first_seen.entry(message.sender.as_str()).or_insert(date);
+        if date < *first {
+            *first = date;
+        }
+    }
+    for date in first_seen.values() {
+        if let Some(day) = days.get_mut(date) {
+            day.new_participants += 1;
+        }
+    }
+
+    let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) else {
+        return Vec::new();
+    };
+    first
+        .iter_days()
+        .take_while(|date| *date <= last)
+        .map(|date| (date, days.remove(&date).unwrap_or_default()))
+        .collect()
+}
+
+/// Flag days where any metric is more than `sensitivity` standard deviations
+/// from its trailing 30-day baseline (an expanding window early in the chat),
+/// strongest anomalies first. For counts the deviation is at least the
+/// square root of the mean, and at least 1, so a first burst after a flat
+/// baseline (new participants after weeks of none) is still caught.
+pub fn detect_anomalies(messages: &[Message], sensitivity: f64) -> Vec<Anomaly> {
+    let days = daily_stats(messages);
+    let mut anomalies = Vec::new();
+
+    for metric in ANOMALY_METRICS {
+        let values: Vec<Option<f64>> = days.iter().map(|(_, day)| metric.value(day)).collect();
+        for (index, (date, _)) in days.iter().enumerate() {
+            let Some(value) = values[index] else { continue };
+            let window_start = index.saturating_sub(ANOMALY_BASELINE_DAYS);
+            let baseline: Vec<f64> = values[window_start..index].iter().flatten().copied().collect();
+            // Too little history to say what's normal
+            if baseline.len() < 3 {
+                continue;
+            }
+
+            let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+            let variance = baseline.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / baseline.len() as f64;
+            let std = variance.sqrt().max(metric.std_floor(mean));
+            if std < 1e-9 {
+                continue;
+            }
+
+            let z_score = (value - mean) / std;
+            if z_score.abs() > sensitivity {
+                anomalies.push(Anomaly {
+                    date: date.format("%Y-%m-%d").to_string(),
+                    metric: metric.name().to_string(),
+                    value,
+                    baseline_mean: mean,
+                    baseline_std: std,
+                    z_score,
+                });
+            }
+        }
+    }
+
+    anomalies.sort_by(|a, b| b.z_score.abs().total_cmp(&a.z_score.abs()));
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_parser::ChatParser;
+
+    #[test]
+    fn flags_new_participants_after_a_flat_baseline() {
+        // John alone from 1 April to 18 May, then three people join at once
+        let start = NaiveDate::from_ymd_opt(2023, 4, 1).unwrap();
+        let mut chat: String = start
+            .iter_days()
+            .take(48)
+            .map(|date| format!("[{}, 10:00:00] John: Morning\n", date.format("%d/%m/%Y")))
+            .collect();
+        for sender in ["Alice", "Bob", "Carol"] {
+            chat.push_str(&format!("[19/05/2023, 12:00:00] {}: Hi all\n", sender));
+        }
+        let messages = ChatParser::new().parse_str(&chat, false).unwrap();
+        let anomalies = detect_anomalies(&messages, 2.5);
+
+        let joined = anomalies.iter().find(|anomaly| anomaly.metric == "new_participants").unwrap();
+        assert_eq!(joined.date, "2023-05-19");
+        assert_eq!(joined.value, 3.0);
+        assert_eq!((joined.baseline_mean, joined.baseline_std), (0.0, 1.0));
+        assert!(anomalies.iter().all(|anomaly| anomaly.date == "2023-05-19"));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::io::{Read, Seek};
+
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::chat_parser::{split_caption, ChatParser};
+use crate::error::ParserError;
+use crate::message::{Message, MessageType};
+
+fn zip_error(error: zip::result::ZipError) -> ParserError {
+    ParserError::io("Failed to read ZIP archive")(error.into())
+}
+
+/// The chat text entry of an export: `_chat.txt` (iOS) or, failing that, the
+/// first `.txt` entry (Android names it after the chat)
+fn chat_entry_name<R: Read + Seek>(archive: &zip::ZipArchive<R>) -> Result<String, ParserError> {
+    archive
+        .file_names()
+        .filter(|name| name.to_ascii_lowercase().ends_with(".txt"))
+        .min_by_key(|name| !name.ends_with("_chat.txt"))
+        .map(str::to_string)
+        .ok_or_else(|| ParserError::InvalidArgument("ZIP archive contains no chat text file".to_string()))
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, name: &str) -> Result<Vec<u8>, ParserError> {
+    let mut entry = archive.by_name(name).map_err(zip_error)?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).map_err(ParserError::io("Failed to read ZIP archive"))?;
+    Ok(bytes)
+}
+
+/// Read the chat text out of a WhatsApp "Export chat" ZIP
+pub fn read_chat_from_zip<R: Read + Seek>(reader: R) -> Result<Vec<u8>, ParserError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+    let name = chat_entry_name(&archive)?;
+    read_entry(&mut archive, &name)
+}
+
+/// The first `limit` bytes of the chat text in an export ZIP and the text's
+/// uncompressed size. Only the central directory and that much of the
+/// entry are read, however large the archive.
+pub fn peek_chat_from_zip<R: Read + Seek>(reader: R, limit: usize) -> Result<(Vec<u8>, u64), ParserError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+    let name = chat_entry_name(&archive)?;
+    let entry = archive.by_name(&name).map_err(zip_error)?;
+    let size = entry.size();
+    let mut head = Vec::new();
+    entry.take(limit as u64).read_to_end(&mut head).map_err(ParserError::io("Failed to read ZIP archive"))?;
+    Ok((head, size))
+}
+
+/// Parse an export made "with media" and set `media_filename` on the
+/// messages the archive's media files belong to (see `attach_media_filenames`)
+pub fn parse_zip_with_media<R: Read + Seek>(
+    parser: &ChatParser,
+    reader: R,
+    include_system: bool,
+) -> Result<Vec<Message>, ParserError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+    let chat_name = chat_entry_name(&archive)?;
+    let media: Vec<String> = archive
+        .file_names()
+        .filter(|name| *name != chat_name && !name.ends_with('/'))
+        .map(|name| name.rsplit('/').next().unwrap_or(name).to_string())
+        .collect();
+    let text = read_entry(&mut archive, &chat_name)?;
+
+    let mut messages = parser.parse_reader(&text[..], include_system)?;
+    attach_media_filenames(&mut messages, &media);
+    Ok(messages)
+}
+
+/// Match media files to messages. A message naming a file in `filenames`
+/// ("IMG-20230518-WA0001.jpg (file attached)", "<attached: …>") gets that
+/// file and becomes a media message. The remaining Android-style files
+/// (`IMG-YYYYMMDD-WANNNN.jpg`, likewise VID, AUD, PTT, STK, DOC) are handed
+/// out in sequence order to the `<Media omitted>` messages of the same day.
+/// This is a heuristic: days with more placeholders than files leave the
+/// later placeholders unmatched.
+pub fn attach_media_filenames(messages: &mut [Message], filenames: &[String]) {
+    let reference = Regex::new(r"<attached: ([^>]+)>|^(\S+\.\w+) \(file attached\)").unwrap();
+    let dated = Regex::new(r"^(?:IMG|VID|AUD|PTT|STK|DOC)-(\d{8})-WA(\d+)").unwrap();
+
+    let available: HashSet<&str> = filenames.iter().map(String::as_str).collect();
+    let mut used = HashSet::new();
+    for message in messages.iter_mut() {
+        let named = reference
+            .captures(message.content.trim())
+            .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+            .map(|name| name.as_str().trim())
+            .filter(|name| available.contains(name));
+        if let Some(name) = named {
+            used.insert(name.to_string());
+            message.media_filename = Some(name.to_string());
+            message.message_type = MessageType::Media;
+            split_caption(message);
+        }
+    }
+
+    let mut by_day: BTreeMap<NaiveDate, Vec<(u32, &str)>> = BTreeMap::new();
+    for name in filenames.iter().filter(|name| !used.contains(name.as_str())) {
+        let Some(captures) = dated.captures(name) else { continue };
+        let (Ok(day), Ok(sequence)) =
+            (NaiveDate::parse_from_str(&captures[1], "%Y%m%d"), captures[2].parse::<u32>())
+        else {
+            continue;
+        };
+        by_day.entry(day).or_default().push((sequence, name));
+    }
+    let mut queues: BTreeMap<NaiveDate, VecDeque<&str>> = by_day
+        .into_iter()
+        .map(|(day, mut files)| {
+            files.sort();
+            (day, files.into_iter().map(|(_, name)| name).collect())
+        })
+        .collect();
+
+    for message in messages.iter_mut() {
+        if !matches!(message.message_type, MessageType::Media) || message.media_filename.is_some() {
+            continue;
+        }
+        if let Some(name) = queues.get_mut(&message.timestamp.date_naive()).and_then(VecDeque::pop_front) {
+            message.media_filename = Some(name.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn archive(entries: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, bytes) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[test]
+    fn matches_media_by_reference_and_by_day() {
+        let chat = "[18/05/2023, 08:00:00] John: <Media omitted>
+[18/05/2023, 08:05:00] Alice: <Media omitted>
+[18/05/2023, 09:00:00] John: VID-20230518-WA0007.mp4 (file attached)
+[19/05/2023, 10:00:00] Alice: <Media omitted>
+";
+        let zip = archive(&[
+            ("_chat.txt", chat.as_bytes()),
+            ("IMG-20230518-WA0002.jpg", b""),
+            ("IMG-20230518-WA0001.jpg", b""),
+            ("VID-20230518-WA0007.mp4", b""),
+        ]);
+        let messages = parse_zip_with_media(&ChatParser::new(), zip, false).unwrap();
+        let files: Vec<Option<&str>> = messages.iter().map(|message| message.media_filename.as_deref()).collect();
+        assert_eq!(files, [Some("IMG-20230518-WA0001.jpg"), Some("IMG-20230518-WA0002.jpg"), Some("VID-20230518-WA0007.mp4"), None]);
+        assert_eq!(messages[2].message_type, MessageType::Media);
+    }
+
+    #[test]
+    fn archive_without_chat_is_an_error() {
+        let zip = archive(&[("IMG-20230518-WA0001.jpg", b"")]);
+        assert!(matches!(read_chat_from_zip(zip), Err(ParserError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn peeks_at_the_head_of_the_chat_entry() {
+        let chat = "[18/05/2023, 08:00:00] John: Hi\n".repeat(100);
+        let zip = archive(&[("IMG-20230518-WA0001.jpg", b""), ("_chat.txt", chat.as_bytes())]);
+        let (head, size) = peek_chat_from_zip(zip, 64).unwrap();
+        assert_eq!((head.as_slice(), size), (&chat.as_bytes()[..64], chat.len() as u64));
+    }
+}
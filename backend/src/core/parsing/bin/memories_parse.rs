@@ -0,0 +1,190 @@
+//! `memories-parse`: parse and export WhatsApp chats without Python.
+//!
+//! ```text
+//! memories-parse chat.txt --format jsonl --out chat.jsonl
+//! memories-parse - --stats < chat.txt
+//! memories-parse export.zip --zip --senders
+//! ```
+//!
+//! Exit codes: 0 success, 1 parse error, 2 bad arguments, 3 I/O error,
+//! 4 input not recognised as a chat export, 5 partial parse (output was
+//! written but some lines were dropped or had their sender corrected; see the
+//! warnings on stderr).
+
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+use whatsapp_parser::analytics::profile_chat;
+use whatsapp_parser::archive::read_chat_from_zip;
+use whatsapp_parser::export::{write_jsonl, write_redacted_jsonl, ManifestWriter, MessageRecord};
+use whatsapp_parser::{ChatParser, ParserError};
+
+const EXIT_PARSE_ERROR: u8 = 1;
+const EXIT_IO_ERROR: u8 = 3;
+const EXIT_UNRECOGNISED: u8 = 4;
+const EXIT_PARTIAL: u8 = 5;
+
+/// How much of the input `--detect` and the format check look at
+const DETECT_SAMPLE_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// One message object per line
+    Jsonl,
+    /// A single JSON array of messages
+    Json,
+    /// JSON Lines with content replaced by its length
+    Redacted,
+}
+
+#[derive(Parser)]
+#[command(name = "memories-parse", version, about = "Parse and export WhatsApp chat exports")]
+struct Args {
+    /// Chat export to read, or - for stdin
+    input: String,
+    /// Treat the input as a WhatsApp "Export chat" ZIP
+    #[arg(long)]
+    zip: bool,
+    /// Output format for the parsed messages
+    #[arg(long, value_enum, default_value = "jsonl")]
+    format: OutputFormat,
+    /// Write output here instead of stdout
+    #[arg(long)]
+    out: Option<String>,
+    /// Keep system messages (group events, notices)
+    #[arg(long)]
+    include_system: bool,
+    /// Print the detected export format and exit
+    #[arg(long, conflicts_with_all = ["stats", "senders"])]
+    detect: bool,
+    /// Print a summary (counts, participants, date range) instead of messages
+    #[arg(long, conflicts_with = "senders")]
+    stats: bool,
+    /// Print message counts per sender instead of messages
+    #[arg(long)]
+    senders: bool,
+    /// End jsonl/redacted output with an integrity manifest line
+    #[arg(long)]
+    manifest: bool,
+    /// Write warnings to stderr as JSON objects, one per line
+    #[arg(long)]
+    warnings_json: bool,
+    /// Skip messages with unparseable timestamps (exit code 5) instead of failing
+    #[arg(long)]
+    lenient: bool,
+}
+
+/// A failure with the exit code it maps to
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl From<ParserError> for Failure {
+    fn from(error: ParserError) -> Self {
+        let code = match &error {
+            // The reader went away (`| head`); not worth reporting
+            ParserError::Io { source, .. } if source.kind() == io::ErrorKind::BrokenPipe => 0,
+            ParserError::Io { .. } => EXIT_IO_ERROR,
+            _ => EXIT_PARSE_ERROR,
+        };
+        Failure { code, message: error.to_string() }
+    }
+}
+
+fn read_input(args: &Args) -> Result<Vec<u8>, ParserError> {
+    let mut bytes = Vec::new();
+    if args.input == "-" {
+        io::stdin().lock().read_to_end(&mut bytes).map_err(ParserError::io("Failed to read stdin"))?;
+    } else {
+        File::open(&args.input)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(ParserError::io("Failed to open file"))?;
+    }
+    if args.zip {
+        bytes = read_chat_from_zip(Cursor::new(bytes))?;
+    }
+    Ok(bytes)
+}
+
+/// Serialise `value` as one line of JSON
+fn write_json_line<W: Write, T: serde::Serialize>(mut writer: W, value: &T) -> Result<(), ParserError> {
+    serde_json::to_writer(&mut writer, value)
+        .map_err(io::Error::from)
+        .and_then(|_| writer.write_all(b"\n"))
+        .and_then(|_| writer.flush())
+        .map_err(ParserError::io("Failed to write output"))
+}
+
+fn run(args: &Args) -> Result<u8, Failure> {
+    let mut parser = ChatParser::new();
+    parser.set_lenient(args.lenient);
+    let input = read_input(args)?;
+
+    let sample = String::from_utf8_lossy(&input[..input.len().min(DETECT_SAMPLE_BYTES)]);
+    let format = parser.detect_format(&sample);
+    if args.detect {
+        write_json_line(io::stdout().lock(), &serde_json::json!({ "format": format }))?;
+    }
+    if format.is_none() {
+        return Err(Failure { code: EXIT_UNRECOGNISED, message: "Input is not a recognised chat export".to_string() });
+    }
+    if args.detect {
+        return Ok(0);
+    }
+
+    let (messages, warnings) = parser.parse_reader_with_warnings(&input[..], args.include_system)?;
+    for warning in &warnings {
+        if args.warnings_json {
+            write_json_line(io::stderr().lock(), warning)?;
+        } else {
+            eprintln!("warning: line {}: {}", warning.line_number, warning.reason);
+        }
+    }
+
+    let out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(File::create(path).map_err(ParserError::io("Failed to create file"))?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(out);
+    if args.stats {
+        write_json_line(out, &profile_chat(&messages))?;
+    } else if args.senders {
+        for (sender, message_count) in ChatParser::sender_counts(&messages) {
+            write_json_line(&mut out, &serde_json::json!({ "sender": sender, "message_count": message_count }))?;
+        }
+    } else {
+        match args.format {
+            OutputFormat::Jsonl | OutputFormat::Redacted if args.manifest => {
+                let mut out = ManifestWriter::new(out);
+                match args.format {
+                    OutputFormat::Jsonl => write_jsonl(&messages, &mut out)?,
+                    _ => write_redacted_jsonl(&messages, &mut out)?,
+                }
+                out.finish().map_err(ParserError::io("Failed to write output"))?;
+            }
+            OutputFormat::Jsonl => write_jsonl(&messages, out)?,
+            OutputFormat::Redacted => write_redacted_jsonl(&messages, out)?,
+            OutputFormat::Json => {
+                let records: Vec<MessageRecord> = messages.iter().map(MessageRecord::from).collect();
+                write_json_line(out, &records)?;
+            }
+        }
+    }
+
+    Ok(if warnings.is_empty() { 0 } else { EXIT_PARTIAL })
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(code) => ExitCode::from(code),
+        Err(Failure { code: 0, .. }) => ExitCode::SUCCESS,
+        Err(Failure { code, message }) => {
+            eprintln!("memories-parse: {}", message);
+            ExitCode::from(code)
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::Timelike;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+use crate::message::{Message, MessageType};
+use crate::text::is_bidi_control;
+
+/// Localised call-log phrases (lowercase) as (phrase, video, missed). Missed
+/// variants come first so they win over their shorter prefixes.
+const CALL_PHRASES: &[(&str, bool, bool)] = &[
+    ("missed voice call", false, true),
+    ("missed video call", true, true),
+    ("missed group voice call", false, true),
+    ("missed group video call", true, true),
+    ("llamada de voz perdida", false, true),
+    ("videollamada perdida", true, true),
+    ("chamada de voz perdida", false, true),
+    ("chamada de vídeo perdida", true, true),
+    ("verpasster sprachanruf", false, true),
+    ("verpasster videoanruf", true, true),
+    ("appel vocal manqué", false, true),
+    ("appel vidéo manqué", true, true),
+    ("chiamata vocale persa", false, true),
+    ("videochiamata persa", true, true),
+    ("voice call", false, false),
+    ("video call", true, false),
+    ("group voice call", false, false),
+    ("group video call", true, false),
+    ("llamada de voz", false, false),
+    ("videollamada", true, false),
+    ("chamada de voz", false, false),
+    ("chamada de vídeo", true, false),
+    ("sprachanruf", false, false),
+    ("videoanruf", true, false),
+    ("appel vocal", false, false),
+    ("appel vidéo", true, false),
+    ("chiamata vocale", false, false),
+    ("videochiamata", true, false),
+];
+
+/// Recognise a call-log line. The phrase must make up the whole message,
+/// optionally followed by ", <duration>" or an iOS hint such as
+/// ", Tap to call back".
+pub fn parse_call(content: &str) -> Option<MessageType> {
+    let cleaned: String = content.chars().filter(|&c| !is_bidi_control(c)).collect();
+    let lowered = cleaned.trim().to_lowercase();
+    CALL_PHRASES.iter().find_map(|&(phrase, video, missed)| {
+        let rest = lowered.strip_prefix(phrase)?;
+        let details = if rest.is_empty() { "" } else { rest.strip_prefix(',')? };
+        Some(MessageType::Call { video, missed, duration_seconds: parse_call_duration(details) })
+    })
+}
+
+/// Parse call durations such as "12 min", "1 hr 5 min", "45 sec", "1,5 min"
+/// or "2 Std. 3 Min." into seconds; `None` when no duration is present
+pub fn parse_call_duration(text: &str) -> Option<u32> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut total = 0.0f64;
+    let mut found = false;
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let mut number = String::new();
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            number.push(chars[i]);
+            i += 1;
+        }
+        // Decimal point or decimal comma ("1.5 min", "1,5 min")
+        if i + 1 < chars.len() && matches!(chars[i], '.' | ',') && chars[i + 1].is_ascii_digit() {
+            number.push('.');
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                number.push(chars[i]);
+                i += 1;
+            }
+        }
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        let mut unit = String::new();
+        while i < chars.len() && chars[i].is_alphabetic() {
+            unit.extend(chars[i].to_lowercase());
+            i += 1;
+        }
+        let multiplier = if unit.starts_with("std") || unit.starts_with("stu") || unit.starts_with('h') {
+            3600.0
+        } else if unit.starts_with('m') {
+            60.0
+        } else if unit.starts_with('s') {
+            1.0
+        } else {
+            continue;
+        };
+        if let Ok(value) = number.parse::<f64>() {
+            total += value * multiplier;
+            found = true;
+        }
+    }
+    found.then(|| total.round() as u32)
+}
+
+/// Summary of a chat's call log
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Default)]
+pub struct CallStats {
+    pub total_calls: usize,
+    pub video_calls: usize,
+    pub missed_calls: usize,
+    /// Fraction of each caller's calls that went unanswered
+    pub missed_rate_by_caller: HashMap<String, f64>,
+    pub total_duration_seconds: u64,
+    pub longest_call_seconds: Option<u32>,
+    pub longest_call_caller: Option<String>,
+    /// Calls started in each local hour, 0–23
+    pub calls_by_hour: Vec<usize>,
+}
+
+/// Aggregate the call-log messages of a chat
+pub fn call_stats(messages: &[Message]) -> CallStats {
+    let mut stats = CallStats { calls_by_hour: vec![0; 24], ..CallStats::default() };
+    let mut by_caller: HashMap<&str, (usize, usize)> = HashMap::new();
+
+    for message in messages {
+        let MessageType::Call { video, missed, duration_seconds } = message.message_type else {
+            continue;
+        };
+        stats.total_calls += 1;
+        stats.calls_by_hour[message.timestamp.hour() as usize] += 1;
+        if video {
+            stats.video_calls += 1;
+        }
+        let caller = by_caller.entry(message.sender.as_str()).or_insert((0, 0));
+        caller.0 += 1;
+        if missed {
+            stats.missed_calls += 1;
+            caller.1 += 1;
+        }
+        if let Some(duration) = duration_seconds {
+            stats.total_duration_seconds += duration as u64;
+            if stats.longest_call_seconds.is_none_or(|longest| duration > longest) {
+                stats.longest_call_seconds = Some(duration);
+                stats.longest_call_caller = Some(message.sender.clone());
+            }
+        }
+    }
+
+    stats.missed_rate_by_caller = by_caller
+        .into_iter()
+        .map(|(caller, (calls, missed))| (caller.to_string(), missed as f64 / calls as f64))
+        .collect();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_call_durations() {
+        assert_eq!(parse_call_duration(" 12 min"), Some(720));
+        assert_eq!(parse_call_duration(" 1 hr 5 min"), Some(3900));
+        assert_eq!(parse_call_duration(" 45 sec"), Some(45));
+        assert_eq!(parse_call_duration(" 1,5 min"), Some(90));
+        assert_eq!(parse_call_duration(" 2 Std. 3 Min."), Some(7380));
+        assert_eq!(parse_call_duration(" tap to call back"), None);
+    }
+
+    #[test]
+    fn recognises_call_phrases_only_as_whole_messages() {
+        assert!(matches!(
+            parse_call("Missed video call"),
+            Some(MessageType::Call { video: true, missed: true, duration_seconds: None })
+        ));
+        assert!(matches!(
+            parse_call("\u{200E}Voice call, 12 min"),
+            Some(MessageType::Call { video: false, missed: false, duration_seconds: Some(720) })
+        ));
+        assert!(parse_call("Voice call me later").is_none());
+    }
+}
@@ -0,0 +1,2002 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::io::{BufRead, Read};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufReader;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use crate::calls::parse_call;
+use crate::error::ParserError;
+use crate::identity::{mark_own_messages, IdentityMatch};
+use crate::message::{GroupEvent, Message, MessageType};
+use crate::patterns::PatternLimits;
+use crate::reactions::parse_reaction;
+use crate::text::{nfc, normalize_whitespace, sender_key, straighten_quotes};
+use crate::type_rules::TypeRules;
+
+/// An export format the parser reads. The parser compiles its line
+/// patterns from `EXPORT_FORMATS`, so the registry can't drift from what
+/// is actually parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ExportFormat {
+    /// Format name; `ChatParser::detect_format` reports the timestamped ones
+    pub id: &'static str,
+    /// Where exports in this format come from
+    pub platform: &'static str,
+    /// Regex matching the first line of a message
+    pub pattern: &'static str,
+    pub example: &'static str,
+    pub locale_notes: &'static str,
+}
+
+/// "[18/05/2023, 08:39:07] John: Hello", the bracketed export
+pub const WHATSAPP_IOS: ExportFormat = ExportFormat {
+    id: "whatsapp_ios",
+    platform: "WhatsApp \"Export chat\" (iOS layout)",
+    // A timestamped line is either "sender: content" or a system notice
+    // without a sender. Seconds are optional (some exports write "HH:MM");
+    // missing seconds parse as :00. Years may have two digits, expanded
+    // with the year pivot
+    pattern: r"^\[(\d{2}/\d{2}/(?:\d{4}|\d{2}), \d{2}:\d{2}(?::\d{2})?)\] (.+)$",
+    example: "[18/05/2023, 08:39:07] John: Hello",
+    locale_notes: "Day-first dates (DD/MM/YYYY or DD/MM/YY) and a 24-hour clock; \
+                   times are the exporting device's local time",
+};
+
+/// "Alice: hey" lines copied out of the app, read by `parse_loose_text`
+pub const COPIED_TEXT: ExportFormat = ExportFormat {
+    id: "copied_text",
+    platform: "Text copied from a WhatsApp chat",
+    pattern: r"^([^:]{1,64}): (.*)$",
+    example: "Alice: hey",
+    locale_notes: "No timestamps; messages get synthetic ones, so time-based statistics are refused",
+};
+
+/// Every format the parser reads
+pub const EXPORT_FORMATS: &[ExportFormat] = &[WHATSAPP_IOS, COPIED_TEXT];
+
+/// Reusable WhatsApp chat parser; the compiled patterns are shared across calls
+#[cfg_attr(feature = "python", pyclass)]
+pub struct ChatParser {
+    line_pattern: Regex,
+    sender_pattern: Regex,
+    timestamp_pattern: Regex,
+    loose_pattern: Regex,
+    created_pattern: Regex,
+    subject_pattern: Regex,
+    icon_pattern: Regex,
+    block_pattern: Regex,
+    disappearing_pattern: Regex,
+    self_join_pattern: Regex,
+    broadcast_sender_pattern: Regex,
+    broadcast_content_pattern: Regex,
+    type_rules: TypeRules,
+    lenient: bool,
+    line_budget: Duration,
+    year_pivot: i32,
+    whitespace: WhitespaceNormalization,
+    known_senders: Option<HashSet<String>>,
+    interpolate_subminute: bool,
+}
+
+impl ChatParser {
+    pub fn new() -> Self {
+        ChatParser {
+            line_pattern: Regex::new(WHATSAPP_IOS.pattern).unwrap(),
+            // Content may be empty ("Alice: " or "Alice:"); such lines are kept as
+            // empty text messages
+            sender_pattern: Regex::new(r"^([^:]+):(?: (.*))?$").unwrap(),
+            timestamp_pattern: Regex::new(r"(\d{2})/(\d{2})/(\d{4}|\d{2}), (\d{2}):(\d{2})(?::(\d{2}))?").unwrap(),
+            loose_pattern: Regex::new(COPIED_TEXT.pattern).unwrap(),
+            // System phrases
+            created_pattern: Regex::new(r#"^([^:]+?) created (?:the )?group "(.*)"$"#).unwrap(),
+            subject_pattern: Regex::new(r#"^([^:]+?) changed the subject (?:from ".*" )?to "(.*)"$"#).unwrap(),
+            icon_pattern: Regex::new(r"^([^:]+?) changed this group's icon$").unwrap(),
+            block_pattern: Regex::new(r"^You (un)?blocked this contact\.?(?: Tap to (?:un)?block\.)?$").unwrap(),
+            disappearing_pattern: Regex::new(
+                r"^(?:([^:]+?) turned (on|off) disappearing messages|Disappearing messages were turned (on|off))(?:\..*)?$",
+            )
+            .unwrap(),
+            self_join_pattern: Regex::new(r"^(?:[^:]+? added you|You were added|You joined using this group's invite link)$").unwrap(),
+            // Broadcast-list markers: "Alice (Broadcast): ..." or "Alice: [Broadcast] ..."
+            broadcast_sender_pattern: Regex::new(r"^(.+?) \((?i:broadcast)\)$").unwrap(),
+            broadcast_content_pattern: Regex::new(r"(?s)^\[(?i:broadcast)\] ?(.*)$").unwrap(),
+            type_rules: TypeRules::default(),
+            lenient: false,
+            line_budget: PatternLimits::default().line_budget,
+            year_pivot: DEFAULT_YEAR_PIVOT,
+            whitespace: WhitespaceNormalization::Preserve,
+            known_senders: None,
+            interpolate_subminute: false,
+        }
+    }
+
+    /// Expand two-digit years to the year in `pivot..pivot + 100` that ends
+    /// in those digits. The default, `DEFAULT_YEAR_PIVOT`, reads 00–99 as
+    /// 2000–2099; a pivot of 1950 reads 95 as 1995 and 05 as 2005. See
+    /// `sliding_year_pivot` for a window that moves with the current year.
+    pub fn set_year_pivot(&mut self, pivot: i32) {
+        self.year_pivot = pivot;
+    }
+
+    /// A pivot that reads two-digit years as at most `years_ahead` years
+    /// after the current one, and otherwise in the past
+    pub fn sliding_year_pivot(years_ahead: i32) -> i32 {
+        Local::now().year() + years_ahead - 99
+    }
+
+    /// In lenient mode a message header with an impossible timestamp (say
+    /// 31/02/2023) is skipped, along with its continuation lines, and
+    /// reported as a `ParseWarning` instead of failing the whole parse
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Whether `set_lenient` is on
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// In lenient mode, also skip a message whose header and type-rule
+    /// matching together take longer than `budget`, and a continuation line
+    /// whose header check does, reporting each as a `ParseWarning`. The
+    /// default is `PatternLimits::default().line_budget`. Strict parses are
+    /// never cut short.
+    pub fn set_line_budget(&mut self, budget: Duration) {
+        self.line_budget = budget;
+    }
+
+    /// Clean up whitespace in message content as later parses build it (see
+    /// `WhitespaceNormalization`); content is kept as exported by default
+    pub fn set_whitespace_normalization(&mut self, normalization: WhitespaceNormalization) {
+        self.whitespace = normalization;
+    }
+
+    /// Strict header validation: with a sender set, a timestamped line only
+    /// starts a message when its timestamp parses and its sender is one of
+    /// `senders` (matched like `Message::sender`); anything else, such as a
+    /// pasted log line, stays part of the previous message. Without one
+    /// (`None`, the default) a parse switches to strict mode on its own when
+    /// `looks_like_pasted_sender` flags a sender seen only once, taking the
+    /// other senders as known. See `detect_senders` for building the set.
+    pub fn set_known_senders<I: IntoIterator<Item = S>, S: AsRef<str>>(&mut self, senders: Option<I>) {
+        self.known_senders =
+            senders.map(|senders| senders.into_iter().map(|sender| sender_key(sender.as_ref())).collect());
+    }
+
+    /// Spread messages that share a minute-precision timestamp (as Android
+    /// exports write them) evenly over that minute, keeping their order:
+    /// in a run of n messages the i-th moves i/n of a minute later. Moved
+    /// messages get `timestamp_interpolated`; a message alone in its minute
+    /// stays put. Every minute-precision message keeps the exported text in
+    /// `original_timestamp`, so `merge_chats` can spread runs that span
+    /// exports. The offsets depend only on the run, so re-parses agree. Off
+    /// by default, and not applied by `parse_reader_batched` or
+    /// `parse_sample`, whose runs may straddle batches.
+    pub fn set_interpolate_subminute(&mut self, interpolate: bool) {
+        self.interpolate_subminute = interpolate;
+    }
+
+    /// Classify messages with `rules` before the built-in detection, in every
+    /// parse this parser runs from now on
+    pub fn set_type_rules(&mut self, rules: TypeRules) {
+        self.type_rules = rules;
+    }
+
+    /// Parse a chat export file into `Message` values, optionally keeping system messages
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_file(&self, file_path: &str, include_system: bool) -> Result<Vec<Message>, ParserError> {
+        let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+        self.parse_reader(BufReader::new(file), include_system)
+    }
+
+    /// Parse an export that may have been cut off mid-write. Only complete
+    /// lines are parsed; a final line with no trailing newline is returned
+    /// verbatim as the second element instead, so a caller following a
+    /// growing file can prepend it to the next chunk. A complete export that
+    /// merely lacks the final newline therefore reports its last line as
+    /// incomplete.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_partial_file(
+        &self,
+        file_path: &str,
+        include_system: bool,
+    ) -> Result<(Vec<Message>, Option<String>), ParserError> {
+        let bytes = std::fs::read(file_path).map_err(ParserError::io("Failed to open file"))?;
+        let (complete, incomplete) = split_incomplete_line(&bytes);
+        Ok((self.parse_reader(complete, include_system)?, incomplete))
+    }
+
+    /// The first `max_messages` messages of an export, for a quick preview
+    /// of a huge file. Reading stops as soon as that many are complete; the
+    /// last one is finished by the next header line (or the end of the
+    /// file), so it has all its continuation lines. Parsed as by
+    /// `parse_reader_batched`, so neither `correct_split_senders` nor
+    /// sub-minute interpolation is applied.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_sample(&self, file_path: &str, max_messages: usize, include_system: bool) -> Result<Vec<Message>, ParserError> {
+        if max_messages == 0 {
+            return Ok(Vec::new());
+        }
+        let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+        let mut sample = Vec::new();
+        self.parse_reader_batched(BufReader::new(file), include_system, max_messages, |batch, _| {
+            sample = batch;
+            ControlFlow::Break(())
+        })?;
+        Ok(sample)
+    }
+
+    /// Parse several export files with the same compiled patterns, keyed by
+    /// path. Stops at the first file that can't be parsed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_many(&self, paths: &[&str], include_system: bool) -> Result<HashMap<String, Vec<Message>>, ParserError> {
+        paths
+            .iter()
+            .map(|path| Ok((path.to_string(), self.parse_file(path, include_system)?)))
+            .collect()
+    }
+
+    /// Parse the parts of an export WhatsApp split because of its size, in
+    /// whatever order they are given, into one timeline. Parts (text files,
+    /// or ZIPs with the `zip` feature) are ordered by the dates they cover
+    /// and stitched where the head of each part repeats the tail of the
+    /// previous one; parts whose overlap can't be aligned fall back to
+    /// `merge_chats` deduplication. Parts that don't overlap at all suggest a
+    /// missing part and are reported as gaps.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_split_export(
+        &self,
+        paths: &[&str],
+        include_system: bool,
+    ) -> Result<(Vec<Message>, Vec<ExportGap>), ParserError> {
+        let mut parts = paths
+            .iter()
+            .map(|path| Ok((*path, self.parse_export_part(path, include_system)?)))
+            .collect::<Result<Vec<_>, ParserError>>()?;
+        parts.retain(|(_, messages)| !messages.is_empty());
+        parts.sort_by_key(|(_, messages)| (messages[0].timestamp, messages[messages.len() - 1].timestamp));
+
+        let mut gaps = Vec::new();
+        let mut parts = parts.into_iter();
+        let Some((mut previous_path, mut stitched)) = parts.next() else { return Ok((Vec::new(), gaps)) };
+        for (path, part) in parts {
+            let last = stitched[stitched.len() - 1].timestamp;
+            if part[0].timestamp > last {
+                gaps.push(ExportGap {
+                    after: previous_path.to_string(),
+                    before: path.to_string(),
+                    from: last,
+                    to: part[0].timestamp,
+                });
+                stitched.extend(part);
+            } else if let Some(overlap) = aligned_overlap(&stitched, &part) {
+                stitched.extend(part.into_iter().skip(overlap));
+            } else {
+                stitched = merge_chats(vec![stitched, part]);
+            }
+            previous_path = path;
+        }
+        respread_interpolated(&mut stitched);
+
+        for (index, message) in stitched.iter_mut().enumerate() {
+            message.id = format!("msg_{}", index + 1);
+        }
+        mark_grouped(&mut stitched);
+        annotate_gaps(&mut stitched);
+        Ok((stitched, gaps))
+    }
+
+    /// Parse one part of a split export, unpacking it first if it is a ZIP
+    #[cfg(not(target_arch = "wasm32"))]
+    fn parse_export_part(&self, path: &str, include_system: bool) -> Result<Vec<Message>, ParserError> {
+        #[cfg(feature = "zip")]
+        if path.to_ascii_lowercase().ends_with(".zip") {
+            let file = File::open(Path::new(path)).map_err(ParserError::io("Failed to open file"))?;
+            let text = crate::archive::read_chat_from_zip(BufReader::new(file))?;
+            return self.parse_reader(&text[..], include_system);
+        }
+        self.parse_file(path, include_system)
+    }
+
+    /// Parse in-memory text according to `config`, marking own messages when
+    /// a user identity is configured
+    pub fn parse_str_with_config(&self, text: &str, config: &ParseConfig) -> Result<Vec<Message>, ParserError> {
+        let mode = IdentityMatch::parse(&config.match_mode)?;
+        let type_rules = TypeRules::new(&config.type_rules)?;
+        let mut messages = self.parse_str(text, config.include_system)?;
+        type_rules.reclassify(&mut messages);
+        if let Some(identity) = &config.user_identity {
+            mark_own_messages(&mut messages, identity, mode);
+        }
+        if config.parse_replies {
+            self.parse_reply_previews(&mut messages);
+        }
+        Ok(messages)
+    }
+
+    /// Parse an export that is already in memory
+    pub fn parse_str(&self, text: &str, include_system: bool) -> Result<Vec<Message>, ParserError> {
+        self.parse_bytes_with_warnings(text.as_bytes(), include_system).map(|(messages, _)| messages)
+    }
+
+    /// Parse an export from any buffered reader
+    pub fn parse_reader<R: BufRead>(&self, reader: R, include_system: bool) -> Result<Vec<Message>, ParserError> {
+        self.parse_reader_with_warnings(reader, include_system).map(|(messages, _)| messages)
+    }
+
+    /// Parse an export from any buffered reader, also returning warnings about
+    /// lines that could not be attached to a message. The input is read in
+    /// full first, as a second, strict pass may be needed (see
+    /// `set_known_senders`).
+    pub fn parse_reader_with_warnings<R: BufRead>(
+        &self,
+        mut reader: R,
+        include_system: bool,
+    ) -> Result<(Vec<Message>, Vec<ParseWarning>), ParserError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(ParserError::io("Failed to read line"))?;
+        self.parse_bytes_with_warnings(&bytes, include_system)
+    }
+
+    fn parse_bytes_with_warnings(
+        &self,
+        bytes: &[u8],
+        include_system: bool,
+    ) -> Result<(Vec<Message>, Vec<ParseWarning>), ParserError> {
+        // Inferring the sender set needs the notices, even if they are dropped
+        let infer = self.known_senders.is_none();
+        let mut warnings = Vec::new();
+        let (mut messages, mut header_lines) =
+            self.collect_messages(bytes, include_system || infer, self.known_senders.as_ref(), &mut warnings)?;
+        if infer {
+            match inferred_known_senders(&messages) {
+                Some(known) => {
+                    warnings.clear();
+                    (messages, header_lines) = self.collect_messages(bytes, include_system, Some(&known), &mut warnings)?;
+                }
+                None if !include_system => drop_system_events(&mut messages, &mut header_lines),
+                None => {}
+            }
+        }
+
+        for correction in self.correct_split_senders(&mut messages) {
+            warnings.push(ParseWarning {
+                line_number: header_lines[correction.index],
+                line: format!("{}: {}", correction.sender_before, correction.content_before),
+                reason: format!("Sender corrected from \"{}\" to \"{}\"", correction.sender_before, correction.sender_after),
+            });
+        }
+        if self.interpolate_subminute {
+            interpolate_subminute(&mut messages);
+        }
+        mark_grouped(&mut messages);
+        annotate_gaps(&mut messages);
+        Ok((messages, warnings))
+    }
+
+    /// One scan collected into messages and the header line of each kept
+    /// message (for reporting sender corrections)
+    fn collect_messages(
+        &self,
+        bytes: &[u8],
+        include_system: bool,
+        known_senders: Option<&HashSet<String>>,
+        warnings: &mut Vec<ParseWarning>,
+    ) -> Result<(Vec<Message>, Vec<usize>), ParserError> {
+        let mut messages = Vec::new();
+        let mut header_lines = Vec::new();
+        self.scan_messages(bytes, include_system, known_senders, warnings, |message, position| {
+            messages.push(message);
+            header_lines.push(position.header_line);
+            ControlFlow::Continue(())
+        })?;
+        Ok((messages, header_lines))
+    }
+
+    /// Count the messages of each sender (by display name, as first seen) in
+    /// a plain parse, without strict header validation. System notices are
+    /// not counted. Feed the senders you trust to `set_known_senders`.
+    pub fn detect_senders<R: BufRead>(&self, reader: R) -> Result<HashMap<String, usize>, ParserError> {
+        let mut counts = HashMap::new();
+        let mut warnings = Vec::new();
+        self.scan_messages(reader, false, None, &mut warnings, |message, _| {
+            *counts.entry(message.sender_display).or_insert(0) += 1;
+            ControlFlow::Continue(())
+        })?;
+        Ok(counts)
+    }
+
+    /// Parse an export in batches of `batch_size` messages, handing each to
+    /// `on_batch` with the progress so far, so memory stays bounded by one
+    /// batch plus the message being read however large the input is.
+    /// `on_batch` returning `ControlFlow::Break` stops the parse. Returns the
+    /// number of messages delivered. Ids, grouping and gaps carry across
+    /// batches, but `correct_split_senders`, which needs the whole chat, is
+    /// not applied, and neither is sub-minute interpolation (a minute's run
+    /// may straddle batches), so minute-precision messages keep their
+    /// exported minute. Senders given to `set_known_senders` are checked,
+    /// but without them there is no switch to strict mode, which needs a
+    /// second pass. A `batch_size` of 0 is treated as 1.
+    pub fn parse_reader_batched<R: BufRead>(
+        &self,
+        reader: R,
+        include_system: bool,
+        batch_size: usize,
+        mut on_batch: impl FnMut(Vec<Message>, &BatchProgress) -> ControlFlow<()>,
+    ) -> Result<usize, ParserError> {
+        let batch_size = batch_size.max(1);
+        let mut batch: Vec<Message> = Vec::with_capacity(batch_size.min(BATCH_PREALLOCATE_LIMIT));
+        let mut delivered = 0;
+        // (sender, timestamp, is system event) of the last message, for grouping and gaps
+        let mut previous: Option<(String, DateTime<Local>, bool)> = None;
+        let mut warnings = Vec::new();
+        let mut stopped = false;
+
+        let mut deliver = |batch: &mut Vec<Message>, lines_read: usize, bytes_read: u64| {
+            delivered += batch.len();
+            let progress = BatchProgress { messages_delivered: delivered, lines_read, bytes_read };
+            let full = std::mem::replace(batch, Vec::with_capacity(batch_size.min(BATCH_PREALLOCATE_LIMIT)));
+            on_batch(full, &progress)
+        };
+        let known_senders = self.known_senders.as_ref();
+        let end = self.scan_messages(reader, include_system, known_senders, &mut warnings, |mut message, position| {
+            let is_system = matches!(message.message_type, MessageType::SystemEvent(_));
+            if let Some((sender, timestamp, previous_system)) = &previous {
+                message.grouped_with_previous = !previous_system
+                    && !is_system
+                    && *sender == message.sender
+                    && (message.timestamp - *timestamp).num_seconds() <= GROUPING_WINDOW_SECONDS;
+                message.gap_before_seconds = Some((message.timestamp - *timestamp).num_seconds().max(0) as u64);
+            }
+            previous = Some((message.sender.clone(), message.timestamp, is_system));
+            batch.push(message);
+            if batch.len() < batch_size {
+                return ControlFlow::Continue(());
+            }
+            let flow = deliver(&mut batch, position.lines_read, position.bytes_read);
+            stopped = flow.is_break();
+            flow
+        })?;
+        if !stopped && !batch.is_empty() {
+            // The last batch; there is nothing left to stop
+            let _ = deliver(&mut batch, end.lines_read, end.bytes_read);
+        }
+        Ok(delivered)
+    }
+
+    /// The line loop behind every reader-based parse: finalized messages
+    /// (with ids) are handed to `emit` in order, with the reader's position
+    /// when each was completed. Stops early when `emit` breaks. Returns the
+    /// final position. With `known_senders`, headers that fail
+    /// `pasted_header` are read as continuation lines. In lenient mode,
+    /// lines and messages over the line budget are skipped with a warning.
+    fn scan_messages<R: BufRead>(
+        &self,
+        reader: R,
+        include_system: bool,
+        known_senders: Option<&HashSet<String>>,
+        warnings: &mut Vec<ParseWarning>,
+        mut emit: impl FnMut(Message, ScanPosition) -> ControlFlow<()>,
+    ) -> Result<ScanPosition, ParserError> {
+        let mut reader = CountingReader { inner: reader, bytes_read: 0 };
+        let mut current_message: Option<Message> = None;
+        // Matching time spent on the current message's header line
+        let mut header_time = Duration::ZERO;
+        let mut kept = 0;
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        let mut current_line = 0;
+        // Lines after a header skipped in lenient mode belong to the skipped message
+        let mut skipping = false;
+        let position = |header_line: usize, lines_read: usize, bytes_read: u64| ScanPosition {
+            header_line,
+            lines_read,
+            bytes_read,
+        };
+
+        // Process each line
+        while let Some(line) = read_line_lossy(&mut reader, &mut buffer).map_err(ParserError::io("Failed to read line"))? {
+            line_number += 1;
+            let clock = self.budget_clock();
+
+            // Check if line starts a new message
+            let mut captures = self.line_pattern.captures(&line);
+            if let (Some(header), Some(known_senders)) = (&captures, known_senders) {
+                if let Some(reason) = self.pasted_header(header, known_senders) {
+                    warnings.push(ParseWarning {
+                        line_number,
+                        line: line.clone(),
+                        reason: format!("Read as part of the previous message: {}", reason),
+                    });
+                    captures = None;
+                }
+            }
+            if let Some(captures) = captures {
+                // If we have a current message being built, finalize it
+                if let Some(message) = current_message.take() {
+                    let finished =
+                        self.finish_message(message, include_system, &mut kept, current_line, header_time, warnings);
+                    if let Some(message) = finished {
+                        let at = position(current_line, line_number - 1, reader.bytes_read);
+                        if emit(message, at).is_break() {
+                            return Ok(at);
+                        }
+                    }
+                }
+                // Extract data from the new message
+                let timestamp_str = captures.get(1).unwrap().as_str();
+                let rest = captures.get(2).unwrap().as_str();
+
+                // Parse the timestamp
+                let timestamp = match self.parse_timestamp(timestamp_str) {
+                    Ok(timestamp) => timestamp,
+                    Err(reason) if self.lenient => {
+                        warnings.push(ParseWarning {
+                            line_number,
+                            line: line.clone(),
+                            reason: format!("Message skipped: {}", reason),
+                        });
+                        skipping = true;
+                        continue;
+                    }
+                    Err(reason) => return Err(ParserError::InvalidTimestamp(reason)),
+                };
+                skipping = false;
+                current_line = line_number;
+
+                // System notices have no "sender: " prefix, or carry a known system
+                // phrase as content (iOS prefixes them with the group name)
+                let (sender, content, event) = match self.sender_pattern.captures(rest) {
+                    Some(parts) if self.detect_group_event(rest, timestamp).is_none() => {
+                        let sender = parts.get(1).unwrap().as_str().to_string();
+                        let content = parts.get(2).map_or("", |content| content.as_str()).to_string();
+                        let event = self.detect_group_event(&content, timestamp);
+                        (sender, content, event)
+                    }
+                    _ => {
+                        let event = self
+                            .detect_group_event(rest, timestamp)
+                            .unwrap_or_else(|| GroupEvent::Other { text: rest.to_string() });
+                        (String::new(), rest.to_string(), Some(event))
+                    }
+                };
+                // Reaction notices have no "sender: " prefix either, but name who reacted
+                let reaction = match &event {
+                    Some(GroupEvent::Other { text }) => parse_reaction(text),
+                    _ => None,
+                };
+                let (sender_display, content, broadcast) = match (&event, &reaction) {
+                    (_, Some(MessageType::Reaction { reactor, .. })) => (reactor.clone(), content, false),
+                    (None, _) => self.strip_broadcast_marker(sender, content),
+                    (Some(_), _) => (sender, content, false),
+                };
+                let sender = sender_key(&sender_display);
+                header_time = clock.map_or(Duration::ZERO, |clock| clock.elapsed());
+                if header_time > self.line_budget {
+                    warnings.push(self.over_budget_warning(line_number, &line, header_time));
+                    skipping = true;
+                    continue;
+                }
+
+                // Create new message; the type is settled once continuation lines are in
+                current_message = Some(Message {
+                    id: String::new(),
+                    timestamp,
+                    sender,
+                    sender_display,
+                    content,
+                    message_type: match (reaction, event) {
+                        (Some(reaction), _) => reaction,
+                        (None, Some(event)) => MessageType::SystemEvent(event),
+                        (None, None) => MessageType::Text,
+                    },
+                    sentiment_score: None,
+                    timestamp_synthetic: false,
+                    timestamp_interpolated: false,
+                    // Minute-precision headers ("HH:MM") are candidates for interpolation
+                    original_timestamp: (self.interpolate_subminute && timestamp_str.matches(':').count() == 1)
+                        .then(|| timestamp_str.to_string()),
+                    is_own: false,
+                    broadcast,
+                    grouped_with_previous: false,
+                    media_filename: None,
+                    caption: None,
+                    reply_to: None,
+                    reply_to_timestamp: None,
+                    gap_before_seconds: None,
+                    word_count: 0,
+                });
+            } else if let Some(ref mut message) = current_message {
+                let elapsed = clock.map(|clock| clock.elapsed());
+                if let Some(elapsed) = elapsed.filter(|elapsed| *elapsed > self.line_budget) {
+                    warnings.push(self.over_budget_warning(line_number, &line, elapsed));
+                    continue;
+                }
+                // If this line doesn't match the pattern, it's a continuation of the previous message
+                message.content.push('\n');
+                message.content.push_str(&line);
+            } else if !skipping && !line.trim().is_empty() {
+                warnings.push(ParseWarning {
+                    line_number,
+                    line,
+                    reason: "Text before the first message was dropped".to_string(),
+                });
+            }
+        }
+
+        // Don't forget the last message
+        let end = position(current_line, line_number, reader.bytes_read);
+        if let Some(message) = current_message {
+            if let Some(message) =
+                self.finish_message(message, include_system, &mut kept, current_line, header_time, warnings)
+            {
+                // Nothing is left to stop, so a break needs no handling
+                let _ = emit(message, end);
+            }
+        }
+        Ok(position(0, line_number, reader.bytes_read))
+    }
+
+    /// A clock for the line budget, running only in lenient mode (and never
+    /// on wasm, which has no `Instant`)
+    fn budget_clock(&self) -> Option<Instant> {
+        (self.lenient && cfg!(not(target_arch = "wasm32"))).then(Instant::now)
+    }
+
+    /// The warning for a line or message skipped after `elapsed` of
+    /// matching; only the start of the line is kept, as it may be huge
+    fn over_budget_warning(&self, line_number: usize, line: &str, elapsed: Duration) -> ParseWarning {
+        ParseWarning {
+            line_number,
+            line: line.chars().take(SKIPPED_LINE_PREVIEW_CHARS).collect(),
+            reason: format!(
+                "Skipped: matching took {} ms, over the {} ms line budget",
+                elapsed.as_millis(),
+                self.line_budget.as_millis()
+            ),
+        }
+    }
+
+    /// Why a header line is not a real message header in strict mode: its
+    /// timestamp doesn't parse, its sender isn't known, or it is an
+    /// unrecognised notice with a `pasted_notice_key`. Recognised system
+    /// notices always pass.
+    fn pasted_header(&self, header: &regex::Captures, known_senders: &HashSet<String>) -> Option<String> {
+        let timestamp = match self.parse_timestamp(&header[1]) {
+            Ok(timestamp) => timestamp,
+            Err(reason) => return Some(reason),
+        };
+        let rest = &header[2];
+        let Some(parts) = self.sender_pattern.captures(rest) else {
+            if self.detect_group_event(rest, timestamp).is_some() {
+                return None;
+            }
+            return pasted_notice_key(rest).map(|key| format!("\"{}\" is not a system notice", key));
+        };
+        let content = parts.get(2).map_or("", |content| content.as_str());
+        if self.detect_group_event(rest, timestamp).is_some() || self.detect_group_event(content, timestamp).is_some() {
+            return None;
+        }
+        let (sender, _, _) = self.strip_broadcast_marker(parts[1].to_string(), String::new());
+        (!known_senders.contains(&sender_key(&sender))).then(|| format!("\"{}\" is not a known sender", sender))
+    }
+
+    /// Name the export format of `sample` (the start of an export), or `None`
+    /// when no line in it looks like a message header. Only the iOS-style
+    /// "[DD/MM/YYYY, HH:MM:SS] " format (or "DD/MM/YY") is recognised.
+    pub fn detect_format(&self, sample: &str) -> Option<&'static str> {
+        sample
+            .lines()
+            .any(|line| self.line_pattern.is_match(line.trim_start_matches('\u{feff}')))
+            .then_some(WHATSAPP_IOS.id)
+    }
+
+    /// Parse chat text copied from the app ("Alice: hey" lines, no timestamps).
+    /// A "Name: " prefix only starts a message when the name is a known participant
+    /// or occurs at least twice, so "Note: buy milk" stays part of the previous
+    /// message. Timestamps are synthesised from `base`, `spacing` apart.
+    pub fn parse_loose_text(
+        &self,
+        text: &str,
+        participants: &[String],
+        base: DateTime<Local>,
+        spacing: chrono::Duration,
+    ) -> Vec<Message> {
+        // First pass: count candidate sender names
+        let mut candidates: HashMap<&str, usize> = HashMap::new();
+        for line in text.lines() {
+            if let Some(captures) = self.loose_pattern.captures(line) {
+                *candidates.entry(captures.get(1).unwrap().as_str().trim()).or_insert(0) += 1;
+            }
+        }
+        let is_sender = |name: &str| {
+            participants.iter().any(|participant| participant == name)
+                || candidates.get(name).copied().unwrap_or(0) >= 2
+        };
+
+        // Second pass: build messages, treating everything else as continuation lines
+        let mut messages: Vec<Message> = Vec::new();
+        for line in text.lines() {
+            let header = self
+                .loose_pattern
+                .captures(line)
+                .filter(|captures| is_sender(captures.get(1).unwrap().as_str().trim()));
+
+            if let Some(captures) = header {
+                let position = messages.len() as i32;
+                let sender_display = captures.get(1).unwrap().as_str().trim().to_string();
+                messages.push(Message {
+                    id: format!("msg_{}", messages.len() + 1),
+                    timestamp: base + spacing * position,
+                    sender: sender_key(&sender_display),
+                    sender_display,
+                    content: captures.get(2).unwrap().as_str().to_string(),
+                    message_type: MessageType::Text,
+                    sentiment_score: None,
+                    timestamp_synthetic: true,
+                    timestamp_interpolated: false,
+                    original_timestamp: None,
+                    is_own: false,
+                    broadcast: false,
+                    grouped_with_previous: false,
+                    media_filename: None,
+                    caption: None,
+                    reply_to: None,
+                    reply_to_timestamp: None,
+                    gap_before_seconds: None,
+                    word_count: 0,
+                });
+            } else if let Some(message) = messages.last_mut() {
+                message.content.push('\n');
+                message.content.push_str(line);
+            }
+        }
+
+        for message in messages.iter_mut() {
+            message.message_type = self.detect_message_type(&message.content);
+            split_caption(message);
+            message.recount_words();
+        }
+        mark_grouped(&mut messages);
+        messages
+    }
+
+    /// Move a leading reply preview out of each message's content into
+    /// `reply_to`. A preview is the run of "> " lines the content starts with;
+    /// when its first line is itself a message header ("> [DD/MM/YYYY, HH:MM:SS]
+    /// Bob: ..."), that timestamp becomes `reply_to_timestamp`. Exports rarely
+    /// include previews, so this is opt-in and best-effort: a message that is
+    /// nothing but a quote is left alone.
+    pub fn parse_reply_previews(&self, messages: &mut [Message]) {
+        for message in messages.iter_mut() {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            let quoted: Vec<&str> = message.content.lines().take_while(|line| line.starts_with('>')).collect();
+            if quoted.is_empty() || quoted.len() == message.content.lines().count() {
+                continue;
+            }
+            let preview: Vec<&str> = quoted.iter().map(|line| line[1..].trim_start()).collect();
+            let reply_to_timestamp = self
+                .line_pattern
+                .captures(preview[0])
+                .and_then(|captures| self.parse_timestamp(captures.get(1).unwrap().as_str()).ok());
+            let reply_to = preview.join("\n");
+            let content = message.content.lines().skip(quoted.len()).collect::<Vec<_>>().join("\n");
+
+            message.reply_to = Some(reply_to);
+            message.reply_to_timestamp = reply_to_timestamp;
+            message.content = content;
+            message.message_type = self.detect_message_type(&message.content);
+            split_caption(message);
+            message.recount_words();
+        }
+    }
+
+    /// Remove a broadcast-list marker from a message, reporting whether one was
+    /// found. Recognised markers are a "(Broadcast)" suffix on the sender and a
+    /// "[Broadcast]" prefix on the content; exports without either are
+    /// indistinguishable from direct messages.
+    fn strip_broadcast_marker(&self, sender: String, content: String) -> (String, String, bool) {
+        if let Some(captures) = self.broadcast_sender_pattern.captures(&sender) {
+            return (captures.get(1).unwrap().as_str().to_string(), content, true);
+        }
+        if let Some(captures) = self.broadcast_content_pattern.captures(&content) {
+            return (sender, captures.get(1).unwrap().as_str().to_string(), true);
+        }
+        (sender, content, false)
+    }
+
+    /// Finalize a message and number it as the next of the `kept` so far,
+    /// dropping system messages unless requested. In lenient mode a message
+    /// whose type matching, on top of the `header_time` its header line
+    /// took, runs over the line budget is dropped with a warning.
+    fn finish_message(
+        &self,
+        mut message: Message,
+        include_system: bool,
+        kept: &mut usize,
+        header_line: usize,
+        header_time: Duration,
+        warnings: &mut Vec<ParseWarning>,
+    ) -> Option<Message> {
+        if matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) && !include_system {
+            return None;
+        }
+        if let Some(trim_continuations) = self.whitespace.trims_continuations() {
+            message.content = normalize_whitespace(&message.content, trim_continuations);
+        }
+        let clock = self.budget_clock();
+        let mut message = self.finalize_message(message);
+        // Type rules run over the whole content and count toward the header line's budget
+        let elapsed = clock.map(|clock| header_time + clock.elapsed());
+        if let Some(elapsed) = elapsed.filter(|elapsed| *elapsed > self.line_budget) {
+            let line = format!("{}: {}", message.sender_display, message.content);
+            warnings.push(self.over_budget_warning(header_line, &line, elapsed));
+            return None;
+        }
+        *kept += 1;
+        message.id = format!("msg_{}", kept);
+        Some(message)
+    }
+
+    /// Re-split messages whose sender looks like a regular sender run
+    /// together with the start of the content, as happens when a display
+    /// name or the content contains " - " ("Alice - Meeting: agenda" where
+    /// Alice is a regular); a sender never holds a ':', which ends it. Only
+    /// senders with fewer than `SENDER_CORRECTION_MAX_COUNT` messages whose
+    /// name contains " - " are considered; regulars (every sender with at
+    /// least that many messages) are never rewritten. The new sender is the longest
+    /// regular whose name, followed by " - ", starts the original
+    /// "sender: content" text. Media messages with a caption are left alone.
+    pub fn correct_split_senders(&self, messages: &mut [Message]) -> Vec<SenderCorrection> {
+        let counts = Self::sender_counts(messages);
+        let count_of: HashMap<&str, u32> = counts.iter().map(|(sender, count)| (sender.as_str(), *count)).collect();
+        let mut regulars: Vec<&str> = counts
+            .iter()
+            .filter(|(_, count)| *count as usize >= SENDER_CORRECTION_MAX_COUNT)
+            .map(|(sender, _)| sender.as_str())
+            .collect();
+        regulars.sort_by_key(|sender| std::cmp::Reverse(sender.len()));
+        let mut displays: HashMap<&str, &str> = HashMap::new();
+        for message in messages.iter() {
+            displays.entry(message.sender.as_str()).or_insert(message.sender_display.as_str());
+        }
+
+        let mut fixes = Vec::new();
+        for (index, message) in messages.iter().enumerate() {
+            let rare = count_of.get(message.sender.as_str()).is_some_and(|count| (*count as usize) < SENDER_CORRECTION_MAX_COUNT);
+            if !rare
+                || message.caption.is_some()
+                || matches!(message.message_type, MessageType::SystemEvent(_))
+                || !message.sender.contains(" - ")
+            {
+                continue;
+            }
+            let raw = format!("{}: {}", message.sender, message.content);
+            let resplit = regulars.iter().find_map(|regular| {
+                let content = raw.strip_prefix(regular)?.strip_prefix(" - ")?;
+                Some((*regular, content.to_string()))
+            });
+            if let Some((regular, content)) = resplit {
+                fixes.push((index, regular.to_string(), displays[regular].to_string(), content));
+            }
+        }
+
+        fixes
+            .into_iter()
+            .map(|(index, sender, sender_display, content)| {
+                let message = &mut messages[index];
+                let sender_before = std::mem::replace(&mut message.sender, sender);
+                message.sender_display = sender_display;
+                let content_before = std::mem::replace(&mut message.content, content);
+                message.message_type = self.detect_message_type(&message.content);
+                split_caption(message);
+                message.recount_words();
+                SenderCorrection {
+                    index,
+                    sender_before,
+                    content_before,
+                    sender_after: message.sender.clone(),
+                    content_after: message.content.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Recognise a system phrase such as a group creation or subject change.
+    /// Some exports write the phrases with typographic quotes, so those are
+    /// straightened before matching.
+    fn detect_group_event(&self, text: &str, timestamp: DateTime<Local>) -> Option<GroupEvent> {
+        let text = &*straighten_quotes(text);
+        if let Some(captures) = self.created_pattern.captures(text) {
+            return Some(GroupEvent::Created {
+                actor: captures.get(1).unwrap().as_str().to_string(),
+                subject: captures.get(2).unwrap().as_str().to_string(),
+            });
+        }
+        if let Some(captures) = self.subject_pattern.captures(text) {
+            return Some(GroupEvent::SubjectChanged {
+                actor: captures.get(1).unwrap().as_str().to_string(),
+                new_subject: captures.get(2).unwrap().as_str().to_string(),
+            });
+        }
+        if let Some(captures) = self.icon_pattern.captures(text) {
+            return Some(GroupEvent::IconChanged { actor: captures.get(1).unwrap().as_str().to_string() });
+        }
+        if let Some(captures) = self.block_pattern.captures(text) {
+            return Some(match captures.get(1) {
+                Some(_) => GroupEvent::ContactUnblocked,
+                None => GroupEvent::ContactBlocked,
+            });
+        }
+        if let Some(captures) = self.disappearing_pattern.captures(text) {
+            let state = captures.get(2).or_else(|| captures.get(3)).unwrap().as_str();
+            return Some(GroupEvent::DisappearingMessages {
+                enabled: state == "on",
+                actor: captures.get(1).map(|actor| actor.as_str().to_string()),
+            });
+        }
+        if self.self_join_pattern.is_match(text) {
+            return Some(GroupEvent::SelfJoined { timestamp });
+        }
+        None
+    }
+
+    /// Set the message type once the full (possibly multi-line) content is known
+    fn finalize_message(&self, mut message: Message) -> Message {
+        if !matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) {
+            message.message_type = self.detect_message_type(&message.content);
+            split_caption(&mut message);
+            message.recount_words();
+        }
+        message
+    }
+
+    /// Determine message type based on content, consulting the user's type
+    /// rules first
+    fn detect_message_type(&self, content: &str) -> MessageType {
+        self.type_rules.classify(content).unwrap_or_else(|| builtin_message_type(content))
+    }
+
+    /// Parse WhatsApp timestamp in format "DD/MM/YYYY, HH:MM:SS" (seconds
+    /// optional; a two-digit year is expanded with the year pivot)
+    fn parse_timestamp(&self, timestamp_str: &str) -> Result<DateTime<Local>, String> {
+        if let Some(captures) = self.timestamp_pattern.captures(timestamp_str) {
+            let day = captures.get(1).unwrap().as_str().parse::<u32>().unwrap();
+            let month = captures.get(2).unwrap().as_str().parse::<u32>().unwrap();
+            let year = match captures.get(3).unwrap().as_str() {
+                two_digits if two_digits.len() == 2 => expand_two_digit_year(two_digits.parse().unwrap(), self.year_pivot),
+                year => year.parse::<i32>().unwrap(),
+            };
+            let hour = captures.get(4).unwrap().as_str().parse::<u32>().unwrap();
+            let minute = captures.get(5).unwrap().as_str().parse::<u32>().unwrap();
+            let second = captures.get(6).map_or(0, |second| second.as_str().parse::<u32>().unwrap());
+
+            let naive_dt = match (
+                chrono::NaiveDate::from_ymd_opt(year, month, day),
+                chrono::NaiveTime::from_hms_opt(hour, minute, second),
+            ) {
+                (Some(date), Some(time)) => NaiveDateTime::new(date, time),
+                _ => return Err("Invalid date/time components".to_string()),
+            };
+
+            // WhatsApp timestamps are wall-clock times in the exporting device's zone
+            match Local.from_local_datetime(&naive_dt).earliest() {
+                Some(dt) => Ok(dt),
+                None => Err("Timestamp falls in a daylight-saving gap".to_string()),
+            }
+        } else {
+            Err("Timestamp doesn't match expected format".to_string())
+        }
+    }
+}
+
+/// How far `ChatParser::parse_reader_batched` has got when it hands over
+/// a batch
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BatchProgress {
+    /// Messages delivered so far, this batch included
+    pub messages_delivered: usize,
+    /// Lines and bytes of input consumed so far
+    pub lines_read: usize,
+    pub bytes_read: u64,
+}
+
+/// Batches preallocate at most this many messages, so a huge `batch_size`
+/// doesn't reserve memory the input never fills
+const BATCH_PREALLOCATE_LIMIT: usize = 10_000;
+
+/// Where `ChatParser::scan_messages` was when it completed a message:
+/// the message's header line (1-based) and the input consumed so far
+#[derive(Debug, Clone, Copy)]
+struct ScanPosition {
+    header_line: usize,
+    lines_read: usize,
+    bytes_read: u64,
+}
+
+/// A reader that counts the bytes consumed from it
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: u64,
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.bytes_read += amount as u64;
+        self.inner.consume(amount);
+    }
+}
+
+/// A line the parser could not use or had to correct, with its 1-based
+/// line number
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseWarning {
+    pub line_number: usize,
+    pub line: String,
+    pub reason: String,
+}
+
+/// A message re-split by `ChatParser::correct_split_senders`
+#[derive(Debug, Clone, Serialize)]
+pub struct SenderCorrection {
+    /// Index of the message in the parsed list
+    pub index: usize,
+    pub sender_before: String,
+    pub content_before: String,
+    pub sender_after: String,
+    pub content_after: String,
+}
+
+/// How much of a line skipped for running over the line budget its
+/// `ParseWarning` keeps
+const SKIPPED_LINE_PREVIEW_CHARS: usize = 200;
+
+/// `ChatParser::correct_split_senders` only re-splits senders with fewer
+/// messages than this
+pub const SENDER_CORRECTION_MAX_COUNT: usize = 3;
+
+/// How `ChatParser` cleans up whitespace in multi-line message content.
+/// Line terminators, including the `\r` of CRLF exports, are always removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhitespaceNormalization {
+    /// Content as exported
+    Preserve,
+    /// Strip trailing whitespace (stray `\r` included) from every line, and
+    /// trailing blank lines from the message
+    TrimTrailing,
+    /// `TrimTrailing`, and strip leading whitespace from continuation lines
+    TrimLines,
+}
+
+impl WhitespaceNormalization {
+    pub fn parse(mode: &str) -> Result<Self, ParserError> {
+        match mode {
+            "preserve" => Ok(WhitespaceNormalization::Preserve),
+            "trim_trailing" => Ok(WhitespaceNormalization::TrimTrailing),
+            "trim_lines" => Ok(WhitespaceNormalization::TrimLines),
+            _ => Err(ParserError::InvalidArgument(format!(
+                "Unknown whitespace normalization '{}' (expected 'preserve', 'trim_trailing' or 'trim_lines')",
+                mode
+            ))),
+        }
+    }
+
+    /// `None` when content is kept as is, otherwise whether continuation
+    /// lines are trimmed at the start too
+    fn trims_continuations(self) -> Option<bool> {
+        match self {
+            WhitespaceNormalization::Preserve => None,
+            WhitespaceNormalization::TrimTrailing => Some(false),
+            WhitespaceNormalization::TrimLines => Some(true),
+        }
+    }
+}
+
+/// Default two-digit year pivot: 00–99 are read as 2000–2099
+pub const DEFAULT_YEAR_PIVOT: i32 = 2000;
+
+/// The year in `pivot..pivot + 100` whose last two digits are `two_digits`
+fn expand_two_digit_year(two_digits: i32, pivot: i32) -> i32 {
+    let year = pivot - pivot.rem_euclid(100) + two_digits;
+    if year < pivot {
+        year + 100
+    } else {
+        year
+    }
+}
+
+/// Options for parsing in-memory text, deserialisable from JSON (for the
+/// WASM build's `config_json`); missing fields take their defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ParseConfig {
+    /// Keep system messages (group events, notices)
+    pub include_system: bool,
+    /// Mark messages from the sender matching this name as own
+    pub user_identity: Option<String>,
+    /// "exact", "prefix" or "fuzzy" (see `IdentityMatch`)
+    pub match_mode: String,
+    /// Split quoted reply previews out of message content (see
+    /// `ChatParser::parse_reply_previews`)
+    pub parse_replies: bool,
+    /// (rule_kind, pattern, resulting_type) triples applied before the
+    /// built-in type detection (see `TypeRules`)
+    pub type_rules: Vec<(String, String, String)>,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        ParseConfig {
+            include_system: false,
+            user_identity: None,
+            match_mode: "exact".to_string(),
+            parse_replies: false,
+            type_rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for ChatParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Determine message type from content with the built-in rules only
+pub(crate) fn builtin_message_type(content: &str) -> MessageType {
+    if let Some(call) = parse_call(content) {
+        call
+    } else if content.contains("<Media omitted>") {
+        MessageType::Media
+    } else if content.starts_with("https://") || content.starts_with("http://") {
+        MessageType::Link
+    } else {
+        MessageType::Text
+    }
+}
+
+/// Move the lines after a media message's first line (the "<Media omitted>"
+/// or attachment line) into `caption`, appending to any caption already
+/// split off. Other message types are left alone.
+pub(crate) fn split_caption(message: &mut Message) {
+    if message.message_type != MessageType::Media {
+        return;
+    }
+    let Some((media_line, rest)) = message.content.split_once('\n') else { return };
+    let rest = rest.trim();
+    if !rest.is_empty() {
+        message.caption = Some(match message.caption.take() {
+            Some(caption) => format!("{}\n{}", rest, caption),
+            None => rest.to_string(),
+        });
+    }
+    message.content = media_line.trim_end().to_string();
+}
+
+/// Split `bytes` after its last newline, returning the unterminated tail
+/// (if non-empty) as text
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn split_incomplete_line(bytes: &[u8]) -> (&[u8], Option<String>) {
+    let end = memchr::memrchr(b'\n', bytes).map_or(0, |newline| newline + 1);
+    let tail = &bytes[end..];
+    (&bytes[..end], (!tail.is_empty()).then(|| String::from_utf8_lossy(tail).into_owned()))
+}
+
+/// Read the next line, replacing invalid UTF-8 sequences so one corrupt byte
+/// doesn't abort the whole parse. Returns `None` at end of input.
+fn read_line_lossy<R: BufRead>(reader: &mut R, buffer: &mut Vec<u8>) -> std::io::Result<Option<String>> {
+    buffer.clear();
+    if reader.read_until(b'\n', buffer)? == 0 {
+        return Ok(None);
+    }
+
+    // Strip the line terminator like `BufRead::lines` does, plus any extra
+    // carriage returns: files converted to CRLF twice end lines in "\r\r\n"
+    if buffer.ends_with(b"\n") {
+        buffer.pop();
+    }
+    while buffer.ends_with(b"\r") {
+        buffer.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(buffer).into_owned()))
+}
+
+/// Merge overlapping exports of the same chat into one timeline: sort by
+/// exported timestamp (stable, so same-minute messages keep export order)
+/// and drop duplicate (timestamp, sender, content) messages. Interpolated
+/// timestamps are compared at the exported minute and re-spread over the
+/// merged run. Ids are renumbered.
+pub fn merge_chats(chats: Vec<Vec<Message>>) -> Vec<Message> {
+    let mut messages: Vec<Message> = chats.into_iter().flatten().collect();
+    messages.sort_by_key(|message| message.exported_timestamp());
+
+    // Content is compared NFC-composed, as exports from different devices
+    // may compose accents differently; the first copy is kept byte-exact
+    let mut seen = HashSet::new();
+    messages.retain(|message| {
+        seen.insert((message.exported_timestamp(), message.sender.clone(), nfc(&message.content).into_owned()))
+    });
+    respread_interpolated(&mut messages);
+
+    for (index, message) in messages.iter_mut().enumerate() {
+        message.id = format!("msg_{}", index + 1);
+    }
+    mark_grouped(&mut messages);
+    annotate_gaps(&mut messages);
+    messages
+}
+
+/// Where two parts of a split export fail to overlap, so messages between
+/// `from` and `to` are probably in a part that wasn't supplied
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportGap {
+    /// Path of the part that ends at `from`
+    pub after: String,
+    /// Path of the part that starts at `to`
+    pub before: String,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+}
+
+/// Number of leading messages of `part` that repeat the tail of `stitched`
+/// (same exported timestamp, sender and content), choosing the longest
+/// such overlap
+#[cfg(not(target_arch = "wasm32"))]
+fn aligned_overlap(stitched: &[Message], part: &[Message]) -> Option<usize> {
+    let same = |a: &Message, b: &Message| {
+        a.exported_timestamp() == b.exported_timestamp() && a.sender == b.sender && nfc(&a.content) == nfc(&b.content)
+    };
+    let earliest = stitched.len().saturating_sub(part.len());
+    (earliest..stitched.len())
+        .find(|&start| stitched[start..].iter().zip(part).all(|(a, b)| same(a, b)))
+        .map(|start| stitched.len() - start)
+}
+
+/// How close (in seconds) a message must follow the previous one from the
+/// same sender to be grouped with it
+pub const GROUPING_WINDOW_SECONDS: i64 = 5 * 60;
+
+/// The sender set for a strict second pass over `messages` (system notices
+/// included), or `None` when no sender seen exactly once has a name that
+/// `looks_like_pasted_sender` and no notice has a `pasted_notice_key`. The
+/// suspicious senders are left out; everyone else counts as known.
+fn inferred_known_senders(messages: &[Message]) -> Option<HashSet<String>> {
+    let mut counts: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut pasted_notice = false;
+    for message in messages {
+        match &message.message_type {
+            MessageType::SystemEvent(GroupEvent::Other { text }) => pasted_notice |= pasted_notice_key(text).is_some(),
+            MessageType::SystemEvent(_) => {}
+            _ => counts.entry(&message.sender).or_insert((&message.sender_display, 0)).1 += 1,
+        }
+    }
+    let suspicious: HashSet<&str> = counts
+        .iter()
+        .filter(|(_, (display, count))| *count == 1 && looks_like_pasted_sender(display))
+        .map(|(sender, _)| *sender)
+        .collect();
+    if suspicious.is_empty() && !pasted_notice {
+        return None;
+    }
+    Some(counts.into_keys().filter(|sender| !suspicious.contains(sender)).map(str::to_string).collect())
+}
+
+/// The key of a notice in "KEY:value" form with a `looks_like_pasted_sender`
+/// key, as in pasted iCalendar data ("DTSTART;TZID=Europe/London:2023...");
+/// WhatsApp's own notices have no such key
+fn pasted_notice_key(text: &str) -> Option<&str> {
+    let (key, _) = text.split_once(':')?;
+    looks_like_pasted_sender(key).then_some(key)
+}
+
+/// Drop system events (and reactions) kept only for inspection,
+/// renumbering the ids
+fn drop_system_events(messages: &mut Vec<Message>, header_lines: &mut Vec<usize>) {
+    let kept: Vec<(Message, usize)> = std::mem::take(messages)
+        .into_iter()
+        .zip(header_lines.drain(..))
+        .filter(|(message, _)| !matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }))
+        .collect();
+    for (index, (mut message, header_line)) in kept.into_iter().enumerate() {
+        message.id = format!("msg_{}", index + 1);
+        messages.push(message);
+        header_lines.push(header_line);
+    }
+}
+
+/// Whether a "sender" reads like the start of pasted text rather than a
+/// contact name: a numbered list item ("1. Buy milk", "2) Hike"), a log
+/// level or other all-caps keyword ("ERROR", "SUMMARY"), code or
+/// key=value syntax ("DTSTART;TZID=Europe/London"), or more than eight
+/// words. Quotes, brackets and symbols alone don't count; contact names
+/// have those.
+pub fn looks_like_pasted_sender(name: &str) -> bool {
+    let name = name.trim();
+    let keyword = name.len() >= 3 && name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+    let syntax = name.contains(['=', ';', '{', '}', '|', '\\']);
+    let number = name.trim_start_matches(|c: char| c.is_ascii_digit());
+    let list_item = number.len() < name.len() && (number.starts_with(". ") || number.starts_with(") "));
+    keyword || syntax || list_item || name.split_whitespace().count() > 8
+}
+
+/// Set `grouped_with_previous` on each message from its predecessor. System
+/// events are never grouped and break a run.
+fn mark_grouped(messages: &mut [Message]) {
+    let is_system = |message: &Message| matches!(message.message_type, MessageType::SystemEvent(_));
+    for index in 0..messages.len() {
+        let grouped = index > 0 && {
+            let (previous, message) = (&messages[index - 1], &messages[index]);
+            !is_system(previous)
+                && !is_system(message)
+                && previous.sender == message.sender
+                && (message.timestamp - previous.timestamp).num_seconds() <= GROUPING_WINDOW_SECONDS
+        };
+        messages[index].grouped_with_previous = grouped;
+    }
+}
+
+/// Spread each run of consecutive messages with the same minute-precision
+/// timestamp (those carrying `original_timestamp`) evenly over its minute
+/// (see `ChatParser::set_interpolate_subminute`)
+fn interpolate_subminute(messages: &mut [Message]) {
+    let mut start = 0;
+    while start < messages.len() {
+        let minute = messages[start].timestamp;
+        let precise = messages[start].original_timestamp.is_none();
+        let run = if precise {
+            1
+        } else {
+            messages[start..]
+                .iter()
+                .take_while(|message| message.original_timestamp.is_some() && message.timestamp == minute)
+                .count()
+        };
+        if run > 1 {
+            for (position, message) in messages[start..start + run].iter_mut().enumerate() {
+                message.timestamp = minute + chrono::Duration::nanoseconds(position as i64 * 60_000_000_000 / run as i64);
+                message.timestamp_interpolated = true;
+            }
+        }
+        start += run;
+    }
+}
+
+/// Spread interpolated minutes again after combining exports, whose
+/// copies of a minute may hold different parts of its run
+fn respread_interpolated(messages: &mut [Message]) {
+    for message in messages.iter_mut().filter(|message| message.timestamp_interpolated) {
+        message.timestamp = message.exported_timestamp();
+        message.timestamp_interpolated = false;
+    }
+    interpolate_subminute(messages);
+}
+
+/// Set `gap_before_seconds` on each message from its predecessor in slice
+/// order. Parsing does this already; run it again after filtering or
+/// reordering messages. A message earlier than its predecessor gets 0.
+pub fn annotate_gaps(messages: &mut [Message]) {
+    for index in 0..messages.len() {
+        let gap = index
+            .checked_sub(1)
+            .map(|previous| (messages[index].timestamp - messages[previous].timestamp).num_seconds().max(0) as u64);
+        messages[index].gap_before_seconds = gap;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAT: &str = "[18/05/2023, 08:39:07] John: Good morning
+[18/05/2023, 08:40:15] Alice: Morning!
+still Alice
+[18/05/2023, 08:41:00] John created group \"Trip\"
+[18/05/2023, 08:42:30] Alice: <Media omitted>
+";
+
+    #[test]
+    fn parses_messages_and_continuation_lines() {
+        let messages = ChatParser::new().parse_str(CHAT, false).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].id, "msg_1");
+        assert_eq!(messages[0].sender, "John");
+        assert_eq!(messages[1].content, "Morning!\nstill Alice");
+        assert_eq!(messages[2].message_type, MessageType::Media);
+    }
+
+    #[test]
+    fn counts_words_of_text_messages_only() {
+        let messages = ChatParser::new().parse_str(CHAT, true).unwrap();
+        let counts: Vec<u32> = messages.iter().map(|message| message.word_count).collect();
+        // "Morning!\nstill Alice" is three words; the event and media have none
+        assert_eq!(counts, [2, 3, 0, 0]);
+    }
+
+    #[test]
+    fn keeps_system_events_when_asked() {
+        let messages = ChatParser::new().parse_str(CHAT, true).unwrap();
+        assert_eq!(messages.len(), 4);
+        match &messages[2].message_type {
+            MessageType::SystemEvent(GroupEvent::Created { actor, subject }) => {
+                assert_eq!(actor, "John");
+                assert_eq!(subject, "Trip");
+            }
+            other => panic!("expected a created event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recognises_system_phrases_with_curly_quotes() {
+        let chat = "[18/05/2023, 08:00:00] John created group \u{201C}Trip\u{201D}
+[18/05/2023, 08:01:00] You joined using this group\u{2019}s invite link
+[18/05/2023, 08:02:00] Alice changed the subject to \u{201C}Trip \u{2019}23\u{201D}
+[18/05/2023, 08:03:00] Alice: it\u{2019}s on
+";
+        let messages = ChatParser::new().parse_str(chat, false).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "it\u{2019}s on");
+
+        let events = ChatParser::new().parse_str(chat, true).unwrap();
+        assert!(matches!(&events[1].message_type, MessageType::SystemEvent(GroupEvent::SelfJoined { .. })));
+        assert!(matches!(
+            &events[2].message_type,
+            MessageType::SystemEvent(GroupEvent::SubjectChanged { new_subject, .. }) if new_subject == "Trip '23"
+        ));
+    }
+
+    #[test]
+    fn recognises_subject_and_icon_changes() {
+        let chat = "[18/05/2023, 08:00:00] Alice changed the subject from \"Trip\" to \"Goa: \"the\" trip\"
+[18/05/2023, 08:01:00] You changed this group's icon
+[18/05/2023, 08:02:00] Alice: nice icon
+";
+        let events = ChatParser::new().parse_str(chat, true).unwrap();
+        assert_eq!(
+            events[0].message_type,
+            MessageType::SystemEvent(GroupEvent::SubjectChanged {
+                actor: "Alice".to_string(),
+                new_subject: "Goa: \"the\" trip".to_string(),
+            })
+        );
+        assert_eq!(events[1].message_type, MessageType::SystemEvent(GroupEvent::IconChanged { actor: "You".to_string() }));
+        assert_eq!(events[1].message_type.as_str(), "system_event");
+        assert_eq!(events[2].message_type, MessageType::Text);
+    }
+
+    #[test]
+    fn recognises_block_and_unblock_notices() {
+        let chat = "[18/05/2023, 08:00:00] John: hi
+[18/05/2023, 08:01:00] You blocked this contact. Tap to unblock.
+[19/05/2023, 09:00:00] You unblocked this contact
+";
+        let messages = ChatParser::new().parse_str(chat, false).unwrap();
+        assert_eq!(messages.len(), 1);
+
+        let events = ChatParser::new().parse_str(chat, true).unwrap();
+        assert_eq!(events[1].message_type, MessageType::SystemEvent(GroupEvent::ContactBlocked));
+        assert_eq!(events[2].message_type, MessageType::SystemEvent(GroupEvent::ContactUnblocked));
+        assert_eq!(events[1].sender, "");
+    }
+
+    #[test]
+    fn interpolates_minute_precision_runs() {
+        let chat = "[18/05/2023, 08:00] John: one
+[18/05/2023, 08:00] Alice: two
+[18/05/2023, 08:00] John: three
+[18/05/2023, 08:00] Alice: four
+[18/05/2023, 08:01] John: alone
+[18/05/2023, 08:02:30] Alice: precise
+";
+        let plain = ChatParser::new().parse_str(chat, false).unwrap();
+        assert!(plain.iter().all(|message| !message.timestamp_interpolated && message.original_timestamp.is_none()));
+
+        let mut parser = ChatParser::new();
+        parser.set_interpolate_subminute(true);
+        let messages = parser.parse_str(chat, false).unwrap();
+        let offsets: Vec<i64> =
+            messages.iter().map(|message| (message.timestamp - plain[0].timestamp).num_seconds()).collect();
+        assert_eq!(offsets, [0, 15, 30, 45, 60, 150]);
+        let flags: Vec<bool> = messages.iter().map(|message| message.timestamp_interpolated).collect();
+        assert_eq!(flags, [true, true, true, true, false, false]);
+        assert_eq!(messages[2].original_timestamp.as_deref(), Some("18/05/2023, 08:00"));
+        assert_eq!(messages[4].original_timestamp.as_deref(), Some("18/05/2023, 08:01"));
+        assert_eq!(messages[5].original_timestamp, None);
+        assert_eq!(messages[3].exported_timestamp(), plain[3].timestamp);
+        assert_eq!(messages[1].gap_before_seconds, Some(15));
+
+        let again = parser.parse_str(chat, false).unwrap();
+        assert!(messages.iter().zip(&again).all(|(first, second)| first.timestamp == second.timestamp));
+    }
+
+    #[test]
+    fn recognises_disappearing_message_notices() {
+        let chat = "[18/05/2023, 08:00:00] Alice turned on disappearing messages. New messages will disappear from this chat 7 days after they're sent, except when kept.
+[18/05/2023, 08:01:00] John: what does that do
+[19/05/2023, 09:00:00] You turned off disappearing messages.
+[20/05/2023, 10:00:00] Disappearing messages were turned on.
+[21/05/2023, 11:00:00] Disappearing messages were turned off
+";
+        let events: Vec<MessageType> =
+            ChatParser::new().parse_str(chat, true).unwrap().into_iter().map(|message| message.message_type).collect();
+        let disappearing = |enabled: bool, actor: Option<&str>| {
+            MessageType::SystemEvent(GroupEvent::DisappearingMessages { enabled, actor: actor.map(str::to_string) })
+        };
+        assert_eq!(
+            events,
+            [
+                disappearing(true, Some("Alice")),
+                MessageType::Text,
+                disappearing(false, Some("You")),
+                disappearing(true, None),
+                disappearing(false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_exports_parse_to_clean_content() {
+        const FIXTURE: &str = include_str!("../../../tests/fixtures/crlf_chat.txt");
+        let mut parser = ChatParser::new();
+        let contents = |parser: &ChatParser| -> Vec<(String, Option<String>)> {
+            let messages = parser.parse_str(FIXTURE, false).unwrap();
+            messages.into_iter().map(|message| (message.content, message.caption)).collect()
+        };
+        let preserved = contents(&parser);
+        assert_eq!(preserved[0].0, "Shopping list:  \n  milk \n  eggs\t\n");
+        assert_eq!(preserved[1].0, "got it");
+        assert!(preserved.iter().all(|(content, _)| !content.contains('\r')));
+
+        parser.set_whitespace_normalization(WhitespaceNormalization::TrimTrailing);
+        let trimmed = contents(&parser);
+        assert_eq!(trimmed[0].0, "Shopping list:\n  milk\n  eggs");
+        assert_eq!(trimmed[2], ("<Media omitted>".to_string(), Some("the receipt".to_string())));
+
+        parser.set_whitespace_normalization(WhitespaceNormalization::TrimLines);
+        assert_eq!(contents(&parser)[0].0, "Shopping list:\nmilk\neggs");
+        assert!(WhitespaceNormalization::parse("strip").is_err());
+    }
+
+    #[test]
+    fn pasted_headers_stay_in_the_message_they_were_pasted_into() {
+        let parser = ChatParser::new();
+        let fixtures = [
+            (include_str!("../../../tests/fixtures/pasted_ics_chat.txt"), 4, "SUMMARY:Climbing at the Arch"),
+            (include_str!("../../../tests/fixtures/pasted_log_chat.txt"), 4, "FATAL: giving up after 3 attempts"),
+            (include_str!("../../../tests/fixtures/pasted_list_chat.txt"), 3, "3. Lunch: the pub at the top"),
+        ];
+        for (chat, count, pasted) in fixtures {
+            let (messages, warnings) = parser.parse_reader_with_warnings(chat.as_bytes(), false).unwrap();
+            assert_eq!(messages.len(), count, "{}", chat);
+            assert!(messages[0].content.contains(pasted), "{}", messages[0].content);
+            assert!(warnings.iter().all(|warning| warning.reason.starts_with("Read as part of the previous message")));
+        }
+        // "Ben" is only seen once too, but has an ordinary name
+        let messages = parser.parse_str(fixtures[0].0, false).unwrap();
+        assert_eq!(messages[3].sender_display, "Ben");
+
+        let mut strict = ChatParser::new();
+        let senders = strict.detect_senders(fixtures[2].0.as_bytes()).unwrap();
+        assert_eq!((senders["Dana"], senders["1. Meet"]), (2, 1));
+        assert!(looks_like_pasted_sender("DTEND;TZID=Europe/London") && !looks_like_pasted_sender("+44 Support Team"));
+        strict.set_known_senders(Some(["Dana"]));
+        let messages = strict.parse_str(fixtures[2].0, false).unwrap();
+        assert_eq!(messages.len(), 2);
+        let mut batched = Vec::new();
+        strict
+            .parse_reader_batched(fixtures[2].0.as_bytes(), false, 1, |batch, _| {
+                batched.extend(batch);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(batched.iter().map(|message| &message.content).collect::<Vec<_>>(), [&messages[0].content, &messages[1].content]);
+        assert!(messages[0].content.ends_with("Chris: perfect"));
+        assert_eq!(messages[1].content, "bring water");
+    }
+
+    #[test]
+    fn annotates_gaps_between_messages() {
+        let messages = ChatParser::new().parse_str(CHAT, true).unwrap();
+        let gaps: Vec<Option<u64>> = messages.iter().map(|message| message.gap_before_seconds).collect();
+        assert_eq!(gaps, [None, Some(68), Some(45), Some(90)]);
+
+        let mut filtered: Vec<Message> = messages.into_iter().filter(|message| message.sender == "Alice").collect();
+        annotate_gaps(&mut filtered);
+        assert_eq!(filtered[1].gap_before_seconds, Some(135));
+    }
+
+    #[test]
+    fn corrects_senders_run_into_content() {
+        let chat = "[18/05/2023, 08:00:00] Alice: hi
+[18/05/2023, 08:01:00] Alice: agenda later
+[18/05/2023, 08:02:00] Bob - Team: ok
+[18/05/2023, 08:03:00] Alice - Meeting - Today: agenda
+[18/05/2023, 08:04:00] Alice: done
+[18/05/2023, 08:05:00] Carol - Ops: hello
+";
+        let (messages, warnings) = ChatParser::new().parse_reader_with_warnings(chat.as_bytes(), false).unwrap();
+        assert_eq!((messages[3].sender.as_str(), messages[3].content.as_str()), ("Alice", "Meeting - Today: agenda"));
+        // Neither Bob nor Carol is a regular, so their names are kept
+        assert_eq!((messages[2].sender.as_str(), messages[5].sender.as_str()), ("Bob - Team", "Carol - Ops"));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line_number, 4);
+        assert_eq!(warnings[0].line, "Alice - Meeting - Today: agenda");
+        assert!(warnings[0].reason.contains("to \"Alice\""));
+        assert!(messages[4].grouped_with_previous);
+    }
+
+    #[test]
+    fn batched_parse_matches_a_full_parse() {
+        let mut text = String::new();
+        for i in 0..7 {
+            text.push_str(&format!("[18/05/2023, 08:{:02}:00] {}: m{}\nmore\n", i, if i < 4 { "John" } else { "Alice" }, i));
+        }
+        let full = ChatParser::new().parse_str(&text, false).unwrap();
+
+        let mut batches: Vec<(Vec<Message>, BatchProgress)> = Vec::new();
+        let delivered = ChatParser::new()
+            .parse_reader_batched(text.as_bytes(), false, 3, |batch, progress| {
+                batches.push((batch, *progress));
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(delivered, 7);
+        let sizes: Vec<usize> = batches.iter().map(|(batch, _)| batch.len()).collect();
+        assert_eq!(sizes, [3, 3, 1]);
+        let progress: Vec<(usize, usize)> = batches.iter().map(|(_, p)| (p.messages_delivered, p.lines_read)).collect();
+        assert_eq!(progress, [(3, 6), (6, 12), (7, 14)]);
+        assert_eq!(batches[2].1.bytes_read, text.len() as u64);
+
+        let summary = |message: &Message| {
+            (message.id.clone(), message.content.clone(), message.grouped_with_previous, message.gap_before_seconds)
+        };
+        let streamed: Vec<_> = batches.iter().flat_map(|(batch, _)| batch.iter().map(summary)).collect();
+        assert_eq!(streamed, full.iter().map(summary).collect::<Vec<_>>());
+
+        // Stopping after the first batch
+        let mut seen = 0;
+        let delivered = ChatParser::new()
+            .parse_reader_batched(text.as_bytes(), false, 2, |batch, _| {
+                seen += batch.len();
+                ControlFlow::Break(())
+            })
+            .unwrap();
+        assert_eq!((delivered, seen), (2, 2));
+    }
+
+    #[test]
+    fn groups_consecutive_messages_from_same_sender() {
+        let messages = ChatParser::new()
+            .parse_str(
+                "[18/05/2023, 08:00:00] John: one\n[18/05/2023, 08:01:00] John: two\n[18/05/2023, 08:02:00] John: three\n[18/05/2023, 09:00:00] John: later\n[18/05/2023, 09:00:30] Alice: hi\n",
+                false,
+            )
+            .unwrap();
+        let grouped: Vec<bool> = messages.iter().map(|message| message.grouped_with_previous).collect();
+        assert_eq!(grouped, [false, true, true, false, false]);
+    }
+
+    #[test]
+    fn invalid_timestamp_is_an_error() {
+        let result = ChatParser::new().parse_str("[31/02/2023, 08:00:00] John: hi\n", false);
+        assert!(matches!(result, Err(ParserError::InvalidTimestamp(_))));
+    }
+
+    #[test]
+    fn separates_media_captions() {
+        let chat = "[18/05/2023, 08:39:07] John: <Media omitted>
+Sunset at the beach
+second caption line
+[18/05/2023, 08:40:00] Alice: <Media omitted>
+[18/05/2023, 08:41:00] Alice: Lovely
+and more text
+";
+        let messages = ChatParser::new().parse_str(chat, false).unwrap();
+        assert_eq!(messages[0].message_type, MessageType::Media);
+        assert_eq!(messages[0].content, "<Media omitted>");
+        assert_eq!(messages[0].caption.as_deref(), Some("Sunset at the beach\nsecond caption line"));
+        assert_eq!(messages[1].caption, None);
+        assert_eq!(messages[2].content, "Lovely\nand more text");
+        assert_eq!(messages[2].caption, None);
+
+        let loose = ChatParser::new().parse_loose_text(
+            "John: <Media omitted>\nSunset\n",
+            &["John".to_string()],
+            Local::now(),
+            chrono::Duration::minutes(1),
+        );
+        assert_eq!(loose[0].content, "<Media omitted>");
+        assert_eq!(loose[0].caption.as_deref(), Some("Sunset"));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn samples_the_first_messages_of_a_file() {
+        let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let parser = ChatParser::new();
+        let complete = parser.parse_file(&fixture("topic_shift_chat.txt"), false).unwrap();
+        let sample = parser.parse_sample(&fixture("topic_shift_chat.txt"), 5, false).unwrap();
+        assert_eq!(sample.len(), 5);
+        let ids = |messages: &[Message]| messages.iter().map(|message| (message.id.clone(), message.content.clone())).collect::<Vec<_>>();
+        assert_eq!(ids(&sample), ids(&complete[..5]));
+        assert_eq!(parser.parse_sample(&fixture("topic_shift_chat.txt"), 100, false).unwrap().len(), complete.len());
+
+        // The last sampled message keeps its continuation lines
+        let first = parser.parse_sample(&fixture("crlf_chat.txt"), 1, false).unwrap();
+        assert_eq!(first[0].content, parser.parse_file(&fixture("crlf_chat.txt"), false).unwrap()[0].content);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn split_export_matches_the_complete_file() {
+        let fixture = |name: &str| format!("{}/tests/fixtures/split_export/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let (part1, part2, part3) = (fixture("part1.txt"), fixture("part2.txt"), fixture("part3.txt"));
+        let parser = ChatParser::new();
+        let complete = parser.parse_file(&fixture("full.txt"), false).unwrap();
+
+        let (stitched, gaps) = parser.parse_split_export(&[&part3, &part1, &part2], false).unwrap();
+        assert!(gaps.is_empty());
+        let key = |message: &Message| {
+            (message.id.clone(), message.timestamp, message.sender.clone(), message.content.clone(), message.grouped_with_previous)
+        };
+        assert_eq!(stitched.iter().map(key).collect::<Vec<_>>(), complete.iter().map(key).collect::<Vec<_>>());
+
+        let (stitched, gaps) = parser.parse_split_export(&[&part3, &part1], false).unwrap();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!((gaps[0].after.as_str(), gaps[0].before.as_str()), (part1.as_str(), part3.as_str()));
+        assert_eq!(gaps[0].from.date_naive(), chrono::NaiveDate::from_ymd_opt(2023, 6, 4).unwrap());
+        assert_eq!(gaps[0].to.date_naive(), chrono::NaiveDate::from_ymd_opt(2023, 6, 6).unwrap());
+        assert_eq!(stitched.len(), complete.len() - 5);
+    }
+
+    #[test]
+    fn two_digit_years_use_the_pivot() {
+        let chat = "[18/05/95, 08:39:07] John: Dial-up again\n[18/05/05, 08:40:00] Alice: Ha\n";
+        let mut parser = ChatParser::new();
+        let years = |parser: &ChatParser| -> Vec<i32> {
+            parser.parse_str(chat, false).unwrap().iter().map(|message| message.timestamp.year()).collect()
+        };
+        assert_eq!(years(&parser), [2095, 2005]);
+        parser.set_year_pivot(1950);
+        assert_eq!(years(&parser), [1995, 2005]);
+        assert_eq!(expand_two_digit_year(0, 1950), 2000);
+        assert_eq!(expand_two_digit_year(50, 1950), 1950);
+        assert_eq!(expand_two_digit_year(49, 1950), 2049);
+        assert_eq!(expand_two_digit_year(99, DEFAULT_YEAR_PIVOT), 2099);
+        let pivot = ChatParser::sliding_year_pivot(10);
+        assert_eq!(expand_two_digit_year((Local::now().year() + 10) % 100, pivot), Local::now().year() + 10);
+        assert_eq!(parser.detect_format("[18/05/95, 08:39:07] John: hi"), Some("whatsapp_ios"));
+    }
+
+    #[test]
+    fn lenient_mode_skips_bad_timestamps() {
+        let chat = "[18/05/2023, 08:39:07] John: Before
+[31/02/2023, 08:40:00] Alice: Impossible date
+and its continuation
+[18/05/2023, 08:41:00] John: After
+";
+        let mut parser = ChatParser::new();
+        assert!(matches!(parser.parse_str(chat, false), Err(ParserError::InvalidTimestamp(_))));
+
+        parser.set_lenient(true);
+        let (messages, warnings) = parser.parse_reader_with_warnings(chat.as_bytes(), false).unwrap();
+        let contents: Vec<&str> = messages.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["Before", "After"]);
+        assert_eq!(messages[1].id, "msg_2");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line_number, 2);
+        assert!(warnings[0].reason.starts_with("Message skipped"));
+    }
+
+    #[test]
+    fn lenient_mode_skips_a_megabyte_line_over_the_line_budget() {
+        let chat = format!(
+            "[18/05/2023, 08:39:07] John: Before\n[18/05/2023, 08:40:00] Alice: {}\n[18/05/2023, 08:41:00] John: After\n",
+            "a".repeat(1 << 20)
+        );
+        let rules = [("regex".to_string(), r"\w+\d".to_string(), "heavy".to_string())];
+        let mut parser = ChatParser::new();
+        parser.set_type_rules(TypeRules::new(&rules).unwrap());
+        parser.set_line_budget(Duration::from_millis(5));
+
+        // Strict parses ignore the budget
+        assert_eq!(parser.parse_str(&chat, false).unwrap().len(), 3);
+
+        parser.set_lenient(true);
+        let started = Instant::now();
+        let (messages, warnings) = parser.parse_reader_with_warnings(chat.as_bytes(), false).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let contents: Vec<&str> = messages.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["Before", "After"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line_number, 2);
+        assert_eq!(warnings[0].line.chars().count(), SKIPPED_LINE_PREVIEW_CHARS);
+        assert!(warnings[0].reason.contains("line budget"));
+    }
+
+    #[test]
+    fn export_formats_are_the_compiled_patterns() {
+        let parser = ChatParser::new();
+        assert_eq!(
+            EXPORT_FORMATS.iter().map(|format| format.pattern).collect::<Vec<_>>(),
+            [parser.line_pattern.as_str(), parser.loose_pattern.as_str()]
+        );
+        assert_eq!(parser.detect_format(WHATSAPP_IOS.example), Some(WHATSAPP_IOS.id));
+        assert_eq!(parser.parse_str(WHATSAPP_IOS.example, false).unwrap()[0].content, "Hello");
+        let loose = parser.parse_loose_text(COPIED_TEXT.example, &["Alice".to_string()], Local::now(), chrono::Duration::minutes(1));
+        assert_eq!(loose[0].content, "hey");
+    }
+
+    #[test]
+    fn warns_about_text_before_first_message() {
+        let parser = ChatParser::new();
+        let (messages, warnings) = parser.parse_reader_with_warnings(format!("stray line\n\n{}", CHAT).as_bytes(), false).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line_number, 1);
+        assert_eq!(warnings[0].line, "stray line");
+        assert_eq!(parser.detect_format(CHAT), Some("whatsapp_ios"));
+        assert_eq!(parser.detect_format("just some notes\n"), None);
+    }
+
+    #[test]
+    fn parse_config_marks_own_messages() {
+        let config: ParseConfig = serde_json::from_str(r#"{"user_identity": "alice", "match_mode": "prefix"}"#).unwrap();
+        let messages = ChatParser::new().parse_str_with_config(CHAT, &config).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(!messages[0].is_own);
+        assert!(messages[1].is_own);
+    }
+
+    #[test]
+    fn parses_reply_previews_when_enabled() {
+        let chat = "[18/05/2023, 08:39:07] John: Good morning
+[18/05/2023, 08:45:00] Alice: > [18/05/2023, 08:39:07] John: Good morning
+> and more
+Morning to you too
+[18/05/2023, 08:46:00] Alice: > quoted without a timestamp
+https://example.com
+[18/05/2023, 08:47:00] John: > just a quote
+";
+        let messages = ChatParser::new().parse_str(chat, false).unwrap();
+        assert!(messages.iter().all(|message| message.reply_to.is_none()));
+
+        let config = ParseConfig { parse_replies: true, ..ParseConfig::default() };
+        let messages = ChatParser::new().parse_str_with_config(chat, &config).unwrap();
+        assert_eq!(messages[1].reply_to.as_deref(), Some("[18/05/2023, 08:39:07] John: Good morning\nand more"));
+        assert_eq!(messages[1].reply_to_timestamp, Some(messages[0].timestamp));
+        assert_eq!(messages[1].content, "Morning to you too");
+        assert_eq!(messages[2].reply_to.as_deref(), Some("quoted without a timestamp"));
+        assert_eq!(messages[2].reply_to_timestamp, None);
+        assert_eq!(messages[2].message_type, MessageType::Link);
+        assert!(messages[3].reply_to.is_none());
+        assert_eq!(messages[3].content, "> just a quote");
+    }
+
+    #[test]
+    fn type_rules_apply_in_both_parsers() {
+        let mut parser = ChatParser::new();
+        parser.set_type_rules(TypeRules::new(&[("prefix", "[BOT]", "bot"), ("exact", "<Media omitted>", "text")]).unwrap());
+        let chat = "[18/05/2023, 08:39:07] Bot: [BOT] deploy done\n[18/05/2023, 08:40:00] Alice: <Media omitted>\n";
+        let messages = parser.parse_str(chat, false).unwrap();
+        assert_eq!(messages[0].message_type.as_str(), "bot");
+        assert_eq!(messages[1].message_type, MessageType::Text);
+
+        let text = "Bot: [BOT] deploy done\nBot: https://example.com\n";
+        let loose = parser.parse_loose_text(text, &[], Local::now(), chrono::Duration::minutes(1));
+        assert_eq!(loose[0].message_type.as_str(), "bot");
+        assert_eq!(loose[1].message_type, MessageType::Link);
+
+        let config: ParseConfig = serde_json::from_str(r#"{"type_rules": [["contains", "deploy", "ops"]]}"#).unwrap();
+        let messages = ChatParser::new().parse_str_with_config(chat, &config).unwrap();
+        assert_eq!(messages[0].message_type.as_str(), "ops");
+
+        let config: ParseConfig = serde_json::from_str(r#"{"type_rules": [["suffix", "x", "ops"]]}"#).unwrap();
+        assert!(matches!(ChatParser::new().parse_str_with_config("", &config), Err(ParserError::InvalidArgument(_))));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn truncated_last_line_is_returned_separately() {
+        let (complete, incomplete) = split_incomplete_line(b"[18/05/2023, 08:39:07] John: Hi\n[18/05/2023, 08:4");
+        assert_eq!(complete, b"[18/05/2023, 08:39:07] John: Hi\n");
+        assert_eq!(incomplete.as_deref(), Some("[18/05/2023, 08:4"));
+        assert_eq!(split_incomplete_line(CHAT.as_bytes()), (CHAT.as_bytes(), None));
+        assert_eq!(split_incomplete_line(b"no newline").0, b"");
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn missing_file_is_an_io_error() {
+        let error = ChatParser::new().parse_file("/nonexistent/chat.txt", false).unwrap_err();
+        assert!(matches!(error, ParserError::Io { .. }));
+        assert!(error.to_string().starts_with("Failed to open file"));
+    }
+
+    #[test]
+    fn merge_chats_deduplicates_and_renumbers() {
+        let parser = ChatParser::new();
+        let first = parser.parse_str("[18/05/2023, 08:00:00] John: a\n[18/05/2023, 08:01:00] Alice: b\n", false).unwrap();
+        let second = parser.parse_str("[18/05/2023, 08:01:00] Alice: b\n[18/05/2023, 08:02:00] John: c\n", false).unwrap();
+        let merged = merge_chats(vec![first, second]);
+        let contents: Vec<&str> = merged.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["a", "b", "c"]);
+        assert_eq!(merged[2].id, "msg_3");
+    }
+
+    #[test]
+    fn merge_chats_matches_differently_composed_exports() {
+        let parser = ChatParser::new();
+        // iOS/macOS export decomposed, Android precomposed
+        let ios = "[18/05/2023, 08:00:00] Jose\u{0301}: cafe\u{0301}?\n[18/05/2023, 08:01:00] Alice: yes\n";
+        let android = "[18/05/2023, 08:00:00] Jos\u{00E9}: caf\u{00E9}?\n[18/05/2023, 08:02:00] Jos\u{00E9}: see you\n";
+        let merged = merge_chats(vec![parser.parse_str(ios, false).unwrap(), parser.parse_str(android, false).unwrap()]);
+
+        assert_eq!(merged.len(), 3);
+        assert!(merged.iter().filter(|message| message.sender != "Alice").all(|message| message.sender == "Jos\u{00E9}"));
+        // The kept copy is byte-exact
+        assert_eq!((merged[0].sender_display.as_str(), merged[0].content.as_str()), ("Jose\u{0301}", "cafe\u{0301}?"));
+    }
+
+    #[test]
+    fn merge_chats_matches_interpolated_exports_by_exported_minute() {
+        let mut parser = ChatParser::new();
+        parser.set_interpolate_subminute(true);
+        // The 08:01 run is two messages long in the first export and three in the second
+        let first = "[18/05/2023, 08:00] John: a\n[18/05/2023, 08:01] Alice: b\n[18/05/2023, 08:01] John: c\n";
+        let second = "[18/05/2023, 08:01] Alice: b\n[18/05/2023, 08:01] John: c\n\
+                      [18/05/2023, 08:01] Alice: d\n[18/05/2023, 08:02] John: e\n";
+        let (first, second) = (parser.parse_str(first, false).unwrap(), parser.parse_str(second, false).unwrap());
+        assert_ne!(first[2].timestamp, second[1].timestamp);
+
+        let merged = merge_chats(vec![first, second]);
+        let contents: Vec<&str> = merged.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["a", "b", "c", "d", "e"]);
+        // The merged run is spread again, as if it had come from one export
+        let seconds: Vec<i64> =
+            merged[1..4].iter().map(|message| (message.timestamp - merged[1].timestamp).num_seconds()).collect();
+        assert_eq!(seconds, [0, 20, 40]);
+        assert!(merged.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+    }
+
+    #[test]
+    fn merge_chats_spreads_single_message_minutes_in_any_order() {
+        let mut parser = ChatParser::new();
+        parser.set_interpolate_subminute(true);
+        // Each export has one message in the 08:00 minute
+        let a = parser.parse_str("[18/05/2023, 08:00] John: a\n[18/05/2023, 08:05] John: later\n", false).unwrap();
+        let b = parser.parse_str("[18/05/2023, 08:00] Alice: b\n", false).unwrap();
+
+        let offsets = |merged: &[Message]| -> Vec<i64> {
+            merged.iter().map(|message| (message.timestamp - merged[0].timestamp).num_seconds()).collect()
+        };
+        let forward = merge_chats(vec![a.clone(), b.clone()]);
+        let backward = merge_chats(vec![b, a]);
+        assert_eq!(offsets(&forward), [0, 30, 300]);
+        assert_eq!(offsets(&backward), offsets(&forward));
+        assert!(forward[..2].iter().chain(&backward[..2]).all(|message| message.timestamp_interpolated));
+    }
+}
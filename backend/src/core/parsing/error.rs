@@ -0,0 +1,56 @@
+use std::fmt;
+use std::io;
+
+/// Errors returned by the parsing, statistics and export functions
+#[derive(Debug)]
+pub enum ParserError {
+    /// A file could not be opened, read or written; `action` says which
+    Io { action: &'static str, source: io::Error },
+    /// A timestamp in the export could not be parsed
+    InvalidTimestamp(String),
+    /// An argument (match mode, export format, …) was not understood
+    InvalidArgument(String),
+    /// No sender matches the user identity; lists the senders that were found
+    UnknownIdentity { identity: String, senders: Vec<String> },
+    /// A time-based statistic was asked for on messages with synthetic timestamps
+    SyntheticTimestamps,
+    /// An export file failed verification; `line` is 1-based where known
+    CorruptExport { line: Option<usize>, detail: String },
+}
+
+impl ParserError {
+    /// Wrap an I/O error with the action that failed ("Failed to open file")
+    pub fn io(action: &'static str) -> impl FnOnce(io::Error) -> ParserError {
+        move |source| ParserError::Io { action, source }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserError::Io { action, source } => write!(f, "{}: {}", action, source),
+            ParserError::InvalidTimestamp(reason) => write!(f, "Failed to parse timestamp: {}", reason),
+            ParserError::InvalidArgument(reason) => f.write_str(reason),
+            ParserError::UnknownIdentity { identity, senders } => write!(
+                f,
+                "No sender matches user identity '{}'; detected senders: {}",
+                identity,
+                senders.join(", ")
+            ),
+            ParserError::SyntheticTimestamps => f.write_str(
+                "Messages have synthetic timestamps (parsed with parse_loose); time-based statistics are unavailable",
+            ),
+            ParserError::CorruptExport { line: Some(line), detail } => write!(f, "Corrupt export at line {}: {}", line, detail),
+            ParserError::CorruptExport { line: None, detail } => write!(f, "Corrupt export: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for ParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParserError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Weekday};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use regex::Regex;
+
+use crate::message::{GroupEvent, Message, MessageType};
+
+/// A date discussed in the chat, found by `ChatParser::detect_shared_events`
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone)]
+pub struct DetectedEvent {
+    pub referenced_date: NaiveDate,
+    pub message_count: usize,
+    pub participants: Vec<String>,
+}
+
+/// Lightweight matcher for date references in message text
+pub(crate) struct DateReferenceResolver {
+    next_weekday: Regex,
+    ordinal_day: Regex,
+    month_day: Regex,
+    day_month: Regex,
+}
+
+impl DateReferenceResolver {
+    pub(crate) fn new() -> Self {
+        // Whole month names or abbreviations only, so "maybe", "marks" and
+        // "decent" aren't read as months
+        let months = r"(jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\b\.?";
+        DateReferenceResolver {
+            next_weekday: Regex::new(r"(?i)\bnext (mon|tues|wednes|thurs|fri|satur|sun)day\b").unwrap(),
+            ordinal_day: Regex::new(r"(?i)\bon the (\d{1,2})(?:st|nd|rd|th)\b").unwrap(),
+            month_day: Regex::new(&format!(r"(?i)\b{} (\d{{1,2}})(?:st|nd|rd|th)?\b", months)).unwrap(),
+            day_month: Regex::new(&format!(r"(?i)\b(\d{{1,2}})(?:st|nd|rd|th)? (?:of )?{}(?:\s|$|[.,!?])", months)).unwrap(),
+        }
+    }
+
+    /// All dates referenced in `text`, resolved relative to `sent`
+    pub(crate) fn resolve(&self, text: &str, sent: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+
+        for captures in self.next_weekday.captures_iter(text) {
+            let target = match captures[1].to_lowercase().as_str() {
+                "mon" => Weekday::Mon,
+                "tues" => Weekday::Tue,
+                "wednes" => Weekday::Wed,
+                "thurs" => Weekday::Thu,
+                "fri" => Weekday::Fri,
+                "satur" => Weekday::Sat,
+                _ => Weekday::Sun,
+            };
+            // "next Friday" means Friday of the following (Monday-start) week
+            let next_monday = sent + Duration::days(7 - sent.weekday().num_days_from_monday() as i64);
+            dates.push(next_monday + Duration::days(target.num_days_from_monday() as i64));
+        }
+
+        for captures in self.ordinal_day.captures_iter(text) {
+            let day: u32 = captures[1].parse().unwrap_or(0);
+            // This month if the day is still ahead, otherwise next month
+            let this_month = NaiveDate::from_ymd_opt(sent.year(), sent.month(), day).filter(|date| *date >= sent);
+            let next_month = if sent.month() == 12 {
+                NaiveDate::from_ymd_opt(sent.year() + 1, 1, day)
+            } else {
+                NaiveDate::from_ymd_opt(sent.year(), sent.month() + 1, day)
+            };
+            dates.extend(this_month.or(next_month));
+        }
+
+        let month_days = self
+            .month_day
+            .captures_iter(text)
+            .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+            .chain(
+                self.day_month
+                    .captures_iter(text)
+                    .map(|captures| (captures[2].to_string(), captures[1].to_string())),
+            );
+        for (month, day) in month_days {
+            let month = Self::month_number(&month);
+            let day: u32 = day.parse().unwrap_or(0);
+            // The next occurrence on or after the message date
+            let date = NaiveDate::from_ymd_opt(sent.year(), month, day)
+                .filter(|date| *date >= sent)
+                .or_else(|| NaiveDate::from_ymd_opt(sent.year() + 1, month, day));
+            dates.extend(date);
+        }
+
+        dates
+    }
+
+    /// 1–12 for a month name matched by `months`, from its first three letters
+    fn month_number(name: &str) -> u32 {
+        const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+        let abbreviation = name.get(..3).unwrap_or(name).to_lowercase();
+        MONTHS.iter().position(|month| *month == abbreviation).map_or(1, |index| index as u32 + 1)
+    }
+}
+
+/// Timeline of group subjects as (timestamp, subject, changed_by), oldest first.
+/// The "created group" line counts as the first entry when present.
+pub fn subject_history(events: &[Message]) -> Vec<(DateTime<Local>, String, String)> {
+    let mut history: Vec<(DateTime<Local>, String, String)> = events
+        .iter()
+        .filter_map(|message| match &message.message_type {
+            MessageType::SystemEvent(GroupEvent::Created { actor, subject }) => {
+                Some((message.timestamp, subject.clone(), actor.clone()))
+            }
+            MessageType::SystemEvent(GroupEvent::SubjectChanged { actor, new_subject }) => {
+                Some((message.timestamp, new_subject.clone(), actor.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    history.sort_by_key(|entry| entry.0);
+    history
+}
+
+/// The subject active at `when`, or `None` if it predates the known history
+pub fn subject_at(events: &[Message], when: DateTime<Local>) -> Option<String> {
+    subject_history(events)
+        .into_iter()
+        .take_while(|entry| entry.0 <= when)
+        .last()
+        .map(|entry| entry.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(text: &str) -> Vec<NaiveDate> {
+        DateReferenceResolver::new().resolve(text, NaiveDate::from_ymd_opt(2023, 5, 18).unwrap())
+    }
+
+    #[test]
+    fn resolves_month_names_and_abbreviations() {
+        let may_26 = NaiveDate::from_ymd_opt(2023, 5, 26).unwrap();
+        assert_eq!(resolve("party on May 26"), vec![may_26]);
+        assert_eq!(resolve("see you 26th of may!"), vec![may_26]);
+        assert_eq!(resolve("back on Sept. 3"), vec![NaiveDate::from_ymd_opt(2023, 9, 3).unwrap()]);
+        assert_eq!(resolve("December 25th"), vec![NaiveDate::from_ymd_opt(2023, 12, 25).unwrap()]);
+        assert_eq!(resolve("since Jan 2"), vec![NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()]);
+    }
+
+    #[test]
+    fn ignores_words_that_start_like_months() {
+        for text in ["maybe 5 of us can come", "got 2 marks off", "Jane 3 said hi", "a decent 7/10", "Marcus 12 years"] {
+            assert!(resolve(text).is_empty(), "{}", text);
+        }
+    }
+}
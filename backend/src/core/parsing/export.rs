@@ -0,0 +1,1078 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use serde::Serialize;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::chat_parser::ChatParser;
+use crate::error::ParserError;
+use crate::message::{GroupEvent, Message, MessageType};
+
+/// A participant in the interaction graph
+struct GraphNode {
+    label: String,
+    message_count: usize,
+    /// Mean of the populated `sentiment_score`s, if any
+    avg_sentiment: Option<f64>,
+}
+
+/// Directed interactions from one participant to another
+#[derive(Default)]
+struct GraphEdge {
+    replies: usize,
+    mentions: usize,
+    /// Seconds between the replied-to message and each reply
+    response_seconds: Vec<i64>,
+}
+
+impl GraphEdge {
+    fn weight(&self) -> usize {
+        self.replies + self.mentions
+    }
+
+    fn median_response_seconds(&self) -> Option<f64> {
+        let mut seconds = self.response_seconds.clone();
+        if seconds.is_empty() {
+            return None;
+        }
+        seconds.sort_unstable();
+        let middle = seconds.len() / 2;
+        Some(if seconds.len().is_multiple_of(2) {
+            (seconds[middle - 1] + seconds[middle]) as f64 / 2.0
+        } else {
+            seconds[middle] as f64
+        })
+    }
+}
+
+/// Who talks to whom. Nodes are senders in order of first appearance; an
+/// edge A → B counts A's messages that directly follow one of B's (a reply)
+/// and A's messages that @-mention B by display name.
+fn interaction_graph(messages: &[Message]) -> (Vec<GraphNode>, BTreeMap<(usize, usize), GraphEdge>) {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut nodes: Vec<GraphNode> = Vec::new();
+    let mut sentiment: Vec<(f64, usize)> = Vec::new();
+    let mut mention_tags: Vec<String> = Vec::new();
+
+    for message in messages.iter().filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_))) {
+        let node = *index.entry(message.sender.as_str()).or_insert_with(|| {
+            nodes.push(GraphNode { label: message.sender_display.clone(), message_count: 0, avg_sentiment: None });
+            sentiment.push((0.0, 0));
+            mention_tags.push(format!("@{}", message.sender_display.to_lowercase()));
+            nodes.len() - 1
+        });
+        nodes[node].message_count += 1;
+        if let Some(score) = message.sentiment_score {
+            sentiment[node].0 += score as f64;
+            sentiment[node].1 += 1;
+        }
+    }
+    for (node, (sum, count)) in nodes.iter_mut().zip(sentiment) {
+        if count > 0 {
+            node.avg_sentiment = Some(sum / count as f64);
+        }
+    }
+
+    let mut edges: BTreeMap<(usize, usize), GraphEdge> = BTreeMap::new();
+    let mut previous: Option<&Message> = None;
+    for message in messages.iter().filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_))) {
+        let source = index[message.sender.as_str()];
+        if let Some(previous) = previous.filter(|previous| previous.sender != message.sender) {
+            let edge = edges.entry((source, index[previous.sender.as_str()])).or_default();
+            edge.replies += 1;
+            edge.response_seconds.push((message.timestamp - previous.timestamp).num_seconds());
+        }
+        let lowered = message.content.to_lowercase();
+        if lowered.contains('@') {
+            for (target, tag) in mention_tags.iter().enumerate() {
+                if target != source && lowered.contains(tag.as_str()) {
+                    edges.entry((source, target)).or_default().mentions += 1;
+                }
+            }
+        }
+        previous = Some(message);
+    }
+
+    (nodes, edges)
+}
+
+/// Quote a string as a DOT identifier
+fn dot_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => {}
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Escape text for an XML attribute value, dropping characters XML 1.0 forbids
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Node size scaled between 1 and 10 by message count relative to the busiest sender
+fn node_size(node: &GraphNode, max_count: usize) -> f64 {
+    1.0 + 9.0 * (node.message_count as f64 / max_count.max(1) as f64).sqrt()
+}
+
+fn render_dot(nodes: &[GraphNode], edges: &BTreeMap<(usize, usize), GraphEdge>) -> String {
+    let max_count = nodes.iter().map(|node| node.message_count).max().unwrap_or(0);
+    let mut out = String::from("digraph chat {\n    node [shape=circle];\n");
+    for node in nodes {
+        out.push_str(&format!(
+            "    {} [width={:.2}, message_count={}",
+            dot_quote(&node.label),
+            node_size(node, max_count) / 4.0,
+            node.message_count
+        ));
+        if let Some(avg) = node.avg_sentiment {
+            out.push_str(&format!(", avg_sentiment={:.4}", avg));
+        }
+        out.push_str("];\n");
+    }
+    for (&(source, target), edge) in edges {
+        out.push_str(&format!(
+            "    {} -> {} [weight={}, replies={}, mentions={}",
+            dot_quote(&nodes[source].label),
+            dot_quote(&nodes[target].label),
+            edge.weight(),
+            edge.replies,
+            edge.mentions
+        ));
+        if let Some(median) = edge.median_response_seconds() {
+            out.push_str(&format!(", median_response_seconds={}", median));
+        }
+        out.push_str("];\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_gexf(nodes: &[GraphNode], edges: &BTreeMap<(usize, usize), GraphEdge>) -> String {
+    let max_count = nodes.iter().map(|node| node.message_count).max().unwrap_or(0);
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">\n",
+        "  <graph defaultedgetype=\"directed\">\n",
+        "    <attributes class=\"node\">\n",
+        "      <attribute id=\"message_count\" title=\"message_count\" type=\"integer\"/>\n",
+        "      <attribute id=\"avg_sentiment\" title=\"avg_sentiment\" type=\"double\"/>\n",
+        "    </attributes>\n",
+        "    <attributes class=\"edge\">\n",
+        "      <attribute id=\"replies\" title=\"replies\" type=\"integer\"/>\n",
+        "      <attribute id=\"mentions\" title=\"mentions\" type=\"integer\"/>\n",
+        "      <attribute id=\"median_response_seconds\" title=\"median_response_seconds\" type=\"double\"/>\n",
+        "    </attributes>\n",
+        "    <nodes>\n",
+    ));
+    for (id, node) in nodes.iter().enumerate() {
+        out.push_str(&format!(
+            "      <node id=\"{}\" label=\"{}\">\n        <attvalues>\n          <attvalue for=\"message_count\" value=\"{}\"/>\n",
+            id,
+            xml_escape(&node.label),
+            node.message_count
+        ));
+        if let Some(avg) = node.avg_sentiment {
+            out.push_str(&format!("          <attvalue for=\"avg_sentiment\" value=\"{:.4}\"/>\n", avg));
+        }
+        out.push_str(&format!(
+            "        </attvalues>\n        <viz:size value=\"{:.2}\"/>\n      </node>\n",
+            node_size(node, max_count)
+        ));
+    }
+    out.push_str("    </nodes>\n    <edges>\n");
+    for (id, (&(source, target), edge)) in edges.iter().enumerate() {
+        out.push_str(&format!(
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\">\n        <attvalues>\n          <attvalue for=\"replies\" value=\"{}\"/>\n          <attvalue for=\"mentions\" value=\"{}\"/>\n",
+            id,
+            source,
+            target,
+            edge.weight(),
+            edge.replies,
+            edge.mentions
+        ));
+        if let Some(median) = edge.median_response_seconds() {
+            out.push_str(&format!(
+                "          <attvalue for=\"median_response_seconds\" value=\"{}\"/>\n",
+                median
+            ));
+        }
+        out.push_str("        </attvalues>\n      </edge>\n");
+    }
+    out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+    out
+}
+
+/// Output formats for `export_graph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Gephi GEXF 1.3
+    Gexf,
+}
+
+impl GraphFormat {
+    pub fn parse(format: &str) -> Result<Self, ParserError> {
+        match format.to_ascii_lowercase().as_str() {
+            "dot" => Ok(GraphFormat::Dot),
+            "gexf" => Ok(GraphFormat::Gexf),
+            other => Err(ParserError::InvalidArgument(format!(
+                "Unknown graph format '{}' (expected 'dot' or 'gexf')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Render the participant interaction graph in `format`
+pub fn render_graph(messages: &[Message], format: GraphFormat) -> String {
+    let (nodes, edges) = interaction_graph(messages);
+    match format {
+        GraphFormat::Dot => render_dot(&nodes, &edges),
+        GraphFormat::Gexf => render_gexf(&nodes, &edges),
+    }
+}
+
+/// Write the participant interaction graph to `path`. Nodes carry message
+/// counts and average sentiment (populate `sentiment_score` first), edges
+/// reply/mention counts and the median response time.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_graph(messages: &[Message], path: &str, format: GraphFormat) -> Result<(), ParserError> {
+    std::fs::write(path, render_graph(messages, format)).map_err(ParserError::io("Failed to write graph"))
+}
+
+/// What `render_events_ics` includes besides the group events. Day events
+/// are all-day and describe the day: message count, participants and the
+/// day's first message.
+#[derive(Debug, Clone, Default)]
+pub struct IcsOptions {
+    /// An all-day event on the day with the most messages
+    pub include_busiest_day: bool,
+    /// An event spanning the longest gap between two messages
+    pub include_longest_silence: bool,
+    /// The `top_busiest_days` days with the most messages (the busiest day
+    /// alone when 0 and `include_busiest_day` is set)
+    pub top_busiest_days: usize,
+    /// A yearly recurring event on the day of the first message
+    pub include_anniversary: bool,
+    /// Dates mentioned by at least two participants (see
+    /// `ChatParser::detect_shared_events`)
+    pub include_occasions: bool,
+    /// The days a run of consecutive active days reaches one of
+    /// `STREAK_MILESTONES`
+    pub include_streak_milestones: bool,
+}
+
+/// Streak lengths, in days, that get a calendar event
+pub const STREAK_MILESTONES: [usize; 5] = [7, 30, 100, 365, 1000];
+
+/// Longest message preview in an ICS day description, in characters
+const ICS_PREVIEW_CHARS: usize = 80;
+
+/// Escape TEXT property values (RFC 5545 §3.3.11)
+fn ics_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Append a content line, folded so no line exceeds 75 octets (RFC 5545 §3.1)
+fn push_ics_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn ics_utc(timestamp: DateTime<Local>) -> String {
+    timestamp.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// One VEVENT. `start` and `end` are complete property values such as
+/// "20230518T083907Z" or ";VALUE=DATE:20230518".
+struct IcsEvent {
+    /// Hashed into the UID, so it must stay the same when the chat is
+    /// re-exported with more messages: calendar apps then update the event
+    /// rather than add a duplicate
+    uid_key: String,
+    stamp: String,
+    start: String,
+    end: Option<String>,
+    summary: String,
+    description: Option<String>,
+    rrule: Option<&'static str>,
+}
+
+impl IcsEvent {
+    /// An event at a single instant
+    fn instant(timestamp: DateTime<Local>, summary: String) -> IcsEvent {
+        let start = ics_utc(timestamp);
+        IcsEvent {
+            uid_key: format!("{}{}", start, summary),
+            stamp: start.clone(),
+            start,
+            end: None,
+            summary,
+            description: None,
+            rrule: None,
+        }
+    }
+
+    /// An all-day event on `day`; `kind` and the day make up the UID key
+    fn all_day(kind: &str, day: NaiveDate, summary: String, description: Option<String>) -> IcsEvent {
+        let date = day.format("%Y%m%d");
+        IcsEvent {
+            uid_key: format!("{}:{}", kind, date),
+            stamp: format!("{}T000000Z", date),
+            start: format!(";VALUE=DATE:{}", date),
+            end: Some(format!(";VALUE=DATE:{}", day.succ_opt().unwrap_or(day).format("%Y%m%d"))),
+            summary,
+            description,
+            rrule: None,
+        }
+    }
+}
+
+fn push_ics_event(ics: &mut String, event: &IcsEvent) {
+    let mut uid = Xxh3::new();
+    uid.update(event.uid_key.as_bytes());
+    let separator = |value: &str| if value.starts_with(';') { "" } else { ":" };
+
+    push_ics_line(ics, "BEGIN:VEVENT");
+    push_ics_line(ics, &format!("UID:{:016x}@memories", uid.digest()));
+    push_ics_line(ics, &format!("DTSTAMP:{}", event.stamp));
+    push_ics_line(ics, &format!("DTSTART{}{}", separator(&event.start), event.start));
+    if let Some(end) = &event.end {
+        push_ics_line(ics, &format!("DTEND{}{}", separator(end), end));
+    }
+    if let Some(rrule) = event.rrule {
+        push_ics_line(ics, &format!("RRULE:{}", rrule));
+    }
+    push_ics_line(ics, &format!("SUMMARY:{}", ics_escape(&event.summary)));
+    if let Some(description) = &event.description {
+        push_ics_line(ics, &format!("DESCRIPTION:{}", ics_escape(description)));
+    }
+    push_ics_line(ics, "END:VEVENT");
+}
+
+/// "1 message", "12 messages"
+fn count_messages(count: usize) -> String {
+    format!("{} message{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// "12 messages from Alice, John" and a preview of the day's first message.
+/// `day_messages` are in time order.
+fn describe_day(day_messages: &[&Message]) -> String {
+    let mut participants: Vec<&str> = day_messages.iter().map(|message| message.sender_display.as_str()).collect();
+    participants.sort_unstable();
+    participants.dedup();
+    let mut description = format!("{} from {}", count_messages(day_messages.len()), participants.join(", "));
+    if let Some(first) = day_messages.iter().find(|message| !message.content.trim().is_empty()) {
+        let line = first.content.lines().next().unwrap_or_default();
+        let mut preview: String = line.chars().take(ICS_PREVIEW_CHARS).collect();
+        if preview.len() < line.len() {
+            preview.push('…');
+        }
+        description.push_str(&format!("\n{}: {}", first.sender_display, preview));
+    }
+    description
+}
+
+/// "3 days 1 hour", "5 hours", "12 minutes"
+fn describe_gap(gap: chrono::Duration) -> String {
+    let unit = |count: i64, name: &str| format!("{} {}{}", count, name, if count == 1 { "" } else { "s" });
+    match (gap.num_days(), gap.num_hours() % 24) {
+        (0, 0) => unit(gap.num_minutes(), "minute"),
+        (0, hours) => unit(hours, "hour"),
+        (days, 0) => unit(days, "day"),
+        (days, hours) => format!("{} {}", unit(days, "day"), unit(hours, "hour")),
+    }
+}
+
+/// Render an iCalendar (RFC 5545) timeline: a VEVENT per group event (parse
+/// with system messages kept), plus the extras `options` asks for. Messages
+/// need not be in order. Times are written in UTC; day events use the
+/// messages' local dates. UIDs depend only on what an event marks, never
+/// on counts, so re-importing a fresh export updates events in place.
+pub fn render_events_ics(messages: &[Message], options: &IcsOptions) -> String {
+    let mut ics = String::new();
+    push_ics_line(&mut ics, "BEGIN:VCALENDAR");
+    push_ics_line(&mut ics, "VERSION:2.0");
+    push_ics_line(&mut ics, "PRODID:-//Memories//whatsapp_parser//EN");
+    push_ics_line(&mut ics, "CALSCALE:GREGORIAN");
+
+    for message in messages {
+        let MessageType::SystemEvent(event) = &message.message_type else { continue };
+        let summary = match event {
+            GroupEvent::Created { actor, subject } => format!("{} created group \"{}\"", actor, subject),
+            GroupEvent::SubjectChanged { actor, new_subject } => {
+                format!("{} changed the subject to \"{}\"", actor, new_subject)
+            }
+            GroupEvent::IconChanged { actor } => format!("{} changed this group's icon", actor),
+            GroupEvent::ContactBlocked => "You blocked this contact".to_string(),
+            GroupEvent::ContactUnblocked => "You unblocked this contact".to_string(),
+            GroupEvent::DisappearingMessages { enabled, actor } => {
+                let state = if *enabled { "on" } else { "off" };
+                match actor {
+                    Some(actor) => format!("{} turned {} disappearing messages", actor, state),
+                    None => format!("Disappearing messages were turned {}", state),
+                }
+            }
+            GroupEvent::SelfJoined { .. } => "You joined the group".to_string(),
+            GroupEvent::Other { text } => text.clone(),
+        };
+        push_ics_event(&mut ics, &IcsEvent::instant(message.timestamp, summary));
+    }
+
+    let mut conversation: Vec<&Message> = messages
+        .iter()
+        .filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_)))
+        .collect();
+    conversation.sort_by_key(|message| message.timestamp);
+    let mut days: BTreeMap<NaiveDate, Vec<&Message>> = BTreeMap::new();
+    for message in &conversation {
+        days.entry(message.timestamp.date_naive()).or_default().push(message);
+    }
+
+    if options.include_anniversary {
+        if let Some((&day, day_messages)) = days.iter().next() {
+            let mut event = IcsEvent::all_day(
+                "anniversary",
+                day,
+                format!("Chat anniversary (since {})", day.year()),
+                Some(format!("First day of the chat: {}", describe_day(day_messages))),
+            );
+            event.rrule = Some("FREQ=YEARLY");
+            push_ics_event(&mut ics, &event);
+        }
+    }
+    let busiest_count = options.top_busiest_days.max(usize::from(options.include_busiest_day));
+    if busiest_count > 0 {
+        let mut ranked: Vec<(&NaiveDate, &Vec<&Message>)> = days.iter().collect();
+        // Earliest day wins ties
+        ranked.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(b.0)));
+        for (rank, (&day, day_messages)) in ranked.into_iter().take(busiest_count).enumerate() {
+            let summary = match rank {
+                0 => format!("Busiest day: {}", count_messages(day_messages.len())),
+                rank => format!("#{} busiest day: {}", rank + 1, count_messages(day_messages.len())),
+            };
+            push_ics_event(&mut ics, &IcsEvent::all_day("busy-day", day, summary, Some(describe_day(day_messages))));
+        }
+    }
+    if options.include_occasions {
+        for occasion in ChatParser::detect_shared_events(messages) {
+            if occasion.participants.len() < 2 {
+                continue;
+            }
+            let description =
+                format!("Mentioned in {} by {}", count_messages(occasion.message_count), occasion.participants.join(", "));
+            let summary = "Occasion discussed in the chat".to_string();
+            push_ics_event(&mut ics, &IcsEvent::all_day("occasion", occasion.referenced_date, summary, Some(description)));
+        }
+    }
+    if options.include_streak_milestones {
+        let mut streak = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for (&day, day_messages) in &days {
+            streak = if previous.and_then(|previous| previous.succ_opt()) == Some(day) { streak + 1 } else { 1 };
+            previous = Some(day);
+            if STREAK_MILESTONES.contains(&streak) {
+                let summary = format!("{}-day streak", streak);
+                let kind = format!("streak-{}", streak);
+                push_ics_event(&mut ics, &IcsEvent::all_day(&kind, day, summary, Some(describe_day(day_messages))));
+            }
+        }
+    }
+    if options.include_longest_silence {
+        let longest = conversation
+            .windows(2)
+            .map(|pair| (pair[1].timestamp - pair[0].timestamp, pair[0], pair[1]))
+            .max_by_key(|(gap, before, _)| (*gap, std::cmp::Reverse(before.timestamp)));
+        if let Some((gap, before, after)) = longest.filter(|(gap, ..)| *gap > chrono::Duration::zero()) {
+            let mut event = IcsEvent::instant(before.timestamp, format!("Longest silence: {}", describe_gap(gap)));
+            event.uid_key = "longest-silence".to_string();
+            event.end = Some(ics_utc(after.timestamp));
+            push_ics_event(&mut ics, &event);
+        }
+    }
+
+    push_ics_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Write `render_events_ics` output to `path`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_events_ics(messages: &[Message], path: &str, options: &IcsOptions) -> Result<(), ParserError> {
+    std::fs::write(path, render_events_ics(messages, options)).map_err(ParserError::io("Failed to write calendar"))
+}
+
+/// Call details of a `MessageRecord`
+#[derive(Serialize)]
+struct CallView {
+    video: bool,
+    missed: bool,
+    duration_seconds: Option<u32>,
+}
+
+/// Reaction details of a `MessageRecord`
+#[derive(Serialize)]
+struct ReactionView<'a> {
+    reactor: &'a str,
+    emoji: &'a str,
+    to_sender: &'a str,
+}
+
+/// A message in the same shape as the Python `parse_whatsapp_chat` dicts,
+/// with the timestamp as an ISO 8601 (RFC 3339) string
+#[derive(Serialize)]
+pub struct MessageRecord<'a> {
+    id: &'a str,
+    timestamp: String,
+    sender: &'a str,
+    sender_display: &'a str,
+    content: &'a str,
+    #[serde(rename = "type")]
+    message_type: &'a str,
+    is_own: bool,
+    broadcast: bool,
+    grouped_with_previous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media_filename: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    caption: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gap_before_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_timestamp: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a GroupEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    call: Option<CallView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reaction: Option<ReactionView<'a>>,
+}
+
+impl<'a> From<&'a Message> for MessageRecord<'a> {
+    fn from(message: &'a Message) -> Self {
+        MessageRecord {
+            id: &message.id,
+            timestamp: message.timestamp.to_rfc3339(),
+            sender: &message.sender,
+            sender_display: &message.sender_display,
+            content: &message.content,
+            message_type: message.message_type.as_str(),
+            is_own: message.is_own,
+            broadcast: message.broadcast,
+            grouped_with_previous: message.grouped_with_previous,
+            media_filename: message.media_filename.as_deref(),
+            caption: message.caption.as_deref(),
+            reply_to: message.reply_to.as_deref(),
+            reply_to_timestamp: message.reply_to_timestamp.map(|timestamp| timestamp.to_rfc3339()),
+            gap_before_seconds: message.gap_before_seconds,
+            original_timestamp: message.original_timestamp.as_deref(),
+            event: match &message.message_type {
+                MessageType::SystemEvent(event) => Some(event),
+                _ => None,
+            },
+            call: match message.message_type {
+                MessageType::Call { video, missed, duration_seconds } => Some(CallView { video, missed, duration_seconds }),
+                _ => None,
+            },
+            reaction: match &message.message_type {
+                MessageType::Reaction { reactor, emoji, to_sender } => Some(ReactionView { reactor, emoji, to_sender }),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Write one `MessageRecord` JSON object per line
+pub fn write_jsonl<W: Write>(messages: &[Message], mut writer: W) -> Result<(), ParserError> {
+    for message in messages {
+        serde_json::to_writer(&mut writer, &MessageRecord::from(message))
+            .map_err(io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .map_err(ParserError::io("Failed to write export"))?;
+    }
+    writer.flush().map_err(ParserError::io("Failed to write export"))
+}
+
+/// Records per checksummed chunk of a JSON Lines export (see `ManifestWriter`)
+pub const MANIFEST_CHUNK_LINES: usize = 1000;
+
+/// Passes JSON Lines through to `inner` while counting lines and hashing
+/// the bytes, then appends a manifest line that `verify_jsonl` checks:
+/// `{"manifest":{"message_count":N,"xxh3":"<hex>","chunk_lines":1000,"chunks":["<hex>",…],"parser_version":"0.1.0"}}`.
+/// Each entry of `chunks` hashes `chunk_lines` records, so a damaged file
+/// can be traced to the chunk holding the damage. The version records which
+/// build wrote the export.
+pub struct ManifestWriter<W: Write> {
+    inner: W,
+    hasher: Xxh3,
+    chunk_hasher: Xxh3,
+    chunk_lines: usize,
+    chunks: Vec<u64>,
+    lines: usize,
+}
+
+impl<W: Write> ManifestWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_chunk_lines(inner, MANIFEST_CHUNK_LINES)
+    }
+
+    /// A writer that checksums every `chunk_lines` records (at least 1)
+    pub fn with_chunk_lines(inner: W, chunk_lines: usize) -> Self {
+        ManifestWriter {
+            inner,
+            hasher: Xxh3::new(),
+            chunk_hasher: Xxh3::new(),
+            chunk_lines: chunk_lines.max(1),
+            chunks: Vec::new(),
+            lines: 0,
+        }
+    }
+
+    /// Write the manifest line and return the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.lines.is_multiple_of(self.chunk_lines) {
+            self.chunks.push(self.chunk_hasher.digest());
+        }
+        let manifest = serde_json::json!({
+            "manifest": {
+                "message_count": self.lines,
+                "xxh3": format!("{:016x}", self.hasher.digest()),
+                "chunk_lines": self.chunk_lines,
+                "chunks": self.chunks.iter().map(|chunk| format!("{:016x}", chunk)).collect::<Vec<_>>(),
+                "parser_version": crate::version::VERSION,
+            }
+        });
+        serde_json::to_writer(&mut self.inner, &manifest)?;
+        self.inner.write_all(b"\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ManifestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        // Newlines inside JSON strings are escaped, so each one ends a record
+        let mut rest = &buf[..written];
+        while let Some(end) = memchr::memchr(b'\n', rest) {
+            self.chunk_hasher.update(&rest[..=end]);
+            rest = &rest[end + 1..];
+            self.lines += 1;
+            if self.lines.is_multiple_of(self.chunk_lines) {
+                self.chunks.push(self.chunk_hasher.digest());
+                self.chunk_hasher.reset();
+            }
+        }
+        self.chunk_hasher.update(rest);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Run `write` against a buffered file at `path`, appending a manifest when asked
+#[cfg(not(target_arch = "wasm32"))]
+fn export_to_file(
+    path: &str,
+    manifest: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), ParserError>,
+) -> Result<(), ParserError> {
+    let file = std::fs::File::create(path).map_err(ParserError::io("Failed to create file"))?;
+    let mut writer = io::BufWriter::new(file);
+    if manifest {
+        let mut manifest_writer = ManifestWriter::new(&mut writer);
+        write(&mut manifest_writer)?;
+        manifest_writer.finish().map_err(ParserError::io("Failed to write export"))?;
+    } else {
+        write(&mut writer)?;
+    }
+    writer.flush().map_err(ParserError::io("Failed to write export"))
+}
+
+/// Write messages to `path` as JSON Lines (see `MessageRecord`), with a
+/// trailing integrity manifest when `manifest` is set
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_jsonl(messages: &[Message], path: &str, manifest: bool) -> Result<(), ParserError> {
+    export_to_file(path, manifest, |writer| write_jsonl(messages, writer))
+}
+
+/// One line of `export_redacted_jsonl`: the message's metadata, with the
+/// content reduced to its length in characters
+#[derive(Serialize)]
+struct RedactedMessage<'a> {
+    id: &'a str,
+    timestamp: String,
+    sender: &'a str,
+    #[serde(rename = "type")]
+    message_type: &'a str,
+    content_length: usize,
+    is_own: bool,
+    broadcast: bool,
+}
+
+/// Write one JSON object per message with the content replaced by its
+/// length, so activity patterns can be shared without the text. System
+/// events are written with their type only; their details (subjects, names)
+/// are content too.
+pub fn write_redacted_jsonl<W: Write>(messages: &[Message], mut writer: W) -> Result<(), ParserError> {
+    for message in messages {
+        let record = RedactedMessage {
+            id: &message.id,
+            timestamp: message.timestamp.to_rfc3339(),
+            sender: &message.sender,
+            message_type: message.message_type.as_str(),
+            content_length: message.content.chars().count(),
+            is_own: message.is_own,
+            broadcast: message.broadcast,
+        };
+        serde_json::to_writer(&mut writer, &record)
+            .map_err(io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .map_err(ParserError::io("Failed to write redacted export"))?;
+    }
+    writer.flush().map_err(ParserError::io("Failed to write redacted export"))
+}
+
+/// Write the redacted JSON Lines export (see `write_redacted_jsonl`) to
+/// `path`, with a trailing integrity manifest when `manifest` is set
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_redacted_jsonl(messages: &[Message], path: &str, manifest: bool) -> Result<(), ParserError> {
+    export_to_file(path, manifest, |writer| write_redacted_jsonl(messages, writer))
+}
+
+/// An export that passed `verify_jsonl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedExport {
+    pub message_count: usize,
+    /// The manifest's `parser_version`: which build wrote the export. `None`
+    /// without a manifest.
+    pub parser_version: Option<String>,
+}
+
+impl VerifiedExport {
+    /// The version that wrote the export, when it isn't this build
+    pub fn version_mismatch(&self) -> Option<&str> {
+        self.parser_version.as_deref().filter(|version| *version != crate::version::VERSION)
+    }
+}
+
+/// Check a JSON Lines export and return its record count and writer's
+/// version. Every line must be a complete JSON object and the file must end
+/// with a newline. When the last line is a manifest (see `ManifestWriter`),
+/// the record count and hash must match it, and a record that doesn't match
+/// its chunk checksum is reported at the first line of that chunk. Without
+/// one, a file cut exactly at a line boundary can't be told apart from a
+/// shorter export, so `require_manifest` rejects it.
+pub fn verify_jsonl(bytes: &[u8], require_manifest: bool) -> Result<VerifiedExport, ParserError> {
+    let corrupt = |line: Option<usize>, detail: &str| ParserError::CorruptExport { line, detail: detail.to_string() };
+    if bytes.is_empty() {
+        return Err(corrupt(None, "file is empty"));
+    }
+    let Some(body) = bytes.strip_suffix(b"\n") else {
+        let line = bytes.iter().filter(|&&byte| byte == b'\n').count() + 1;
+        return Err(corrupt(Some(line), "file is truncated (no final newline)"));
+    };
+
+    let lines: Vec<&[u8]> = body.split(|&byte| byte == b'\n').collect();
+    let mut records = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_slice::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(record)) => records.push(record),
+            _ => return Err(corrupt(Some(index + 1), "not a complete JSON object")),
+        }
+    }
+
+    let Some(manifest) = records.last().and_then(|record| record.get("manifest")) else {
+        if require_manifest {
+            return Err(corrupt(None, "no manifest line (truncated, or exported without a manifest)"));
+        }
+        return Ok(VerifiedExport { message_count: records.len(), parser_version: None });
+    };
+    let manifest_line = lines.len();
+    let expected_count = manifest.get("message_count").and_then(serde_json::Value::as_u64);
+    let expected_hash = manifest.get("xxh3").and_then(serde_json::Value::as_str);
+    let (Some(expected_count), Some(expected_hash)) = (expected_count, expected_hash) else {
+        return Err(corrupt(Some(manifest_line), "manifest is missing message_count or xxh3"));
+    };
+
+    let message_count = lines.len() - 1;
+    if expected_count != message_count as u64 {
+        return Err(ParserError::CorruptExport {
+            line: None,
+            detail: format!("manifest lists {} messages but the file has {}", expected_count, message_count),
+        });
+    }
+    // Chunk checksums, when the manifest has them, say where the damage is
+    if let Some(chunks) = manifest.get("chunks") {
+        let chunk_lines = manifest.get("chunk_lines").and_then(serde_json::Value::as_u64).filter(|&lines| lines > 0);
+        let expected: Option<Vec<&str>> =
+            chunks.as_array().and_then(|chunks| chunks.iter().map(serde_json::Value::as_str).collect());
+        let (Some(chunk_lines), Some(expected)) = (chunk_lines, expected) else {
+            return Err(corrupt(Some(manifest_line), "manifest has malformed chunk checksums"));
+        };
+        let chunk_lines = chunk_lines as usize;
+        if expected.len() != message_count.div_ceil(chunk_lines) {
+            return Err(corrupt(Some(manifest_line), "manifest chunk count does not match the message count"));
+        }
+        for (index, (chunk, expected)) in lines[..message_count].chunks(chunk_lines).zip(expected).enumerate() {
+            let mut hasher = Xxh3::new();
+            for line in chunk {
+                hasher.update(line);
+                hasher.update(b"\n");
+            }
+            if format!("{:016x}", hasher.digest()) != expected {
+                let first = index * chunk_lines + 1;
+                return Err(ParserError::CorruptExport {
+                    line: Some(first),
+                    detail: format!(
+                        "chunk {} (lines {}-{}) does not match its checksum",
+                        index + 1,
+                        first,
+                        first + chunk.len() - 1
+                    ),
+                });
+            }
+        }
+    }
+    // The hash covers everything before the manifest line
+    let manifest_start = memchr::memrchr(b'\n', body).map_or(0, |position| position + 1);
+    let hash = format!("{:016x}", xxhash_rust::xxh3::xxh3_64(&bytes[..manifest_start]));
+    if hash != expected_hash {
+        return Err(corrupt(None, "content hash does not match the manifest"));
+    }
+    let parser_version = manifest.get("parser_version").and_then(serde_json::Value::as_str).map(str::to_string);
+    Ok(VerifiedExport { message_count, parser_version })
+}
+
+/// Verify the JSON Lines export at `path` (see `verify_jsonl`)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn verify_export(path: &str, require_manifest: bool) -> Result<VerifiedExport, ParserError> {
+    let bytes = std::fs::read(path).map_err(ParserError::io("Failed to open file"))?;
+    verify_jsonl(&bytes, require_manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_parser::ChatParser;
+
+    #[test]
+    fn dot_quoting_escapes_quotes_and_backslashes() {
+        assert_eq!(dot_quote(r#"Jo "JJ" \ O"#), r#""Jo \"JJ\" \\ O""#);
+    }
+
+    #[test]
+    fn ics_has_a_vevent_per_group_event() {
+        let messages = ChatParser::new()
+            .parse_str(
+                "[18/05/2023, 08:00:00] John created group \"Trip; 2023\"\n\
+                 [18/05/2023, 08:01:00] John: Welcome\n\
+                 [18/05/2023, 08:02:00] Alice: Hi\n\
+                 [21/05/2023, 10:00:00] Alice left\n\
+                 [21/05/2023, 10:00:00] John: Bye\n",
+                true,
+            )
+            .unwrap();
+        let options = IcsOptions { include_busiest_day: true, include_longest_silence: true, ..IcsOptions::default() };
+        let ics = render_events_ics(&messages, &options);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 4);
+        assert!(ics.contains("SUMMARY:John created group \"Trip\\; 2023\"\r\n"));
+        assert!(ics.contains("SUMMARY:Alice left\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20230518\r\n"));
+        assert!(ics.contains("SUMMARY:Busiest day: 2 messages\r\n"));
+        assert!(ics.contains("SUMMARY:Longest silence: 3 days 1 hour\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+
+        let plain = render_events_ics(&messages, &IcsOptions::default());
+        assert_eq!(plain.matches("BEGIN:VEVENT").count(), 2);
+    }
+
+    #[test]
+    fn ics_day_events_describe_the_day_and_keep_their_uids() {
+        let mut chat = String::new();
+        for day in 1..=8 {
+            chat.push_str(&format!("[{:02}/05/2023, 09:00:00] John: morning, day {}\n", day, day));
+        }
+        chat.push_str("[03/05/2023, 10:00:00] Alice: see you on 25 December\n");
+        chat.push_str("[03/05/2023, 10:05:00] John: December 25 it is\n");
+        let messages = ChatParser::new().parse_str(&chat, false).unwrap();
+        let options = IcsOptions {
+            top_busiest_days: 10,
+            include_anniversary: true,
+            include_occasions: true,
+            include_streak_milestones: true,
+            ..IcsOptions::default()
+        };
+        let ics = render_events_ics(&messages, &options);
+        let unfolded = ics.replace("\r\n ", "");
+
+        assert!(unfolded.contains("DTSTART;VALUE=DATE:20230501\r\nDTEND;VALUE=DATE:20230502\r\nRRULE:FREQ=YEARLY\r\n"));
+        assert!(unfolded.contains("SUMMARY:Busiest day: 3 messages\r\n"));
+        assert!(unfolded.contains("DESCRIPTION:3 messages from Alice\\, John\\nJohn: morning\\, day 3\r\n"));
+        assert_eq!(unfolded.matches("busiest day: 1 message").count(), 7);
+        assert!(unfolded.contains("DTSTART;VALUE=DATE:20231225\r\nDTEND;VALUE=DATE:20231226\r\n"));
+        assert!(unfolded.contains("SUMMARY:7-day streak\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+
+        // One more message changes the counts but not the UIDs
+        let uids = |ics: &str| -> Vec<String> {
+            let mut uids: Vec<String> = ics.lines().filter(|line| line.starts_with("UID:")).map(str::to_string).collect();
+            uids.sort();
+            uids
+        };
+        chat.push_str("[08/05/2023, 09:30:00] Alice: late\n");
+        let more = ChatParser::new().parse_str(&chat, false).unwrap();
+        assert_eq!(uids(&render_events_ics(&more, &options)), uids(&ics));
+    }
+
+    #[test]
+    fn ics_lines_are_folded() {
+        let mut ics = String::new();
+        push_ics_line(&mut ics, &format!("SUMMARY:{}", "é".repeat(60)));
+        let lines: Vec<&str> = ics.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+    }
+
+    #[test]
+    fn renders_reply_edges() {
+        let messages = ChatParser::new()
+            .parse_str("[18/05/2023, 08:00:00] John: hi\n[18/05/2023, 08:01:00] Alice: hey @John\n", false)
+            .unwrap();
+        let dot = render_graph(&messages, GraphFormat::Dot);
+        assert!(dot.contains(r#""Alice" -> "John" [weight=2, replies=1, mentions=1, median_response_seconds=60];"#));
+        assert!(GraphFormat::parse("graphml").is_err());
+    }
+
+    #[test]
+    fn redacted_export_drops_content() {
+        let messages = ChatParser::new()
+            .parse_str("[18/05/2023, 08:00:00] John: secret plans\n[18/05/2023, 08:01:00] Alice: ok 👍\n", false)
+            .unwrap();
+        let mut output = Vec::new();
+        write_redacted_jsonl(&messages, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("secret") && !output.contains("👍"));
+        let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["sender"], "John");
+        assert_eq!(lines[0]["content_length"], 12);
+        assert_eq!(lines[1]["content_length"], 4);
+    }
+
+    #[test]
+    fn manifest_detects_truncation_and_bit_flips() {
+        let messages = ChatParser::new()
+            .parse_str("[18/05/2023, 08:00:00] John: hello\n[18/05/2023, 08:01:00] Alice: hi there\n", false)
+            .unwrap();
+        let mut writer = ManifestWriter::new(Vec::new());
+        write_jsonl(&messages, &mut writer).unwrap();
+        let bytes = writer.finish().unwrap();
+        let verified = verify_jsonl(&bytes, true).unwrap();
+        assert_eq!(verified.message_count, 2);
+        assert_eq!(verified.parser_version.as_deref(), Some(crate::version::VERSION));
+        assert_eq!(verified.version_mismatch(), None);
+
+        // Cut inside the second record
+        let first_line = memchr::memchr(b'\n', &bytes).unwrap();
+        let error = verify_jsonl(&bytes[..first_line + 10], false).unwrap_err();
+        assert!(matches!(error, ParserError::CorruptExport { line: Some(2), .. }));
+
+        // Cut at a line boundary, losing the manifest along with a record
+        assert_eq!(verify_jsonl(&bytes[..first_line + 1], false).unwrap().message_count, 1);
+        assert!(verify_jsonl(&bytes[..first_line + 1], true).is_err());
+
+        // Flip a bit in a content character; the JSON still parses
+        let mut flipped = bytes.clone();
+        let position = memchr::memmem::find(&flipped, b"hello").unwrap();
+        flipped[position] ^= 0x01;
+        let error = verify_jsonl(&flipped, false).unwrap_err();
+        assert!(matches!(error, ParserError::CorruptExport { line: Some(1), .. }));
+        assert!(error.to_string().contains("chunk 1 (lines 1-2)"));
+
+        assert!(verify_jsonl(b"", false).is_err());
+
+        // The version isn't hashed, so an export from another build still verifies
+        let from_older = String::from_utf8(bytes)
+            .unwrap()
+            .replace(&format!("\"parser_version\":\"{}\"", crate::version::VERSION), "\"parser_version\":\"0.0.1\"");
+        assert_eq!(verify_jsonl(from_older.as_bytes(), true).unwrap().version_mismatch(), Some("0.0.1"));
+    }
+
+    #[test]
+    fn manifest_chunks_locate_a_bit_flip() {
+        let chat: String =
+            (0..25).map(|minute| format!("[18/05/2023, 08:{:02}:00] John: message {}\n", minute, minute)).collect();
+        let messages = ChatParser::new().parse_str(&chat, false).unwrap();
+        let mut writer = ManifestWriter::with_chunk_lines(Vec::new(), 10);
+        write_jsonl(&messages, &mut writer).unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(verify_jsonl(&bytes, true).unwrap().message_count, 25);
+
+        // "message 14" is on line 15, in the second chunk
+        let mut flipped = bytes.clone();
+        let position = memchr::memmem::find(&flipped, b"message 14").unwrap();
+        flipped[position] ^= 0x01;
+        let error = verify_jsonl(&flipped, true).unwrap_err();
+        assert!(matches!(error, ParserError::CorruptExport { line: Some(11), .. }));
+        assert!(error.to_string().contains("chunk 2 (lines 11-20)"));
+
+        // The short last chunk is checked too
+        let mut flipped = bytes.clone();
+        let position = memchr::memmem::find(&flipped, b"message 24").unwrap();
+        flipped[position] ^= 0x01;
+        let error = verify_jsonl(&flipped, true).unwrap_err();
+        assert!(error.to_string().contains("chunk 3 (lines 21-25)"));
+    }
+}
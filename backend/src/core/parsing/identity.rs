@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::chat_parser::ChatParser;
+use crate::error::ParserError;
+use crate::message::{Message, MessageType};
+use crate::text::fold_case;
+
+/// How a user identity is matched against sender names
+#[derive(Debug, Clone, Copy)]
+pub enum IdentityMatch {
+    /// Same name, ignoring case (full Unicode case folding), composition and
+    /// surrounding whitespace
+    Exact,
+    /// The identity is the start of the sender name ("Vishnu" ~ "Vishnu Tej")
+    Prefix,
+    /// Small spelling differences, compared against the whole name or its first word
+    Fuzzy,
+}
+
+impl IdentityMatch {
+    pub fn parse(mode: &str) -> Result<Self, ParserError> {
+        match mode {
+            "exact" => Ok(IdentityMatch::Exact),
+            "prefix" => Ok(IdentityMatch::Prefix),
+            "fuzzy" => Ok(IdentityMatch::Fuzzy),
+            _ => Err(ParserError::InvalidArgument(format!(
+                "Unknown match mode '{}' (expected 'exact', 'prefix' or 'fuzzy')",
+                mode
+            ))),
+        }
+    }
+
+    /// Match quality in (0, 1] (1 = exact), or `None` when the name doesn't match
+    pub fn score(self, sender: &str, identity: &str) -> Option<f64> {
+        let sender = fold_case(sender.trim());
+        let identity = fold_case(identity.trim());
+        if identity.is_empty() {
+            return None;
+        }
+        if sender == identity {
+            return Some(1.0);
+        }
+        match self {
+            IdentityMatch::Exact => None,
+            IdentityMatch::Prefix => {
+                let rest = sender.strip_prefix(identity.as_str())?;
+                rest.starts_with(char::is_whitespace)
+                    .then(|| identity.chars().count() as f64 / sender.chars().count() as f64)
+            }
+            IdentityMatch::Fuzzy => {
+                let first_word = sender.split_whitespace().next().unwrap_or("");
+                let best = similarity(&sender, &identity).max(similarity(first_word, &identity));
+                (best >= 0.75).then_some(best)
+            }
+        }
+    }
+}
+
+/// Levenshtein edit distance over Unicode scalar values
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Edit-distance similarity in [0, 1]
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+/// Distinct non-system senders, sorted
+pub fn detected_senders(messages: &[Message]) -> Vec<String> {
+    let mut senders: Vec<String> = messages
+        .iter()
+        .filter(|message| !matches!(message.message_type, MessageType::SystemEvent(_)))
+        .map(|message| message.sender.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    senders.sort();
+    senders
+}
+
+/// Find the sender name that corresponds to `identity`. The best-scoring
+/// sender wins, ties going to the more active one. On failure the detected
+/// senders are returned so the caller can ask the user to pick.
+pub(crate) fn resolve_own_sender(messages: &[Message], identity: &str, mode: IdentityMatch) -> Result<String, Vec<String>> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for message in messages {
+        if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+            *counts.entry(message.sender.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    counts
+        .iter()
+        .filter_map(|(sender, count)| mode.score(sender, identity).map(|score| (score, *count, *sender)))
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(b.2.cmp(a.2)))
+        .map(|(_, _, sender)| sender.to_string())
+        .ok_or_else(|| detected_senders(messages))
+}
+
+/// Set `is_own` on every message sent by the resolved own sender
+pub fn mark_own_messages(messages: &mut [Message], identity: &str, mode: IdentityMatch) {
+    if let Ok(own) = resolve_own_sender(messages, identity, mode) {
+        for message in messages.iter_mut() {
+            message.is_own = message.sender == own && !matches!(message.message_type, MessageType::SystemEvent(_));
+        }
+    }
+}
+
+/// Indices of own and others' messages (system messages are in neither),
+/// plus the canonical own sender name
+pub fn split_by_ownership(
+    messages: &[Message],
+    identity: &str,
+    mode: IdentityMatch,
+) -> Result<(Vec<usize>, Vec<usize>, String), ParserError> {
+    let own = resolve_own_sender(messages, identity, mode).map_err(|senders| ParserError::UnknownIdentity {
+        identity: identity.to_string(),
+        senders,
+    })?;
+    let mut own_indices = Vec::new();
+    let mut other_indices = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        if matches!(message.message_type, MessageType::SystemEvent(_)) {
+            continue;
+        }
+        if message.sender == own {
+            own_indices.push(index);
+        } else {
+            other_indices.push(index);
+        }
+    }
+    Ok((own_indices, other_indices, own))
+}
+
+/// "+" and the digits of a sender name that looks like a phone number: a
+/// leading "+", then 7 to 15 digits with only spaces, dots, dashes and
+/// parentheses between them
+pub(crate) fn normalize_phone_number(name: &str) -> Option<String> {
+    let rest = name.trim().strip_prefix('+')?;
+    if !rest.chars().all(|c| c.is_ascii_digit() || c.is_whitespace() || matches!(c, '(' | ')' | '-' | '.')) {
+        return None;
+    }
+    let digits: String = rest.chars().filter(char::is_ascii_digit).collect();
+    (7..=15).contains(&digits.len()).then(|| format!("+{}", digits))
+}
+
+impl ChatParser {
+    /// Rewrite phone-number senders ("+1 (555) 123-4567") to "+" and their
+    /// digits ("+15551234567"), so the formatted and unformatted variants of
+    /// one number count as one sender. `sender_display` is left as exported.
+    /// Returns each phone-number sender seen, mapped to its normalized form.
+    pub fn normalize_sender_names(messages: &mut [Message]) -> HashMap<String, String> {
+        let mut mapping: HashMap<String, String> = HashMap::new();
+        for message in messages.iter_mut() {
+            if let Some(normalized) = mapping.get(&message.sender) {
+                message.sender = normalized.clone();
+                continue;
+            }
+            if let Some(normalized) = normalize_phone_number(&message.sender) {
+                mapping.insert(std::mem::replace(&mut message.sender, normalized.clone()), normalized);
+            }
+        }
+        mapping
+    }
+
+    /// Phone numbers written in messages, as (number as written, sender,
+    /// timestamp) in message order. A pure-regex match: E.164 ("+447911123456"),
+    /// grouped international and local formats ("+1 (555) 123-4567",
+    /// "020 7946 0958", "555.123.4567") and bare runs of 10 to 15 digits, with
+    /// 7 to 15 digits in all. Shorter bare runs (order numbers, years) and
+    /// numbers inside URLs are skipped. System events are not searched.
+    pub fn detect_shared_phone_numbers(messages: &[Message]) -> Vec<(String, String, DateTime<Local>)> {
+        let candidate = Regex::new(r"\+?(?:\(\d{1,4}\)|\d{1,4})(?:[ .\-]?(?:\(\d{1,4}\)|\d{1,4})){1,6}").unwrap();
+        let url = Regex::new(r"(?i)(?:https?://|www\.)\S+").unwrap();
+
+        let mut found = Vec::new();
+        for message in messages {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            let content = &message.content;
+            let urls: Vec<(usize, usize)> = url.find_iter(content).map(|url| (url.start(), url.end())).collect();
+            for number in candidate.find_iter(content) {
+                let (start, end) = (number.start(), number.end());
+                let standalone = !content[..start].ends_with(|c: char| c.is_alphanumeric() || matches!(c, '+' | '/' | '.' | ':'))
+                    && !content[end..].starts_with(|c: char| c.is_alphanumeric() || matches!(c, '/' | ':'));
+                let in_url = urls.iter().any(|&(url_start, url_end)| start < url_end && url_start < end);
+                if standalone && !in_url && looks_like_phone_number(number.as_str()) {
+                    found.push((number.as_str().to_string(), message.sender.clone(), message.timestamp));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Digit count and shape checks for `detect_shared_phone_numbers` matches
+fn looks_like_phone_number(text: &str) -> bool {
+    let digits = text.chars().filter(char::is_ascii_digit).count();
+    if !(7..=15).contains(&digits) {
+        return false;
+    }
+    let formatted = text.starts_with('+') || text.contains(|c: char| !c.is_ascii_digit());
+    formatted || digits >= 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_phone_numbers_in_messages() {
+        let messages = ChatParser::new()
+            .parse_str(
+                "[18/05/2023, 08:00:00] John: call me on +447911123456 or +1 (555) 123-4567\n\
+                 [18/05/2023, 08:01:00] Alice: office is 020 7946 0958, cell 555.123.4567\n\
+                 [18/05/2023, 08:02:00] Bob: 5551234567 works too\n\
+                 [18/05/2023, 08:03:00] Bob: order 1234567 arrives 18/05/2023 at 10:30\n\
+                 [18/05/2023, 08:04:00] Alice: see https://example.com/track/+447911123456 and wa.me/447911123456\n",
+                false,
+            )
+            .unwrap();
+        let shared = ChatParser::detect_shared_phone_numbers(&messages);
+        let found: Vec<(&str, &str)> = shared.iter().map(|(number, sender, _)| (number.as_str(), sender.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                ("+447911123456", "John"),
+                ("+1 (555) 123-4567", "John"),
+                ("020 7946 0958", "Alice"),
+                ("555.123.4567", "Alice"),
+                ("5551234567", "Bob"),
+            ]
+        );
+    }
+
+    #[test]
+    fn phone_number_variants_collapse() {
+        let mut messages = ChatParser::new()
+            .parse_str(
+                "[18/05/2023, 08:00:00] +1 (555) 123-4567: hi\n\
+                 [18/05/2023, 08:01:00] +15551234567: again\n\
+                 [18/05/2023, 08:02:00] Alice: +1 555 is not a sender\n\
+                 [18/05/2023, 08:03:00] +1 (555) 123-4567: third\n\
+                 [18/05/2023, 08:04:00] +44 Support Team: hello\n",
+                false,
+            )
+            .unwrap();
+        let mapping = ChatParser::normalize_sender_names(&mut messages);
+        assert_eq!(mapping.len(), 2);
+        assert_eq!(mapping["+1 (555) 123-4567"], "+15551234567");
+        assert_eq!(mapping["+15551234567"], "+15551234567");
+        let senders: Vec<&str> = messages.iter().map(|message| message.sender.as_str()).collect();
+        assert_eq!(senders, ["+15551234567", "+15551234567", "Alice", "+15551234567", "+44 Support Team"]);
+        assert_eq!(messages[0].sender_display, "+1 (555) 123-4567");
+    }
+
+    #[test]
+    fn match_modes() {
+        assert_eq!(IdentityMatch::Exact.score(" vishnu tej ", "Vishnu Tej"), Some(1.0));
+        assert_eq!(IdentityMatch::Exact.score("Vishnu Tej", "Vishnu"), None);
+        assert!(IdentityMatch::Prefix.score("Vishnu Tej", "Vishnu").is_some());
+        assert!(IdentityMatch::Prefix.score("Vishnutej", "Vishnu").is_none());
+        assert!(IdentityMatch::Fuzzy.score("Vishnu Tej", "Vishny").is_some());
+        assert!(IdentityMatch::parse("loose").is_err());
+    }
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("héllo", "hello"), 1);
+    }
+}
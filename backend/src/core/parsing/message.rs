@@ -0,0 +1,223 @@
+use chrono::{DateTime, Local, Timelike};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::chat_parser::{builtin_message_type, split_caption};
+use crate::text::sender_key;
+
+/// Structured group/system events recognised in system lines
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GroupEvent {
+    Created { actor: String, subject: String },
+    SubjectChanged { actor: String, new_subject: String },
+    IconChanged { actor: String },
+    /// "You blocked this contact" in a one-to-one chat
+    ContactBlocked,
+    /// "You unblocked this contact"
+    ContactUnblocked,
+    /// "Alice turned on disappearing messages", or "Disappearing messages
+    /// were turned off" with no actor
+    DisappearingMessages { enabled: bool, actor: Option<String> },
+    /// The exporting user joined or was added, which bounds the visible history
+    SelfJoined { timestamp: DateTime<Local> },
+    /// Any other system notice, kept verbatim
+    Other { text: String },
+}
+
+impl GroupEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GroupEvent::Created { .. } => "created",
+            GroupEvent::SubjectChanged { .. } => "subject_changed",
+            GroupEvent::IconChanged { .. } => "icon_changed",
+            GroupEvent::ContactBlocked => "contact_blocked",
+            GroupEvent::ContactUnblocked => "contact_unblocked",
+            GroupEvent::DisappearingMessages { .. } => "disappearing_messages",
+            GroupEvent::SelfJoined { .. } => "self_joined",
+            GroupEvent::Other { .. } => "other",
+        }
+    }
+}
+
+/// What kind of message a line is, settled once its full content is known.
+/// Equality and hashing include variant data, so two calls of different
+/// durations are different keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageType {
+    Text,
+    Media,
+    Link,
+    /// A call-log line ("Missed voice call", "Video call, 1 hr 5 min"); the
+    /// message's sender is the caller
+    Call { video: bool, missed: bool, duration_seconds: Option<u32> },
+    /// An emoji-reaction notice ("Alice reacted ❤️ to Your message"), with
+    /// names as written; the message's sender is the reactor. Dropped with
+    /// the system events unless those are kept.
+    Reaction { reactor: String, emoji: String, to_sender: String },
+    SystemEvent(GroupEvent),
+    /// A type named by a user `TypeRule` ("bot", "expense", ...)
+    Custom(String),
+}
+
+impl MessageType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MessageType::Text => "text",
+            MessageType::Media => "media",
+            MessageType::Link => "link",
+            MessageType::Call { .. } => "call",
+            MessageType::Reaction { .. } => "reaction",
+            MessageType::SystemEvent(_) => "system_event",
+            MessageType::Custom(name) => name,
+        }
+    }
+}
+
+/// A single parsed chat message
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub timestamp: DateTime<Local>,
+    /// Sender name with bidi control characters stripped, used as the key in stats
+    pub sender: String,
+    /// Sender name exactly as exported, for display
+    pub sender_display: String,
+    pub content: String,
+    pub message_type: MessageType,
+    pub sentiment_score: Option<f32>,
+    /// Set by `parse_loose`, whose input carries no real timestamps
+    pub timestamp_synthetic: bool,
+    /// Moved within its minute by `ChatParser::set_interpolate_subminute`
+    #[serde(default)]
+    pub timestamp_interpolated: bool,
+    /// The timestamp as exported ("18/05/2023, 08:39"), kept on
+    /// minute-precision messages when interpolating
+    #[serde(default)]
+    pub original_timestamp: Option<String>,
+    /// Sent by the user whose identity was given to the parser
+    pub is_own: bool,
+    /// Sent to a broadcast list. Only detected when the export marks it (see
+    /// `ChatParser::strip_broadcast_marker`); plain exports carry no marker.
+    pub broadcast: bool,
+    /// Same sender as the preceding message and sent within
+    /// `chat_parser::GROUPING_WINDOW_SECONDS` of it, so a chat UI can collapse the header
+    pub grouped_with_previous: bool,
+    /// Media file from a "with media" export ZIP that belongs to this message
+    /// (see `archive::attach_media_filenames`)
+    pub media_filename: Option<String>,
+    /// Text sent along with a media message, split out of `content` so
+    /// that `content` holds only the media line
+    pub caption: Option<String>,
+    /// Quoted preview of the message this one replies to (see
+    /// `ChatParser::parse_reply_previews`)
+    pub reply_to: Option<String>,
+    /// When the replied-to message was sent, if the preview carries its
+    /// timestamp. Best-effort: most previews only quote the text.
+    pub reply_to_timestamp: Option<DateTime<Local>>,
+    /// Seconds since the previous message (None for the first), for "2 days
+    /// later" separators; see `chat_parser::annotate_gaps`
+    #[serde(default)]
+    pub gap_before_seconds: Option<u64>,
+    /// Whitespace-separated words in `content`, zero for media, calls,
+    /// reactions and system events; kept up to date whenever the parser
+    /// settles the type
+    #[serde(default)]
+    pub word_count: u32,
+}
+
+impl Message {
+    /// Build a message outside a parse, for tests and scripts. The type is
+    /// detected from `content` with the built-in rules (a media caption is
+    /// split off as the parsers do); `id` is empty and the flags are unset.
+    pub fn new(sender: String, content: String, timestamp: DateTime<Local>) -> Message {
+        let mut message = Message {
+            id: String::new(),
+            timestamp,
+            sender: sender_key(&sender),
+            sender_display: sender,
+            message_type: builtin_message_type(&content),
+            content,
+            sentiment_score: None,
+            timestamp_synthetic: false,
+            timestamp_interpolated: false,
+            original_timestamp: None,
+            is_own: false,
+            broadcast: false,
+            grouped_with_previous: false,
+            media_filename: None,
+            caption: None,
+            reply_to: None,
+            reply_to_timestamp: None,
+            gap_before_seconds: None,
+            word_count: 0,
+        };
+        split_caption(&mut message);
+        message.recount_words();
+        message
+    }
+
+    /// The timestamp with the export's precision: an interpolated
+    /// timestamp's minute, otherwise `timestamp` itself
+    pub fn exported_timestamp(&self) -> DateTime<Local> {
+        if !self.timestamp_interpolated {
+            return self.timestamp;
+        }
+        self.timestamp.with_nanosecond(0).and_then(|timestamp| timestamp.with_second(0)).unwrap_or(self.timestamp)
+    }
+
+    /// Set `word_count` from the current content and type
+    pub(crate) fn recount_words(&mut self) {
+        self.word_count = match self.message_type {
+            MessageType::Text | MessageType::Link | MessageType::Custom(_) => self.content.split_whitespace().count() as u32,
+            _ => 0,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn new_detects_the_message_type() {
+        let now = Local::now();
+        let message = Message::new("\u{200e}Alice".to_string(), "https://example.com".to_string(), now);
+        assert_eq!((message.sender.as_str(), message.sender_display.as_str()), ("Alice", "\u{200e}Alice"));
+        assert_eq!(message.message_type, MessageType::Link);
+        assert_eq!(message.sentiment_score, None);
+
+        let media = Message::new("Bob".to_string(), "<Media omitted>\nLook!".to_string(), now);
+        assert_eq!((media.message_type, media.content.as_str(), media.caption.as_deref()), (MessageType::Media, "<Media omitted>", Some("Look!")));
+        let call = Message::new("Bob".to_string(), "Missed voice call".to_string(), now);
+        assert!(matches!(call.message_type, MessageType::Call { missed: true, .. }));
+    }
+
+    #[test]
+    fn message_types_work_as_map_keys() {
+        let call = |duration_seconds| MessageType::Call { video: false, missed: false, duration_seconds };
+        let types = [
+            MessageType::Text,
+            MessageType::Text,
+            call(Some(60)),
+            call(Some(60)),
+            call(None),
+            MessageType::Custom("bot".to_string()),
+        ];
+        let mut counts: HashMap<MessageType, usize> = HashMap::new();
+        for message_type in types {
+            *counts.entry(message_type).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&MessageType::Text], 2);
+        assert_eq!(counts[&call(Some(60))], 2);
+        assert_ne!(MessageType::Custom("bot".to_string()), MessageType::Text);
+        assert_eq!(
+            MessageType::SystemEvent(GroupEvent::Other { text: "x".to_string() }),
+            MessageType::SystemEvent(GroupEvent::Other { text: "x".to_string() })
+        );
+    }
+}
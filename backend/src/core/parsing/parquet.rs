@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+
+use arrow2::array::{Array, BooleanArray, Float32Array, PrimitiveArray, UInt32Array, UInt64Array, Utf8Array};
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow2::io::parquet::write::{
+    transverse, CompressionOptions, Encoding, FileWriter, RowGroupIterator, Version, WriteOptions,
+};
+use chrono::{DateTime, Local};
+
+use crate::error::ParserError;
+use crate::message::{Message, MessageType};
+
+fn parquet_error(error: arrow2::error::Error) -> ParserError {
+    match error {
+        arrow2::error::Error::Io(source) => ParserError::io("Failed to write Parquet")(source),
+        other => ParserError::io("Failed to write Parquet")(io::Error::other(other.to_string())),
+    }
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".to_string()))
+}
+
+fn timestamps(values: impl Iterator<Item = Option<DateTime<Local>>>) -> Box<dyn Array> {
+    let micros: Vec<Option<i64>> = values.map(|value| value.map(|timestamp| timestamp.timestamp_micros())).collect();
+    PrimitiveArray::<i64>::from(micros).to(timestamp_type()).boxed()
+}
+
+fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Box<dyn Array> {
+    Utf8Array::<i32>::from(values.collect::<Vec<_>>()).boxed()
+}
+
+fn booleans(values: impl Iterator<Item = bool>) -> Box<dyn Array> {
+    BooleanArray::from_slice(values.collect::<Vec<_>>()).boxed()
+}
+
+/// One column per `Message` field. `message_type` is the type name;
+/// system events carry their details as JSON in `event`, and calls theirs
+/// in the `call_*` columns.
+fn message_columns(messages: &[Message]) -> (Schema, Chunk<Box<dyn Array>>) {
+    let call = |message: &Message| match message.message_type {
+        MessageType::Call { video, missed, duration_seconds } => Some((video, missed, duration_seconds)),
+        _ => None,
+    };
+    let events: Vec<Option<String>> = messages
+        .iter()
+        .map(|message| match &message.message_type {
+            MessageType::SystemEvent(event) => serde_json::to_string(event).ok(),
+            _ => None,
+        })
+        .collect();
+
+    let columns: Vec<(&str, bool, Box<dyn Array>)> = vec![
+        ("id", false, strings(messages.iter().map(|message| Some(message.id.as_str())))),
+        ("timestamp", false, timestamps(messages.iter().map(|message| Some(message.timestamp)))),
+        ("sender", false, strings(messages.iter().map(|message| Some(message.sender.as_str())))),
+        ("sender_display", false, strings(messages.iter().map(|message| Some(message.sender_display.as_str())))),
+        ("content", false, strings(messages.iter().map(|message| Some(message.content.as_str())))),
+        ("message_type", false, strings(messages.iter().map(|message| Some(message.message_type.as_str())))),
+        ("event", true, strings(events.iter().map(Option::as_deref))),
+        ("call_video", true, BooleanArray::from(messages.iter().map(|message| call(message).map(|call| call.0)).collect::<Vec<_>>()).boxed()),
+        ("call_missed", true, BooleanArray::from(messages.iter().map(|message| call(message).map(|call| call.1)).collect::<Vec<_>>()).boxed()),
+        (
+            "call_duration_seconds",
+            true,
+            UInt32Array::from(messages.iter().map(|message| call(message).and_then(|call| call.2)).collect::<Vec<_>>()).boxed(),
+        ),
+        ("sentiment_score", true, Float32Array::from(messages.iter().map(|message| message.sentiment_score).collect::<Vec<_>>()).boxed()),
+        ("timestamp_synthetic", false, booleans(messages.iter().map(|message| message.timestamp_synthetic))),
+        ("is_own", false, booleans(messages.iter().map(|message| message.is_own))),
+        ("broadcast", false, booleans(messages.iter().map(|message| message.broadcast))),
+        ("grouped_with_previous", false, booleans(messages.iter().map(|message| message.grouped_with_previous))),
+        ("media_filename", true, strings(messages.iter().map(|message| message.media_filename.as_deref()))),
+        ("caption", true, strings(messages.iter().map(|message| message.caption.as_deref()))),
+        ("reply_to", true, strings(messages.iter().map(|message| message.reply_to.as_deref()))),
+        ("reply_to_timestamp", true, timestamps(messages.iter().map(|message| message.reply_to_timestamp))),
+        ("gap_before_seconds", true, UInt64Array::from(messages.iter().map(|message| message.gap_before_seconds).collect::<Vec<_>>()).boxed()),
+    ];
+
+    let fields: Vec<Field> =
+        columns.iter().map(|(name, nullable, array)| Field::new(*name, array.data_type().clone(), *nullable)).collect();
+    let arrays: Vec<Box<dyn Array>> = columns.into_iter().map(|(_, _, array)| array).collect();
+    (Schema::from(fields), Chunk::new(arrays))
+}
+
+/// Write messages as a Snappy-compressed Parquet file, one row per message
+/// and one column per field (see `message_columns`), for Spark, DuckDB or
+/// pandas. Timestamps are `Timestamp(Microsecond, UTC)`.
+pub fn write_parquet<W: Write>(messages: &[Message], writer: W) -> Result<(), ParserError> {
+    let (schema, chunk) = message_columns(messages);
+    let options = WriteOptions {
+        write_statistics: true,
+        compression: CompressionOptions::Snappy,
+        version: Version::V2,
+        data_pagesize_limit: None,
+    };
+    let encodings = schema.fields.iter().map(|field| transverse(&field.data_type, |_| Encoding::Plain)).collect();
+    let row_groups =
+        RowGroupIterator::try_new(vec![Ok(chunk)].into_iter(), &schema, options, encodings).map_err(parquet_error)?;
+
+    let mut writer = FileWriter::try_new(writer, schema, options).map_err(parquet_error)?;
+    for group in row_groups {
+        writer.write(group.map_err(parquet_error)?).map_err(parquet_error)?;
+    }
+    writer.end(None).map_err(parquet_error)?;
+    Ok(())
+}
+
+/// Write `write_parquet` output to `output_path`
+pub fn export_to_parquet(messages: &[Message], output_path: &str) -> Result<(), ParserError> {
+    let file = std::fs::File::create(output_path).map_err(ParserError::io("Failed to create Parquet file"))?;
+    write_parquet(messages, io::BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_parser::ChatParser;
+    use arrow2::io::parquet::read::{infer_schema, read_metadata, FileReader};
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trips_rows_and_timestamps() {
+        let chat = "[18/05/2023, 08:00:00] John created group \"Trip\"
+[18/05/2023, 08:01:00] John: hello
+[18/05/2023, 08:02:00] Alice: Missed voice call
+[18/05/2023, 08:03:00] Alice: <Media omitted>
+look
+";
+        let messages = ChatParser::new().parse_str(chat, true).unwrap();
+        let mut bytes = Vec::new();
+        write_parquet(&messages, &mut bytes).unwrap();
+
+        let mut reader = Cursor::new(bytes);
+        let metadata = read_metadata(&mut reader).unwrap();
+        assert_eq!(metadata.num_rows, 4);
+        let schema = infer_schema(&metadata).unwrap();
+        let timestamp = schema.fields.iter().find(|field| field.name == "timestamp").unwrap();
+        assert_eq!(timestamp.data_type, timestamp_type());
+
+        let chunks: Vec<_> = FileReader::new(reader, metadata.row_groups, schema.clone(), None, None, None)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let column = |name: &str| schema.fields.iter().position(|field| field.name == name).unwrap();
+        let micros = chunks[0].arrays()[column("timestamp")].as_any().downcast_ref::<PrimitiveArray<i64>>().unwrap();
+        assert_eq!(micros.value(1), messages[1].timestamp.timestamp_micros());
+        let captions = chunks[0].arrays()[column("caption")].as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+        assert_eq!((captions.get(3), captions.get(1)), (Some("look"), None));
+        let missed = chunks[0].arrays()[column("call_missed")].as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert_eq!((missed.get(2), missed.get(1)), (Some(true), None));
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::chat_parser::ChatParser;
+use crate::error::ParserError;
+use crate::message::Message;
+
+/// Caps applied to patterns that come from users rather than this crate.
+///
+/// The regex crate never backtracks, so a pattern like `(a+)+$` runs in
+/// time linear in the input; what can still hurt is a pattern that compiles
+/// to a huge automaton (large counted repetitions of Unicode classes such as
+/// `\w{1000}`) matched against very long lines. The size limits reject the
+/// former at compile time and the line budget bounds the latter.
+///
+/// The safe subset is therefore the regex crate's whole syntax: there are
+/// no look-arounds or backreferences to opt out of. Patterns should keep
+/// counted repetitions small (`\w{1,20}`, not `\w{1000}`), prefer `\d`
+/// and ASCII classes (`(?-u:\w)`) over Unicode ones when repeating, and
+/// stay under `max_length` characters and `nest_limit` levels of groups.
+#[derive(Debug, Clone)]
+pub struct PatternLimits {
+    /// Maximum pattern length in characters, checked before compiling
+    pub max_length: usize,
+    /// Maximum nesting depth of groups and repetitions (`RegexBuilder::nest_limit`)
+    pub nest_limit: u32,
+    /// Maximum compiled program size in bytes (`RegexBuilder::size_limit`)
+    pub size_limit: usize,
+    /// Maximum cache size of the lazy DFA in bytes (`RegexBuilder::dfa_size_limit`)
+    pub dfa_size_limit: usize,
+    /// Time allowed for matching one message; slower messages are skipped
+    /// (see also `ChatParser::set_line_budget`)
+    pub line_budget: Duration,
+}
+
+impl Default for PatternLimits {
+    fn default() -> Self {
+        PatternLimits {
+            max_length: 1000,
+            nest_limit: 32,
+            size_limit: 1 << 20,
+            dfa_size_limit: 2 << 20,
+            line_budget: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Compile a user-supplied pattern within `limits`. Errors name the pattern
+/// (or its length, for overlong ones) and say whether it was invalid or too
+/// large.
+pub fn compile_pattern(pattern: &str, limits: &PatternLimits) -> Result<Regex, ParserError> {
+    let length = pattern.chars().count();
+    if length > limits.max_length {
+        return Err(ParserError::InvalidArgument(format!(
+            "Pattern is too long: {} characters, more than the limit of {}",
+            length, limits.max_length
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .nest_limit(limits.nest_limit)
+        .size_limit(limits.size_limit)
+        .dfa_size_limit(limits.dfa_size_limit)
+        .build()
+        .map_err(|error| match error {
+            regex::Error::CompiledTooBig(limit) => ParserError::InvalidArgument(format!(
+                "Pattern '{}' is too large: it compiles to more than {} bytes",
+                pattern, limit
+            )),
+            other => ParserError::InvalidArgument(format!("Invalid pattern '{}': {}", pattern, other)),
+        })
+}
+
+/// Result of `find_messages_by_pattern`
+#[derive(Debug, Clone, Default)]
+pub struct PatternSearch {
+    /// Indices of the matching messages
+    pub matches: Vec<usize>,
+    /// Indices of messages skipped because matching them exceeded the line budget
+    pub skipped: Vec<usize>,
+}
+
+impl ChatParser {
+    /// Indices of messages whose content matches the user-supplied regex
+    /// `pattern`, compiled and run within `limits`. A message whose match
+    /// takes longer than `limits.line_budget` is recorded in `skipped`
+    /// instead of `matches`, so one pathological line can't stall a search.
+    pub fn find_messages_by_pattern(
+        messages: &[Message],
+        pattern: &str,
+        limits: &PatternLimits,
+    ) -> Result<PatternSearch, ParserError> {
+        let regex = compile_pattern(pattern, limits)?;
+        let mut search = PatternSearch::default();
+        for (index, message) in messages.iter().enumerate() {
+            let started = Instant::now();
+            let matched = regex.is_match(&message.content);
+            if started.elapsed() > limits.line_budget {
+                search.skipped.push(index);
+            } else if matched {
+                search.matches.push(index);
+            }
+        }
+        Ok(search)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_oversized_pattern_by_name() {
+        let error = compile_pattern(r"\w{1000}", &PatternLimits::default()).unwrap_err();
+        assert!(matches!(error, ParserError::InvalidArgument(_)));
+        assert!(error.to_string().contains(r"Pattern '\w{1000}' is too large"));
+        let error = compile_pattern("(unclosed", &PatternLimits::default()).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid pattern '(unclosed'"));
+    }
+
+    #[test]
+    fn rejects_overlong_and_deeply_nested_patterns() {
+        let error = compile_pattern(&"a|".repeat(600), &PatternLimits::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Pattern is too long: 1200 characters, more than the limit of 1000");
+        let nested = format!("{}a{}", "(".repeat(40), ")".repeat(40));
+        let error = compile_pattern(&nested, &PatternLimits::default()).unwrap_err();
+        assert!(error.to_string().contains("maximum number of nested parentheses"), "{}", error);
+        assert!(compile_pattern(&nested, &PatternLimits { nest_limit: 100, ..PatternLimits::default() }).is_ok());
+    }
+
+    #[test]
+    fn stays_responsive_on_a_one_megabyte_line() {
+        let long_line = format!("{}b", "a".repeat(1 << 20));
+        let text = format!("[18/05/2023, 08:00:00] John: {}\n[18/05/2023, 08:01:00] Alice: aaa\n", long_line);
+        let messages = ChatParser::new().parse_str(&text, false).unwrap();
+
+        let started = Instant::now();
+        let search = ChatParser::find_messages_by_pattern(&messages, "(a+)+$", &PatternLimits::default()).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // The long line ends in "b": it fails to match in time or is skipped
+        assert_eq!(search.matches, [1]);
+
+        let limits = PatternLimits { line_budget: Duration::ZERO, ..PatternLimits::default() };
+        let search = ChatParser::find_messages_by_pattern(&messages, "(a+)+$", &limits).unwrap();
+        assert_eq!(search.skipped, [0, 1]);
+    }
+}
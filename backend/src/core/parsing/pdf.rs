@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::io;
+
+use chrono::NaiveDate;
+use owned_ttf_parser::{AsFaceRef, OwnedFace};
+use printpdf::{BuiltinFont, Color, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Rgb};
+
+use crate::analytics::profile_chat;
+use crate::error::ParserError;
+use crate::message::{Message, MessageType};
+
+/// Points to millimetres
+const PT_TO_MM: f32 = 25.4 / 72.0;
+const MARGIN_MM: f32 = 15.0;
+/// Width of the timestamp column left of the message text
+const TIME_COLUMN_MM: f32 = 14.0;
+
+/// Unicode fonts tried, in order, when `PdfOptions::font_path` is not set:
+/// (regular, bold) pairs from common Linux, macOS and Windows installs
+const SYSTEM_FONTS: &[(&str, &str)] = &[
+    ("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf", "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf"),
+    ("/usr/share/fonts/TTF/DejaVuSans.ttf", "/usr/share/fonts/TTF/DejaVuSans-Bold.ttf"),
+    ("/usr/share/fonts/dejavu/DejaVuSans.ttf", "/usr/share/fonts/dejavu/DejaVuSans-Bold.ttf"),
+    ("/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf", "/usr/share/fonts/truetype/noto/NotoSans-Bold.ttf"),
+    ("/Library/Fonts/Arial Unicode.ttf", "/Library/Fonts/Arial Unicode.ttf"),
+    ("C:\\Windows\\Fonts\\arial.ttf", "C:\\Windows\\Fonts\\arialbd.ttf"),
+];
+
+/// Sender name colours, assigned from the most active sender down
+const SENDER_COLORS: &[(f32, f32, f32)] = &[
+    (0.03, 0.45, 0.33),
+    (0.13, 0.35, 0.70),
+    (0.70, 0.25, 0.10),
+    (0.50, 0.20, 0.60),
+    (0.75, 0.50, 0.00),
+    (0.10, 0.55, 0.65),
+    (0.65, 0.15, 0.40),
+    (0.35, 0.45, 0.10),
+];
+
+/// Page sizes for `render_pdf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    pub fn parse(size: &str) -> Result<Self, ParserError> {
+        match size.to_ascii_lowercase().as_str() {
+            "a4" => Ok(PageSize::A4),
+            "letter" => Ok(PageSize::Letter),
+            other => Err(ParserError::InvalidArgument(format!(
+                "Unknown page size '{}' (expected 'a4' or 'letter')",
+                other
+            ))),
+        }
+    }
+
+    /// Width and height in millimetres
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+/// Layout options for `render_pdf`
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    /// Body text size in points
+    pub font_size: f32,
+    /// Keep media messages (as their placeholder or attached file name)
+    pub include_media_placeholders: bool,
+    /// TrueType font to embed; defaults to the first of `SYSTEM_FONTS` found
+    pub font_path: Option<String>,
+    /// Bold variant for sender names; defaults to `font_path`'s pair, or `font_path` itself
+    pub bold_font_path: Option<String>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page_size: PageSize::A4,
+            font_size: 10.0,
+            include_media_placeholders: true,
+            font_path: None,
+            bold_font_path: None,
+        }
+    }
+}
+
+/// A font added to the document plus what's needed to measure text in it
+struct PdfFont {
+    font: IndirectFontRef,
+    /// Glyph metrics of an embedded font; `None` for the built-in Helvetica
+    face: Option<OwnedFace>,
+    widths: HashMap<char, f32>,
+}
+
+impl PdfFont {
+    fn embedded(doc: &PdfDocumentReference, path: &str) -> Result<Self, ParserError> {
+        let bytes = std::fs::read(path).map_err(ParserError::io("Failed to read font"))?;
+        let face = OwnedFace::from_vec(bytes.clone(), 0)
+            .map_err(|e| ParserError::InvalidArgument(format!("Failed to load font '{}': {}", path, e)))?;
+        let font = doc
+            .add_external_font(&bytes[..])
+            .map_err(|e| ParserError::InvalidArgument(format!("Failed to load font '{}': {}", path, e)))?;
+        Ok(PdfFont { font, face: Some(face), widths: HashMap::new() })
+    }
+
+    fn builtin(doc: &PdfDocumentReference, builtin: BuiltinFont) -> Result<Self, ParserError> {
+        let font = doc.add_builtin_font(builtin).map_err(pdf_error)?;
+        Ok(PdfFont { font, face: None, widths: HashMap::new() })
+    }
+
+    /// Whether the font can draw `c`
+    fn has_glyph(&self, c: char) -> bool {
+        match &self.face {
+            Some(face) => face.as_face_ref().glyph_index(c).is_some(),
+            // Helvetica is set in WinAnsiEncoding, roughly Latin-1
+            None => (c as u32) < 0x100,
+        }
+    }
+
+    /// Replace characters the font can't draw (emoji, other scripts) with a
+    /// fallback glyph, so nothing is silently dropped and widths stay right
+    fn prepare(&self, text: &str) -> String {
+        let fallback = if self.has_glyph('□') { '□' } else { '?' };
+        text.chars()
+            .filter(|&c| c == '\n' || !(c.is_control() || matches!(c, '\u{FE0F}' | '\u{200D}')))
+            .map(|c| if c == '\n' || self.has_glyph(c) { c } else { fallback })
+            .collect()
+    }
+
+    /// Width of one (prepared) character as a fraction of the font size
+    fn em_width(&mut self, c: char) -> f32 {
+        if let Some(&width) = self.widths.get(&c) {
+            return width;
+        }
+        let width = match &self.face {
+            Some(face) => {
+                let face = face.as_face_ref();
+                face.glyph_index(c)
+                    .and_then(|glyph| face.glyph_hor_advance(glyph))
+                    .map_or(0.5, |advance| advance as f32 / face.units_per_em() as f32)
+            }
+            None => 0.55,
+        };
+        self.widths.insert(c, width);
+        width
+    }
+
+    /// Width of prepared text in millimetres
+    fn width(&mut self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.em_width(c)).sum::<f32>() * size * PT_TO_MM
+    }
+
+    /// Break prepared text into lines: the first `first_width` mm wide, the
+    /// rest `width` mm. Words longer than a line are split.
+    fn wrap(&mut self, text: &str, size: f32, first_width: f32, width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut line_width = 0.0;
+        let space = self.em_width(' ') * size * PT_TO_MM;
+        for paragraph in text.split('\n') {
+            for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+                let limit = if lines.is_empty() { first_width } else { width };
+                let word_width = self.width(word, size);
+                let gap = if line.is_empty() { 0.0 } else { space };
+                if line_width + gap + word_width <= limit {
+                    if !line.is_empty() {
+                        line.push(' ');
+                    }
+                    line.push_str(word);
+                    line_width += gap + word_width;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0.0;
+                    if word_width <= width {
+                        line.push_str(word);
+                        line_width = word_width;
+                        continue;
+                    }
+                }
+                for c in word.chars() {
+                    let limit = if lines.is_empty() { first_width } else { width };
+                    let char_width = self.em_width(c) * size * PT_TO_MM;
+                    if line_width + char_width > limit && !line.is_empty() {
+                        lines.push(std::mem::take(&mut line));
+                        line_width = 0.0;
+                    }
+                    line.push(c);
+                    line_width += char_width;
+                }
+            }
+            lines.push(std::mem::take(&mut line));
+            line_width = 0.0;
+        }
+        lines
+    }
+}
+
+fn pdf_error(error: printpdf::Error) -> ParserError {
+    ParserError::io("Failed to render PDF")(io::Error::other(error.to_string()))
+}
+
+/// Regular and bold fonts for the document: the configured ones, else a
+/// Unicode system font, else the built-in Helvetica pair
+fn load_fonts(doc: &PdfDocumentReference, options: &PdfOptions) -> Result<(PdfFont, PdfFont), ParserError> {
+    let paths = match &options.font_path {
+        Some(path) => Some((path.clone(), options.bold_font_path.clone().unwrap_or_else(|| path.clone()))),
+        None => SYSTEM_FONTS
+            .iter()
+            .find(|(regular, _)| std::path::Path::new(regular).is_file())
+            .map(|(regular, bold)| {
+                let bold = if std::path::Path::new(bold).is_file() { bold } else { regular };
+                (regular.to_string(), bold.to_string())
+            }),
+    };
+    match paths {
+        Some((regular, bold)) => Ok((PdfFont::embedded(doc, &regular)?, PdfFont::embedded(doc, &bold)?)),
+        None => Ok((
+            PdfFont::builtin(doc, BuiltinFont::Helvetica)?,
+            PdfFont::builtin(doc, BuiltinFont::HelveticaBold)?,
+        )),
+    }
+}
+
+/// Lays text out top to bottom, starting new pages as needed
+struct Writer {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    page_width: f32,
+    page_height: f32,
+    y: f32,
+}
+
+impl Writer {
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(self.page_width), Mm(self.page_height), "Messages");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = self.page_height - MARGIN_MM;
+    }
+
+    /// Move down one line of `height` mm, breaking the page if it wouldn't fit
+    fn advance(&mut self, height: f32) {
+        if self.y - height < MARGIN_MM {
+            self.new_page();
+        }
+        self.y -= height;
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, font: &PdfFont, color: (f32, f32, f32)) {
+        if text.is_empty() {
+            return;
+        }
+        self.layer.set_fill_color(Color::Rgb(Rgb::new(color.0, color.1, color.2, None)));
+        self.layer.use_text(text, size, Mm(x), Mm(self.y), &font.font);
+    }
+}
+
+const BLACK: (f32, f32, f32) = (0.0, 0.0, 0.0);
+const GRAY: (f32, f32, f32) = (0.45, 0.45, 0.45);
+
+/// Render messages as a printable PDF: a title page with chat statistics,
+/// then the messages grouped by day, with timestamps in the left margin,
+/// sender names in bold and a colour per sender, and long content wrapped.
+/// Text is real (searchable) text in an embedded Unicode font when one is
+/// available; characters the font lacks, such as most emoji, are drawn as a
+/// fallback glyph.
+pub fn render_pdf_bytes(messages: &[Message], options: &PdfOptions) -> Result<Vec<u8>, ParserError> {
+    let (page_width, page_height) = options.page_size.dimensions();
+    let (doc, page, layer) = PdfDocument::new("Chat export", Mm(page_width), Mm(page_height), "Title");
+    let (mut regular, mut bold) = load_fonts(&doc, options)?;
+    let layer = doc.get_page(page).get_layer(layer);
+    let mut writer = Writer { doc, layer, page_width, page_height, y: page_height - MARGIN_MM };
+
+    let size = options.font_size;
+    let line_height = size * PT_TO_MM * 1.35;
+    let text_left = MARGIN_MM + TIME_COLUMN_MM;
+    let text_width = page_width - text_left - MARGIN_MM;
+
+    // Title page
+    let profile = profile_chat(messages);
+    writer.advance(size * 2.4 * PT_TO_MM * 2.0);
+    writer.text("Chat export", size * 2.4, MARGIN_MM, &bold, BLACK);
+    writer.advance(line_height * 2.0);
+    let mut summary = vec![format!("{} messages, {} media", profile.message_count, profile.media_count)];
+    if let (Some(first), Some(last)) = (profile.first_message, profile.last_message) {
+        summary.push(format!("{} to {}", first.format("%-d %B %Y"), last.format("%-d %B %Y")));
+    }
+    summary.push(String::new());
+    summary.push("Participants".to_string());
+    for (sender, count) in &profile.participants {
+        summary.push(format!("{}: {} messages", sender, count));
+    }
+    for line in summary {
+        let prepared = regular.prepare(&line);
+        for wrapped in regular.wrap(&prepared, size, page_width - 2.0 * MARGIN_MM, page_width - 2.0 * MARGIN_MM) {
+            writer.text(&wrapped, size, MARGIN_MM, &regular, BLACK);
+            writer.advance(line_height);
+        }
+    }
+
+    // Messages, grouped by day
+    let colors: HashMap<&str, (f32, f32, f32)> = profile
+        .participants
+        .iter()
+        .enumerate()
+        .map(|(index, (sender, _))| (sender.as_str(), SENDER_COLORS[index % SENDER_COLORS.len()]))
+        .collect();
+    let mut current_day: Option<NaiveDate> = None;
+    if !messages.is_empty() {
+        writer.new_page();
+    }
+    for message in messages {
+        let (content, color) = match &message.message_type {
+            MessageType::Media if !options.include_media_placeholders => continue,
+            MessageType::Media => {
+                let placeholder = match &message.media_filename {
+                    Some(name) => format!("[media: {}]", name),
+                    None => message.content.clone(),
+                };
+                match &message.caption {
+                    Some(caption) => (format!("{}\n{}", placeholder, caption), GRAY),
+                    None => (placeholder, GRAY),
+                }
+            }
+            MessageType::SystemEvent(_) | MessageType::Reaction { .. } => (message.content.clone(), GRAY),
+            _ => (message.content.clone(), BLACK),
+        };
+
+        let day = message.timestamp.date_naive();
+        if current_day != Some(day) {
+            if current_day.is_some() {
+                writer.advance(line_height * 0.5);
+            }
+            writer.advance(line_height * 1.2);
+            writer.text(&day.format("%A, %-d %B %Y").to_string(), size * 1.1, MARGIN_MM, &bold, BLACK);
+            writer.advance(line_height * 0.3);
+            current_day = Some(day);
+        }
+
+        writer.advance(line_height);
+        writer.text(&message.timestamp.format("%H:%M").to_string(), size * 0.85, MARGIN_MM, &regular, GRAY);
+        let mut first_width = text_width;
+        let mut first_left = text_left;
+        if !matches!(message.message_type, MessageType::SystemEvent(_)) {
+            let name = bold.prepare(&format!("{}: ", message.sender_display));
+            let name_width = bold.width(&name, size).min(text_width / 2.0);
+            writer.text(&name, size, text_left, &bold, colors.get(message.sender.as_str()).copied().unwrap_or(BLACK));
+            first_width -= name_width;
+            first_left += name_width;
+        }
+        let prepared = regular.prepare(&content);
+        for (index, line) in regular.wrap(&prepared, size, first_width, text_width).iter().enumerate() {
+            if index > 0 {
+                writer.advance(line_height);
+            }
+            writer.text(line, size, if index == 0 { first_left } else { text_left }, &regular, color);
+        }
+    }
+
+    writer.doc.save_to_bytes().map_err(pdf_error)
+}
+
+/// Write `render_pdf_bytes` output to `output_path`
+pub fn render_pdf(messages: &[Message], output_path: &str, options: &PdfOptions) -> Result<(), ParserError> {
+    let bytes = render_pdf_bytes(messages, options)?;
+    std::fs::write(output_path, bytes).map_err(ParserError::io("Failed to write PDF"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_parser::ChatParser;
+
+    /// Check that `startxref` points at the cross-reference section, which
+    /// printpdf writes as an `/XRef` stream object
+    fn assert_valid_xref(pdf: &[u8]) {
+        assert!(pdf.starts_with(b"%PDF-"));
+        let tail = String::from_utf8_lossy(&pdf[pdf.len().saturating_sub(64)..]).into_owned();
+        assert!(tail.trim_end().ends_with("%%EOF"));
+        let start = tail.rfind("startxref").expect("no startxref");
+        let offset: usize = tail[start + 9..].split_whitespace().next().unwrap().parse().unwrap();
+        let section = String::from_utf8_lossy(&pdf[offset..pdf.len().min(offset + 256)]).into_owned();
+        assert!(
+            section.starts_with("xref") || (section.split_whitespace().nth(2) == Some("obj") && section.contains("/XRef")),
+            "startxref does not point at a cross-reference section"
+        );
+    }
+
+    fn chat(count: usize) -> Vec<Message> {
+        let text: String = (0..count)
+            .map(|i| {
+                let sender = ["Alice", "Bob", "Chandra"][i % 3];
+                let content = if i % 10 == 0 {
+                    "A much longer message that goes on and on so that it has to wrap onto several lines of the page, with an emoji 🎉 and العربية too".to_string()
+                } else {
+                    format!("message number {}", i)
+                };
+                format!("[{:02}/05/2023, {:02}:{:02}:00] {}: {}\n", 1 + i / 1440 % 28, i / 60 % 24, i % 60, sender, content)
+            })
+            .collect();
+        ChatParser::new().parse_str(&text, false).unwrap()
+    }
+
+    #[test]
+    fn renders_a_valid_pdf() {
+        let messages = chat(300);
+        assert_valid_xref(&render_pdf_bytes(&messages, &PdfOptions::default()).unwrap());
+
+        let letter = PdfOptions { page_size: PageSize::Letter, font_size: 14.0, ..PdfOptions::default() };
+        assert_valid_xref(&render_pdf_bytes(&messages, &letter).unwrap());
+        assert!(PageSize::parse("a5").is_err());
+    }
+
+    #[test]
+    fn renders_ten_thousand_messages() {
+        let pdf = render_pdf_bytes(&chat(10_000), &PdfOptions::default()).unwrap();
+        assert_valid_xref(&pdf);
+    }
+
+    #[test]
+    fn wraps_long_words() {
+        let doc = PdfDocument::empty("test");
+        let mut font = PdfFont::builtin(&doc, BuiltinFont::Helvetica).unwrap();
+        let lines = font.wrap(&"x".repeat(200), 10.0, 50.0, 100.0);
+        assert!(lines.len() > 2);
+        assert!(lines.iter().all(|line| font.width(line, 10.0) <= 100.0));
+        assert_eq!(font.prepare("hi 😀\nthere"), "hi ?\nthere");
+        assert_eq!(font.wrap("one two\nthree", 10.0, 100.0, 100.0), ["one two", "three"]);
+    }
+}
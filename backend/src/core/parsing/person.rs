@@ -0,0 +1,295 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::ParserError;
+use crate::identity::{detected_senders, normalize_phone_number, resolve_own_sender, IdentityMatch};
+use crate::message::{Message, MessageType};
+use crate::text::{extract_emojis, fold_case, sender_key, tokenize_words_with, STOP_WORDS};
+
+/// Entries in each "top shared" list of a `PersonProfile`
+pub const PROFILE_TOP_COUNT: usize = 10;
+
+/// Highlight messages in a `PersonProfile`
+pub const PROFILE_HIGHLIGHT_COUNT: usize = 5;
+
+/// A message quoted in a `PersonProfile`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileMessage {
+    /// Index in the messages the profile was built from
+    pub index: usize,
+    pub sender: String,
+    pub timestamp: DateTime<Local>,
+    pub content: String,
+}
+
+/// A message picked for the top of a "memories with X" page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileHighlight {
+    /// "longest", "most_reacted" or "after_gap"
+    pub reason: &'static str,
+    pub message: ProfileMessage,
+}
+
+/// Everything the user and one other person exchanged in a chat (see
+/// `person_profile`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PersonProfile {
+    /// The person's most active sender name
+    pub person: String,
+    /// Every sender name taken to be the person, most active first
+    pub aliases: Vec<String>,
+    /// The user's sender name
+    pub user: String,
+    pub messages_from_person: usize,
+    pub messages_from_user: usize,
+    pub first_message: Option<ProfileMessage>,
+    pub last_message: Option<ProfileMessage>,
+    /// Mean seconds the person took to answer the user, and the other way
+    /// round; `None` when nobody ever answered
+    pub person_reply_seconds: Option<f64>,
+    pub user_reply_seconds: Option<f64>,
+    /// Emoji both of them used, by combined uses
+    pub top_shared_emoji: Vec<(String, usize)>,
+    /// Words (case-folded, stop words and links left out) both of them
+    /// used, by combined uses
+    pub top_shared_words: Vec<(String, usize)>,
+    pub shared_links: usize,
+    pub media_exchanged: usize,
+    /// Mean sentiment score per month ("YYYY-MM"), over the exchanged
+    /// messages that carry one
+    pub sentiment_trend: Vec<(String, f32)>,
+    /// Up to `PROFILE_HIGHLIGHT_COUNT` messages, in chat order
+    pub highlights: Vec<ProfileHighlight>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Person,
+    User,
+}
+
+/// Sender names standing for `person`: the same name ignoring case and
+/// composition, or the same phone number however it's formatted. Most
+/// active first.
+fn person_aliases(messages: &[Message], person: &str) -> Vec<String> {
+    let phone = normalize_phone_number(person);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for message in messages {
+        if matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) {
+            continue;
+        }
+        let sender = message.sender.as_str();
+        if IdentityMatch::Exact.score(sender, person).is_some()
+            || (phone.is_some() && normalize_phone_number(sender) == phone)
+        {
+            *counts.entry(sender).or_insert(0) += 1;
+        }
+    }
+    let mut aliases: Vec<(&str, usize)> = counts.into_iter().collect();
+    aliases.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    aliases.into_iter().map(|(sender, _)| sender.to_string()).collect()
+}
+
+/// Entries of two count maps present in both, by combined count (ties
+/// alphabetical), at most `PROFILE_TOP_COUNT`
+fn top_shared(person: &HashMap<String, usize>, user: &HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut shared: Vec<(String, usize)> = person
+        .iter()
+        .filter_map(|(key, count)| user.get(key).map(|other| (key.clone(), count + other)))
+        .collect();
+    shared.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    shared.truncate(PROFILE_TOP_COUNT);
+    shared
+}
+
+/// A profile of what the user (`user_identity`, matched as by
+/// `split_by_ownership` in exact mode) and `person` exchanged, for a
+/// "memories with X" page. `person` is the name as the user knows it;
+/// every sender with that name (ignoring case and composition) or the same
+/// phone number counts as them. Only their two sides of the chat are
+/// looked at, in a single pass: a reply is a message from one of them
+/// right after one from the other, and reactions (when system messages
+/// were parsed) are credited to the reacted-to side's latest message.
+/// Highlights are the longest message, the most reacted one and the
+/// messages that broke the longest silences, topped up with the next
+/// longest messages.
+pub fn person_profile(messages: &[Message], person: &str, user_identity: &str) -> Result<PersonProfile, ParserError> {
+    let aliases = person_aliases(messages, person);
+    if aliases.is_empty() {
+        return Err(ParserError::UnknownIdentity { identity: person.to_string(), senders: detected_senders(messages) });
+    }
+    let user = resolve_own_sender(messages, user_identity, IdentityMatch::Exact).map_err(|senders| {
+        ParserError::UnknownIdentity { identity: user_identity.to_string(), senders }
+    })?;
+    if aliases.contains(&user) {
+        return Err(ParserError::InvalidArgument(format!("'{}' is the user, not another person", person)));
+    }
+    let side_of = |sender: &str| {
+        if sender == user {
+            Some(Side::User)
+        } else if aliases.iter().any(|alias| alias == sender) {
+            Some(Side::Person)
+        } else {
+            None
+        }
+    };
+
+    let mut counts = [0usize; 2];
+    let mut reply_totals = [(0i64, 0usize); 2];
+    let mut emoji: [HashMap<String, usize>; 2] = Default::default();
+    let mut words: [HashMap<String, usize>; 2] = Default::default();
+    let (mut shared_links, mut media_exchanged) = (0, 0);
+    let mut sentiment: BTreeMap<String, (f32, u32)> = BTreeMap::new();
+    let mut lengths: Vec<(usize, usize)> = Vec::new();
+    let mut gaps: Vec<(i64, usize)> = Vec::new();
+    let mut reactions: HashMap<usize, usize> = HashMap::new();
+    let mut latest = [None::<usize>; 2];
+    let mut previous: Option<(Side, DateTime<Local>)> = None;
+    let (mut first, mut last) = (None, None);
+
+    for (index, message) in messages.iter().enumerate() {
+        if let MessageType::Reaction { to_sender, .. } = &message.message_type {
+            let target = if to_sender == "You" { Some(Side::User) } else { side_of(&sender_key(to_sender)) };
+            if let Some(reacted) = target.and_then(|side| latest[side as usize]) {
+                *reactions.entry(reacted).or_insert(0) += 1;
+            }
+            continue;
+        }
+        if matches!(message.message_type, MessageType::SystemEvent(_)) {
+            continue;
+        }
+        let Some(side) = side_of(&message.sender) else { continue };
+        let slot = side as usize;
+
+        counts[slot] += 1;
+        latest[slot] = Some(index);
+        first.get_or_insert(index);
+        last = Some(index);
+        if let Some((previous_side, previous_timestamp)) = previous {
+            let seconds = (message.timestamp - previous_timestamp).num_seconds().max(0);
+            if previous_side != side {
+                reply_totals[slot].0 += seconds;
+                reply_totals[slot].1 += 1;
+            }
+            gaps.push((seconds, index));
+        }
+        previous = Some((side, message.timestamp));
+
+        match message.message_type {
+            MessageType::Media => media_exchanged += 1,
+            MessageType::Link => shared_links += 1,
+            _ => {}
+        }
+        if matches!(message.message_type, MessageType::Text | MessageType::Link | MessageType::Custom(_)) {
+            lengths.push((message.content.chars().count(), index));
+            for found in extract_emojis(&message.content) {
+                *emoji[slot].entry(found).or_insert(0) += 1;
+            }
+            let folded = fold_case(&message.content);
+            for word in tokenize_words_with(&folded, true) {
+                if !STOP_WORDS.contains(&word) {
+                    *words[slot].entry(word.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+        if let Some(score) = message.sentiment_score {
+            let month = sentiment.entry(message.timestamp.format("%Y-%m").to_string()).or_insert((0.0, 0));
+            month.0 += score;
+            month.1 += 1;
+        }
+    }
+
+    let quote = |index: usize| ProfileMessage {
+        index,
+        sender: messages[index].sender.clone(),
+        timestamp: messages[index].timestamp,
+        content: messages[index].content.clone(),
+    };
+    let mean_reply = |slot: usize| {
+        let (total, replies) = reply_totals[slot];
+        (replies > 0).then(|| total as f64 / replies as f64)
+    };
+
+    lengths.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    gaps.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let most_reacted = reactions.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))).map(|(&index, _)| index);
+    let candidates = lengths
+        .first()
+        .map(|&(_, index)| ("longest", index))
+        .into_iter()
+        .chain(most_reacted.map(|index| ("most_reacted", index)))
+        .chain(gaps.iter().filter(|(seconds, _)| *seconds > 0).map(|&(_, index)| ("after_gap", index)))
+        .chain(lengths.iter().skip(1).map(|&(_, index)| ("longest", index)));
+    let mut picked: Vec<(&'static str, usize)> = Vec::new();
+    for (reason, index) in candidates {
+        if picked.len() == PROFILE_HIGHLIGHT_COUNT {
+            break;
+        }
+        if !picked.iter().any(|&(_, seen)| seen == index) {
+            picked.push((reason, index));
+        }
+    }
+    picked.sort_by_key(|&(_, index)| index);
+
+    Ok(PersonProfile {
+        person: aliases[0].clone(),
+        user,
+        messages_from_person: counts[Side::Person as usize],
+        messages_from_user: counts[Side::User as usize],
+        first_message: first.map(quote),
+        last_message: last.map(quote),
+        person_reply_seconds: mean_reply(Side::Person as usize),
+        user_reply_seconds: mean_reply(Side::User as usize),
+        top_shared_emoji: top_shared(&emoji[0], &emoji[1]),
+        top_shared_words: top_shared(&words[0], &words[1]),
+        shared_links,
+        media_exchanged,
+        sentiment_trend: sentiment.into_iter().map(|(month, (total, count))| (month, total / count as f32)).collect(),
+        highlights: picked.into_iter().map(|(reason, index)| ProfileHighlight { reason, message: quote(index) }).collect(),
+        aliases,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_parser::ChatParser;
+
+    const CHAT: &str = "[18/05/2023, 08:00:00] Vishnu: Morning Priya 😊 the trip photos are up
+[18/05/2023, 08:10:00] priya: Morning! The trip was amazing 😊
+[18/05/2023, 08:11:00] Tom: nice
+[18/05/2023, 08:12:00] Vishnu: https://photos.example.com/trip
+[18/05/2023, 08:13:00] Priya reacted ❤️ to Vishnu's message
+[20/05/2023, 09:00:00] Priya: <Media omitted>
+[20/05/2023, 09:30:00] Vishnu: That trip photo is my new wallpaper, honestly the best one of the whole album
+";
+
+    #[test]
+    fn profiles_the_exchange_between_two_people() {
+        let messages = ChatParser::new().parse_str(CHAT, true).unwrap();
+        let profile = person_profile(&messages, "PRIYA", "Vishnu").unwrap();
+        assert_eq!(profile.aliases, ["Priya", "priya"]);
+        assert_eq!((profile.person.as_str(), profile.user.as_str()), ("Priya", "Vishnu"));
+        assert_eq!((profile.messages_from_person, profile.messages_from_user), (2, 3));
+        assert_eq!(profile.first_message.as_ref().map(|message| message.index), Some(0));
+        assert_eq!(profile.last_message.as_ref().map(|message| message.index), Some(6));
+        // Priya answered after 10 minutes and about two days, Vishnu after 2 and 30 minutes
+        assert_eq!(profile.person_reply_seconds, Some((600.0 + 175_680.0) / 2.0));
+        assert_eq!(profile.user_reply_seconds, Some((120.0 + 1_800.0) / 2.0));
+        assert_eq!(profile.top_shared_emoji, [("😊".to_string(), 2)]);
+        assert_eq!(profile.top_shared_words[0], ("trip".to_string(), 3));
+        assert_eq!((profile.shared_links, profile.media_exchanged), (1, 1));
+
+        let highlights: Vec<(&str, usize)> =
+            profile.highlights.iter().map(|highlight| (highlight.reason, highlight.message.index)).collect();
+        assert_eq!(highlights, [("longest", 0), ("after_gap", 1), ("most_reacted", 3), ("after_gap", 5), ("longest", 6)]);
+    }
+
+    #[test]
+    fn unknown_people_are_an_error() {
+        let messages = ChatParser::new().parse_str(CHAT, false).unwrap();
+        assert!(matches!(person_profile(&messages, "Nobody", "Vishnu"), Err(ParserError::UnknownIdentity { .. })));
+        assert!(matches!(person_profile(&messages, "vishnu", "Vishnu"), Err(ParserError::InvalidArgument(_))));
+    }
+}
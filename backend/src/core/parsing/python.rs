@@ -0,0 +1,2205 @@
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::{ControlFlow, Deref};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::analytics::{
+    detect_language, Chronotype, EngagementWeights, ensure_real_timestamps, has_synthetic_timestamps, language_breakdown, style_metrics,
+    MediaBurst, GAP_MARKER_MIN_SECONDS, LANGUAGE_MIN_LENGTH, MEDIA_BURST_MAX_GAP_SECONDS, MEDIA_BURST_MIN_COUNT,
+};
+use crate::anomaly::{detect_anomalies, Anomaly};
+use crate::archive::parse_zip_with_media;
+use crate::calls::{call_stats, CallStats};
+use crate::chat_parser::{annotate_gaps, merge_chats, ChatParser, WhitespaceNormalization, EXPORT_FORMATS};
+use crate::error::ParserError;
+use crate::events::{subject_at, subject_history, DetectedEvent};
+use crate::export::{export_events_ics, export_graph, export_jsonl, export_redacted_jsonl, verify_export, GraphFormat, IcsOptions};
+use crate::identity::{mark_own_messages, split_by_ownership, IdentityMatch};
+use crate::message::{GroupEvent, Message, MessageType};
+use crate::patterns::PatternLimits;
+use crate::person::person_profile;
+use crate::reactions::ReactionEvent;
+#[cfg(feature = "parquet")]
+use crate::parquet::export_to_parquet;
+#[cfg(feature = "pdf")]
+use crate::pdf::{render_pdf, PageSize, PdfOptions};
+use crate::search::FuzzyMatch;
+use crate::sentiment::{
+    score_sentences, score_sentiment, sentiment_by_sender, SentenceBreakdown, SentenceSentiment, SentimentGranularity,
+};
+use crate::similarity::{BroadcastEvent, SimilarCluster};
+#[cfg(feature = "store")]
+use crate::store::ChatStore;
+use crate::text::{bidi_isolate, split_sentences};
+use crate::type_rules::TypeRules;
+use crate::validation::{validate_chat_file, ValidationOptions};
+use crate::version::{build_info, VERSION};
+
+impl From<ParserError> for PyErr {
+    fn from(error: ParserError) -> PyErr {
+        match error {
+            ParserError::Io { .. } => PyErr::new::<pyo3::exceptions::PyIOError, _>(error.to_string()),
+            _ => PyErr::new::<pyo3::exceptions::PyValueError, _>(error.to_string()),
+        }
+    }
+}
+
+/// Convert a group event to a Python dict with a `kind` key plus the variant's fields
+fn group_event_to_dict(py: Python, event: &GroupEvent) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("kind", event.kind())?;
+    match event {
+        GroupEvent::Created { actor, subject } => {
+            dict.set_item("actor", actor)?;
+            dict.set_item("subject", subject)?;
+        }
+        GroupEvent::SubjectChanged { actor, new_subject } => {
+            dict.set_item("actor", actor)?;
+            dict.set_item("new_subject", new_subject)?;
+        }
+        GroupEvent::IconChanged { actor } => {
+            dict.set_item("actor", actor)?;
+        }
+        GroupEvent::ContactBlocked | GroupEvent::ContactUnblocked => {}
+        GroupEvent::DisappearingMessages { enabled, actor } => {
+            dict.set_item("enabled", enabled)?;
+            dict.set_item("actor", actor)?;
+        }
+        GroupEvent::SelfJoined { timestamp } => {
+            dict.set_item("timestamp", timestamp.to_rfc3339())?;
+        }
+        GroupEvent::Other { text } => {
+            dict.set_item("text", text)?;
+        }
+    }
+    Ok(dict.to_object(py))
+}
+
+/// Call details of a call-log message as a Python dict
+fn call_to_dict(py: Python, video: bool, missed: bool, duration_seconds: Option<u32>) -> PyResult<PyObject> {
+    let call = PyDict::new(py);
+    call.set_item("video", video)?;
+    call.set_item("missed", missed)?;
+    call.set_item("duration_seconds", duration_seconds)?;
+    Ok(call.to_object(py))
+}
+
+fn reaction_to_dict(py: Python, reactor: &str, emoji: &str, to_sender: &str) -> PyResult<PyObject> {
+    let reaction = PyDict::new(py);
+    reaction.set_item("reactor", reactor)?;
+    reaction.set_item("emoji", emoji)?;
+    reaction.set_item("to_sender", to_sender)?;
+    Ok(reaction.to_object(py))
+}
+
+#[pymethods]
+impl Message {
+    /// Build a synthetic message for testing analysis code. `timestamp` is
+    /// an RFC 3339 string; the type is inferred from `content` as the parser
+    /// would.
+    #[new]
+    fn py_new(sender: String, content: String, timestamp: &str) -> PyResult<Self> {
+        Ok(Message::new(sender, content, parse_datetime_arg(timestamp)?))
+    }
+
+    #[getter]
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Timestamp as an RFC 3339 string, matching `parse_whatsapp_chat`
+    #[getter]
+    fn timestamp(&self) -> String {
+        self.timestamp.to_rfc3339()
+    }
+
+    /// True when the timestamp was synthesised rather than read from the export
+    #[getter]
+    fn timestamp_synthetic(&self) -> bool {
+        self.timestamp_synthetic
+    }
+
+    /// True when the timestamp was spread within its minute (see
+    /// `ChatParser.set_interpolate_subminute`)
+    #[getter]
+    fn timestamp_interpolated(&self) -> bool {
+        self.timestamp_interpolated
+    }
+
+    /// The timestamp as exported ("18/05/2023, 08:39") on interpolated
+    /// messages, otherwise None
+    #[getter]
+    fn original_timestamp(&self) -> Option<&str> {
+        self.original_timestamp.as_deref()
+    }
+
+    /// True for messages sent by the parser's `user_identity`
+    #[getter]
+    fn is_own(&self) -> bool {
+        self.is_own
+    }
+
+    /// True when the export marked the message as sent to a broadcast list
+    #[getter]
+    fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// True when the previous message has the same sender and was sent within
+    /// a few minutes, so a chat UI can collapse this message's header
+    #[getter]
+    fn grouped_with_previous(&self) -> bool {
+        self.grouped_with_previous
+    }
+
+    /// Media file from the export ZIP attached to this message, if matched
+    #[getter]
+    fn media_filename(&self) -> Option<&str> {
+        self.media_filename.as_deref()
+    }
+
+    /// Text sent with a media message, separated from `content`
+    #[getter]
+    fn caption(&self) -> Option<&str> {
+        self.caption.as_deref()
+    }
+
+    /// Quoted preview of the replied-to message, when parsed with `parse_replies`
+    #[getter]
+    fn reply_to(&self) -> Option<&str> {
+        self.reply_to.as_deref()
+    }
+
+    /// RFC 3339 timestamp of the replied-to message, if its preview carried one
+    #[getter]
+    fn reply_to_timestamp(&self) -> Option<String> {
+        self.reply_to_timestamp.map(|timestamp| timestamp.to_rfc3339())
+    }
+
+    /// Seconds since the previous message, None for the first
+    #[getter]
+    fn gap_before_seconds(&self) -> Option<u64> {
+        self.gap_before_seconds
+    }
+
+    /// Words in the content, zero for anything but text
+    #[getter]
+    fn word_count(&self) -> u32 {
+        self.word_count
+    }
+
+    /// Sender name used as the key in statistics (bidi control marks stripped)
+    #[getter]
+    fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Sender name exactly as it appears in the export
+    #[getter]
+    fn sender_display(&self) -> &str {
+        &self.sender_display
+    }
+
+    #[getter]
+    fn content(&self) -> &str {
+        &self.content
+    }
+
+    #[getter]
+    fn message_type(&self) -> &str {
+        self.message_type.as_str()
+    }
+
+    /// The structured group event for system messages, `None` otherwise
+    #[getter]
+    fn event(&self, py: Python) -> PyResult<Option<PyObject>> {
+        match &self.message_type {
+            MessageType::SystemEvent(event) => group_event_to_dict(py, event).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Call details (`video`, `missed`, `duration_seconds`) for call-log
+    /// messages, `None` otherwise
+    #[getter]
+    fn call(&self, py: Python) -> PyResult<Option<PyObject>> {
+        match &self.message_type {
+            MessageType::Call { video, missed, duration_seconds } => {
+                call_to_dict(py, *video, *missed, *duration_seconds).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Reaction details (`reactor`, `emoji`, `to_sender`) for reaction
+    /// notices, `None` otherwise
+    #[getter]
+    fn reaction(&self, py: Python) -> PyResult<Option<PyObject>> {
+        match &self.message_type {
+            MessageType::Reaction { reactor, emoji, to_sender } => reaction_to_dict(py, reactor, emoji, to_sender).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Sentiment score in [-1, 1], populated by the caller's sentiment analysis
+    #[getter]
+    fn sentiment_score(&self) -> Option<f32> {
+        self.sentiment_score
+    }
+
+    #[setter]
+    fn set_sentiment_score(&mut self, score: Option<f32>) {
+        self.sentiment_score = score;
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Message(id={:?}, sender={:?}, timestamp={:?})", self.id, self.sender, self.timestamp())
+    }
+}
+
+/// Number of messages converted between signal checks and GIL hand-offs
+const CONVERSION_CHUNK: usize = 10_000;
+
+/// A Python sequence of `Message` objects, extracted in chunks with signal
+/// checks in between so Ctrl-C is honoured while converting huge lists
+struct MessageList(Vec<Message>);
+
+impl Deref for MessageList {
+    type Target = [Message];
+
+    fn deref(&self) -> &[Message] {
+        &self.0
+    }
+}
+
+impl<'source> FromPyObject<'source> for MessageList {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        let py = ob.py();
+        let mut messages = Vec::with_capacity(ob.len().unwrap_or(0));
+        for (index, item) in ob.iter()?.enumerate() {
+            messages.push(item?.extract::<Message>()?);
+            if (index + 1) % CONVERSION_CHUNK == 0 {
+                py.check_signals()?;
+            }
+        }
+        Ok(MessageList(messages))
+    }
+}
+
+/// Convert items to Python objects in chunks, checking for signals and briefly
+/// releasing the GIL between chunks so other threads aren't stalled
+fn convert_in_chunks<T, O>(
+    py: Python,
+    items: Vec<T>,
+    mut convert: impl FnMut(Python, T) -> PyResult<O>,
+) -> PyResult<Vec<O>> {
+    let mut objects = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        objects.push(convert(py, item)?);
+        if (index + 1) % CONVERSION_CHUNK == 0 {
+            py.check_signals()?;
+            py.allow_threads(|| ());
+        }
+    }
+    Ok(objects)
+}
+
+/// Convert parsed messages into Python `Message` objects
+fn messages_into_py(py: Python, messages: Vec<Message>) -> PyResult<Vec<Py<Message>>> {
+    convert_in_chunks(py, messages, Py::new)
+}
+
+#[pymethods]
+impl ChatParser {
+    #[new]
+    fn py_new() -> Self {
+        Self::new()
+    }
+
+    /// Parse a WhatsApp chat export file into `Message` objects. Parsing runs
+    /// without holding the GIL. When `user_identity` is given, messages from the
+    /// matching sender (see `split_by_ownership`) get `is_own` set. With
+    /// `parse_replies`, quoted reply previews move into `reply_to`. System
+    /// messages (group events and reaction notices) are dropped unless
+    /// `skip_system_messages` is false.
+    #[pyo3(signature = (file_path, user_identity = None, match_mode = "exact", parse_replies = false, skip_system_messages = true))]
+    fn parse_chat(
+        &self,
+        py: Python,
+        file_path: &str,
+        user_identity: Option<&str>,
+        match_mode: &str,
+        parse_replies: bool,
+        skip_system_messages: bool,
+    ) -> PyResult<Vec<Py<Message>>> {
+        let mode = IdentityMatch::parse(match_mode)?;
+        let mut messages = py.allow_threads(|| self.parse_file(file_path, !skip_system_messages))?;
+        if let Some(identity) = user_identity {
+            mark_own_messages(&mut messages, identity, mode);
+        }
+        if parse_replies {
+            self.parse_reply_previews(&mut messages);
+        }
+        messages_into_py(py, messages)
+    }
+
+    /// The first `max_messages` messages of an export, for a quick preview:
+    /// reading stops once that many are complete, however large the file.
+    /// As with parse_chat_batched, senders that look mis-split are not
+    /// corrected.
+    #[pyo3(name = "parse_sample", signature = (file_path, max_messages = 100, skip_system_messages = true))]
+    fn py_parse_sample(
+        &self,
+        py: Python,
+        file_path: &str,
+        max_messages: usize,
+        skip_system_messages: bool,
+    ) -> PyResult<Vec<Py<Message>>> {
+        let messages = py.allow_threads(|| self.parse_sample(file_path, max_messages, !skip_system_messages))?;
+        messages_into_py(py, messages)
+    }
+
+    /// Parse a possibly truncated export, returning (messages, incomplete)
+    /// where `incomplete` is the raw text of a final line with no trailing
+    /// newline (None when the file ends cleanly). Only complete lines are
+    /// parsed, so prepend `incomplete` to whatever is appended next.
+    #[pyo3(name = "parse_partial_file", signature = (file_path, skip_system_messages = true))]
+    fn py_parse_partial_file(
+        &self,
+        py: Python,
+        file_path: &str,
+        skip_system_messages: bool,
+    ) -> PyResult<(Vec<Py<Message>>, Option<String>)> {
+        let (messages, incomplete) = py.allow_threads(|| self.parse_partial_file(file_path, !skip_system_messages))?;
+        Ok((messages_into_py(py, messages)?, incomplete))
+    }
+
+    /// Parse a large export with bounded memory: `callback(batch, progress)`
+    /// is called with each list of up to `batch_size` Message objects, and
+    /// only one batch is held at a time. `progress` is a dict with
+    /// "messages_delivered" (this batch included), "lines_read", "bytes_read"
+    /// and "total_bytes". Returning False from the callback stops the parse.
+    /// An exception from the callback aborts it and propagates with a
+    /// `messages_delivered` attribute counting the messages of the batches
+    /// the callback completed. Returns the number of messages delivered.
+    /// Unlike `parse_chat`, senders that look mis-split are not corrected.
+    #[pyo3(name = "parse_chat_batched", signature = (file_path, batch_size, callback, skip_system_messages = true))]
+    fn py_parse_chat_batched(
+        &self,
+        py: Python,
+        file_path: &str,
+        batch_size: usize,
+        callback: PyObject,
+        skip_system_messages: bool,
+    ) -> PyResult<usize> {
+        if batch_size == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("batch_size must be at least 1"));
+        }
+        let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+        let total_bytes = file.metadata().ok().map(|metadata| metadata.len());
+        let mut completed = 0;
+        let mut failure: Option<PyErr> = None;
+        let delivered = py.allow_threads(|| {
+            self.parse_reader_batched(BufReader::new(file), !skip_system_messages, batch_size, |batch, progress| {
+                Python::with_gil(|py| {
+                    let result = (|| {
+                        let batch = messages_into_py(py, batch)?;
+                        let info = PyDict::new(py);
+                        info.set_item("messages_delivered", progress.messages_delivered)?;
+                        info.set_item("lines_read", progress.lines_read)?;
+                        info.set_item("bytes_read", progress.bytes_read)?;
+                        info.set_item("total_bytes", total_bytes)?;
+                        let returned = callback.call1(py, (batch, info))?;
+                        // Only an explicit False stops; None and other values continue
+                        Ok(!returned.as_ref(py).is(PyBool::new(py, false)))
+                    })();
+                    match result {
+                        Ok(true) => {
+                            completed = progress.messages_delivered;
+                            ControlFlow::Continue(())
+                        }
+                        Ok(false) => {
+                            completed = progress.messages_delivered;
+                            ControlFlow::Break(())
+                        }
+                        Err(error) => {
+                            failure = Some(error);
+                            ControlFlow::Break(())
+                        }
+                    }
+                })
+            })
+        })?;
+        if let Some(error) = failure {
+            // Best effort: exceptions without a __dict__ can't take the attribute
+            let _ = error.value(py).setattr("messages_delivered", completed);
+            return Err(error);
+        }
+        Ok(delivered)
+    }
+
+    /// Parse several export files in one call, reusing this parser's compiled
+    /// patterns; returns {path: [Message, ...]}. All files are parsed without
+    /// holding the GIL, and the first failure raises.
+    #[pyo3(name = "parse_many", signature = (paths, user_identity = None, match_mode = "exact"))]
+    fn py_parse_many(
+        &self,
+        py: Python,
+        paths: Vec<&str>,
+        user_identity: Option<&str>,
+        match_mode: &str,
+    ) -> PyResult<HashMap<String, Vec<Py<Message>>>> {
+        let mode = IdentityMatch::parse(match_mode)?;
+        let chats = py.allow_threads(|| {
+            let mut chats = self.parse_many(&paths, false)?;
+            if let Some(identity) = user_identity {
+                for messages in chats.values_mut() {
+                    mark_own_messages(messages, identity, mode);
+                }
+            }
+            Ok::<_, ParserError>(chats)
+        })?;
+        chats.into_iter().map(|(path, messages)| Ok((path, messages_into_py(py, messages)?))).collect()
+    }
+
+    /// Read two-digit years ("18/05/95") as the year in pivot..pivot+100
+    /// ending in those digits in later parses. The default 2000 reads them as
+    /// 2000–2099; 1950 reads 95 as 1995.
+    #[pyo3(name = "set_year_pivot")]
+    fn py_set_year_pivot(&mut self, pivot: i32) {
+        self.set_year_pivot(pivot);
+    }
+
+    /// In later parses, skip a message whose timestamp can't be parsed, or
+    /// whose matching takes over 50 ms, instead of raising or stalling.
+    /// ChatStore.ingest reports skipped messages as UserWarnings.
+    #[pyo3(name = "set_lenient")]
+    fn py_set_lenient(&mut self, lenient: bool) {
+        self.set_lenient(lenient);
+    }
+
+    /// In later parses, spread messages sharing a minute-precision timestamp
+    /// (Android exports) evenly over that minute, in their original order.
+    /// Moved messages have `timestamp_interpolated` set; every minute-precision
+    /// message keeps the exported text in `original_timestamp`. Deterministic,
+    /// so re-parses agree; not applied by parse_chat_batched or parse_sample.
+    #[pyo3(name = "set_interpolate_subminute")]
+    fn py_set_interpolate_subminute(&mut self, interpolate: bool) {
+        self.set_interpolate_subminute(interpolate);
+    }
+
+    /// Clean up whitespace in message content in later parses: "preserve"
+    /// (the default) keeps it as exported, "trim_trailing" strips trailing
+    /// spaces and stray carriage returns from every line and trailing blank
+    /// lines, and "trim_lines" also strips leading whitespace from
+    /// continuation lines. CRLF line endings are always removed.
+    #[pyo3(name = "set_whitespace_normalization")]
+    fn py_set_whitespace_normalization(&mut self, mode: &str) -> PyResult<()> {
+        self.set_whitespace_normalization(WhitespaceNormalization::parse(mode)?);
+        Ok(())
+    }
+
+    /// Validate message headers strictly in later parses: a timestamped line
+    /// only starts a message when its timestamp parses and its sender is in
+    /// `senders`; other lines (a pasted log, say) stay in the previous
+    /// message. None, the default, turns strict mode on only when a parse
+    /// finds a one-off sender that looks like pasted text.
+    #[pyo3(name = "set_known_senders")]
+    fn py_set_known_senders(&mut self, senders: Option<Vec<String>>) {
+        self.set_known_senders(senders);
+    }
+
+    /// {display name: message count} from a plain parse of a chat export,
+    /// for choosing the senders to pass to set_known_senders
+    #[pyo3(name = "detect_senders")]
+    fn py_detect_senders(&self, py: Python, file_path: &str) -> PyResult<HashMap<String, usize>> {
+        py.allow_threads(|| {
+            let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+            Ok(self.detect_senders(BufReader::new(file))?)
+        })
+    }
+
+    /// Classify messages with ordered (rule_kind, pattern, resulting_type)
+    /// rules before the built-in detection in later parses. `rule_kind` is
+    /// "contains", "regex", "prefix" or "exact"; a `resulting_type` other than
+    /// "text", "media" or "link" becomes a custom type name. Invalid rules
+    /// raise ValueError and leave the current rules in place.
+    #[pyo3(name = "set_type_rules")]
+    fn py_set_type_rules(&mut self, rules: Vec<(String, String, String)>) -> PyResult<()> {
+        self.set_type_rules(TypeRules::new(&rules)?);
+        Ok(())
+    }
+
+    /// Parse the parts of an export WhatsApp split by size (text files or
+    /// ZIPs, in any order) into one list, as if the export were one file.
+    /// Parts are ordered by the dates they cover and their overlapping tails
+    /// removed. Parts that don't overlap raise a UserWarning naming the
+    /// missing date range, since a part is probably missing.
+    #[pyo3(name = "parse_split_export", signature = (paths, skip_system_messages = true))]
+    fn py_parse_split_export(
+        &self,
+        py: Python,
+        paths: Vec<&str>,
+        skip_system_messages: bool,
+    ) -> PyResult<Vec<Py<Message>>> {
+        let (messages, gaps) = py.allow_threads(|| self.parse_split_export(&paths, !skip_system_messages))?;
+        for gap in gaps {
+            let text = format!(
+                "No messages between {} and {} ({} ends before {} starts); is a part missing?",
+                gap.from.to_rfc3339(),
+                gap.to.to_rfc3339(),
+                gap.after,
+                gap.before
+            );
+            PyErr::warn(py, py.get_type::<pyo3::exceptions::PyUserWarning>(), &text, 1)?;
+        }
+        messages_into_py(py, messages)
+    }
+
+    /// `with ChatParser() as parser:` for batch jobs; the parser holds no
+    /// resources, so leaving the block only ends the scope
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(&self, _exc_type: &PyAny, _exc_value: &PyAny, _traceback: &PyAny) -> bool {
+        false
+    }
+
+    /// Parse timestamp-less chat text (or a file containing it). Messages get
+    /// synthetic timestamps starting at `base` (RFC 3339, default the Unix epoch)
+    /// and `spacing_seconds` apart, flagged with `timestamp_synthetic`.
+    #[pyo3(signature = (text_or_path, participants = None, base = None, spacing_seconds = 60))]
+    fn parse_loose(
+        &self,
+        py: Python,
+        text_or_path: &str,
+        participants: Option<Vec<String>>,
+        base: Option<&str>,
+        spacing_seconds: i64,
+    ) -> PyResult<Vec<Py<Message>>> {
+        let text = if Path::new(text_or_path).is_file() {
+            match std::fs::read(text_or_path) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(e) => return Err(PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Failed to open file: {}", e))),
+            }
+        } else {
+            text_or_path.to_string()
+        };
+
+        let base = match base {
+            Some(base) => parse_datetime_arg(base)?,
+            None => Local.timestamp_opt(0, 0).unwrap(),
+        };
+
+        let messages = self.parse_loose_text(
+            &text,
+            &participants.unwrap_or_default(),
+            base,
+            chrono::Duration::seconds(spacing_seconds),
+        );
+        messages_into_py(py, messages)
+    }
+
+    /// Message counts per ISO weekday as a list of 7 ints (0=Monday … 6=Sunday)
+    #[staticmethod]
+    #[pyo3(name = "count_messages_by_weekday")]
+    fn py_count_messages_by_weekday(messages: MessageList) -> PyResult<[usize; 7]> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::count_messages_by_weekday(&messages))
+    }
+
+    /// Index (0=Monday … 6=Sunday) of the weekday with the most messages
+    #[staticmethod]
+    #[pyo3(name = "busiest_weekday")]
+    fn py_busiest_weekday(messages: MessageList) -> PyResult<u32> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::busiest_weekday(&messages))
+    }
+
+    /// 7×24 message counts, `grid[weekday][hour]` with 0=Monday
+    #[staticmethod]
+    #[pyo3(name = "heatmap")]
+    fn py_heatmap(messages: MessageList) -> PyResult<[[u32; 24]; 7]> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::heatmap(&messages))
+    }
+
+    /// Mean sentiment per hour of day, 24 floats (0.0 for hours without a
+    /// scored message); run `compute_sentiment_scores` first
+    #[staticmethod]
+    #[pyo3(name = "compute_sentiment_by_hour")]
+    fn py_compute_sentiment_by_hour(messages: MessageList) -> PyResult<[f32; 24]> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::compute_sentiment_by_hour(&messages))
+    }
+
+    /// Emotionally intense messages sent late at night (see the Rust docs);
+    /// run `compute_sentiment_scores` first
+    #[staticmethod]
+    #[pyo3(name = "detect_late_night_confessions")]
+    fn py_detect_late_night_confessions(
+        py: Python,
+        messages: MessageList,
+        hour_start: u32,
+        hour_end: u32,
+        sentiment_threshold: f32,
+    ) -> PyResult<Vec<Py<Message>>> {
+        ensure_real_timestamps(&messages)?;
+        let found = Self::detect_late_night_confessions(&messages, hour_start, hour_end, sentiment_threshold);
+        messages_into_py(py, found.into_iter().cloned().collect())
+    }
+
+    /// Repeated phrases (up to `max_word_count` words, in at least
+    /// `min_occurrences` messages) containing a word that isn't ordinary
+    /// English, as (phrase, message count) tuples, most used first
+    #[staticmethod]
+    #[pyo3(name = "detect_inside_jokes", signature = (messages, min_occurrences = 3, max_word_count = 3))]
+    fn py_detect_inside_jokes(
+        py: Python,
+        messages: MessageList,
+        min_occurrences: usize,
+        max_word_count: usize,
+    ) -> Vec<(String, usize)> {
+        py.allow_threads(|| Self::detect_inside_jokes(&messages, min_occurrences, max_word_count))
+    }
+
+    /// Each sender's longest word (by grapheme count), links excluded
+    #[staticmethod]
+    #[pyo3(name = "compute_longest_word")]
+    fn py_compute_longest_word(py: Python, messages: MessageList) -> HashMap<String, String> {
+        py.allow_threads(|| Self::compute_longest_word(&messages))
+    }
+
+    /// {sender: number of text messages ending with a question mark};
+    /// trailing whitespace, emoji and "!" are ignored
+    #[staticmethod]
+    #[pyo3(name = "questions_asked")]
+    fn py_questions_asked(py: Python, messages: MessageList) -> HashMap<String, u32> {
+        py.allow_threads(|| Self::questions_asked(&messages))
+    }
+
+    /// Messages sent within `window_days` of an anniversary of the first
+    /// message, for "this day X years ago"
+    #[staticmethod]
+    #[pyo3(name = "find_anniversary_messages", signature = (messages, window_days = 7))]
+    fn py_find_anniversary_messages(py: Python, messages: MessageList, window_days: u32) -> PyResult<Vec<Py<Message>>> {
+        ensure_real_timestamps(&messages)?;
+        let found = Self::find_anniversary_messages(&messages, window_days);
+        messages_into_py(py, found.into_iter().cloned().collect())
+    }
+
+    /// Questions no other sender replied to within `timeout_hours`,
+    /// including any left at the end of the chat
+    #[staticmethod]
+    #[pyo3(name = "detect_unanswered_questions")]
+    fn py_detect_unanswered_questions(py: Python, messages: MessageList, timeout_hours: f64) -> PyResult<Vec<Py<Message>>> {
+        ensure_real_timestamps(&messages)?;
+        let found = Self::detect_unanswered_questions(&messages, timeout_hours);
+        messages_into_py(py, found.into_iter().cloned().collect())
+    }
+
+    /// Indices of the messages whose content contains `query`
+    #[staticmethod]
+    #[pyo3(name = "find_messages_by_content", signature = (messages, query, case_sensitive = false))]
+    fn py_find_messages_by_content(
+        py: Python,
+        messages: MessageList,
+        query: &str,
+        case_sensitive: bool,
+    ) -> Vec<usize> {
+        py.allow_threads(|| Self::find_messages_by_content(&messages, query, case_sensitive))
+    }
+
+    /// Search message content with a regular expression. Returns (matching
+    /// indices, skipped indices): the pattern must compile within
+    /// `size_limit`/`dfa_size_limit` bytes and stay under 1000 characters and
+    /// 32 levels of nesting (ValueError otherwise), and messages taking
+    /// longer than `line_budget_ms` to match are skipped.
+    #[staticmethod]
+    #[pyo3(
+        name = "find_messages_by_pattern",
+        signature = (messages, pattern, size_limit = None, dfa_size_limit = None, line_budget_ms = None)
+    )]
+    fn py_find_messages_by_pattern(
+        py: Python,
+        messages: MessageList,
+        pattern: &str,
+        size_limit: Option<usize>,
+        dfa_size_limit: Option<usize>,
+        line_budget_ms: Option<u64>,
+    ) -> PyResult<(Vec<usize>, Vec<usize>)> {
+        let defaults = PatternLimits::default();
+        let limits = PatternLimits {
+            size_limit: size_limit.unwrap_or(defaults.size_limit),
+            dfa_size_limit: dfa_size_limit.unwrap_or(defaults.dfa_size_limit),
+            line_budget: line_budget_ms.map_or(defaults.line_budget, Duration::from_millis),
+            ..defaults
+        };
+        let search = py.allow_threads(|| Self::find_messages_by_pattern(&messages, pattern, &limits))?;
+        Ok((search.matches, search.skipped))
+    }
+
+    /// Distinct senders per day as ("YYYY-MM-DD", count) tuples, in date order
+    #[staticmethod]
+    #[pyo3(name = "compute_daily_active_users")]
+    fn py_compute_daily_active_users(messages: MessageList) -> PyResult<Vec<(String, usize)>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::compute_daily_active_users(&messages)
+            .into_iter()
+            .map(|(day, count)| (day.format("%Y-%m-%d").to_string(), count))
+            .collect())
+    }
+
+    /// Month-over-month message volume change as ("YYYY-MM", pct) tuples
+    #[staticmethod]
+    #[pyo3(name = "month_over_month_growth")]
+    fn py_month_over_month_growth(messages: MessageList) -> PyResult<Vec<(String, f64)>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::month_over_month_growth(&messages))
+    }
+
+    /// Dates the participants talk about, with how many messages mention each
+    #[staticmethod]
+    #[pyo3(name = "detect_shared_events")]
+    fn py_detect_shared_events(messages: MessageList) -> PyResult<Vec<DetectedEvent>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(Self::detect_shared_events(&messages))
+    }
+
+    /// Indices of messages where the conversation moves to a new topic, from
+    /// the TF-IDF cosine distance between the 5 text messages before and
+    /// after each point; `threshold` is the minimum distance in [0, 1]
+    #[staticmethod]
+    #[pyo3(name = "detect_topic_shifts", signature = (messages, threshold = 0.8))]
+    fn py_detect_topic_shifts(py: Python, messages: MessageList, threshold: f64) -> Vec<usize> {
+        py.allow_threads(|| Self::detect_topic_shifts(&messages, threshold))
+    }
+
+    /// Clusters of near-duplicate text messages (estimated Jaccard
+    /// similarity of three-word shingles above `threshold`), largest first,
+    /// as SimilarCluster objects. Messages under 10 words are skipped.
+    #[staticmethod]
+    #[pyo3(name = "cluster_similar", signature = (messages, threshold = 0.8))]
+    fn py_cluster_similar(py: Python, messages: MessageList, threshold: f64) -> Vec<SimilarCluster> {
+        py.allow_threads(|| Self::cluster_similar(&messages, threshold))
+    }
+
+    /// The text message with the most characters (the earliest on ties), or
+    /// None when there are no text messages
+    #[staticmethod]
+    #[pyo3(name = "longest_message")]
+    fn py_longest_message(messages: MessageList) -> Option<Message> {
+        Self::longest_message(&messages).cloned()
+    }
+
+    /// Text messages that appear to contain programming code
+    #[staticmethod]
+    #[pyo3(name = "detect_code_snippets")]
+    fn py_detect_code_snippets(py: Python, messages: MessageList) -> PyResult<Vec<Py<Message>>> {
+        let found = Self::detect_code_snippets(&messages);
+        messages_into_py(py, found.into_iter().cloned().collect())
+    }
+
+    /// Populate `sentiment_score` on text messages in place using the bundled
+    /// lexicon; `emoji_weight` scales the emoji table's contribution (0 = off)
+    #[staticmethod]
+    #[pyo3(signature = (messages, emoji_weight = 0.0))]
+    fn compute_sentiment_scores(mut messages: Vec<PyRefMut<Message>>, emoji_weight: f32) {
+        for message in messages.iter_mut() {
+            message.sentiment_score = match message.message_type {
+                MessageType::Text => Some(score_sentiment(&message.content, emoji_weight)),
+                _ => None,
+            };
+        }
+    }
+
+    /// Rewrite phone-number senders to "+" and digits in place, merging
+    /// formatting variants of one number; returns {original: normalized}
+    #[staticmethod]
+    #[pyo3(name = "normalize_sender_names")]
+    fn py_normalize_sender_names(mut messages: Vec<PyRefMut<Message>>) -> HashMap<String, String> {
+        let mut owned: Vec<Message> = messages.iter().map(|message| message.deref().clone()).collect();
+        let mapping = Self::normalize_sender_names(&mut owned);
+        for (message, normalized) in messages.iter_mut().zip(owned) {
+            message.sender = normalized.sender;
+        }
+        mapping
+    }
+
+    /// Index of the first message from `sender` (trimmed, case-insensitive), or None
+    #[staticmethod]
+    #[pyo3(name = "find_first_occurrence_of_sender")]
+    fn py_find_first_occurrence_of_sender(messages: MessageList, sender: &str) -> Option<usize> {
+        Self::find_first_occurrence_of_sender(&messages, sender)
+    }
+
+    /// Conversation sessions as a list of message lists, split at silences
+    /// longer than `gap_hours`
+    #[staticmethod]
+    #[pyo3(name = "split_by_conversation_gap")]
+    fn py_split_by_conversation_gap(
+        py: Python,
+        messages: MessageList,
+        gap_hours: f64,
+    ) -> PyResult<Vec<Vec<Py<Message>>>> {
+        ensure_real_timestamps(&messages)?;
+        let sessions = py.allow_threads(|| Self::split_by_conversation_gap(&messages, gap_hours));
+        sessions.into_iter().map(|session| messages_into_py(py, session)).collect()
+    }
+
+    /// Messages grouped by sender in their original order (system events
+    /// dropped), as a dict of sender to message list
+    #[staticmethod]
+    #[pyo3(name = "by_sender")]
+    fn py_by_sender(py: Python, messages: MessageList) -> PyResult<HashMap<String, Vec<Py<Message>>>> {
+        let partitions = Self::by_sender(messages.0);
+        partitions.into_iter().map(|(sender, messages)| Ok((sender, messages_into_py(py, messages)?))).collect()
+    }
+
+    /// Formality score in [0, 1] per sender (capitalization, end punctuation,
+    /// absence of chat abbreviations)
+    #[staticmethod]
+    #[pyo3(name = "compute_formality_score")]
+    fn py_compute_formality_score(py: Python, messages: MessageList) -> HashMap<String, f64> {
+        py.allow_threads(|| Self::compute_formality_score(&messages))
+    }
+
+    /// Each sender's share of the messages (fractions summing to 1), system
+    /// events excluded
+    #[staticmethod]
+    #[pyo3(name = "balance")]
+    fn py_balance(py: Python, messages: MessageList) -> HashMap<String, f64> {
+        py.allow_threads(|| Self::balance(&messages))
+    }
+
+    /// Mean character count of each sender's replies to someone else
+    /// (consecutive messages from the same sender are not replies)
+    #[staticmethod]
+    #[pyo3(name = "compute_average_response_length")]
+    fn py_compute_average_response_length(py: Python, messages: MessageList) -> HashMap<String, f64> {
+        py.allow_threads(|| Self::compute_average_response_length(&messages))
+    }
+
+    /// Mean seconds each sender took to reply to someone else. With
+    /// `use_interpolated` false, interpolated timestamps are compared at
+    /// their exported minute, so same-minute replies count as zero.
+    #[staticmethod]
+    #[pyo3(name = "average_response_time", signature = (messages, use_interpolated = true))]
+    fn py_average_response_time(py: Python, messages: MessageList, use_interpolated: bool) -> PyResult<HashMap<String, f64>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(py.allow_threads(|| Self::average_response_time(&messages, use_interpolated)))
+    }
+
+    /// {(sender, replied_to): count}: how often each sender's message directly
+    /// follows one from another sender, as edges of a social graph
+    #[staticmethod]
+    #[pyo3(name = "interaction_matrix")]
+    fn py_interaction_matrix(py: Python, messages: MessageList) -> HashMap<(String, String), u32> {
+        py.allow_threads(|| Self::interaction_matrix(&messages))
+    }
+
+    /// Messages per active day for each sender (days they sent nothing don't
+    /// count)
+    #[staticmethod]
+    #[pyo3(name = "compute_average_daily_messages")]
+    fn py_compute_average_daily_messages(py: Python, messages: MessageList) -> PyResult<HashMap<String, f64>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(py.allow_threads(|| Self::compute_average_daily_messages(&messages)))
+    }
+
+    /// Each sender's daily rhythm as Chronotype objects, most active first:
+    /// a label such as "night owl" with the circular mean hour, concentration
+    /// and hourly counts behind it. `timezone` is a UTC offset ("+05:30") to
+    /// read times of day in; by default they are read as exported.
+    #[staticmethod]
+    #[pyo3(name = "chronotype", signature = (messages, timezone = None))]
+    fn py_chronotype(py: Python, messages: MessageList, timezone: Option<&str>) -> PyResult<Vec<Chronotype>> {
+        ensure_real_timestamps(&messages)?;
+        let timezone = timezone
+            .map(|offset| {
+                offset.parse::<FixedOffset>().map_err(|_| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Invalid timezone '{}' (expected a UTC offset such as '+05:30')",
+                        offset
+                    ))
+                })
+            })
+            .transpose()?;
+        Ok(py.allow_threads(|| Self::chronotype(&messages, timezone)))
+    }
+
+    /// (word, count) pairs across the chat, most frequent first; with
+    /// `exclude_urls`, links don't count as words
+    #[staticmethod]
+    #[pyo3(name = "word_frequency", signature = (messages, exclude_urls = false))]
+    fn py_word_frequency(py: Python, messages: MessageList, exclude_urls: bool) -> Vec<(String, usize)> {
+        py.allow_threads(|| Self::word_frequency(&messages, exclude_urls))
+    }
+
+    /// Mean words per message for each sender; with `exclude_urls`, links
+    /// don't count as words
+    #[staticmethod]
+    #[pyo3(name = "avg_message_length", signature = (messages, exclude_urls = false))]
+    fn py_avg_message_length(py: Python, messages: MessageList, exclude_urls: bool) -> HashMap<String, f64> {
+        py.allow_threads(|| Self::avg_message_length(&messages, exclude_urls))
+    }
+
+    /// Fraction of each sender's messages sent in bursts: three or more in a
+    /// row from that sender, each within 60 seconds of the last
+    #[staticmethod]
+    #[pyo3(name = "compute_burst_score")]
+    fn py_compute_burst_score(py: Python, messages: MessageList) -> PyResult<HashMap<String, f64>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(py.allow_threads(|| Self::compute_burst_score(&messages)))
+    }
+
+    /// {sender: coefficient of variation of the seconds between their
+    /// messages}; high values mean sporadic texting. Senders with fewer than
+    /// 3 messages get NaN.
+    #[staticmethod]
+    #[pyo3(name = "compute_timing_irregularity")]
+    fn py_compute_timing_irregularity(py: Python, messages: MessageList) -> PyResult<HashMap<String, f64>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(py.allow_threads(|| Self::compute_timing_irregularity(&messages)))
+    }
+
+    /// The emoji reactions in a chat as ReactionEvent objects, in order.
+    /// Reaction notices are system lines, so parse with
+    /// skip_system_messages=False.
+    #[staticmethod]
+    #[pyo3(name = "parse_reactions")]
+    fn py_parse_reactions(py: Python, messages: MessageList) -> Vec<ReactionEvent> {
+        py.allow_threads(|| Self::parse_reactions(&messages))
+    }
+
+    /// Mean number of emoji per message for each sender, to find the
+    /// chat's emoji person
+    #[staticmethod]
+    #[pyo3(name = "emoji_rate")]
+    fn py_emoji_rate(py: Python, messages: MessageList) -> HashMap<String, f64> {
+        py.allow_threads(|| Self::emoji_rate(&messages))
+    }
+
+    /// [(sender, score)] highest first: a weighted sum of message count,
+    /// mean words per message, response rate and emoji per message, each
+    /// scaled to [0, 1] by its chat-wide maximum
+    #[staticmethod]
+    #[pyo3(
+        name = "rank_senders_by_engagement",
+        signature = (messages, message_count = 0.4, message_length = 0.2, response_rate = 0.2, emoji_rate = 0.2)
+    )]
+    fn py_rank_senders_by_engagement(
+        py: Python,
+        messages: MessageList,
+        message_count: f64,
+        message_length: f64,
+        response_rate: f64,
+        emoji_rate: f64,
+    ) -> Vec<(String, f64)> {
+        let weights = EngagementWeights { message_count, message_length, response_rate, emoji_rate };
+        py.allow_threads(|| Self::rank_senders_by_engagement_with(&messages, &weights))
+    }
+
+    /// {emoji: uses} across the chat, media captions included
+    #[staticmethod]
+    #[pyo3(name = "emoji_frequencies")]
+    fn py_emoji_frequencies(py: Python, messages: MessageList) -> HashMap<String, usize> {
+        py.allow_threads(|| Self::emoji_frequencies(&messages))
+    }
+
+    /// Mean sentiment score of the messages containing each emoji used more
+    /// than 5 times; run `compute_sentiment_scores` first
+    #[staticmethod]
+    #[pyo3(name = "compute_emoji_sentiment")]
+    fn py_compute_emoji_sentiment(py: Python, messages: MessageList) -> HashMap<String, f32> {
+        py.allow_threads(|| Self::compute_emoji_sentiment(&messages))
+    }
+
+    /// Recompute `gap_before_seconds` in place, e.g. after filtering messages
+    #[staticmethod]
+    #[pyo3(name = "annotate_gaps")]
+    fn py_annotate_gaps(mut messages: Vec<PyRefMut<Message>>) {
+        let mut owned: Vec<Message> = messages.iter().map(|message| message.deref().clone()).collect();
+        annotate_gaps(&mut owned);
+        for (message, annotated) in messages.iter_mut().zip(owned) {
+            message.gap_before_seconds = annotated.gap_before_seconds;
+        }
+    }
+
+    /// "2 days later" separators as (index, gap_seconds, label) for gaps of at
+    /// least `min_gap_seconds` (an hour by default); the separator goes
+    /// before `messages[index]`
+    #[staticmethod]
+    #[pyo3(name = "gap_markers", signature = (messages, min_gap_seconds = GAP_MARKER_MIN_SECONDS))]
+    fn py_gap_markers(py: Python, messages: MessageList, min_gap_seconds: u64) -> PyResult<Vec<(usize, u64, String)>> {
+        ensure_real_timestamps(&messages)?;
+        let markers = py.allow_threads(|| Self::gap_markers(&messages, min_gap_seconds));
+        Ok(markers.into_iter().map(|marker| (marker.index, marker.gap_seconds, marker.label)).collect())
+    }
+
+    /// Runs of at least `min_count` media messages from one sender, each
+    /// within `max_gap_seconds` of the previous, as MediaBurst objects
+    #[staticmethod]
+    #[pyo3(
+        name = "group_media_bursts",
+        signature = (messages, max_gap_seconds = MEDIA_BURST_MAX_GAP_SECONDS, min_count = MEDIA_BURST_MIN_COUNT)
+    )]
+    fn py_group_media_bursts(
+        py: Python,
+        messages: MessageList,
+        max_gap_seconds: u64,
+        min_count: usize,
+    ) -> PyResult<Vec<MediaBurst>> {
+        ensure_real_timestamps(&messages)?;
+        Ok(py.allow_threads(|| Self::group_media_bursts(&messages, max_gap_seconds, min_count)))
+    }
+
+    /// `messages` with each burst replaced by one "N media files" summary
+    /// row; `bursts` must come from `group_media_bursts` on the same list
+    #[staticmethod]
+    #[pyo3(name = "collapse_media_bursts")]
+    fn py_collapse_media_bursts(py: Python, messages: MessageList, bursts: Vec<MediaBurst>) -> PyResult<Vec<Py<Message>>> {
+        if let Some(burst) = bursts.iter().find(|burst| burst.indices.iter().any(|&index| index >= messages.len())) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Burst at index {} is outside the {} messages given",
+                burst.indices[0],
+                messages.len()
+            )));
+        }
+        let collapsed = py.allow_threads(|| Self::collapse_media_bursts(&messages, &bursts));
+        messages_into_py(py, collapsed)
+    }
+
+    /// Phone numbers written in messages, as (number, sender, timestamp)
+    /// tuples with RFC 3339 timestamps; numbers inside URLs are skipped
+    #[staticmethod]
+    #[pyo3(name = "detect_shared_phone_numbers")]
+    fn py_detect_shared_phone_numbers(py: Python, messages: MessageList) -> Vec<(String, String, String)> {
+        let found = py.allow_threads(|| Self::detect_shared_phone_numbers(&messages));
+        found
+            .into_iter()
+            .map(|(number, sender, timestamp)| (number, sender, timestamp.to_rfc3339()))
+            .collect()
+    }
+
+    /// Senders of a chat export with their message counts, sorted by count
+    /// descending, from a single pass over the file
+    #[pyo3(name = "sender_counts")]
+    fn py_sender_counts(&self, py: Python, file_path: &str) -> PyResult<Vec<(String, u32)>> {
+        py.allow_threads(|| {
+            let messages = self.parse_file(file_path, false)?;
+            Ok(Self::sender_counts(&messages))
+        })
+    }
+
+    /// Parse only the system messages (group events) of a chat export
+    fn parse_events(&self, py: Python, file_path: &str) -> PyResult<Vec<Py<Message>>> {
+        let messages = py.allow_threads(|| self.parse_file(file_path, true))?;
+        let events = messages
+            .into_iter()
+            .filter(|message| matches!(message.message_type, MessageType::SystemEvent(_)))
+            .collect();
+        messages_into_py(py, events)
+    }
+}
+
+/// Direction-isolate text for HTML (`html=True`) or plain-text/Markdown renderers
+#[pyfunction]
+#[pyo3(name = "bidi_isolate", signature = (text, html = false))]
+fn py_bidi_isolate(text: &str, html: bool) -> String {
+    bidi_isolate(text, html)
+}
+
+#[pymethods]
+impl DetectedEvent {
+    #[getter]
+    fn message_count(&self) -> usize {
+        self.message_count
+    }
+
+    #[getter]
+    fn participants(&self) -> Vec<String> {
+        self.participants.clone()
+    }
+
+    /// The referenced date as "YYYY-MM-DD"
+    #[getter]
+    fn referenced_date(&self) -> String {
+        self.referenced_date.format("%Y-%m-%d").to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DetectedEvent(referenced_date={:?}, message_count={})", self.referenced_date(), self.message_count)
+    }
+}
+
+#[pymethods]
+impl FuzzyMatch {
+    #[getter]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    #[getter]
+    fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Start of `token` in the message content (a str index)
+    #[getter]
+    fn start(&self) -> usize {
+        self.start
+    }
+
+    #[getter]
+    fn end(&self) -> usize {
+        self.end
+    }
+
+    #[getter]
+    fn distance(&self) -> usize {
+        self.distance
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FuzzyMatch(index={}, token={:?}, distance={})", self.index, self.token, self.distance)
+    }
+}
+
+/// Messages with a word within `max_distance` edits of `query`, ranked by
+/// distance then newest first, as FuzzyMatch objects (message index, matched
+/// token and its span). Queries under 4 characters allow at most one edit;
+/// multi-word queries are compared with whole short messages.
+#[pyfunction]
+#[pyo3(name = "fuzzy_search", signature = (messages, query, max_distance = 2))]
+fn py_fuzzy_search(py: Python, messages: MessageList, query: &str, max_distance: usize) -> Vec<FuzzyMatch> {
+    py.allow_threads(|| ChatParser::fuzzy_search(&messages, query, max_distance))
+}
+
+/// Case-insensitive search returning (exact, fuzzy): indices of messages
+/// containing `query`, and, only when there are none, `fuzzy_search`
+/// matches to offer as "did you mean" results
+#[pyfunction]
+#[pyo3(name = "search_messages", signature = (messages, query, max_distance = 2))]
+fn py_search_messages(py: Python, messages: MessageList, query: &str, max_distance: usize) -> (Vec<usize>, Vec<FuzzyMatch>) {
+    let results = py.allow_threads(|| ChatParser::search_messages(&messages, query, max_distance));
+    (results.exact, results.fuzzy)
+}
+
+/// Convert a JSON value to the matching Python objects (dicts, lists, str,
+/// int, float, bool, None)
+fn json_to_py(py: Python, value: &serde_json::Value) -> PyResult<PyObject> {
+    Ok(match value {
+        serde_json::Value::Null => py.None(),
+        serde_json::Value::Bool(flag) => flag.into_py(py),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into_py(py),
+            None => number.as_f64().into_py(py),
+        },
+        serde_json::Value::String(text) => text.into_py(py),
+        serde_json::Value::Array(items) => {
+            items.iter().map(|item| json_to_py(py, item)).collect::<PyResult<Vec<PyObject>>>()?.into_py(py)
+        }
+        serde_json::Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, field) in fields {
+                dict.set_item(key, json_to_py(py, field)?)?;
+            }
+            dict.to_object(py)
+        }
+    })
+}
+
+/// Convert a parsed message to the dict shape returned by `parse_whatsapp_chat`
+fn message_to_dict(py: Python, message: Message) -> PyResult<PyObject> {
+    let py_message = PyDict::new(py);
+    py_message.set_item("id", message.id)?;
+    py_message.set_item("timestamp", message.timestamp.to_rfc3339())?;
+    py_message.set_item("sender", message.sender)?;
+    py_message.set_item("sender_display", message.sender_display)?;
+    py_message.set_item("content", message.content)?;
+    py_message.set_item("type", message.message_type.as_str())?;
+    py_message.set_item("is_own", message.is_own)?;
+    py_message.set_item("broadcast", message.broadcast)?;
+    py_message.set_item("grouped_with_previous", message.grouped_with_previous)?;
+    py_message.set_item("gap_before_seconds", message.gap_before_seconds)?;
+    py_message.set_item("timestamp_interpolated", message.timestamp_interpolated)?;
+    if let Some(original_timestamp) = message.original_timestamp {
+        py_message.set_item("original_timestamp", original_timestamp)?;
+    }
+    py_message.set_item("word_count", message.word_count)?;
+    if let Some(media_filename) = message.media_filename {
+        py_message.set_item("media_filename", media_filename)?;
+    }
+    if let Some(caption) = message.caption {
+        py_message.set_item("caption", caption)?;
+    }
+    if let Some(reply_to) = message.reply_to {
+        py_message.set_item("reply_to", reply_to)?;
+        py_message.set_item("reply_to_timestamp", message.reply_to_timestamp.map(|timestamp| timestamp.to_rfc3339()))?;
+    }
+    match &message.message_type {
+        MessageType::SystemEvent(event) => py_message.set_item("event", group_event_to_dict(py, event)?)?,
+        MessageType::Call { video, missed, duration_seconds } => {
+            py_message.set_item("call", call_to_dict(py, *video, *missed, *duration_seconds)?)?
+        }
+        MessageType::Reaction { reactor, emoji, to_sender } => {
+            py_message.set_item("reaction", reaction_to_dict(py, reactor, emoji, to_sender)?)?
+        }
+        _ => {}
+    }
+    Ok(py_message.to_object(py))
+}
+
+/// Parse a WhatsApp chat export file and extract messages. System messages
+/// (group events, notices) are dropped unless `skip_system_messages` is false,
+/// in which case they are returned with type "system_event" and an "event" dict.
+/// Messages from the sender matching `user_identity` (per `match_mode`) have
+/// "is_own" set. With `lenient`, a message whose timestamp can't be parsed is
+/// skipped and reported as a UserWarning (with its line number) instead of
+/// raising, as are messages whose matching takes over 50 ms, any other
+/// lines the parser had to drop and any message whose sender it corrected. With `interpolate_subminute`, messages sharing
+/// a minute-precision timestamp are spread over that minute (see
+/// `ChatParser.set_interpolate_subminute`).
+#[pyfunction]
+#[pyo3(
+    signature = (file_path, user_identity, skip_system_messages = true, match_mode = "exact", lenient = false, interpolate_subminute = false)
+)]
+fn parse_whatsapp_chat(
+    py: Python,
+    file_path: &str,
+    user_identity: &str,
+    skip_system_messages: bool,
+    match_mode: &str,
+    lenient: bool,
+    interpolate_subminute: bool,
+) -> PyResult<Vec<PyObject>> {
+    let mode = IdentityMatch::parse(match_mode)?;
+    let (messages, warnings) = py.allow_threads(|| {
+        let mut parser = ChatParser::new();
+        parser.set_lenient(lenient);
+        parser.set_interpolate_subminute(interpolate_subminute);
+        let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+        let (mut messages, warnings) = parser.parse_reader_with_warnings(BufReader::new(file), !skip_system_messages)?;
+        mark_own_messages(&mut messages, user_identity, mode);
+        Ok::<_, PyErr>((messages, warnings))
+    })?;
+    if lenient {
+        for warning in warnings {
+            let text = format!("line {}: {}: {:?}", warning.line_number, warning.reason, warning.line);
+            PyErr::warn(py, py.get_type::<pyo3::exceptions::PyUserWarning>(), &text, 1)?;
+        }
+    }
+
+    // Convert to Python dicts
+    convert_in_chunks(py, messages, message_to_dict)
+}
+
+/// Like `parse_whatsapp_chat`, for a ZIP exported "with media": message
+/// dicts whose media file was found in the archive carry "media_filename"
+#[pyfunction]
+#[pyo3(signature = (zip_path, user_identity, skip_system_messages = true, match_mode = "exact"))]
+fn parse_whatsapp_zip_with_media(
+    py: Python,
+    zip_path: &str,
+    user_identity: &str,
+    skip_system_messages: bool,
+    match_mode: &str,
+) -> PyResult<Vec<PyObject>> {
+    let mode = IdentityMatch::parse(match_mode)?;
+    let messages = py.allow_threads(|| {
+        let file = File::open(zip_path).map_err(ParserError::io("Failed to open file"))?;
+        let mut messages = parse_zip_with_media(&ChatParser::new(), BufReader::new(file), !skip_system_messages)?;
+        mark_own_messages(&mut messages, user_identity, mode);
+        Ok::<_, PyErr>(messages)
+    })?;
+    convert_in_chunks(py, messages, message_to_dict)
+}
+
+/// Split messages into (own indices, other indices, own sender name) for a
+/// two-column view. `match_mode` is "exact", "prefix" or "fuzzy". Raises
+/// ValueError listing the detected senders when nobody matches.
+#[pyfunction]
+#[pyo3(name = "split_by_ownership", signature = (messages, user_identity, match_mode = "exact"))]
+fn py_split_by_ownership(
+    messages: MessageList,
+    user_identity: &str,
+    match_mode: &str,
+) -> PyResult<(Vec<usize>, Vec<usize>, String)> {
+    let mode = IdentityMatch::parse(match_mode)?;
+    Ok(split_by_ownership(&messages, user_identity, mode)?)
+}
+
+/// What the user and `person` exchanged, for a "memories with X" page, as
+/// plain dicts and lists ready for `json.dumps`: message counts, first and
+/// last message, mean reply seconds each way, top shared emoji and words,
+/// links and media exchanged, the monthly sentiment trend and up to five
+/// highlight messages. Senders with the person's name in any case, or with
+/// their phone number formatted differently, count as them. Raises
+/// ValueError listing the detected senders when either name matches nobody.
+#[pyfunction]
+#[pyo3(name = "person_profile")]
+fn py_person_profile(py: Python, messages: MessageList, person: &str, user_identity: &str) -> PyResult<PyObject> {
+    let profile = py.allow_threads(|| person_profile(&messages, person, user_identity))?;
+    let value = serde_json::to_value(&profile).map_err(|error| PyErr::new::<pyo3::exceptions::PyValueError, _>(error.to_string()))?;
+    json_to_py(py, &value)
+}
+
+/// Average sentiment score per sender ("who's the positive one")
+#[pyfunction]
+#[pyo3(name = "sentiment_by_sender")]
+fn py_sentiment_by_sender(py: Python, messages: MessageList) -> HashMap<String, f32> {
+    py.allow_threads(|| sentiment_by_sender(&messages))
+}
+
+#[pymethods]
+impl SentenceSentiment {
+    /// Start of the sentence in the message content (a str index)
+    #[getter]
+    fn start(&self) -> usize {
+        self.start
+    }
+
+    #[getter]
+    fn end(&self) -> usize {
+        self.end
+    }
+
+    #[getter]
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[getter]
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SentenceSentiment(start={}, end={}, score={:.3})", self.start, self.end, self.score)
+    }
+}
+
+#[pymethods]
+impl SentenceBreakdown {
+    #[getter]
+    fn sentences(&self) -> Vec<SentenceSentiment> {
+        self.sentences.clone()
+    }
+
+    #[getter]
+    fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    #[getter]
+    fn min(&self) -> f32 {
+        self.min
+    }
+
+    #[getter]
+    fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// The lowest-scoring sentence, or None for an empty message
+    #[getter(most_negative)]
+    fn py_most_negative(&self) -> Option<SentenceSentiment> {
+        self.most_negative().cloned()
+    }
+}
+
+/// Sentiment of `content` in [-1, 1] with the bundled lexicon. With
+/// granularity="sentence", returns a SentenceBreakdown instead: a score and
+/// character span per sentence, plus their mean, min and max.
+#[pyfunction]
+#[pyo3(name = "score_sentiment", signature = (content, emoji_weight = 0.0, granularity = "message"))]
+fn py_score_sentiment(py: Python, content: &str, emoji_weight: f32, granularity: &str) -> PyResult<PyObject> {
+    Ok(match SentimentGranularity::parse(granularity)? {
+        SentimentGranularity::Message => score_sentiment(content, emoji_weight).into_py(py),
+        SentimentGranularity::Sentence => score_sentences(content, emoji_weight).into_py(py),
+    })
+}
+
+/// Sentences of `content` as (start, end, text), with str-index spans
+#[pyfunction]
+#[pyo3(name = "split_sentences")]
+fn py_split_sentences(content: &str) -> Vec<(usize, usize, &str)> {
+    split_sentences(content)
+}
+
+/// Merge several exports of one chat into a single deduplicated timeline
+#[pyfunction]
+#[pyo3(name = "merge_chats")]
+fn py_merge_chats(py: Python, chats: Vec<MessageList>) -> PyResult<Vec<Py<Message>>> {
+    let merged = merge_chats(chats.into_iter().map(|chat| chat.0).collect());
+    messages_into_py(py, merged)
+}
+
+/// How many of the given chats each sender appears in, as {sender: count}
+#[pyfunction]
+#[pyo3(name = "detect_recurring_senders")]
+fn py_detect_recurring_senders(py: Python, chats: Vec<MessageList>) -> HashMap<String, usize> {
+    let chats: Vec<Vec<Message>> = chats.into_iter().map(|chat| chat.0).collect();
+    py.allow_threads(|| ChatParser::detect_recurring_senders(&chats))
+}
+
+/// Messages `sender` pasted into two or more of the given chats, as
+/// BroadcastEvent objects: copies whose word counts have at least
+/// `min_similarity` cosine similarity to the earliest copy
+#[pyfunction]
+#[pyo3(name = "detect_broadcast_messages", signature = (chats, sender, min_similarity = 0.9))]
+fn py_detect_broadcast_messages(
+    py: Python,
+    chats: Vec<MessageList>,
+    sender: &str,
+    min_similarity: f64,
+) -> Vec<BroadcastEvent> {
+    let chats: Vec<Vec<Message>> = chats.into_iter().map(|chat| chat.0).collect();
+    py.allow_threads(|| ChatParser::detect_broadcast_messages(&chats, sender, min_similarity))
+}
+
+/// Group subject timeline as a list of (timestamp, subject, changed_by) tuples
+#[pyfunction]
+#[pyo3(name = "subject_history")]
+fn py_subject_history(events: MessageList) -> PyResult<Vec<(String, String, String)>> {
+    ensure_real_timestamps(&events)?;
+    Ok(subject_history(&events)
+        .into_iter()
+        .map(|(timestamp, subject, actor)| (timestamp.to_rfc3339(), subject, actor))
+        .collect())
+}
+
+/// Group subject active at the RFC 3339 datetime `when`
+#[pyfunction]
+#[pyo3(name = "subject_at")]
+fn py_subject_at(events: MessageList, when: &str) -> PyResult<Option<String>> {
+    ensure_real_timestamps(&events)?;
+    Ok(subject_at(&events, parse_datetime_arg(when)?))
+}
+
+/// Parse an RFC 3339 datetime argument coming from Python
+fn parse_datetime_arg(value: &str) -> PyResult<DateTime<Local>> {
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(dt) => Ok(dt.with_timezone(&Local)),
+        Err(e) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            format!("Failed to parse datetime: {}", e)
+        )),
+    }
+}
+
+/// Whether any message carries a synthetic timestamp from `parse_loose`
+#[pyfunction]
+#[pyo3(name = "has_synthetic_timestamps")]
+fn py_has_synthetic_timestamps(messages: MessageList) -> bool {
+    has_synthetic_timestamps(&messages)
+}
+
+#[pymethods]
+impl Anomaly {
+    #[getter]
+    fn date(&self) -> &str {
+        &self.date
+    }
+
+    #[getter]
+    fn metric(&self) -> &str {
+        &self.metric
+    }
+
+    #[getter]
+    fn value(&self) -> f64 {
+        self.value
+    }
+
+    #[getter]
+    fn baseline_mean(&self) -> f64 {
+        self.baseline_mean
+    }
+
+    #[getter]
+    fn baseline_std(&self) -> f64 {
+        self.baseline_std
+    }
+
+    /// Signed z-score; its absolute value is the anomaly's magnitude
+    #[getter]
+    fn z_score(&self) -> f64 {
+        self.z_score
+    }
+
+    #[getter]
+    fn magnitude(&self) -> f64 {
+        self.z_score.abs()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Anomaly(date={:?}, metric={:?}, z_score={:.2})", self.date, self.metric, self.z_score)
+    }
+}
+
+/// "Something happened" days: message volume, average sentiment or new
+/// participants deviating beyond `sensitivity` z-scores from the trailing
+/// 30-day baseline, sorted by magnitude
+#[pyfunction]
+#[pyo3(name = "detect_anomalies", signature = (messages, sensitivity = 2.5))]
+fn py_detect_anomalies(py: Python, messages: MessageList, sensitivity: f64) -> PyResult<Vec<Anomaly>> {
+    ensure_real_timestamps(&messages)?;
+    Ok(py.allow_threads(|| detect_anomalies(&messages, sensitivity)))
+}
+
+/// Per-sender style metrics and the chat-wide baseline, as
+/// ({sender: {metric: value}}, {metric: value})
+#[pyfunction]
+#[pyo3(name = "style_metrics", signature = (messages, min_messages = 20))]
+fn py_style_metrics(
+    py: Python,
+    messages: MessageList,
+    min_messages: usize,
+) -> (HashMap<String, HashMap<String, f64>>, HashMap<String, f64>) {
+    py.allow_threads(|| style_metrics(&messages, min_messages))
+}
+
+/// Write an iCalendar file with an event per group event and optionally
+/// all-day milestone events: the top `top_busiest_days` days, a yearly
+/// chat anniversary, occasions discussed by several participants and
+/// activity streaks of 7, 30, 100, 365 and 1000 days. Day events describe
+/// the day (message count, participants, first message). A "longest
+/// silence" event can be added too. UIDs are stable, so importing a fresh
+/// export updates the events instead of duplicating them. Pass
+/// `parser.parse_events(path) + parser.parse_chat(path)` so the group
+/// events are included.
+#[pyfunction]
+#[pyo3(
+    name = "export_events_ics",
+    signature = (
+        messages,
+        path,
+        include_busiest_day = false,
+        include_longest_silence = false,
+        top_busiest_days = 0,
+        include_anniversary = false,
+        include_occasions = false,
+        include_streak_milestones = false
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+fn py_export_events_ics(
+    py: Python,
+    messages: MessageList,
+    path: &str,
+    include_busiest_day: bool,
+    include_longest_silence: bool,
+    top_busiest_days: usize,
+    include_anniversary: bool,
+    include_occasions: bool,
+    include_streak_milestones: bool,
+) -> PyResult<()> {
+    ensure_real_timestamps(&messages)?;
+    let options = IcsOptions {
+        include_busiest_day,
+        include_longest_silence,
+        top_busiest_days,
+        include_anniversary,
+        include_occasions,
+        include_streak_milestones,
+    };
+    py.allow_threads(|| export_events_ics(&messages, path, &options))?;
+    Ok(())
+}
+
+/// Write the participant interaction graph to `path` as Graphviz DOT
+/// ("dot") or Gephi GEXF ("gexf"). Nodes carry message counts and average
+/// sentiment (run `compute_sentiment_scores` first), edges reply/mention
+/// counts and the median response time.
+#[pyfunction]
+#[pyo3(name = "export_graph")]
+fn py_export_graph(py: Python, messages: MessageList, path: &str, format: &str) -> PyResult<()> {
+    ensure_real_timestamps(&messages)?;
+    let format = GraphFormat::parse(format)?;
+    py.allow_threads(|| export_graph(&messages, path, format))?;
+    Ok(())
+}
+
+/// Write messages to `path` as JSON Lines, one `parse_whatsapp_chat`-style
+/// object per line. With `manifest`, a final line records the message count
+/// and hashes (one per 1000 messages, and one overall) so `verify_export`
+/// can detect truncation or corruption.
+#[pyfunction]
+#[pyo3(name = "export_jsonl", signature = (messages, path, manifest = false))]
+fn py_export_jsonl(py: Python, messages: MessageList, path: &str, manifest: bool) -> PyResult<()> {
+    py.allow_threads(|| export_jsonl(&messages, path, manifest))?;
+    Ok(())
+}
+
+/// Write messages to `path` as JSON Lines with timestamp, sender and type but
+/// only the length of each message's content, for sharing activity patterns.
+/// `manifest` works as for `export_jsonl`.
+#[pyfunction]
+#[pyo3(name = "export_redacted_jsonl", signature = (messages, path, manifest = false))]
+fn py_export_redacted_jsonl(py: Python, messages: MessageList, path: &str, manifest: bool) -> PyResult<()> {
+    py.allow_threads(|| export_redacted_jsonl(&messages, path, manifest))?;
+    Ok(())
+}
+
+/// Check a JSON Lines export. Returns {"ok": True, "message_count": n}, or
+/// {"ok": False, "line": n or None, "detail": "..."} when the file is
+/// truncated, has a damaged line, or doesn't match its manifest (required
+/// unless `require_manifest` is false). Damage a chunk checksum catches is
+/// reported at the chunk's first line, with the chunk named in "detail".
+/// An export written by a different parser version raises a UserWarning.
+/// Raises IOError only when the file can't be read.
+#[pyfunction]
+#[pyo3(name = "verify_export", signature = (path, require_manifest = true))]
+fn py_verify_export(py: Python, path: &str, require_manifest: bool) -> PyResult<PyObject> {
+    let result = py.allow_threads(|| verify_export(path, require_manifest));
+    let report = PyDict::new(py);
+    match result {
+        Ok(verified) => {
+            if let Some(version) = verified.version_mismatch() {
+                let text = format!("{} was written by parser {}; this is {}", path, version, VERSION);
+                PyErr::warn(py, py.get_type::<pyo3::exceptions::PyUserWarning>(), &text, 1)?;
+            }
+            report.set_item("ok", true)?;
+            report.set_item("message_count", verified.message_count)?;
+        }
+        Err(ParserError::CorruptExport { line, detail }) => {
+            report.set_item("ok", false)?;
+            report.set_item("line", line)?;
+            report.set_item("detail", detail)?;
+        }
+        Err(error) => return Err(error.into()),
+    }
+    Ok(report.to_object(py))
+}
+
+/// Check an upload without parsing it in full: only a bounded head (and,
+/// for large text files, a middle and a tail sample) is read, and for a ZIP
+/// only its directory and the head of the chat entry. Returns {"acceptable",
+/// "container", "format", "encoding", "estimated_message_count",
+/// "text_size", "warnings"}, with up to three warnings as {"line_number",
+/// "line", "reason"} dicts.
+#[pyfunction]
+#[pyo3(
+    name = "validate_chat_file",
+    signature = (path, sample_lines = 2000, sample_bytes = 262144, sample_middle_and_tail = true)
+)]
+fn py_validate_chat_file(
+    py: Python,
+    path: &str,
+    sample_lines: usize,
+    sample_bytes: usize,
+    sample_middle_and_tail: bool,
+) -> PyResult<PyObject> {
+    let options = ValidationOptions { sample_lines, sample_bytes, sample_middle_and_tail };
+    let validation = py.allow_threads(|| validate_chat_file(path, &options))?;
+    let report = PyDict::new(py);
+    report.set_item("acceptable", validation.acceptable)?;
+    report.set_item("container", validation.container)?;
+    report.set_item("format", validation.format)?;
+    report.set_item("encoding", validation.encoding)?;
+    report.set_item("estimated_message_count", validation.estimated_message_count)?;
+    report.set_item("text_size", validation.text_size)?;
+    let warnings = validation
+        .warnings
+        .iter()
+        .map(|warning| {
+            let dict = PyDict::new(py);
+            dict.set_item("line_number", warning.line_number)?;
+            dict.set_item("line", &warning.line)?;
+            dict.set_item("reason", &warning.reason)?;
+            Ok(dict.to_object(py))
+        })
+        .collect::<PyResult<Vec<PyObject>>>()?;
+    report.set_item("warnings", warnings)?;
+    Ok(report.to_object(py))
+}
+
+/// Render messages to a printable PDF at `output_path`: a title page with
+/// chat statistics, then messages by day with sender-coloured names and
+/// timestamps in the margin. `page_size` is "a4" or "letter". Text is
+/// embedded in `font_path` (a TrueType font) or a Unicode system font.
+#[cfg(feature = "pdf")]
+#[pyfunction]
+#[pyo3(
+    name = "render_pdf",
+    signature = (messages, output_path, page_size = "a4", font_size = 10.0, include_media_placeholders = true, font_path = None, bold_font_path = None)
+)]
+#[allow(clippy::too_many_arguments)]
+fn py_render_pdf(
+    py: Python,
+    messages: MessageList,
+    output_path: &str,
+    page_size: &str,
+    font_size: f32,
+    include_media_placeholders: bool,
+    font_path: Option<String>,
+    bold_font_path: Option<String>,
+) -> PyResult<()> {
+    let options = PdfOptions {
+        page_size: PageSize::parse(page_size)?,
+        font_size,
+        include_media_placeholders,
+        font_path,
+        bold_font_path,
+    };
+    py.allow_threads(|| render_pdf(&messages, output_path, &options))?;
+    Ok(())
+}
+
+/// Write messages to a Snappy-compressed Parquet file at `output_path`, one
+/// row per message, for pandas, DuckDB or Spark. Timestamps are stored as
+/// microseconds in UTC; system event details are JSON in the "event" column.
+#[cfg(feature = "parquet")]
+#[pyfunction]
+#[pyo3(name = "export_to_parquet")]
+fn py_export_to_parquet(py: Python, messages: MessageList, output_path: &str) -> PyResult<()> {
+    py.allow_threads(|| export_to_parquet(&messages, output_path))?;
+    Ok(())
+}
+
+#[pymethods]
+impl MediaBurst {
+    #[getter]
+    fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// First member's timestamp (RFC 3339)
+    #[getter]
+    fn start(&self) -> String {
+        self.start.to_rfc3339()
+    }
+
+    /// Last member's timestamp (RFC 3339)
+    #[getter]
+    fn end(&self) -> String {
+        self.end.to_rfc3339()
+    }
+
+    #[getter]
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Indices of the member messages
+    #[getter]
+    fn indices(&self) -> Vec<usize> {
+        self.indices.clone()
+    }
+
+    /// The members' captions joined by newlines, or None
+    #[getter]
+    fn caption(&self) -> Option<&str> {
+        self.caption.as_deref()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MediaBurst(sender={:?}, count={}, start={:?})", self.sender, self.count, self.start.to_rfc3339())
+    }
+}
+
+#[pymethods]
+impl ReactionEvent {
+    #[getter]
+    fn reactor(&self) -> &str {
+        &self.reactor
+    }
+
+    #[getter]
+    fn emoji(&self) -> &str {
+        &self.emoji
+    }
+
+    /// Whose message was reacted to ("You" for the exporting user)
+    #[getter]
+    fn reacted_to_sender(&self) -> &str {
+        &self.reacted_to_sender
+    }
+
+    /// When the reaction was sent (RFC 3339)
+    #[getter]
+    fn timestamp(&self) -> String {
+        self.timestamp.to_rfc3339()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ReactionEvent(reactor={:?}, emoji={:?}, reacted_to_sender={:?})", self.reactor, self.emoji, self.reacted_to_sender)
+    }
+}
+
+#[pymethods]
+impl SimilarCluster {
+    #[getter]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Index of the earliest member
+    #[getter]
+    fn canonical(&self) -> usize {
+        self.canonical
+    }
+
+    #[getter]
+    fn first_sender(&self) -> &str {
+        &self.first_sender
+    }
+
+    /// The earliest member's timestamp (RFC 3339)
+    #[getter]
+    fn first_timestamp(&self) -> String {
+        self.first_timestamp.to_rfc3339()
+    }
+
+    /// Indices of the members, earliest first
+    #[getter]
+    fn indices(&self) -> Vec<usize> {
+        self.indices.clone()
+    }
+
+    /// (timestamp, sender) of each member, earliest first
+    #[getter]
+    fn timeline(&self) -> Vec<(String, String)> {
+        self.timeline.iter().map(|(timestamp, sender)| (timestamp.to_rfc3339(), sender.clone())).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SimilarCluster(size={}, first_sender={:?}, first_timestamp={:?})",
+            self.size,
+            self.first_sender,
+            self.first_timestamp.to_rfc3339()
+        )
+    }
+}
+
+#[pymethods]
+impl BroadcastEvent {
+    /// The earliest copy's content
+    #[getter]
+    fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// When each copy was sent (RFC 3339), earliest first
+    #[getter]
+    fn timestamps(&self) -> Vec<String> {
+        self.timestamps.iter().map(|timestamp| timestamp.to_rfc3339()).collect()
+    }
+
+    /// The chat each copy was sent in, matching `timestamps`
+    #[getter]
+    fn chat_indices(&self) -> Vec<usize> {
+        self.chat_indices.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BroadcastEvent(content={:?}, chat_indices={:?})", self.content, self.chat_indices)
+    }
+}
+
+#[pymethods]
+impl Chronotype {
+    #[getter]
+    fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    #[getter]
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Circular mean time of day in hours (0–24), None without a clear mean
+    #[getter]
+    fn mean_hour(&self) -> Option<f64> {
+        self.mean_hour
+    }
+
+    /// 1 when every message is sent at the same time of day, near 0 when
+    /// they are spread around the clock
+    #[getter]
+    fn concentration(&self) -> f64 {
+        self.concentration
+    }
+
+    #[getter]
+    fn message_count(&self) -> u32 {
+        self.message_count
+    }
+
+    /// Messages sent in each hour, 0–23
+    #[getter]
+    fn hourly(&self) -> Vec<u32> {
+        self.hourly.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Chronotype(sender={:?}, label={:?}, mean_hour={:?})", self.sender, self.label, self.mean_hour)
+    }
+}
+
+#[pymethods]
+impl CallStats {
+    #[getter]
+    fn total_calls(&self) -> usize {
+        self.total_calls
+    }
+
+    #[getter]
+    fn video_calls(&self) -> usize {
+        self.video_calls
+    }
+
+    #[getter]
+    fn missed_calls(&self) -> usize {
+        self.missed_calls
+    }
+
+    /// Fraction of each caller's calls that went unanswered
+    #[getter]
+    fn missed_rate_by_caller(&self) -> HashMap<String, f64> {
+        self.missed_rate_by_caller.clone()
+    }
+
+    #[getter]
+    fn total_duration_seconds(&self) -> u64 {
+        self.total_duration_seconds
+    }
+
+    #[getter]
+    fn longest_call_seconds(&self) -> Option<u32> {
+        self.longest_call_seconds
+    }
+
+    #[getter]
+    fn longest_call_caller(&self) -> Option<String> {
+        self.longest_call_caller.clone()
+    }
+
+    /// Calls started in each local hour, 0–23
+    #[getter]
+    fn calls_by_hour(&self) -> Vec<usize> {
+        self.calls_by_hour.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CallStats(total_calls={}, missed_calls={}, total_duration_seconds={})",
+            self.total_calls, self.missed_calls, self.total_duration_seconds
+        )
+    }
+}
+
+/// Call totals, missed rate per caller, durations and calls by hour
+#[pyfunction]
+#[pyo3(name = "call_stats")]
+fn py_call_stats(py: Python, messages: MessageList) -> CallStats {
+    py.allow_threads(|| call_stats(&messages))
+}
+
+/// ISO 639-3 code ("eng", "spa", …) of the text's language, or `None` when
+/// it is too short or undetectable
+#[pyfunction]
+#[pyo3(name = "detect_language", signature = (content, min_length = LANGUAGE_MIN_LENGTH))]
+fn py_detect_language(content: &str, min_length: usize) -> Option<&'static str> {
+    detect_language(content, min_length).map(|lang| lang.code())
+}
+
+/// Text message counts per detected language code
+#[pyfunction]
+#[pyo3(name = "language_breakdown", signature = (messages, min_length = LANGUAGE_MIN_LENGTH))]
+fn py_language_breakdown(py: Python, messages: MessageList, min_length: usize) -> HashMap<String, usize> {
+    py.allow_threads(|| language_breakdown(&messages, min_length))
+}
+
+#[cfg(feature = "store")]
+#[pymethods]
+impl ChatStore {
+    /// Open or create the SQLite store at `path`
+    #[new]
+    fn py_new(py: Python, path: &str) -> PyResult<Self> {
+        Ok(py.allow_threads(|| ChatStore::open(path))?)
+    }
+
+    #[getter(schema_version)]
+    fn py_schema_version(&self) -> PyResult<usize> {
+        Ok(self.schema_version()?)
+    }
+
+    /// Parse an export and store it as `chat_name`; returns how many
+    /// messages were new. `parser` carries the parse settings (lenient mode,
+    /// year pivot, type rules, interpolation; a default ChatParser when
+    /// None). When `user_identity` is given, messages from the matching
+    /// sender (see `split_by_ownership`) are stored with `is_own` set. With
+    /// a lenient parser, skipped lines are reported as UserWarnings. Runs
+    /// without holding the GIL, so other threads can ingest meanwhile.
+    #[pyo3(name = "ingest", signature = (file_path, chat_name, parser = None, user_identity = None, match_mode = "exact"))]
+    fn py_ingest(
+        &self,
+        py: Python,
+        file_path: &str,
+        chat_name: &str,
+        parser: Option<PyRef<ChatParser>>,
+        user_identity: Option<&str>,
+        match_mode: &str,
+    ) -> PyResult<usize> {
+        let mode = IdentityMatch::parse(match_mode)?;
+        let default_parser;
+        let parser = match &parser {
+            Some(parser) => parser.deref(),
+            None => {
+                default_parser = ChatParser::new();
+                &default_parser
+            }
+        };
+        let (inserted, warnings) = py.allow_threads(|| {
+            let file = File::open(Path::new(file_path)).map_err(ParserError::io("Failed to open file"))?;
+            let (mut messages, warnings) = parser.parse_reader_with_warnings(BufReader::new(file), true)?;
+            if let Some(identity) = user_identity {
+                mark_own_messages(&mut messages, identity, mode);
+            }
+            Ok::<_, ParserError>((self.ingest_messages(&messages, chat_name)?, warnings))
+        })?;
+        if parser.is_lenient() {
+            for warning in warnings {
+                let text = format!("line {}: {}: {:?}", warning.line_number, warning.reason, warning.line);
+                PyErr::warn(py, py.get_type::<pyo3::exceptions::PyUserWarning>(), &text, 1)?;
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Stored messages of a chat, filtered by RFC 3339 `start` (inclusive)
+    /// and `end` (exclusive), `sender` and a `text` substring
+    #[pyo3(name = "query", signature = (chat_name, start = None, end = None, sender = None, text = None))]
+    fn py_query(
+        &self,
+        py: Python,
+        chat_name: &str,
+        start: Option<&str>,
+        end: Option<&str>,
+        sender: Option<&str>,
+        text: Option<&str>,
+    ) -> PyResult<Vec<Py<Message>>> {
+        let start = start.map(parse_datetime_arg).transpose()?;
+        let end = end.map(parse_datetime_arg).transpose()?;
+        let messages = py.allow_threads(|| self.query(chat_name, start, end, sender, text))?;
+        messages_into_py(py, messages)
+    }
+
+    /// Totals for a chat as a dict: message_count, first_message and
+    /// last_message (RFC 3339), active_days, senders as (name, count)
+    /// pairs and message_types
+    #[pyo3(name = "stats")]
+    fn py_stats(&self, py: Python, chat_name: &str) -> PyResult<PyObject> {
+        let stats = py.allow_threads(|| self.stats(chat_name))?;
+        let report = PyDict::new(py);
+        report.set_item("message_count", stats.message_count)?;
+        report.set_item("first_message", stats.first_message.map(|timestamp| timestamp.to_rfc3339()))?;
+        report.set_item("last_message", stats.last_message.map(|timestamp| timestamp.to_rfc3339()))?;
+        report.set_item("active_days", stats.active_days)?;
+        report.set_item("senders", stats.senders)?;
+        report.set_item("message_types", stats.message_types)?;
+        Ok(report.into())
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("ChatStore(schema_version={})", self.schema_version()?))
+    }
+}
+
+/// Where this build came from: {"version", "git_hash", "target",
+/// "pyo3_version"}, for logging next to parser output
+#[pyfunction]
+#[pyo3(name = "build_info")]
+fn py_build_info(py: Python) -> PyResult<PyObject> {
+    let info = build_info();
+    let report = PyDict::new(py);
+    report.set_item("version", info.version)?;
+    report.set_item("git_hash", info.git_hash)?;
+    report.set_item("target", info.target)?;
+    report.set_item("pyo3_version", info.pyo3_version)?;
+    Ok(report.into())
+}
+
+/// The export formats the parser reads, as {"id", "platform", "pattern",
+/// "example", "locale_notes"} dicts taken from the patterns it compiles
+#[pyfunction]
+#[pyo3(name = "supported_formats")]
+fn py_supported_formats(py: Python) -> PyResult<Vec<PyObject>> {
+    EXPORT_FORMATS
+        .iter()
+        .map(|format| {
+            let descriptor = PyDict::new(py);
+            descriptor.set_item("id", format.id)?;
+            descriptor.set_item("platform", format.platform)?;
+            descriptor.set_item("pattern", format.pattern)?;
+            descriptor.set_item("example", format.example)?;
+            descriptor.set_item("locale_notes", format.locale_notes)?;
+            Ok(descriptor.into())
+        })
+        .collect()
+}
+
+/// Register Python module
+#[pymodule]
+fn whatsapp_parser(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("__version__", VERSION)?;
+    m.add_class::<Message>()?;
+    m.add_class::<ChatParser>()?;
+    m.add_class::<Anomaly>()?;
+    m.add_class::<DetectedEvent>()?;
+    m.add_class::<CallStats>()?;
+    m.add_class::<FuzzyMatch>()?;
+    m.add_class::<Chronotype>()?;
+    m.add_class::<MediaBurst>()?;
+    m.add_class::<SimilarCluster>()?;
+    m.add_class::<BroadcastEvent>()?;
+    m.add_class::<ReactionEvent>()?;
+    m.add_class::<SentenceSentiment>()?;
+    m.add_class::<SentenceBreakdown>()?;
+    #[cfg(feature = "store")]
+    m.add_class::<ChatStore>()?;
+    m.add_function(wrap_pyfunction!(parse_whatsapp_chat, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_whatsapp_zip_with_media, m)?)?;
+    m.add_function(wrap_pyfunction!(py_sentiment_by_sender, m)?)?;
+    m.add_function(wrap_pyfunction!(py_score_sentiment, m)?)?;
+    m.add_function(wrap_pyfunction!(py_split_sentences, m)?)?;
+    m.add_function(wrap_pyfunction!(py_subject_history, m)?)?;
+    m.add_function(wrap_pyfunction!(py_subject_at, m)?)?;
+    m.add_function(wrap_pyfunction!(py_has_synthetic_timestamps, m)?)?;
+    m.add_function(wrap_pyfunction!(py_merge_chats, m)?)?;
+    m.add_function(wrap_pyfunction!(py_detect_recurring_senders, m)?)?;
+    m.add_function(wrap_pyfunction!(py_detect_broadcast_messages, m)?)?;
+    m.add_function(wrap_pyfunction!(py_split_by_ownership, m)?)?;
+    m.add_function(wrap_pyfunction!(py_person_profile, m)?)?;
+    m.add_function(wrap_pyfunction!(py_detect_anomalies, m)?)?;
+    m.add_function(wrap_pyfunction!(py_bidi_isolate, m)?)?;
+    m.add_function(wrap_pyfunction!(py_style_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_graph, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_events_ics, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_jsonl, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_redacted_jsonl, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_export, m)?)?;
+    m.add_function(wrap_pyfunction!(py_validate_chat_file, m)?)?;
+    #[cfg(feature = "pdf")]
+    m.add_function(wrap_pyfunction!(py_render_pdf, m)?)?;
+    #[cfg(feature = "parquet")]
+    m.add_function(wrap_pyfunction!(py_export_to_parquet, m)?)?;
+    m.add_function(wrap_pyfunction!(py_call_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(py_detect_language, m)?)?;
+    m.add_function(wrap_pyfunction!(py_language_breakdown, m)?)?;
+    m.add_function(wrap_pyfunction!(py_fuzzy_search, m)?)?;
+    m.add_function(wrap_pyfunction!(py_search_messages, m)?)?;
+    m.add_function(wrap_pyfunction!(py_build_info, m)?)?;
+    m.add_function(wrap_pyfunction!(py_supported_formats, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Local};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::chat_parser::ChatParser;
+use crate::message::{Message, MessageType};
+use crate::text::{is_bidi_control, is_emoji};
+
+/// Recognise an emoji-reaction notice from newer exports: "Alice reacted ❤️
+/// to Your message" or "You reacted 😂 to Bob's message". Names are kept as
+/// written; "Your message" is attributed to "You".
+pub fn parse_reaction(text: &str) -> Option<MessageType> {
+    let cleaned: String = text.chars().filter(|&c| !is_bidi_control(c)).collect();
+    let cleaned = cleaned.trim().trim_end_matches('.');
+    let (reactor, rest) = cleaned.split_once(" reacted ")?;
+    let (emoji, target) = rest.split_once(" to ")?;
+    if reactor.is_empty() || emoji.contains(char::is_whitespace) || !emoji.chars().any(is_emoji) {
+        return None;
+    }
+    let owner = target.strip_suffix(" message")?;
+    let to_sender = match owner {
+        "Your" | "your" => "You",
+        _ => owner.strip_suffix("'s").or_else(|| owner.strip_suffix("\u{2019}s")).filter(|name| !name.is_empty())?,
+    };
+    Some(MessageType::Reaction { reactor: reactor.to_string(), emoji: emoji.to_string(), to_sender: to_sender.to_string() })
+}
+
+/// One emoji reaction (see `ChatParser::parse_reactions`)
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReactionEvent {
+    pub reactor: String,
+    pub emoji: String,
+    pub reacted_to_sender: String,
+    pub timestamp: DateTime<Local>,
+}
+
+impl ChatParser {
+    /// The emoji reactions in a chat, in message order. Reaction notices are
+    /// system lines, so parse with system messages kept.
+    pub fn parse_reactions(messages: &[Message]) -> Vec<ReactionEvent> {
+        messages
+            .iter()
+            .filter_map(|message| match &message.message_type {
+                MessageType::Reaction { reactor, emoji, to_sender } => Some(ReactionEvent {
+                    reactor: reactor.clone(),
+                    emoji: emoji.clone(),
+                    reacted_to_sender: to_sender.clone(),
+                    timestamp: message.timestamp,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../../tests/fixtures/reactions_chat.txt");
+
+    #[test]
+    fn recognises_reaction_phrases() {
+        let reaction = |reactor: &str, emoji: &str, to_sender: &str| {
+            Some(MessageType::Reaction { reactor: reactor.into(), emoji: emoji.into(), to_sender: to_sender.into() })
+        };
+        assert_eq!(parse_reaction("Alice reacted ❤️ to Your message"), reaction("Alice", "❤️", "You"));
+        assert_eq!(parse_reaction("\u{200e}You reacted 👍🏽 to Bob’s message."), reaction("You", "👍🏽", "Bob"));
+        assert_eq!(parse_reaction("Alice reacted badly to the news"), None);
+    }
+
+    #[test]
+    fn collects_reactions_from_a_parse() {
+        let messages = ChatParser::new().parse_str(FIXTURE, true).unwrap();
+        let reactions = ChatParser::parse_reactions(&messages);
+        let summary: Vec<(&str, &str, &str)> = reactions
+            .iter()
+            .map(|reaction| (reaction.reactor.as_str(), reaction.emoji.as_str(), reaction.reacted_to_sender.as_str()))
+            .collect();
+        assert_eq!(summary, [("Alice", "❤️", "You"), ("You", "😂", "Alice"), ("Bob", "👍", "Alice")]);
+        let reacted = messages.iter().find(|message| matches!(message.message_type, MessageType::Reaction { .. })).unwrap();
+        assert_eq!((reacted.sender.as_str(), reacted.message_type.as_str()), ("Alice", "reaction"));
+
+        // Like other notices, reactions are dropped unless system messages are kept
+        let without = ChatParser::new().parse_str(FIXTURE, false).unwrap();
+        assert!(ChatParser::parse_reactions(&without).is_empty());
+        assert_eq!(without.len(), 4);
+    }
+}
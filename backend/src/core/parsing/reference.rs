@@ -0,0 +1,76 @@
+//! Bundled English reference vocabulary, stored as a Bloom filter.
+//!
+//! `data/english_reference.bloom` holds about 49,000 words from English
+//! Wikipedia and US TV/film subtitle frequency lists, at a 1% false-positive
+//! rate. Rebuild it with `scripts/build_english_reference.py`, whose layout
+//! and hashing must match `BloomFilter`.
+
+use crate::text::CHAT_ABBREVIATIONS;
+
+const ENGLISH_REFERENCE: &[u8] = include_bytes!("data/english_reference.bloom");
+
+/// A read-only Bloom filter over lowercased words: `b"BLM1"`, the hash
+/// count (u8), the bit count (u32 LE), then the bits. Probes use 64-bit
+/// FNV-1a with double hashing.
+pub struct BloomFilter<'a> {
+    hashes: u32,
+    bits: u64,
+    table: &'a [u8],
+}
+
+impl<'a> BloomFilter<'a> {
+    /// Read a filter, or `None` if `bytes` isn't one
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        let header = bytes.get(..9)?;
+        if &header[..4] != b"BLM1" {
+            return None;
+        }
+        let hashes = u32::from(header[4]);
+        let bits = u64::from(u32::from_le_bytes(header[5..9].try_into().ok()?));
+        let table = &bytes[9..];
+        (hashes > 0 && bits > 0 && table.len() as u64 * 8 >= bits).then_some(BloomFilter { hashes, bits, table })
+    }
+
+    /// Whether `word` (already lowercased) may be in the set; never wrong
+    /// about words that were added
+    pub fn contains(&self, word: &str) -> bool {
+        let h1 = fnv1a(word.as_bytes());
+        let h2 = splitmix64(h1) | 1;
+        (0..u64::from(self.hashes)).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.bits;
+            self.table[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+        })
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3))
+}
+
+pub(crate) fn splitmix64(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Whether a lowercased word is ordinary English: in the bundled reference
+/// vocabulary or a common chat abbreviation
+pub fn is_english_word(word: &str) -> bool {
+    let reference = BloomFilter::from_bytes(ENGLISH_REFERENCE).expect("bundled reference filter is valid");
+    CHAT_ABBREVIATIONS.contains(&word) || reference.contains(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_filter_knows_common_words() {
+        for word in ["the", "phone", "don't", "yeah", "lol"] {
+            assert!(is_english_word(word), "{}", word);
+        }
+        assert!(!is_english_word("snorfblat"));
+        assert!(BloomFilter::from_bytes(b"BLM0\x01\x08\x00\x00\x00\xff").is_none());
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::chat_parser::ChatParser;
+use crate::message::{Message, MessageType};
+use crate::text::is_word_char;
+
+/// Queries shorter than this (in characters) allow at most one edit, since
+/// short words are within two edits of far too many others
+pub const FUZZY_SHORT_QUERY: usize = 4;
+
+/// A message matching a fuzzy query, with the closest token in it
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzyMatch {
+    /// Index of the message
+    pub index: usize,
+    /// The matched word, or the whole message for multi-word queries
+    pub token: String,
+    /// Span of `token` in the message content, in characters
+    pub start: usize,
+    pub end: usize,
+    /// Edit distance between `token` and the query (case-insensitive)
+    pub distance: usize,
+}
+
+/// Result of `ChatParser::search_messages`
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    /// Indices of messages containing the query (case-insensitive)
+    pub exact: Vec<usize>,
+    /// "Did you mean" matches, filled only when `exact` is empty
+    pub fuzzy: Vec<FuzzyMatch>,
+}
+
+/// Edit distance between `a` and `b` if it is at most `max`. Only the
+/// diagonal band of width `2 * max + 1` is computed, so each comparison
+/// costs O(max · len) rather than O(len²).
+pub fn banded_levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let beyond = max + 1;
+    let mut previous: Vec<usize> = (0..=b.len()).map(|j| j.min(beyond)).collect();
+    let mut current = vec![beyond; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        let row = i + 1;
+        let low = row.saturating_sub(max).max(1);
+        let high = (row + max).min(b.len());
+        current.fill(beyond);
+        current[0] = row.min(beyond);
+        let mut row_min = current[0];
+        for j in low..=high {
+            let substitution = previous[j - 1] + usize::from(*ca != b[j - 1]);
+            let cell = substitution.min(previous[j] + 1).min(current[j - 1] + 1).min(beyond);
+            current[j] = cell;
+            row_min = row_min.min(cell);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|distance| *distance <= max)
+}
+
+impl ChatParser {
+    /// Messages containing a word within `max_distance` edits of `query`
+    /// (case-insensitive, counted in Unicode scalar values), ranked by
+    /// distance and then newest first. Queries under `FUZZY_SHORT_QUERY`
+    /// characters allow at most one edit. A query of several words is
+    /// compared with whole messages of similar length instead. System events
+    /// are not searched.
+    pub fn fuzzy_search(messages: &[Message], query: &str, max_distance: usize) -> Vec<FuzzyMatch> {
+        let query: Vec<char> = query.trim().to_lowercase().chars().collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let max_distance = if query.len() < FUZZY_SHORT_QUERY { max_distance.min(1) } else { max_distance };
+        let whole_message = query.iter().any(|c| c.is_whitespace());
+
+        let mut matches: Vec<FuzzyMatch> = Vec::new();
+        for (index, message) in messages.iter().enumerate() {
+            if matches!(message.message_type, MessageType::SystemEvent(_)) {
+                continue;
+            }
+            let candidates: Vec<(usize, usize, &str)> = if whole_message {
+                let content = message.content.trim();
+                let offset = message.content[..message.content.len() - message.content.trim_start().len()].chars().count();
+                vec![(offset, offset + content.chars().count(), content)]
+            } else {
+                word_spans(&message.content)
+            };
+
+            let best = candidates
+                .into_iter()
+                .filter_map(|(start, end, token)| {
+                    let lowered: Vec<char> = token.to_lowercase().chars().collect();
+                    banded_levenshtein(&lowered, &query, max_distance).map(|distance| (distance, start, end, token))
+                })
+                .min_by_key(|(distance, start, ..)| (*distance, *start));
+            if let Some((distance, start, end, token)) = best {
+                matches.push(FuzzyMatch { index, token: token.to_string(), start, end, distance });
+            }
+        }
+
+        matches.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| messages[b.index].timestamp.cmp(&messages[a.index].timestamp))
+                .then_with(|| b.index.cmp(&a.index))
+        });
+        matches
+    }
+
+    /// Case-insensitive substring search, falling back to `fuzzy_search`
+    /// suggestions when nothing matches exactly
+    pub fn search_messages(messages: &[Message], query: &str, max_distance: usize) -> SearchResults {
+        let exact = Self::find_messages_by_content(messages, query, false);
+        let fuzzy = if exact.is_empty() { Self::fuzzy_search(messages, query, max_distance) } else { Vec::new() };
+        SearchResults { exact, fuzzy }
+    }
+}
+
+/// Words of `text` (see `text::tokenize_words`) with their character spans
+fn word_spans(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut spans = Vec::new();
+    let mut word_start: Option<(usize, usize)> = None;
+    let mut chars = 0;
+    for (byte, c) in text.char_indices() {
+        match (is_word_char(c), word_start) {
+            (true, None) => word_start = Some((byte, chars)),
+            (false, Some((start_byte, start_char))) => {
+                spans.push((start_char, chars, &text[start_byte..byte]));
+                word_start = None;
+            }
+            _ => {}
+        }
+        chars += 1;
+    }
+    if let Some((start_byte, start_char)) = word_start {
+        spans.push((start_char, chars, &text[start_byte..]));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::levenshtein;
+
+    fn parse(text: &str) -> Vec<Message> {
+        ChatParser::new().parse_str(text, false).unwrap()
+    }
+
+    #[test]
+    fn banded_distance_agrees_with_full_levenshtein() {
+        let words = ["tomorrow", "tommorow", "tmrw", "morrow", "", "çà", "ça", "tomorrows", "borrow"];
+        for a in words {
+            for b in words {
+                let (a_chars, b_chars): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+                for max in 0..4 {
+                    let full = levenshtein(a, b);
+                    assert_eq!(banded_levenshtein(&a_chars, &b_chars, max), (full <= max).then_some(full), "{} {} {}", a, b, max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_misspellings_ranked_by_distance_then_recency() {
+        let messages = parse(
+            "[18/05/2023, 08:00:00] John: See you Tomorrow!\n\
+             [18/05/2023, 09:00:00] Alice: ok tommorow then\n\
+             [18/05/2023, 10:00:00] John: tomorow it is\n\
+             [18/05/2023, 11:00:00] Alice: borrow a car\n",
+        );
+        let matches = ChatParser::fuzzy_search(&messages, "tommorow", 2);
+        let found: Vec<(usize, &str, usize)> =
+            matches.iter().map(|found| (found.index, found.token.as_str(), found.distance)).collect();
+        assert_eq!(found, [(1, "tommorow", 0), (2, "tomorow", 1), (0, "Tomorrow", 2)]);
+        assert_eq!((matches[2].start, matches[2].end), (8, 16));
+
+        let results = ChatParser::search_messages(&messages, "tomorrow", 2);
+        assert_eq!(results.exact, [0]);
+        assert!(results.fuzzy.is_empty());
+        let results = ChatParser::search_messages(&messages, "tomorrrow", 2);
+        assert!(results.exact.is_empty());
+        assert_eq!(results.fuzzy[0].index, 0);
+    }
+
+    #[test]
+    fn short_queries_allow_one_edit() {
+        let messages = parse("[18/05/2023, 08:00:00] John: cat\n[18/05/2023, 08:01:00] John: cut\n[18/05/2023, 08:02:00] John: dog\n");
+        let indices: Vec<usize> = ChatParser::fuzzy_search(&messages, "cat", 3).iter().map(|found| found.index).collect();
+        assert_eq!(indices, [0, 1]);
+    }
+
+    #[test]
+    fn multi_word_queries_match_whole_messages() {
+        let messages = parse("[18/05/2023, 08:00:00] John: good mornin\n[18/05/2023, 08:01:00] John: good morning everyone here\n");
+        let matches = ChatParser::fuzzy_search(&messages, "good morning", 2);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].index, matches[0].distance, matches[0].end), (0, 1, 11));
+    }
+}
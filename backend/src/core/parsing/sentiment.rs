@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::error::ParserError;
+use crate::message::Message;
+use crate::text::{split_sentences, tokenize_words};
+
+/// Word valences for the bundled lexicon scorer, on a -1..1 scale
+const SENTIMENT_LEXICON: &[(&str, f32)] = &[
+    ("love", 0.8), ("loved", 0.8), ("lovely", 0.7), ("happy", 0.7), ("glad", 0.5),
+    ("great", 0.6), ("good", 0.4), ("nice", 0.4), ("awesome", 0.7), ("amazing", 0.7),
+    ("wonderful", 0.8), ("fantastic", 0.8), ("excellent", 0.7), ("best", 0.6), ("fun", 0.5),
+    ("thanks", 0.4), ("thank", 0.4), ("congrats", 0.6), ("congratulations", 0.6), ("yay", 0.6),
+    ("excited", 0.6), ("beautiful", 0.6), ("cool", 0.3), ("enjoy", 0.5), ("enjoyed", 0.5),
+    ("haha", 0.4), ("lol", 0.3), ("miss", -0.2), ("sad", -0.6), ("bad", -0.5),
+    ("awful", -0.7), ("terrible", -0.7), ("horrible", -0.7), ("hate", -0.8), ("hated", -0.8),
+    ("angry", -0.6), ("annoyed", -0.5), ("annoying", -0.5), ("upset", -0.5), ("sorry", -0.3),
+    ("worst", -0.7), ("sick", -0.4), ("tired", -0.3), ("worried", -0.4), ("scared", -0.5),
+    ("cry", -0.5), ("crying", -0.5), ("hurt", -0.5), ("pain", -0.5), ("disappointed", -0.6),
+    ("stupid", -0.6), ("ugh", -0.4), ("boring", -0.4), ("lonely", -0.5), ("stressed", -0.5),
+];
+
+/// Bundled emoji sentiment table, on the same -1..1 scale as the lexicon
+const EMOJI_SENTIMENT: &[(char, f32)] = &[
+    ('😀', 0.6), ('😃', 0.6), ('😄', 0.6), ('😁', 0.5), ('😆', 0.5), ('😂', 0.4),
+    ('🤣', 0.4), ('😊', 0.7), ('🙂', 0.4), ('😍', 0.8), ('🥰', 0.8), ('😘', 0.7),
+    ('😎', 0.5), ('🥳', 0.7), ('👍', 0.5), ('👏', 0.5), ('🙌', 0.5), ('🎉', 0.6),
+    ('❤', 0.8), ('💕', 0.8), ('💖', 0.8), ('😉', 0.4), ('🤗', 0.6), ('✨', 0.4),
+    ('😐', 0.0), ('🤔', 0.0), ('😕', -0.3), ('😟', -0.4), ('😢', -0.6), ('😭', -0.5),
+    ('😞', -0.6), ('😔', -0.5), ('😩', -0.5), ('😫', -0.5), ('😠', -0.7), ('😡', -0.8),
+    ('🤬', -0.9), ('💔', -0.7), ('👎', -0.5), ('😤', -0.5), ('😒', -0.5), ('🙄', -0.4),
+];
+
+/// Words that flip the valence of the following word
+const NEGATIONS: &[&str] = &["not", "no", "never", "don't", "dont", "isn't", "wasn't", "can't", "cant"];
+
+/// Score a message in [-1, 1] with the bundled word lexicon, plus emoji
+/// valences scaled by `emoji_weight` (0 disables the emoji contribution)
+pub fn score_sentiment(content: &str, emoji_weight: f32) -> f32 {
+    let mut total = 0.0f32;
+
+    let lowered = content.to_lowercase();
+    let mut negate = false;
+    for word in tokenize_words(&lowered) {
+        if NEGATIONS.contains(&word) {
+            negate = true;
+            continue;
+        }
+        if let Some(&(_, valence)) = SENTIMENT_LEXICON.iter().find(|(entry, _)| *entry == word) {
+            total += if negate { -valence } else { valence };
+        }
+        negate = false;
+    }
+
+    if emoji_weight != 0.0 {
+        for c in content.chars() {
+            if let Some(&(_, valence)) = EMOJI_SENTIMENT.iter().find(|(emoji, _)| *emoji == c) {
+                total += emoji_weight * valence;
+            }
+        }
+    }
+
+    // Squash the raw sum into [-1, 1] (VADER-style normalisation)
+    total / (total * total + 1.0).sqrt()
+}
+
+/// Whether sentiment is scored for a message as a whole or per sentence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SentimentGranularity {
+    Message,
+    Sentence,
+}
+
+impl SentimentGranularity {
+    /// Parse "message" or "sentence"
+    pub fn parse(granularity: &str) -> Result<Self, ParserError> {
+        match granularity {
+            "message" => Ok(SentimentGranularity::Message),
+            "sentence" => Ok(SentimentGranularity::Sentence),
+            _ => Err(ParserError::InvalidArgument(format!(
+                "Unknown granularity '{}' (expected 'message' or 'sentence')",
+                granularity
+            ))),
+        }
+    }
+}
+
+/// Score of one sentence of a message (see `text::split_sentences`)
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Serialize)]
+pub struct SentenceSentiment {
+    /// Span of the sentence in the message content, in characters
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub score: f32,
+}
+
+/// Per-sentence scores of a message with their aggregate. `mean`, `min` and
+/// `max` are 0 when the message has no sentences.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, Serialize)]
+pub struct SentenceBreakdown {
+    pub sentences: Vec<SentenceSentiment>,
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl SentenceBreakdown {
+    /// The lowest-scoring sentence (the first on ties), for highlighting
+    pub fn most_negative(&self) -> Option<&SentenceSentiment> {
+        self.sentences.iter().reduce(|lowest, sentence| if sentence.score < lowest.score { sentence } else { lowest })
+    }
+}
+
+/// Score each sentence of `content` with `score_sentiment`, so one bitter
+/// line in a long rant isn't averaged away
+pub fn score_sentences(content: &str, emoji_weight: f32) -> SentenceBreakdown {
+    let sentences: Vec<SentenceSentiment> = split_sentences(content)
+        .into_iter()
+        .map(|(start, end, text)| SentenceSentiment { start, end, text: text.to_string(), score: score_sentiment(text, emoji_weight) })
+        .collect();
+    if sentences.is_empty() {
+        return SentenceBreakdown { sentences, mean: 0.0, min: 0.0, max: 0.0 };
+    }
+    let scores = sentences.iter().map(|sentence| sentence.score);
+    let mean = scores.clone().sum::<f32>() / sentences.len() as f32;
+    let min = scores.clone().fold(f32::INFINITY, f32::min);
+    let max = scores.fold(f32::NEG_INFINITY, f32::max);
+    SentenceBreakdown { sentences, mean, min, max }
+}
+
+/// Average sentiment score per sender, ignoring messages without a score
+pub fn sentiment_by_sender(messages: &[Message]) -> HashMap<String, f32> {
+    let mut totals: HashMap<String, (f32, u32)> = HashMap::new();
+
+    for message in messages {
+        if let Some(score) = message.sentiment_score {
+            let entry = totals.entry(message.sender.clone()).or_insert((0.0, 0));
+            entry.0 += score;
+            entry.1 += 1;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(sender, (sum, count))| (sender, sum / count as f32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_each_sentence() {
+        let breakdown = score_sentences("I love this place. The service was terrible! Anyway", 0.0);
+        let scores: Vec<(&str, bool)> = breakdown.sentences.iter().map(|s| (s.text.as_str(), s.score < 0.0)).collect();
+        assert_eq!(scores, [("I love this place.", false), ("The service was terrible!", true), ("Anyway", false)]);
+        assert_eq!((breakdown.min, breakdown.max), (breakdown.sentences[1].score, breakdown.sentences[0].score));
+        assert!((breakdown.mean - breakdown.sentences.iter().map(|s| s.score).sum::<f32>() / 3.0).abs() < 1e-6);
+        assert_eq!(breakdown.most_negative().map(|s| (s.start, s.end)), Some((19, 44)));
+
+        let empty = score_sentences("", 0.0);
+        assert!(empty.most_negative().is_none());
+        assert_eq!(empty.mean, 0.0);
+        assert!(SentimentGranularity::parse("word").is_err());
+    }
+}
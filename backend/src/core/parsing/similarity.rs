@@ -0,0 +1,289 @@
+use chrono::{DateTime, Local};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::chat_parser::ChatParser;
+use crate::message::{Message, MessageType};
+use crate::reference::{fnv1a, splitmix64};
+use crate::text::{fold_case, sender_key, tokenize_words};
+
+/// Messages with fewer words are left out of `cluster_similar`, so short
+/// replies ("ok", "see you there") don't all cluster together
+pub const SIMILAR_MIN_WORDS: usize = 10;
+
+/// Messages with fewer words are left out of `detect_broadcast_messages`, so
+/// a "thanks" or "ok" sent in several chats isn't a broadcast
+pub const BROADCAST_MIN_WORDS: usize = 3;
+
+/// Words per shingle
+const SHINGLE_WORDS: usize = 3;
+
+/// MinHash signature length; the LSH bands split it evenly
+const MINHASH_SIZE: usize = 64;
+
+/// Messages whose content is near-identical (see
+/// `ChatParser::cluster_similar`), such as one forward spreading through
+/// several chats
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimilarCluster {
+    pub size: usize,
+    /// Index of the earliest member, the canonical copy
+    pub canonical: usize,
+    pub first_sender: String,
+    pub first_timestamp: DateTime<Local>,
+    /// Indices of the members, earliest first
+    pub indices: Vec<usize>,
+    /// When and by whom each member was sent, earliest first
+    pub timeline: Vec<(DateTime<Local>, String)>,
+}
+
+/// One message a sender pasted into several chats (see
+/// `ChatParser::detect_broadcast_messages`)
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BroadcastEvent {
+    /// The earliest copy's content
+    pub content: String,
+    /// When each copy was sent, earliest first
+    pub timestamps: Vec<DateTime<Local>>,
+    /// The chat each copy was sent in, matching `timestamps`
+    pub chat_indices: Vec<usize>,
+}
+
+/// Sparse word-count vector keyed by word
+type WordCounts = HashMap<String, f64>;
+
+/// Word counts of a message, case-folded
+fn term_counts(content: &str) -> WordCounts {
+    let mut counts = HashMap::new();
+    for word in tokenize_words(&fold_case(content)) {
+        *counts.entry(word.to_string()).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+/// Cosine similarity of two word-count vectors
+fn cosine_similarity(left: &WordCounts, right: &WordCounts) -> f64 {
+    let norm = |vector: &WordCounts| vector.values().map(|count| count * count).sum::<f64>().sqrt();
+    let dot: f64 = left.iter().filter_map(|(word, count)| right.get(word).map(|other| count * other)).sum();
+    dot / (norm(left) * norm(right))
+}
+
+/// Hashes of the overlapping `SHINGLE_WORDS`-word runs of a message, or
+/// `None` if it has fewer than `SIMILAR_MIN_WORDS` words
+fn shingles(content: &str) -> Option<Vec<u64>> {
+    let folded = fold_case(content);
+    let words: Vec<&str> = tokenize_words(&folded).collect();
+    if words.len() < SIMILAR_MIN_WORDS {
+        return None;
+    }
+    Some(words.windows(SHINGLE_WORDS).map(|shingle| fnv1a(shingle.join(" ").as_bytes())).collect())
+}
+
+/// The MinHash signature of a shingle set: for each of `MINHASH_SIZE`
+/// seeded hash functions, the smallest hash of any shingle. 32-bit values
+/// keep a million signatures to a few hundred megabytes.
+fn signature(shingles: &[u64], seeds: &[(u64, u64)]) -> Vec<u32> {
+    seeds
+        .iter()
+        .map(|&(multiplier, offset)| {
+            let hash = |shingle: u64| (splitmix64(shingle.wrapping_mul(multiplier).wrapping_add(offset)) >> 32) as u32;
+            shingles.iter().map(|&shingle| hash(shingle)).min().unwrap_or(u32::MAX)
+        })
+        .collect()
+}
+
+/// Rows per LSH band. Pairs with Jaccard similarity s share a band with
+/// probability 1 - (1 - s^r)^b, which rises steeply around (1/b)^(1/r); the
+/// largest r that keeps that point at or below `threshold` keeps misses rare.
+fn band_rows(threshold: f64) -> usize {
+    let mut rows = 1;
+    while rows * 2 <= MINHASH_SIZE {
+        let candidate = rows * 2;
+        let bands = (MINHASH_SIZE / candidate) as f64;
+        if (1.0 / bands).powf(1.0 / candidate as f64) > threshold {
+            break;
+        }
+        rows = candidate;
+    }
+    rows
+}
+
+/// Fraction of matching signature positions, an estimate of the Jaccard
+/// similarity of the two shingle sets
+fn estimated_similarity(left: &[u32], right: &[u32]) -> f64 {
+    left.iter().zip(right).filter(|(left, right)| left == right).count() as f64 / MINHASH_SIZE as f64
+}
+
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+impl ChatParser {
+    /// Clusters of near-duplicate text messages, for following a forward as
+    /// it spreads. Similarity is the Jaccard similarity of the messages'
+    /// three-word shingles (case-folded), estimated with MinHash; messages
+    /// whose estimate exceeds `threshold` join the same cluster, so
+    /// membership is transitive. Candidate pairs come from LSH banding, so
+    /// the work grows with the number of messages, not the number of pairs.
+    /// Messages under `SIMILAR_MIN_WORDS` words are skipped. Clusters have at
+    /// least two members and are listed largest first, then by first
+    /// timestamp.
+    pub fn cluster_similar(messages: &[Message], threshold: f64) -> Vec<SimilarCluster> {
+        let mut state = 0x5EED_u64;
+        let seeds: Vec<(u64, u64)> = (0..MINHASH_SIZE)
+            .map(|_| {
+                state = splitmix64(state);
+                let multiplier = state | 1;
+                state = splitmix64(state);
+                (multiplier, state)
+            })
+            .collect();
+        let (positions, signatures): (Vec<usize>, Vec<Vec<u32>>) = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| matches!(message.message_type, MessageType::Text | MessageType::Link))
+            .filter_map(|(index, message)| Some((index, signature(&shingles(&message.content)?, &seeds))))
+            .unzip();
+
+        let rows = band_rows(threshold);
+        let mut parents: Vec<usize> = (0..signatures.len()).collect();
+        for band in 0..MINHASH_SIZE / rows {
+            // The previous member of each bucket; checking each newcomer
+            // against it keeps a bucket of identical forwards linear
+            let mut buckets: HashMap<&[u32], usize> = HashMap::new();
+            for (member, signature) in signatures.iter().enumerate() {
+                let key = &signature[band * rows..(band + 1) * rows];
+                if let Some(previous) = buckets.insert(key, member) {
+                    if estimated_similarity(&signatures[previous], signature) > threshold {
+                        let (left, right) = (find_root(&mut parents, previous), find_root(&mut parents, member));
+                        parents[left] = right;
+                    }
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (member, &index) in positions.iter().enumerate() {
+            let root = find_root(&mut parents, member);
+            groups.entry(root).or_default().push(index);
+        }
+        let mut clusters: Vec<SimilarCluster> = groups
+            .into_values()
+            .filter(|indices| indices.len() > 1)
+            .map(|mut indices| {
+                indices.sort_by_key(|&index| (messages[index].timestamp, index));
+                let first = &messages[indices[0]];
+                SimilarCluster {
+                    size: indices.len(),
+                    canonical: indices[0],
+                    first_sender: first.sender.clone(),
+                    first_timestamp: first.timestamp,
+                    timeline: indices.iter().map(|&index| (messages[index].timestamp, messages[index].sender.clone())).collect(),
+                    indices,
+                }
+            })
+            .collect();
+        clusters.sort_by(|a, b| b.size.cmp(&a.size).then(a.first_timestamp.cmp(&b.first_timestamp)).then(a.canonical.cmp(&b.canonical)));
+        clusters
+    }
+
+    /// Messages `sender` sent to two or more of `chats`, found by comparing
+    /// the word counts (case-folded) of the sender's text messages across
+    /// chats with cosine similarity. The sender is matched as by
+    /// `find_first_occurrence_of_sender`. Going through the copies in time
+    /// order, each joins the first broadcast whose earliest copy it is at
+    /// least `min_similarity` similar to; copies in the same chat count
+    /// once towards the two chats. Messages under `BROADCAST_MIN_WORDS`
+    /// words are skipped. Broadcasts are listed by first timestamp.
+    pub fn detect_broadcast_messages(chats: &[Vec<Message>], sender: &str, min_similarity: f64) -> Vec<BroadcastEvent> {
+        let wanted = fold_case(&sender_key(sender));
+        let mut copies: Vec<(usize, &Message)> = chats
+            .iter()
+            .enumerate()
+            .flat_map(|(chat, messages)| messages.iter().map(move |message| (chat, message)))
+            .filter(|(_, message)| matches!(message.message_type, MessageType::Text | MessageType::Link))
+            .filter(|(_, message)| fold_case(&message.sender) == wanted)
+            .filter(|(_, message)| tokenize_words(&message.content).count() >= BROADCAST_MIN_WORDS)
+            .collect();
+        copies.sort_by_key(|(chat, message)| (message.timestamp, *chat));
+
+        // (earliest copy's word counts, copies as (chat, message))
+        let mut groups: Vec<(WordCounts, Vec<(usize, &Message)>)> = Vec::new();
+        for (chat, message) in copies {
+            let terms = term_counts(&message.content);
+            match groups.iter_mut().find(|(first, _)| cosine_similarity(first, &terms) >= min_similarity) {
+                Some((_, members)) => members.push((chat, message)),
+                None => groups.push((terms, vec![(chat, message)])),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|(_, members)| members.iter().any(|(chat, _)| *chat != members[0].0))
+            .map(|(_, members)| BroadcastEvent {
+                content: members[0].1.content.clone(),
+                timestamps: members.iter().map(|(_, message)| message.timestamp).collect(),
+                chat_indices: members.iter().map(|(chat, _)| *chat).collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_a_forward_and_its_edited_copies() {
+        let chat = "[18/05/2023, 08:00:00] John: Forwarded: the council says the bridge will be closed all weekend for repairs so plan ahead\n\
+                    [18/05/2023, 08:05:00] Alice: ok\n\
+                    [18/05/2023, 09:00:00] Alice: THE COUNCIL SAYS the bridge will be closed all weekend for repairs so plan ahead!!\n\
+                    [18/05/2023, 09:30:00] Priya: I think we should book the restaurant for saturday night before it fills up\n\
+                    [19/05/2023, 07:00:00] Tom: the council says the bridge will be closed all weekend for repairs so plan ahead\n\
+                    [19/05/2023, 07:01:00] Tom: ok\n";
+        let messages = ChatParser::new().parse_str(chat, false).unwrap();
+        let clusters = ChatParser::cluster_similar(&messages, 0.7);
+        assert_eq!(clusters.len(), 1);
+        let cluster = &clusters[0];
+        assert_eq!((cluster.size, cluster.canonical, cluster.indices.clone()), (3, 0, vec![0, 2, 4]));
+        assert_eq!(cluster.first_sender, "John");
+        assert_eq!(cluster.timeline.iter().map(|(_, sender)| sender.as_str()).collect::<Vec<_>>(), ["John", "Alice", "Tom"]);
+        // Case and punctuation aside, Alice's and Tom's copies are identical
+        let exact = ChatParser::cluster_similar(&messages, 0.99);
+        assert_eq!(exact.iter().map(|cluster| cluster.indices.clone()).collect::<Vec<_>>(), [vec![2, 4]]);
+    }
+
+    #[test]
+    fn finds_a_message_pasted_into_two_chats() {
+        let family = "[18/05/2023, 08:00:00] John: Reminder: dinner at mine on Saturday, bring a dish!\n\
+                      [18/05/2023, 08:01:00] Mum: lovely\n\
+                      [18/05/2023, 09:00:00] John: ok thanks\n";
+        let friends = "[18/05/2023, 08:02:00] john: reminder dinner at mine on saturday bring a dish\n\
+                       [18/05/2023, 08:03:00] Alice: Reminder: dinner at mine on Saturday, bring a dish!\n\
+                       [18/05/2023, 09:05:00] John: ok thanks\n";
+        let work = "[18/05/2023, 08:04:00] John: Reminder: the report is due on Saturday\n";
+        let chats: Vec<Vec<Message>> =
+            [family, friends, work].iter().map(|chat| ChatParser::new().parse_str(chat, false).unwrap()).collect();
+
+        let broadcasts = ChatParser::detect_broadcast_messages(&chats, "JOHN", 0.9);
+        assert_eq!(broadcasts.len(), 1);
+        assert_eq!(broadcasts[0].content, "Reminder: dinner at mine on Saturday, bring a dish!");
+        assert_eq!(broadcasts[0].chat_indices, [0, 1]);
+        assert_eq!(broadcasts[0].timestamps, [chats[0][0].timestamp, chats[1][0].timestamp]);
+        // "ok thanks" is too short to count, and the work reminder differs
+        assert!(ChatParser::detect_broadcast_messages(&chats, "Alice", 0.9).is_empty());
+    }
+
+    #[test]
+    fn band_rows_track_the_threshold() {
+        assert!(band_rows(0.5) < band_rows(0.9));
+        assert_eq!(band_rows(0.0), 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! A SQLite-backed chat store: exports are ingested once and queried
+//! afterwards, instead of being re-parsed for every request.
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use chrono::{DateTime, Local, TimeZone};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::chat_parser::ChatParser;
+use crate::error::ParserError;
+use crate::message::Message;
+
+/// Schema changes, applied in order. `PRAGMA user_version` records how many
+/// have run, so a store written by an older build is brought up to date
+/// when opened. Append new steps; never edit one that has shipped.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE chats (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE messages (
+        chat_id INTEGER NOT NULL REFERENCES chats (id),
+        hash TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        sender TEXT NOT NULL,
+        message_type TEXT NOT NULL,
+        content TEXT NOT NULL,
+        record TEXT NOT NULL,
+        PRIMARY KEY (chat_id, hash)
+    );
+    CREATE INDEX messages_by_time ON messages (chat_id, timestamp, position);
+    CREATE INDEX messages_by_sender ON messages (chat_id, sender);",
+];
+
+/// How long a write waits for another connection's lock before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn store_error(action: &'static str) -> impl FnOnce(rusqlite::Error) -> ParserError {
+    move |error| ParserError::io(action)(io::Error::other(error.to_string()))
+}
+
+/// Content hash of a message, stable across exports of the same chat: the
+/// exported timestamp, sender, content and caption, plus how many identical
+/// messages came before it, so a repeated "ok" in the same minute is kept
+fn message_hash(message: &Message, occurrence: u32) -> String {
+    let mut hasher = Xxh3::new();
+    for field in [
+        message.exported_timestamp().to_rfc3339().as_str(),
+        &message.sender,
+        &message.content,
+        message.caption.as_deref().unwrap_or(""),
+    ] {
+        hasher.update(field.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.update(&occurrence.to_le_bytes());
+    format!("{:016x}", hasher.digest())
+}
+
+/// Totals for one stored chat, computed in SQL
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreStats {
+    pub message_count: u64,
+    pub first_message: Option<DateTime<Local>>,
+    pub last_message: Option<DateTime<Local>>,
+    /// Distinct local dates with at least one message
+    pub active_days: u64,
+    /// Messages per sender, most active first
+    pub senders: Vec<(String, u64)>,
+    /// Messages per type name (see `MessageType::as_str`)
+    pub message_types: BTreeMap<String, u64>,
+}
+
+/// A chat database on disk. Each chat is stored under a name, and
+/// re-ingesting a longer export of it adds only the messages that are new.
+/// The database runs in WAL mode and ingests in an immediate transaction,
+/// so several stores (or threads) can share one file.
+#[cfg_attr(feature = "python", pyclass)]
+pub struct ChatStore {
+    connection: Mutex<Connection>,
+}
+
+impl ChatStore {
+    /// Open or create the store at `path`, running pending migrations
+    pub fn open(path: &str) -> Result<ChatStore, ParserError> {
+        let mut connection = Connection::open(path).map_err(store_error("Failed to open chat store"))?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(store_error("Failed to open chat store"))?;
+        connection
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .map_err(store_error("Failed to open chat store"))?;
+        migrate(&mut connection)?;
+        Ok(ChatStore { connection: Mutex::new(connection) })
+    }
+
+    /// Schema version of the open store, the number of migrations applied
+    pub fn schema_version(&self) -> Result<usize, ParserError> {
+        let connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        schema_version(&connection).map_err(store_error("Failed to read chat store"))
+    }
+
+    /// Parse the export at `file_path` (system messages included) and
+    /// store it as `chat_name`, returning how many messages were new
+    pub fn ingest(&self, parser: &ChatParser, file_path: &str, chat_name: &str) -> Result<usize, ParserError> {
+        let messages = parser.parse_file(file_path, true)?;
+        self.ingest_messages(&messages, chat_name)
+    }
+
+    /// Store already-parsed messages as `chat_name`; messages whose hash is
+    /// already stored for the chat are skipped
+    pub fn ingest_messages(&self, messages: &[Message], chat_name: &str) -> Result<usize, ParserError> {
+        let write_error = || store_error("Failed to write chat store");
+        let mut connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate).map_err(write_error())?;
+        transaction
+            .execute("INSERT OR IGNORE INTO chats (name) VALUES (?1)", [chat_name])
+            .map_err(write_error())?;
+        let chat_id: i64 = transaction
+            .query_row("SELECT id FROM chats WHERE name = ?1", [chat_name], |row| row.get(0))
+            .map_err(write_error())?;
+
+        let mut inserted = 0;
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT OR IGNORE INTO messages
+                     (chat_id, hash, position, timestamp, sender, message_type, content, record)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )
+                .map_err(write_error())?;
+            let mut occurrences: HashMap<String, u32> = HashMap::new();
+            for (position, message) in messages.iter().enumerate() {
+                let occurrence = occurrences.entry(message_hash(message, 0)).or_insert(0);
+                let hash = message_hash(message, *occurrence);
+                *occurrence += 1;
+                let record = serde_json::to_string(message)
+                    .map_err(|error| ParserError::io("Failed to write chat store")(error.into()))?;
+                inserted += insert
+                    .execute(params![
+                        chat_id,
+                        hash,
+                        position as i64,
+                        message.timestamp.timestamp(),
+                        message.sender,
+                        message.message_type.as_str(),
+                        message.content,
+                        record,
+                    ])
+                    .map_err(write_error())?;
+            }
+        }
+        transaction.commit().map_err(write_error())?;
+        Ok(inserted)
+    }
+
+    /// Stored messages of `chat_name` in chat order, narrowed to those sent
+    /// at or after `start` and before `end`, by `sender` (the sender key),
+    /// and whose content contains `text` (ignoring ASCII case)
+    pub fn query(
+        &self,
+        chat_name: &str,
+        start: Option<DateTime<Local>>,
+        end: Option<DateTime<Local>>,
+        sender: Option<&str>,
+        text: Option<&str>,
+    ) -> Result<Vec<Message>, ParserError> {
+        let read_error = || store_error("Failed to read chat store");
+        let connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let chat_id = chat_id(&connection, chat_name)?;
+
+        let mut sql = String::from("SELECT record FROM messages WHERE chat_id = ?");
+        let mut values = vec![Value::Integer(chat_id)];
+        if let Some(start) = start {
+            sql.push_str(" AND timestamp >= ?");
+            values.push(Value::Integer(start.timestamp()));
+        }
+        if let Some(end) = end {
+            sql.push_str(" AND timestamp < ?");
+            values.push(Value::Integer(end.timestamp()));
+        }
+        if let Some(sender) = sender {
+            sql.push_str(" AND sender = ?");
+            values.push(Value::Text(sender.to_string()));
+        }
+        if let Some(text) = text {
+            sql.push_str(" AND content LIKE ? ESCAPE '\\'");
+            let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            values.push(Value::Text(format!("%{}%", escaped)));
+        }
+        sql.push_str(" ORDER BY timestamp, position");
+
+        let mut statement = connection.prepare(&sql).map_err(read_error())?;
+        let records = statement
+            .query_map(rusqlite::params_from_iter(values), |row| row.get::<_, String>(0))
+            .map_err(read_error())?;
+        let mut messages = Vec::new();
+        for record in records {
+            let record = record.map_err(read_error())?;
+            let message = serde_json::from_str(&record).map_err(|error| ParserError::CorruptExport {
+                line: None,
+                detail: format!("stored message is unreadable: {}", error),
+            })?;
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    /// Totals for `chat_name` (see `StoreStats`)
+    pub fn stats(&self, chat_name: &str) -> Result<StoreStats, ParserError> {
+        let read_error = || store_error("Failed to read chat store");
+        let connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let chat_id = chat_id(&connection, chat_name)?;
+
+        let (message_count, first, last, active_days) = connection
+            .query_row(
+                "SELECT COUNT(*), MIN(timestamp), MAX(timestamp),
+                        COUNT(DISTINCT date(timestamp, 'unixepoch', 'localtime'))
+                 FROM messages WHERE chat_id = ?1",
+                [chat_id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, Option<i64>>(2)?, row.get::<_, i64>(3)?)),
+            )
+            .map_err(read_error())?;
+        let local = |seconds: Option<i64>| seconds.and_then(|seconds| Local.timestamp_opt(seconds, 0).single());
+
+        let mut statement = connection
+            .prepare(
+                "SELECT sender, COUNT(*) AS count FROM messages WHERE chat_id = ?1
+                 GROUP BY sender ORDER BY count DESC, sender",
+            )
+            .map_err(read_error())?;
+        let senders = statement
+            .query_map([chat_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64)))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(read_error())?;
+
+        let mut statement = connection
+            .prepare("SELECT message_type, COUNT(*) FROM messages WHERE chat_id = ?1 GROUP BY message_type")
+            .map_err(read_error())?;
+        let message_types = statement
+            .query_map([chat_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64)))
+            .and_then(|rows| rows.collect::<Result<BTreeMap<_, _>, _>>())
+            .map_err(read_error())?;
+
+        Ok(StoreStats {
+            message_count: message_count as u64,
+            first_message: local(first),
+            last_message: local(last),
+            active_days: active_days as u64,
+            senders,
+            message_types,
+        })
+    }
+}
+
+fn schema_version(connection: &Connection) -> rusqlite::Result<usize> {
+    connection.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0)).map(|version| version as usize)
+}
+
+/// Apply the migrations the store hasn't seen yet, all in one transaction
+/// so that two processes opening a new store don't both run them
+fn migrate(connection: &mut Connection) -> Result<(), ParserError> {
+    let migrate_error = || store_error("Failed to migrate chat store");
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate).map_err(migrate_error())?;
+    let version = schema_version(&transaction).map_err(migrate_error())?;
+    if version > MIGRATIONS.len() {
+        return Err(ParserError::InvalidArgument(format!(
+            "Chat store has schema version {}, newer than this build supports ({})",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+    for migration in &MIGRATIONS[version..] {
+        transaction.execute_batch(migration).map_err(migrate_error())?;
+    }
+    transaction.pragma_update(None, "user_version", MIGRATIONS.len() as i64).map_err(migrate_error())?;
+    transaction.commit().map_err(migrate_error())
+}
+
+fn chat_id(connection: &Connection, chat_name: &str) -> Result<i64, ParserError> {
+    connection
+        .query_row("SELECT id FROM chats WHERE name = ?1", [chat_name], |row| row.get(0))
+        .optional()
+        .map_err(store_error("Failed to read chat store"))?
+        .ok_or_else(|| ParserError::InvalidArgument(format!("No chat named '{}' in the store", chat_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("store_{}_{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn remove_store(path: &str) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    fn write_chat(path: &str, text: &str) {
+        std::fs::File::create(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    const CHAT: &str = "[18/05/2023, 08:00:00] John: Good morning\n\
+                        [18/05/2023, 08:00:00] Alice: ok\n\
+                        [18/05/2023, 08:00:00] Alice: ok\n\
+                        [19/05/2023, 09:30:00] John: <Media omitted>\n";
+
+    #[test]
+    fn reingesting_a_longer_export_adds_only_new_messages() {
+        let (db, chat) = (temp_path("upsert.db"), temp_path("upsert.txt"));
+        let store = ChatStore::open(&db).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        write_chat(&chat, CHAT);
+        assert_eq!(store.ingest(&ChatParser::new(), &chat, "family").unwrap(), 4);
+        write_chat(&chat, &format!("{}[20/05/2023, 21:00:00] Alice: Good night\n", CHAT));
+        assert_eq!(store.ingest(&ChatParser::new(), &chat, "family").unwrap(), 1);
+        drop(store);
+
+        let store = ChatStore::open(&db).unwrap();
+        let all = store.query("family", None, None, None, None).unwrap();
+        assert_eq!(all.iter().map(|message| message.content.as_str()).collect::<Vec<_>>(), ["Good morning", "ok", "ok", "<Media omitted>", "Good night"]);
+        let good = store.query("family", None, None, Some("Alice"), Some("GOOD")).unwrap();
+        assert_eq!(good.len(), 1);
+        let day = |d: u32| Local.with_ymd_and_hms(2023, 5, d, 0, 0, 0).unwrap();
+        assert_eq!(store.query("family", Some(day(19)), Some(day(20)), None, None).unwrap().len(), 1);
+        assert!(store.query("family", None, None, None, Some("%")).unwrap().is_empty());
+
+        let stats = store.stats("family").unwrap();
+        assert_eq!((stats.message_count, stats.active_days), (5, 3));
+        assert_eq!(stats.senders, [("Alice".to_string(), 3), ("John".to_string(), 2)]);
+        assert_eq!(stats.message_types.get("media"), Some(&1));
+        assert_eq!(stats.first_message, Some(all[0].timestamp));
+        assert!(matches!(store.stats("work"), Err(ParserError::InvalidArgument(_))));
+        drop(store);
+        std::fs::remove_file(&chat).unwrap();
+        remove_store(&db);
+    }
+
+    #[test]
+    fn concurrent_ingests_share_one_file() {
+        let db = temp_path("concurrent.db");
+        ChatStore::open(&db).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    let store = ChatStore::open(&db).unwrap();
+                    let messages = ChatParser::new().parse_str(CHAT, true).unwrap();
+                    (0..10).map(|round| store.ingest_messages(&messages, &format!("chat{}", (thread + round) % 3)).unwrap()).sum::<usize>()
+                })
+            })
+            .collect();
+        let inserted: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+        assert_eq!(inserted, 3 * 4);
+        let store = ChatStore::open(&db).unwrap();
+        assert_eq!(store.stats("chat2").unwrap().message_count, 4);
+        drop(store);
+        remove_store(&db);
+    }
+}
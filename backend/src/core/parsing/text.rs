@@ -0,0 +1,337 @@
+use std::borrow::Cow;
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Whether `c` is a bidi control: LRM/RLM/ALM marks, embeddings and
+/// overrides (U+202A–U+202E) or isolates (U+2066–U+2069)
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{061C}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Sender name normalised for keying: bidi controls stripped, trimmed and
+/// NFC-composed, so a "José" typed precomposed (Android) and one exported
+/// decomposed (macOS, some keyboards) are the same sender
+pub fn sender_key(name: &str) -> String {
+    name.chars().filter(|c| !is_bidi_control(*c)).nfc().collect::<String>().trim().to_string()
+}
+
+/// `text` in Unicode NFC, for comparing strings that may be composed
+/// differently; borrows `text` when it is already NFC
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    if is_nfc(text) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.nfc().collect())
+    }
+}
+
+/// Full Unicode case folding of `text`, NFC-composed ("Straße" and
+/// "STRASSE" fold alike), for case-insensitive name matching
+pub fn fold_case(text: &str) -> String {
+    caseless::default_case_fold_str(text).nfc().collect()
+}
+
+/// Characters that belong inside a word. Besides letters and digits this keeps
+/// apostrophes, the Arabic tatweel, zero-width (non-)joiners and Arabic/Hebrew
+/// combining marks, none of which should split a word.
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+        || matches!(
+            c,
+            '\'' | '\u{0640}' | '\u{200C}' | '\u{200D}' | '\u{0591}'..='\u{05C7}' | '\u{064B}'..='\u{065F}' | '\u{0670}'
+        )
+}
+
+/// Replace typographic quotes and apostrophes (‘ ’ ‛ “ ” „ ‟) with their
+/// straight ASCII forms; borrows `text` when it has none
+pub fn straighten_quotes(text: &str) -> Cow<'_, str> {
+    let curly = |c: char| matches!(c, '\u{2018}'..='\u{201F}' | '\u{2032}' | '\u{2033}');
+    if !text.contains(curly) {
+        return Cow::Borrowed(text);
+    }
+    text.chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+            c => c,
+        })
+        .collect::<String>()
+        .into()
+}
+
+/// Strip trailing whitespace (stray `\r` included) from each line and
+/// trailing blank lines from the end; with `trim_continuations`, leading
+/// whitespace from every line after the first as well
+pub fn normalize_whitespace(text: &str, trim_continuations: bool) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            normalized.push('\n');
+        }
+        let line = line.trim_end();
+        normalized.push_str(if index > 0 && trim_continuations { line.trim_start() } else { line });
+    }
+    normalized.truncate(normalized.trim_end().len());
+    normalized
+}
+
+/// Split text into word tokens (see `is_word_char`)
+pub fn tokenize_words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !is_word_char(c)).filter(|word| !word.is_empty())
+}
+
+/// Whether a whitespace-separated token is a link ("https://…", "www.…")
+pub fn is_url_token(token: &str) -> bool {
+    let lowered = token.trim_start_matches(['(', '<', '"', '\'']).to_ascii_lowercase();
+    lowered.starts_with("http://") || lowered.starts_with("https://") || lowered.starts_with("www.")
+}
+
+/// `tokenize_words`, optionally skipping links first so that a URL doesn't
+/// count as "https", "example", "com", …
+pub fn tokenize_words_with(text: &str, exclude_urls: bool) -> Vec<&str> {
+    if exclude_urls {
+        text.split_whitespace().filter(|token| !is_url_token(token)).flat_map(tokenize_words).collect()
+    } else {
+        tokenize_words(text).collect()
+    }
+}
+
+/// Wrap text in a direction isolate so mixed LTR/RTL content renders
+/// correctly: `<span dir="auto">…</span>` (HTML-escaped) for HTML, or
+/// FSI…PDI for plain text and Markdown
+pub fn bidi_isolate(text: &str, html: bool) -> String {
+    let text: String = text.chars().filter(|c| !is_bidi_control(*c)).collect();
+    if html {
+        let escaped = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;");
+        format!("<span dir=\"auto\">{}</span>", escaped)
+    } else {
+        format!("\u{2068}{}\u{2069}", text)
+    }
+}
+
+/// Code heuristics used by `detect_code_snippets`
+pub fn looks_like_code(content: &str) -> bool {
+    if let Some(start) = content.find('`') {
+        if content[start + 1..].contains('`') {
+            return true;
+        }
+    }
+
+    content.lines().any(|line| {
+        let punctuation = ['{', '}', ';'].iter().filter(|&&c| line.contains(c)).count();
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        punctuation >= 2 || (indented && !line.trim().is_empty())
+    })
+}
+
+/// Chat shorthand that marks a message as informal
+pub const CHAT_ABBREVIATIONS: &[&str] = &[
+    "u", "ur", "r", "lol", "omg", "idk", "btw", "pls", "plz", "thx", "ty", "brb", "imo", "tbh", "gonna", "wanna",
+];
+
+/// Common English function words, ignored when comparing what messages are about
+pub const STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "but", "if", "so", "to", "of", "in", "on", "at", "for", "with", "by", "from",
+    "is", "are", "was", "were", "be", "been", "am", "it", "it's", "this", "that", "i", "i'm", "you", "we", "he",
+    "she", "they", "me", "my", "your", "our", "us", "them", "do", "did", "have", "has", "had", "will", "would",
+    "can", "could", "not", "no", "yes", "just", "what", "how", "about", "there", "then", "too", "also", "some",
+];
+
+/// Whether `c` is an emoji base character (pictographs, symbols, dingbats).
+/// Skin-tone modifiers, variation selectors and joiners are not counted.
+pub fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F300}'..='\u{1F3FA}'
+            | '\u{1F400}'..='\u{1FAFF}'
+            | '\u{1F000}'..='\u{1F2FF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2300}'..='\u{23FF}'
+            | '\u{2B00}'..='\u{2BFF}'
+    )
+}
+
+/// Emoji in `text`, keeping ZWJ sequences (👨‍👩‍👧), skin tones and variation
+/// selectors attached to their base emoji
+pub fn extract_emojis(text: &str) -> Vec<String> {
+    let mut emojis: Vec<String> = Vec::new();
+    let mut joining = false;
+    for c in text.chars() {
+        let is_modifier = matches!(c, '\u{1F3FB}'..='\u{1F3FF}' | '\u{FE0F}');
+        if c == '\u{200D}' {
+            joining = !emojis.is_empty();
+            if let Some(last) = emojis.last_mut() {
+                last.push(c);
+            }
+            continue;
+        }
+        if is_modifier || (joining && is_emoji(c)) {
+            if let Some(last) = emojis.last_mut() {
+                last.push(c);
+            }
+        } else if is_emoji(c) {
+            emojis.push(c.to_string());
+        }
+        joining = false;
+    }
+    emojis
+}
+
+/// Terminators that end a sentence even without a following space
+const CJK_TERMINATORS: &[char] = &['。', '！', '？'];
+const TERMINATORS: &[char] = &['.', '!', '?', '…', '‼', '⁉', '。', '！', '？'];
+/// Closing quotes and brackets that stay with the sentence they end
+const CLOSERS: &[char] = &['"', '\'', ')', ']', '”', '’', '」', '』', '）'];
+
+/// Sentences of `text` as (start, end, sentence) with character spans,
+/// surrounding whitespace trimmed. Besides the usual terminators (runs such
+/// as "?!" or "..." stay together) this splits after CJK 。！？ and at line
+/// breaks, and leniently for chat: "ok.See you" splits before the capital,
+/// an emoji followed by a capitalised word ends a sentence, and an ellipsis
+/// followed by a lowercase word does not.
+pub fn split_sentences(text: &str) -> Vec<(usize, usize, &str)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut offsets: Vec<usize> = text.char_indices().map(|(byte, _)| byte).collect();
+    offsets.push(text.len());
+
+    let mut sentences = Vec::new();
+    let mut push = |start: usize, end: usize| {
+        let start = (start..end).find(|&i| !chars[i].is_whitespace()).unwrap_or(end);
+        let end = (start..end).rev().find(|&i| !chars[i].is_whitespace()).map_or(start, |i| i + 1);
+        if start < end {
+            sentences.push((start, end, &text[offsets[start]..offsets[end]]));
+        }
+    };
+    let next_word = |from: usize| (from..chars.len()).find(|&i| !chars[i].is_whitespace());
+
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let boundary = if c == '\n' {
+            Some(i + 1)
+        } else if TERMINATORS.contains(&c) {
+            let mut end = i;
+            while end < chars.len() && TERMINATORS.contains(&chars[end]) {
+                end += 1;
+            }
+            let run = &chars[i..end];
+            while end < chars.len() && CLOSERS.contains(&chars[end]) {
+                end += 1;
+            }
+            let ellipsis = run.contains(&'…') || run.iter().filter(|&&c| c == '.').count() >= 2;
+            let split = match chars.get(end) {
+                None => true,
+                Some(_) if run.iter().any(|c| CJK_TERMINATORS.contains(c)) => true,
+                // A trailing emoji ("Great! 😂") stays with the sentence; the
+                // emoji rule below decides whether to split after it
+                Some(next) if next.is_whitespace() => next_word(end).is_none_or(|word| {
+                    !(is_emoji(chars[word]) || (ellipsis && chars[word].is_lowercase()))
+                }),
+                Some(next) => next.is_uppercase() && i > 0 && chars[i - 1].is_alphabetic(),
+            };
+            i = end - 1;
+            split.then_some(end)
+        } else if is_emoji(c) {
+            let mut end = i + 1;
+            while end < chars.len()
+                && (matches!(chars[end], '\u{1F3FB}'..='\u{1F3FF}' | '\u{FE0F}' | '\u{200D}') || is_emoji(chars[end]))
+            {
+                end += 1;
+            }
+            i = end - 1;
+            next_word(end).filter(|&word| chars[word].is_uppercase()).map(|_| end)
+        } else {
+            None
+        };
+        if let Some(end) = boundary {
+            push(start, end);
+            start = end;
+        }
+        i += 1;
+    }
+    push(start, chars.len());
+    sentences
+}
+
+/// Laughter markers across languages (matched as whole words, case-insensitive)
+pub const LAUGHTER_WORDS: &[&str] = &[
+    "haha", "hahaha", "hahahaha", "hehe", "lol", "lmao", "lmfao", "rofl", "xd", "jaja", "jajaja",
+    "jeje", "kkkk", "kkkkk", "rsrs", "mdr", "ptdr", "555", "5555", "www", "ㅋㅋ", "ㅋㅋㅋ", "哈哈", "哈哈哈",
+];
+
+/// Whether a message laughs: a laughter word, 😂/🤣, or a long "hahaha"/"ㅋㅋㅋ" run
+pub fn contains_laughter(content: &str) -> bool {
+    if content.contains('😂') || content.contains('🤣') {
+        return true;
+    }
+    let lowered = content.to_lowercase();
+    let laughs = tokenize_words(&lowered).any(|word| {
+        LAUGHTER_WORDS.contains(&word)
+            || (word.len() >= 4 && word.trim_start_matches("ha").is_empty())
+            || (word.chars().count() >= 2 && word.chars().all(|c| c == 'ㅋ'))
+    });
+    laughs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_words() {
+        let words: Vec<&str> = tokenize_words("don't stop, ok?").collect();
+        assert_eq!(words, ["don't", "stop", "ok"]);
+    }
+
+    #[test]
+    fn skips_url_tokens() {
+        let text = "see https://example.com/a-b and (www.test.org) ok";
+        assert_eq!(tokenize_words_with(text, true), ["see", "and", "ok"]);
+        assert_eq!(tokenize_words_with(text, false).len(), 11);
+    }
+
+    #[test]
+    fn straightens_quotes() {
+        assert_eq!(straighten_quotes("this group\u{2019}s \u{201C}Trip\u{201D}"), "this group's \"Trip\"");
+        assert!(matches!(straighten_quotes("plain 'text'"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn splits_sentences() {
+        let texts = |text| split_sentences(text).into_iter().map(|(_, _, sentence)| sentence).collect::<Vec<&str>>();
+        assert_eq!(texts("Hi there. How are you?! Fine..."), ["Hi there.", "How are you?!", "Fine..."]);
+        assert_eq!(texts("well... maybe. ok.See you at 3.30 then"), ["well... maybe.", "ok.", "See you at 3.30 then"]);
+        assert_eq!(texts("That was great 😂😂 Then we left! 🎉 Bye"), ["That was great 😂😂", "Then we left! 🎉", "Bye"]);
+        assert_eq!(texts("He said \"no.\" Then left\nsecond line"), ["He said \"no.\"", "Then left", "second line"]);
+        assert_eq!(texts("今天很好。你呢？我也好！"), ["今天很好。", "你呢？", "我也好！"]);
+        assert!(texts("  ").is_empty());
+
+        let spans = split_sentences("Ça va? Très bien.");
+        assert_eq!((spans[1].0, spans[1].1), (7, 17));
+    }
+
+    #[test]
+    fn extracts_emoji_sequences() {
+        assert_eq!(extract_emojis("hi 👍🏽 and 👨\u{200D}👩\u{200D}👧 😂😂"), ["👍🏽", "👨\u{200D}👩\u{200D}👧", "😂", "😂"]);
+        assert!(extract_emojis("plain text").is_empty());
+    }
+
+    #[test]
+    fn strips_bidi_controls_from_sender_keys() {
+        assert_eq!(sender_key("\u{200F}דנה\u{200E} "), "דנה");
+    }
+
+    #[test]
+    fn composes_sender_keys_and_folds_case_fully() {
+        assert_eq!(sender_key("Jose\u{0301} "), "Jos\u{00E9}");
+        assert!(matches!(nfc("Jos\u{00E9}"), Cow::Borrowed(_)));
+        assert_eq!(nfc("Jose\u{0301}"), "Jos\u{00E9}");
+        assert_eq!(fold_case("STRASSE"), fold_case("Straße"));
+        assert_eq!(fold_case("JOSE\u{0301}"), fold_case("jos\u{00E9}"));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::chat_parser::ChatParser;
+use crate::message::{Message, MessageType};
+use crate::text::{tokenize_words, STOP_WORDS};
+
+/// Messages on each side of a candidate boundary in `detect_topic_shifts`
+pub const TOPIC_WINDOW: usize = 5;
+
+/// Sparse TF-IDF vector keyed by term
+type TermVector = HashMap<String, f64>;
+
+/// Lowercased content words of a message, stop words removed
+fn terms(content: &str) -> Vec<String> {
+    let lowered = content.to_lowercase();
+    let terms = tokenize_words(&lowered)
+        .filter(|word| word.chars().count() > 1 && !STOP_WORDS.contains(word))
+        .map(str::to_string)
+        .collect();
+    terms
+}
+
+/// Inverse document frequency of each term, treating each message as a document
+fn inverse_document_frequency(documents: &[Vec<String>]) -> HashMap<&str, f64> {
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for document in documents {
+        let unique: HashSet<&str> = document.iter().map(String::as_str).collect();
+        for term in unique {
+            *frequency.entry(term).or_insert(0) += 1;
+        }
+    }
+    let total = documents.len() as f64;
+    frequency
+        .into_iter()
+        .map(|(term, count)| (term, (total / count as f64).ln() + 1.0))
+        .collect()
+}
+
+/// TF-IDF vector of several documents taken together
+fn window_vector(documents: &[Vec<String>], idf: &HashMap<&str, f64>) -> TermVector {
+    let mut vector = TermVector::new();
+    for term in documents.iter().flatten() {
+        *vector.entry(term.clone()).or_insert(0.0) += idf[term.as_str()];
+    }
+    vector
+}
+
+/// Cosine distance in [0, 1], or `None` if either vector is empty
+fn cosine_distance(left: &TermVector, right: &TermVector) -> Option<f64> {
+    let norm = |vector: &TermVector| vector.values().map(|weight| weight * weight).sum::<f64>().sqrt();
+    let (left_norm, right_norm) = (norm(left), norm(right));
+    if left_norm == 0.0 || right_norm == 0.0 {
+        return None;
+    }
+    let dot: f64 = left.iter().filter_map(|(term, weight)| right.get(term).map(|other| weight * other)).sum();
+    Some(1.0 - dot / (left_norm * right_norm))
+}
+
+impl ChatParser {
+    /// Indices of messages that start a new topic. Each boundary between
+    /// text messages is scored by the cosine distance between the TF-IDF
+    /// vectors of the `TOPIC_WINDOW` text messages before and after it;
+    /// within a run of boundaries scoring above `threshold`, the highest
+    /// scoring one is reported.
+    pub fn detect_topic_shifts(messages: &[Message], threshold: f64) -> Vec<usize> {
+        let (positions, documents): (Vec<usize>, Vec<Vec<String>>) = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| matches!(message.message_type, MessageType::Text))
+            .map(|(index, message)| (index, terms(&message.content)))
+            .unzip();
+        if documents.len() < 2 * TOPIC_WINDOW {
+            return Vec::new();
+        }
+        let idf = inverse_document_frequency(&documents);
+
+        let mut shifts = Vec::new();
+        let mut best: Option<(usize, f64)> = None;
+        for boundary in TOPIC_WINDOW..=documents.len() - TOPIC_WINDOW {
+            let before = window_vector(&documents[boundary - TOPIC_WINDOW..boundary], &idf);
+            let after = window_vector(&documents[boundary..boundary + TOPIC_WINDOW], &idf);
+            match cosine_distance(&before, &after) {
+                Some(distance) if distance > threshold => {
+                    if best.is_none_or(|(_, best_distance)| distance > best_distance) {
+                        best = Some((boundary, distance));
+                    }
+                }
+                _ => {
+                    if let Some((boundary, _)) = best.take() {
+                        shifts.push(positions[boundary]);
+                    }
+                }
+            }
+        }
+        if let Some((boundary, _)) = best {
+            shifts.push(positions[boundary]);
+        }
+        shifts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../../tests/fixtures/topic_shift_chat.txt");
+
+    #[test]
+    fn finds_shift_from_dinner_to_travel() {
+        let messages = ChatParser::new().parse_str(FIXTURE, false).unwrap();
+        let shifts = ChatParser::detect_topic_shifts(&messages, 0.8);
+        assert_eq!(shifts.len(), 1);
+        assert!(messages[shifts[0]].content.contains("flight"));
+        assert!(ChatParser::detect_topic_shifts(&messages[..8], 0.8).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use regex::Regex;
+
+use crate::error::ParserError;
+use crate::message::{Message, MessageType};
+use crate::patterns::{compile_pattern, PatternLimits};
+
+/// How a `TypeRule` pattern is compared with message content
+#[derive(Debug, Clone)]
+enum RuleMatcher {
+    Contains(String),
+    Prefix(String),
+    Exact(String),
+    Regex(Regex),
+}
+
+/// One user-defined classification: content matching the pattern gets the
+/// rule's message type
+#[derive(Debug, Clone)]
+pub struct TypeRule {
+    matcher: RuleMatcher,
+    message_type: MessageType,
+}
+
+impl TypeRule {
+    /// Build a rule from (rule_kind, pattern, resulting_type). `rule_kind` is
+    /// "contains", "prefix", "exact" or "regex" (compiled within the default
+    /// `PatternLimits`). `resulting_type` "text", "media" or "link" maps to
+    /// the built-in type; any other name becomes `MessageType::Custom`. Calls
+    /// and system events carry fields a rule can't supply, so those names are
+    /// rejected.
+    pub fn new(rule_kind: &str, pattern: &str, resulting_type: &str) -> Result<Self, ParserError> {
+        if pattern.is_empty() {
+            return Err(ParserError::InvalidArgument("Type rule pattern must not be empty".to_string()));
+        }
+        let matcher = match rule_kind {
+            "contains" => RuleMatcher::Contains(pattern.to_string()),
+            "prefix" => RuleMatcher::Prefix(pattern.to_string()),
+            "exact" => RuleMatcher::Exact(pattern.to_string()),
+            "regex" => RuleMatcher::Regex(compile_pattern(pattern, &PatternLimits::default())?),
+            other => {
+                return Err(ParserError::InvalidArgument(format!(
+                    "Unknown rule kind '{}'; expected 'contains', 'regex', 'prefix' or 'exact'",
+                    other
+                )))
+            }
+        };
+        let message_type = match resulting_type.trim() {
+            "" => return Err(ParserError::InvalidArgument("Type rule result must not be empty".to_string())),
+            "text" => MessageType::Text,
+            "media" => MessageType::Media,
+            "link" => MessageType::Link,
+            "call" | "reaction" | "system_event" => {
+                return Err(ParserError::InvalidArgument(format!(
+                    "Type rules can't produce '{}' messages",
+                    resulting_type
+                )))
+            }
+            name => MessageType::Custom(name.to_string()),
+        };
+        Ok(TypeRule { matcher, message_type })
+    }
+
+    fn matches(&self, content: &str) -> bool {
+        match &self.matcher {
+            RuleMatcher::Contains(pattern) => content.contains(pattern.as_str()),
+            RuleMatcher::Prefix(pattern) => content.starts_with(pattern.as_str()),
+            RuleMatcher::Exact(pattern) => content == pattern,
+            RuleMatcher::Regex(regex) => regex.is_match(content),
+        }
+    }
+}
+
+/// Ordered user rules consulted before the built-in message-type detection;
+/// the first matching rule wins
+#[derive(Debug, Clone, Default)]
+pub struct TypeRules {
+    rules: Vec<TypeRule>,
+}
+
+impl TypeRules {
+    /// Validate and compile (rule_kind, pattern, resulting_type) triples
+    /// (see `TypeRule::new`), failing on the first invalid one
+    pub fn new<S: AsRef<str>>(rules: &[(S, S, S)]) -> Result<Self, ParserError> {
+        let rules = rules
+            .iter()
+            .map(|(kind, pattern, resulting_type)| TypeRule::new(kind.as_ref(), pattern.as_ref(), resulting_type.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(TypeRules { rules })
+    }
+
+    /// Type given by the first rule matching `content`, if any
+    pub fn classify(&self, content: &str) -> Option<MessageType> {
+        self.rules.iter().find(|rule| rule.matches(content)).map(|rule| rule.message_type.clone())
+    }
+
+    /// Retype already-parsed messages the rules match; system events are
+    /// left alone
+    pub fn reclassify(&self, messages: &mut [Message]) {
+        for message in messages.iter_mut() {
+            if matches!(message.message_type, MessageType::SystemEvent(_) | MessageType::Reaction { .. }) {
+                continue;
+            }
+            if let Some(message_type) = self.classify(&message.content) {
+                message.message_type = message_type;
+                message.recount_words();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = TypeRules::new(&[
+            ("prefix", "[BOT]", "bot"),
+            ("regex", r"^\d+ ?€$", "expense"),
+            ("contains", "<Media omitted>", "text"),
+        ])
+        .unwrap();
+        assert_eq!(rules.classify("[BOT] build passed"), Some(MessageType::Custom("bot".to_string())));
+        assert_eq!(rules.classify("12 €").unwrap().as_str(), "expense");
+        assert_eq!(rules.classify("<Media omitted>"), Some(MessageType::Text));
+        assert!(rules.classify("hello").is_none());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(TypeRules::new(&[("suffix", "x", "bot")]).is_err());
+        assert!(TypeRules::new(&[("regex", "(unclosed", "bot")]).is_err());
+        assert!(TypeRules::new(&[("prefix", "", "bot")]).is_err());
+        assert!(TypeRules::new(&[("prefix", "x", "call")]).is_err());
+        assert!(TypeRules::new(&[("prefix", "x", " ")]).is_err());
+    }
+}
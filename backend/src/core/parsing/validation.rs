@@ -0,0 +1,224 @@
+//! A quick yes/no on an uploaded file before it is queued for a full parse.
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::chat_parser::{split_incomplete_line, ChatParser, ParseWarning};
+use crate::error::ParserError;
+
+/// Parse warnings reported by `validate_chat_file`
+const VALIDATION_WARNING_COUNT: usize = 3;
+
+/// How much of a file `validate_chat_file` may read
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    /// Lines parsed from each sample at most
+    pub sample_lines: usize,
+    /// Bytes read for each sample at most
+    pub sample_bytes: usize,
+    /// Also sample the middle and the end of large files, so an export
+    /// whose density changes over time is estimated better
+    pub sample_middle_and_tail: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions { sample_lines: 2000, sample_bytes: 256 * 1024, sample_middle_and_tail: true }
+    }
+}
+
+/// The verdict of `validate_chat_file`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatValidation {
+    /// A supported chat export: a known format in a readable encoding
+    pub acceptable: bool,
+    /// "text", or "zip" for an "Export chat" archive
+    pub container: &'static str,
+    /// The export format (see `ChatParser::detect_format`), if recognised
+    pub format: Option<&'static str>,
+    /// "utf-8", "utf-8-sig" (with a byte order mark), "utf-16-le",
+    /// "utf-16-be" or "unknown" (not valid UTF-8); only UTF-8 is parsed
+    pub encoding: &'static str,
+    /// Messages in the whole chat: exact when the sample covered it,
+    /// otherwise extrapolated from the sampled messages per byte
+    pub estimated_message_count: usize,
+    /// Size of the chat text (uncompressed, for a ZIP)
+    pub text_size: u64,
+    /// The first parse warnings of the head sample
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// One stretch of the chat text, cut to whole lines
+struct Sample {
+    bytes: Vec<u8>,
+    /// Whether it runs to the end of the text
+    complete: bool,
+}
+
+impl Sample {
+    /// Keep at most `max_lines` whole lines, dropping a partial last line
+    /// unless the sample runs to the end of the text
+    fn new(mut bytes: Vec<u8>, complete: bool, max_lines: usize) -> Sample {
+        let mut complete = complete;
+        if let Some(end) = memchr::memchr_iter(b'\n', &bytes).nth(max_lines.saturating_sub(1)) {
+            complete &= end + 1 == bytes.len();
+            bytes.truncate(end + 1);
+        } else if !complete {
+            let whole = split_incomplete_line(&bytes).0.len();
+            bytes.truncate(whole);
+        }
+        Sample { bytes, complete }
+    }
+
+    /// A sample starting mid-text: skip to the first line that starts a
+    /// message, so a continuation isn't reported as stray text
+    fn skip_to_header(&mut self, parser: &ChatParser) {
+        let mut start = 0;
+        for line in self.bytes.split_inclusive(|&byte| byte == b'\n') {
+            if parser.detect_format(&String::from_utf8_lossy(line)).is_some() {
+                break;
+            }
+            start += line.len();
+        }
+        self.bytes.drain(..start);
+    }
+}
+
+/// Name the encoding of the start of a text from its byte order mark, or
+/// by whether it is valid UTF-8 (a character cut off at the end is fine)
+fn detect_encoding(head: &[u8]) -> &'static str {
+    match head {
+        [0xEF, 0xBB, 0xBF, ..] => "utf-8-sig",
+        [0xFF, 0xFE, ..] => "utf-16-le",
+        [0xFE, 0xFF, ..] => "utf-16-be",
+        _ => match std::str::from_utf8(head) {
+            Err(error) if error.error_len().is_some() => "unknown",
+            _ => "utf-8",
+        },
+    }
+}
+
+/// Read up to `length` bytes at `offset`
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, length: usize) -> Result<Vec<u8>, ParserError> {
+    reader.seek(SeekFrom::Start(offset)).map_err(ParserError::io("Failed to read file"))?;
+    let mut bytes = Vec::with_capacity(length);
+    reader.take(length as u64).read_to_end(&mut bytes).map_err(ParserError::io("Failed to read file"))?;
+    Ok(bytes)
+}
+
+/// Judge a chat export from samples of it without reading it in full, for
+/// upload endpoints that need an answer quickly. Plain text is sampled at
+/// the head and, with `sample_middle_and_tail`, in the middle and at the
+/// end. For a ZIP only the central directory is read to find the chat
+/// entry and its size, and only the head of that entry is inflated.
+/// Samples are parsed leniently, so a bad timestamp shows up as a warning.
+pub fn validate_chat_file(path: &str, options: &ValidationOptions) -> Result<ChatValidation, ParserError> {
+    let mut file = File::open(path).map_err(ParserError::io("Failed to open file"))?;
+    let file_size = file.metadata().map_err(ParserError::io("Failed to open file"))?.len();
+    let magic = read_at(&mut file, 0, 4)?;
+    if magic == b"PK\x03\x04" {
+        return validate_zip(file, options);
+    }
+
+    let sample_bytes = options.sample_bytes.max(1);
+    let head = read_at(&mut file, 0, sample_bytes)?;
+    let mut samples = vec![Sample::new(head, file_size <= sample_bytes as u64, options.sample_lines)];
+    if options.sample_middle_and_tail && file_size > 3 * sample_bytes as u64 {
+        for offset in [(file_size - sample_bytes as u64) / 2, file_size - sample_bytes as u64] {
+            let bytes = read_at(&mut file, offset, sample_bytes)?;
+            samples.push(Sample::new(bytes, offset + sample_bytes as u64 >= file_size, options.sample_lines));
+        }
+    }
+    judge("text", file_size, samples)
+}
+
+#[cfg(feature = "zip")]
+fn validate_zip(file: File, options: &ValidationOptions) -> Result<ChatValidation, ParserError> {
+    let (head, text_size) = crate::archive::peek_chat_from_zip(std::io::BufReader::new(file), options.sample_bytes.max(1))?;
+    let complete = head.len() as u64 >= text_size;
+    judge("zip", text_size, vec![Sample::new(head, complete, options.sample_lines)])
+}
+
+#[cfg(not(feature = "zip"))]
+fn validate_zip(_file: File, _options: &ValidationOptions) -> Result<ChatValidation, ParserError> {
+    Err(ParserError::InvalidArgument("ZIP uploads need the zip feature".to_string()))
+}
+
+fn judge(container: &'static str, text_size: u64, mut samples: Vec<Sample>) -> Result<ChatValidation, ParserError> {
+    let mut parser = ChatParser::new();
+    parser.set_lenient(true);
+    let encoding = detect_encoding(&samples[0].bytes);
+    let format = parser.detect_format(&String::from_utf8_lossy(&samples[0].bytes));
+
+    let mut warnings = Vec::new();
+    let (mut messages, mut bytes) = (0, 0);
+    for (index, sample) in samples.iter_mut().enumerate() {
+        if index > 0 {
+            sample.skip_to_header(&parser);
+        }
+        let (parsed, sample_warnings) = parser.parse_reader_with_warnings(&sample.bytes[..], true)?;
+        if index == 0 {
+            warnings = sample_warnings.into_iter().take(VALIDATION_WARNING_COUNT).collect();
+        }
+        messages += parsed.len();
+        bytes += sample.bytes.len();
+    }
+    let estimated_message_count = if samples[0].complete || bytes == 0 {
+        messages
+    } else {
+        (messages as f64 / bytes as f64 * text_size as f64).round() as usize
+    };
+    Ok(ChatValidation {
+        acceptable: format.is_some() && matches!(encoding, "utf-8" | "utf-8-sig"),
+        container,
+        format,
+        encoding,
+        estimated_message_count,
+        text_size,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_temp(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("validation_{}_{}", std::process::id(), name));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn estimates_a_large_export_from_samples() {
+        let line = "[18/05/2023, 08:00:00] John: a line of exactly sixty-four bytes\n";
+        assert_eq!(line.len(), 64);
+        let mut text = line.repeat(20_000);
+        text.push_str("[31/02/2023, 08:00:00] John: bad date\n");
+        let path = write_temp("large.txt", text.as_bytes());
+        let options = ValidationOptions { sample_bytes: 4096, ..ValidationOptions::default() };
+        let validation = validate_chat_file(&path, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(validation.acceptable);
+        assert_eq!((validation.container, validation.format, validation.encoding), ("text", Some("whatsapp_ios"), "utf-8"));
+        assert!((19_900..=20_100).contains(&validation.estimated_message_count), "{}", validation.estimated_message_count);
+        assert!(validation.warnings.is_empty());
+    }
+
+    #[test]
+    fn small_files_are_counted_exactly_and_rejected_when_unrecognised() {
+        let path = write_temp("small.txt", b"[31/02/2023, 08:00:00] John: bad date\n[18/05/2023, 08:01:00] Alice: hi\n");
+        let validation = validate_chat_file(&path, &ValidationOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(validation.estimated_message_count, 1);
+        assert_eq!(validation.warnings[0].line_number, 1);
+
+        let path = write_temp("utf16.txt", &[0xFF, 0xFE, b'[', 0, b'1', 0]);
+        let validation = validate_chat_file(&path, &ValidationOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!validation.acceptable);
+        assert_eq!(validation.encoding, "utf-16-le");
+    }
+}
@@ -0,0 +1,29 @@
+//! Which build of the parser is running, for logging alongside its output.
+
+use serde::Serialize;
+
+/// The crate version from cargo.toml
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Details compiled in by build.rs; a field is "unknown" when the build
+/// couldn't find it (no git checkout, no Cargo.lock)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Abbreviated commit the crate was built from
+    pub git_hash: &'static str,
+    /// Target triple, "x86_64-unknown-linux-gnu"
+    pub target: &'static str,
+    /// The pyo3 version Cargo.lock resolved, whether or not the `python`
+    /// feature is enabled
+    pub pyo3_version: &'static str,
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: VERSION,
+        git_hash: env!("MEMORIES_GIT_HASH"),
+        target: env!("MEMORIES_BUILD_TARGET"),
+        pyo3_version: env!("MEMORIES_PYO3_VERSION"),
+    }
+}
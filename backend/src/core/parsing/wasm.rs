@@ -0,0 +1,74 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::analytics::profile_chat;
+use crate::chat_parser::{ChatParser, ParseConfig};
+use crate::export::MessageRecord;
+
+/// Serialise to plain JS objects (maps as objects, `None` as null)
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Read the JSON parse options; an empty string means the defaults
+fn parse_config(config_json: &str) -> Result<ParseConfig, JsValue> {
+    if config_json.trim().is_empty() {
+        return Ok(ParseConfig::default());
+    }
+    serde_json::from_str(config_json).map_err(|e| JsValue::from_str(&format!("Invalid parse config: {}", e)))
+}
+
+/// Parse an exported chat held in memory into an array of message objects.
+/// `config_json` is a JSON object with optional `include_system`,
+/// `user_identity` and `match_mode` fields.
+#[wasm_bindgen(js_name = parseChatText)]
+pub fn parse_chat_text(text: &str, config_json: &str) -> Result<JsValue, JsValue> {
+    let config = parse_config(config_json)?;
+    let messages = ChatParser::new()
+        .parse_str_with_config(text, &config)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    to_js(&messages.iter().map(MessageRecord::from).collect::<Vec<_>>())
+}
+
+/// Quick preview of an exported chat: counts, participants and date range
+#[wasm_bindgen(js_name = profileChatText)]
+pub fn profile_chat_text(text: &str) -> Result<JsValue, JsValue> {
+    let messages = ChatParser::new().parse_str(text, true).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    to_js(&profile_chat(&messages))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const FIXTURE: &str = include_str!("../../../tests/fixtures/late_night_chat.txt");
+
+    #[wasm_bindgen_test]
+    fn parses_fixture_into_js_objects() {
+        let value = parse_chat_text(FIXTURE, r#"{"user_identity": "Alice"}"#).unwrap();
+        let messages: Vec<serde_json::Value> = serde_wasm_bindgen::from_value(value).unwrap();
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[0]["sender"], "John");
+        assert_eq!(messages[0]["type"], "text");
+        assert_eq!(messages[1]["is_own"], true);
+        let timestamp = messages[0]["timestamp"].as_str().unwrap();
+        assert!(timestamp.starts_with("2023-05-18T21:15:00"));
+    }
+
+    #[wasm_bindgen_test]
+    fn profiles_fixture() {
+        let value = profile_chat_text(FIXTURE).unwrap();
+        let profile: serde_json::Value = serde_wasm_bindgen::from_value(value).unwrap();
+        assert_eq!(profile["message_count"], 6);
+        assert_eq!(profile["participants"].as_array().unwrap().len(), 2);
+        assert!(profile["last_message"].as_str().unwrap().starts_with("2023-05-19T09:00:00"));
+    }
+
+    #[wasm_bindgen_test]
+    fn rejects_bad_config() {
+        assert!(parse_chat_text(FIXTURE, "{not json").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Integration tests for the `memories-parse` binary (`cargo test --features cli`)
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+
+use assert_cmd::Command;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/late_night_chat.txt");
+
+fn memories_parse() -> Command {
+    Command::cargo_bin("memories-parse").unwrap()
+}
+
+fn json_lines(output: &[u8]) -> Vec<serde_json::Value> {
+    String::from_utf8_lossy(output).lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[test]
+fn exports_jsonl_to_stdout() {
+    let output = memories_parse().arg(FIXTURE).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let messages = json_lines(&output.stdout);
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[0]["sender"], "John");
+    assert_eq!(messages[0]["content"], "Dinner was good");
+}
+
+#[test]
+fn writes_to_out_file() {
+    let out = std::env::temp_dir().join(format!("memories-parse-{}.jsonl", std::process::id()));
+    memories_parse().arg(FIXTURE).args(["--format", "redacted", "--out"]).arg(&out).assert().success();
+    let written = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    let records = json_lines(&written);
+    assert_eq!(records.len(), 6);
+    assert_eq!(records[0]["content_length"], 15);
+    assert!(!String::from_utf8_lossy(&written).contains("Dinner"));
+}
+
+#[test]
+fn reads_stdin_and_prints_stats() {
+    let output = memories_parse()
+        .args(["-", "--stats"])
+        .write_stdin(std::fs::read(FIXTURE).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stats = &json_lines(&output.stdout)[0];
+    assert_eq!(stats["message_count"], 6);
+    assert_eq!(stats["participants"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn detects_format_and_rejects_other_text() {
+    let output = memories_parse().args([FIXTURE, "--detect"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json_lines(&output.stdout)[0]["format"], "whatsapp_ios");
+
+    let output = memories_parse().args(["-", "--detect"]).write_stdin("just some notes\n").output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(json_lines(&output.stdout)[0]["format"].is_null());
+}
+
+#[test]
+fn partial_parse_reports_warnings_as_json() {
+    let input = format!("exported by a third-party tool\n{}", std::fs::read_to_string(FIXTURE).unwrap());
+    let output = memories_parse().args(["-", "--senders", "--warnings-json"]).write_stdin(input).output().unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(json_lines(&output.stdout).len(), 2);
+    let warnings = json_lines(&output.stderr);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["line_number"], 1);
+}
+
+#[test]
+fn lenient_skips_bad_timestamps() {
+    let input = format!("{}[31/02/2023, 08:40:00] Alice: Impossible date\n", std::fs::read_to_string(FIXTURE).unwrap());
+    let output = memories_parse().args(["-", "--stats"]).write_stdin(input.clone()).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = memories_parse().args(["-", "--stats", "--lenient"]).write_stdin(input).output().unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(json_lines(&output.stdout)[0]["message_count"], 6);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Message skipped"));
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+    let output = memories_parse().arg("/nonexistent/chat.txt").output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to open file"));
+}
+
+#[test]
+fn reads_chat_from_zip() {
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    archive.start_file("IMG-20230518-WA0001.jpg", options).unwrap();
+    archive.write_all(b"\xff\xd8").unwrap();
+    archive.start_file("_chat.txt", options).unwrap();
+    archive.write_all(&std::fs::read(FIXTURE).unwrap()).unwrap();
+    let bytes = archive.finish().unwrap().into_inner();
+
+    let output = memories_parse().args(["-", "--zip", "--senders"]).write_stdin(bytes).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let senders = json_lines(&output.stdout);
+    assert_eq!(senders.len(), 2);
+    assert_eq!(senders.iter().map(|sender| sender["message_count"].as_u64().unwrap()).sum::<u64>(), 6);
+}
@@ -93,4 +93,33 @@ Just some random text
         messages = parse_with_python(file_path, "Test User")
         assert len(messages) == 0
     finally:
-        os.unlink(file_path)
\ No newline at end of file
+        os.unlink(file_path)
+
+def test_parse_with_python_empty_content():
+    """Test a line with nothing after the colon is kept as an empty text message."""
+    content = """[18/05/2023, 08:39:07] John: 
+[18/05/2023, 08:40:15] Test User: Hello?
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = parse_with_python(file_path, "Test User")
+        assert len(messages) == 2
+        assert messages[0].sender == "John"
+        assert messages[0].content == ""
+        assert messages[0].type == "text"
+    finally:
+        os.unlink(file_path)
+
+def test_parse_with_python_seconds_less_timestamps():
+    """Test timestamps without seconds parse as HH:MM:00."""
+    content = """[18/05/2023, 08:39] John: No seconds here
+[18/05/2023, 08:40:15] Test User: With seconds
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = parse_with_python(file_path, "Test User")
+        assert len(messages) == 2
+        assert messages[0].timestamp == datetime(2023, 5, 18, 8, 39, 0)
+        assert messages[1].timestamp == datetime(2023, 5, 18, 8, 40, 15)
+    finally:
+        os.unlink(file_path)
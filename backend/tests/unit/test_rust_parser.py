@@ -0,0 +1,1835 @@
+import pytest # type: ignore
+import json
+import os
+import signal
+import tempfile
+import threading
+import time
+from datetime import datetime
+from pathlib import Path
+
+whatsapp_parser = pytest.importorskip("whatsapp_parser")
+
+TEST_DATA_DIR = Path(__file__).parent.parent / "fixtures"
+
+def create_test_chat_file(content):
+    """Create a temporary file with the given content."""
+    with tempfile.NamedTemporaryFile(mode="w", suffix=".txt", delete=False) as f:
+        f.write(content)
+        return f.name
+
+def test_sentiment_by_sender():
+    """Test per-sender sentiment averages skip unscored messages."""
+    content = """[18/05/2023, 08:39:07] John: What a great day!
+[18/05/2023, 08:40:15] Alice: This is awful.
+[18/05/2023, 08:41:00] John: Loving it
+[18/05/2023, 08:42:30] Alice: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert len(messages) == 4
+
+        for message, score in zip(messages, [0.8, -0.6, 0.4, None]):
+            message.sentiment_score = score
+
+        averages = whatsapp_parser.sentiment_by_sender(messages)
+        assert averages == pytest.approx({"John": 0.6, "Alice": -0.6})
+    finally:
+        os.unlink(file_path)
+
+def test_subject_history():
+    """Test the group subject timeline starts at creation and follows renames."""
+    content = """[01/05/2023, 10:00:00] Alice created group "Trip"
+[01/05/2023, 10:01:00] Alice: Welcome all
+[03/05/2023, 09:00:00] Bob changed the subject from "Trip" to "Goa Trip"
+[10/05/2023, 18:30:00] Trip Group: Alice changed the subject to "Goa 2023"
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        assert len(parser.parse_chat(file_path)) == 1
+
+        events = parser.parse_events(file_path)
+        history = whatsapp_parser.subject_history(events)
+        assert [(subject, actor) for _, subject, actor in history] == [
+            ("Trip", "Alice"),
+            ("Goa Trip", "Bob"),
+            ("Goa 2023", "Alice"),
+        ]
+
+        assert whatsapp_parser.subject_at(events, "2023-04-30T00:00:00+00:00") is None
+        assert whatsapp_parser.subject_at(events, "2023-05-05T00:00:00+00:00") == "Goa Trip"
+        assert whatsapp_parser.subject_at(events, "2023-06-01T00:00:00+00:00") == "Goa 2023"
+    finally:
+        os.unlink(file_path)
+
+def test_subject_history_without_creation_line():
+    """Test the history starts with the first rename when the creation line is missing."""
+    content = """[03/05/2023, 09:00:00] Bob changed the subject to "Goa Trip"
+[03/05/2023, 09:05:00] Bob: Better name
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        events = whatsapp_parser.ChatParser().parse_events(file_path)
+        history = whatsapp_parser.subject_history(events)
+        assert len(history) == 1
+        assert history[0][1:] == ("Goa Trip", "Bob")
+        assert events[0].event == {"kind": "subject_changed", "actor": "Bob", "new_subject": "Goa Trip"}
+    finally:
+        os.unlink(file_path)
+
+def test_parse_chat_tolerates_invalid_utf8():
+    """Test an invalid byte in one line doesn't abort the parse."""
+    content = (
+        "[18/05/2023, 08:39:07] John: Hello\n".encode("utf-8")
+        + b"[18/05/2023, 08:40:15] Alice: broken \xff byte\n"
+        + "[18/05/2023, 08:42:30] John: Still here ❤\n".encode("utf-8")
+    )
+    with tempfile.NamedTemporaryFile(mode="wb", suffix=".txt", delete=False) as f:
+        f.write(content)
+        file_path = f.name
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert len(messages) == 3
+        assert messages[1].content == "broken � byte"
+        assert messages[2].content == "Still here ❤"
+    finally:
+        os.unlink(file_path)
+
+def test_parse_loose_without_timestamps():
+    """Test parsing copied chat text with synthetic timestamps."""
+    text = """Alice: hey
+Bob: hi there
+Note: buy milk
+Alice: did you see
+the game yesterday?
+Bob: yes!"""
+    messages = whatsapp_parser.ChatParser().parse_loose(
+        text, base="2023-05-18T10:00:00+00:00", spacing_seconds=30
+    )
+    assert [m.sender for m in messages] == ["Alice", "Bob", "Alice", "Bob"]
+    assert messages[1].content == "hi there\nNote: buy milk"
+    assert messages[2].content == "did you see\nthe game yesterday?"
+    assert datetime.fromisoformat(messages[0].timestamp) == datetime.fromisoformat("2023-05-18T10:00:00+00:00")
+    assert datetime.fromisoformat(messages[3].timestamp) == datetime.fromisoformat("2023-05-18T10:01:30+00:00")
+    assert all(m.timestamp_synthetic for m in messages)
+    assert whatsapp_parser.has_synthetic_timestamps(messages)
+
+    with pytest.raises(ValueError, match="synthetic"):
+        whatsapp_parser.subject_history(messages)
+    with pytest.raises(ValueError, match="synthetic"):
+        whatsapp_parser.export_graph(messages, "unused.dot", "dot")
+
+def test_parse_loose_with_participants():
+    """Test a single-occurrence sender is accepted when listed as a participant."""
+    text = "Alice: hey\nCarol: only once\nAlice: ok"
+    messages = whatsapp_parser.ChatParser().parse_loose(text, participants=["Carol"])
+    assert [m.sender for m in messages] == ["Alice", "Carol", "Alice"]
+
+def test_count_messages_by_weekday():
+    """Test weekday counts are indexed Monday=0 and the busiest day is found."""
+    content = """[15/05/2023, 09:00:00] John: Monday
+[17/05/2023, 09:00:00] John: Wednesday
+[17/05/2023, 12:00:00] Alice: Wednesday again
+[21/05/2023, 23:30:00] Alice: Sunday night
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        counts = whatsapp_parser.ChatParser.count_messages_by_weekday(messages)
+        assert counts == [1, 0, 2, 0, 0, 0, 1]
+        assert whatsapp_parser.ChatParser.busiest_weekday(messages) == 2
+    finally:
+        os.unlink(file_path)
+
+def test_parse_whatsapp_chat_system_messages():
+    """Test system messages are skipped by default and kept on request."""
+    content = """[01/05/2023, 10:00:00] Alice created group "Trip"
+[01/05/2023, 10:01:00] Alice: Welcome all
+[01/05/2023, 10:02:00] Messages and calls are end-to-end encrypted.
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.parse_whatsapp_chat(file_path, "Alice")
+        assert [m["type"] for m in messages] == ["text"]
+
+        messages = whatsapp_parser.parse_whatsapp_chat(file_path, "Alice", skip_system_messages=False)
+        assert [m["type"] for m in messages] == ["system_event", "text", "system_event"]
+        assert messages[0]["event"] == {"kind": "created", "actor": "Alice", "subject": "Trip"}
+        assert messages[2]["event"]["kind"] == "other"
+        assert messages[2]["sender"] == ""
+    finally:
+        os.unlink(file_path)
+
+def test_merge_chats_overlapping_exports():
+    """Test merging two overlapping exports yields the deduplicated union."""
+    first = create_test_chat_file("""[18/05/2023, 08:39:07] John: Hello
+[18/05/2023, 08:40:15] Alice: Hi
+[18/05/2023, 08:42:30] John: How are you?
+""")
+    second = create_test_chat_file("""[18/05/2023, 08:40:15] Alice: Hi
+[18/05/2023, 08:42:30] John: How are you?
+[18/05/2023, 09:00:00] Alice: Good, thanks
+""")
+    try:
+        parser = whatsapp_parser.ChatParser()
+        merged = whatsapp_parser.merge_chats([parser.parse_chat(second), parser.parse_chat(first)])
+        assert [m.content for m in merged] == ["Hello", "Hi", "How are you?", "Good, thanks"]
+        assert [m.id for m in merged] == ["msg_1", "msg_2", "msg_3", "msg_4"]
+    finally:
+        os.unlink(first)
+        os.unlink(second)
+
+def test_keyboard_interrupt_during_parse_is_honored():
+    """Test Ctrl-C during a long parse/convert interrupts within bounded time."""
+    lines = [f"[18/05/2023, 08:{i // 60 % 60:02d}:{i % 60:02d}] John: message number {i}\n" for i in range(300_000)]
+    file_path = create_test_chat_file("".join(lines))
+
+    def interrupt(signum, frame):
+        raise KeyboardInterrupt
+
+    previous = signal.signal(signal.SIGALRM, interrupt)
+    try:
+        signal.setitimer(signal.ITIMER_REAL, 0.01)
+        started = time.monotonic()
+        with pytest.raises(KeyboardInterrupt):
+            whatsapp_parser.parse_whatsapp_chat(file_path, "John")
+        assert time.monotonic() - started < 10
+    finally:
+        signal.setitimer(signal.ITIMER_REAL, 0)
+        signal.signal(signal.SIGALRM, previous)
+        os.unlink(file_path)
+
+def test_compute_sentiment_scores_emoji_weight():
+    """Test emoji weighting turns an otherwise-neutral message positive."""
+    content = """[18/05/2023, 08:39:07] John: See you at 5 😀
+[18/05/2023, 08:40:15] Alice: This is awful
+[18/05/2023, 08:42:30] John: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+
+        whatsapp_parser.ChatParser.compute_sentiment_scores(messages)
+        assert messages[0].sentiment_score == 0.0
+        assert messages[1].sentiment_score < 0
+        assert messages[2].sentiment_score is None
+
+        whatsapp_parser.ChatParser.compute_sentiment_scores(messages, emoji_weight=1.0)
+        assert messages[0].sentiment_score > 0
+    finally:
+        os.unlink(file_path)
+
+def test_split_by_ownership_match_modes():
+    """Test own/others split with exact, prefix and fuzzy identity matching."""
+    content = """[18/05/2023, 08:39:07] John: Hello
+[18/05/2023, 08:40:15] Vishnu Tej: Hi John
+[18/05/2023, 08:42:30] John: How are you?
+[18/05/2023, 08:43:00] Vishnu Tej: Good
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path, "vishnu", match_mode="prefix")
+        assert [m.is_own for m in messages] == [False, True, False, True]
+
+        own, others, name = whatsapp_parser.split_by_ownership(messages, "Vishnu", "prefix")
+        assert (own, others, name) == ([1, 3], [0, 2], "Vishnu Tej")
+
+        assert whatsapp_parser.split_by_ownership(messages, "Vishny", "fuzzy")[2] == "Vishnu Tej"
+
+        with pytest.raises(ValueError, match="detected senders: John, Vishnu Tej"):
+            whatsapp_parser.split_by_ownership(messages, "Vishnu")
+
+        dicts = whatsapp_parser.parse_whatsapp_chat(file_path, "Vishnu Tej")
+        assert [m["is_own"] for m in dicts] == [False, True, False, True]
+    finally:
+        os.unlink(file_path)
+
+def test_detect_late_night_confessions():
+    """Test intense messages inside a window wrapping midnight are found."""
+    parser = whatsapp_parser.ChatParser()
+    messages = parser.parse_chat(str(TEST_DATA_DIR / "late_night_chat.txt"))
+    whatsapp_parser.ChatParser.compute_sentiment_scores(messages)
+
+    confessions = whatsapp_parser.ChatParser.detect_late_night_confessions(messages, 23, 4, 0.5)
+    assert [m.content for m in confessions] == [
+        "I hate how lonely I feel lately",
+        "I love you, you are not alone",
+        "thanks, that was wonderful to hear",
+    ]
+
+def test_compute_sentiment_by_hour():
+    """Test positive evening messages score high in hours 18-22 only."""
+    messages = whatsapp_parser.ChatParser().parse_chat(str(TEST_DATA_DIR / "evening_chat.txt"))
+    whatsapp_parser.ChatParser.compute_sentiment_scores(messages)
+
+    by_hour = whatsapp_parser.ChatParser.compute_sentiment_by_hour(messages)
+    assert len(by_hour) == 24
+    assert all(by_hour[hour] > 0.3 for hour in range(18, 23))
+    assert all(by_hour[hour] == 0.0 for hour in range(24) if not 18 <= hour <= 22)
+
+def test_detect_code_snippets():
+    """Test code-like messages are flagged without false positives on prose."""
+    content = """[18/05/2023, 08:39:07] John: try this: if (x) { return y; }
+[18/05/2023, 08:40:15] Alice: I went home; it was late
+[18/05/2023, 08:41:00] John: use `git rebase` instead
+[18/05/2023, 08:42:30] Alice: like so:
+    for i in range(3):
+        print(i)
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        snippets = whatsapp_parser.ChatParser.detect_code_snippets(messages)
+        assert [m.id for m in snippets] == ["msg_1", "msg_3", "msg_4"]
+    finally:
+        os.unlink(file_path)
+
+def test_self_joined_event():
+    """Test an "added you" line becomes a self_joined event with its timestamp."""
+    content = """[02/05/2023, 18:20:05] Alice added you
+[02/05/2023, 18:21:00] Alice: Welcome!
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        events = whatsapp_parser.ChatParser().parse_events(file_path)
+        assert len(events) == 1
+        event = events[0].event
+        assert event["kind"] == "self_joined"
+        assert datetime.fromisoformat(event["timestamp"]) == datetime(2023, 5, 2, 18, 20, 5).astimezone()
+    finally:
+        os.unlink(file_path)
+
+def test_detect_anomalies_flags_busy_day():
+    """Test a sudden spike in volume is reported with its supporting numbers."""
+    lines = []
+    for day in range(1, 21):
+        for i in range(2 + day % 2):
+            lines.append(f"[{day:02d}/05/2023, 10:{i:02d}:00] John: Morning {i}")
+    for i in range(40):
+        lines.append(f"[21/05/2023, 12:{i:02d}:00] Alice: Big news {i}")
+    file_path = create_test_chat_file("\n".join(lines) + "\n")
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        anomalies = whatsapp_parser.detect_anomalies(messages, 3.0)
+        assert anomalies[0].date == "2023-05-21"
+        assert anomalies[0].metric == "message_count"
+        assert anomalies[0].value == 40
+        assert anomalies[0].magnitude > 3.0
+        assert [a.magnitude for a in anomalies] == sorted((a.magnitude for a in anomalies), reverse=True)
+    finally:
+        os.unlink(file_path)
+
+def test_sender_counts_sorted_by_count():
+    """Test sender counts are computed and ordered most active first."""
+    content = """[18/05/2023, 08:39:07] John: Hello
+[18/05/2023, 08:40:15] Alice: Hi
+[18/05/2023, 08:41:00] Bob: Hey
+[18/05/2023, 08:42:30] Alice: How are you?
+[18/05/2023, 08:43:00] Alice: Anyone?
+[18/05/2023, 08:44:00] Bob: Here
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        counts = whatsapp_parser.ChatParser().sender_counts(file_path)
+        assert counts == [("Alice", 3), ("Bob", 2), ("John", 1)]
+    finally:
+        os.unlink(file_path)
+
+def test_month_over_month_growth():
+    """Test doubling volume is 100% growth and growth after an empty month is infinite."""
+    content = """[10/01/2023, 08:00:00] John: one
+[11/01/2023, 08:00:00] Alice: two
+[01/02/2023, 08:00:00] John: three
+[02/02/2023, 08:00:00] John: four
+[03/02/2023, 08:00:00] Alice: five
+[04/02/2023, 08:00:00] Alice: six
+[05/04/2023, 08:00:00] John: seven
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        growth = whatsapp_parser.ChatParser.month_over_month_growth(messages)
+        assert growth == [("2023-02", 100.0), ("2023-03", -100.0), ("2023-04", float("inf"))]
+    finally:
+        os.unlink(file_path)
+
+def test_bidi_sender_names_and_isolation():
+    """Test RTL sender names are keyed without bidi marks but displayed as exported."""
+    parser = whatsapp_parser.ChatParser()
+    messages = parser.parse_chat(str(TEST_DATA_DIR / "bidi_chat.txt"))
+    assert [m.sender for m in messages] == ["أحمد", "דנה", "أحمد", "דנה"]
+    assert messages[0].sender_display == "‏أحمد‏"
+    assert messages[3].sender_display == "‫דנה‬"
+    assert parser.sender_counts(str(TEST_DATA_DIR / "bidi_chat.txt")) == [("דנה", 2), ("أحمد", 2)]
+
+    mixed = messages[2].content
+    assert whatsapp_parser.bidi_isolate(mixed) == "⁨" + mixed + "⁩"
+    assert whatsapp_parser.bidi_isolate("<b>שלום</b>", html=True) == '<span dir="auto">&lt;b&gt;שלום&lt;/b&gt;</span>'
+
+def test_detect_shared_events():
+    """Test date references in different styles are grouped by the date they mean."""
+    content = """[18/05/2023, 08:39:07] John: Party next Friday?
+[18/05/2023, 08:40:15] Alice: Yes! Friday works. Also the concert is on the 3rd
+[19/05/2023, 10:00:00] Bob: see you on May 26th then
+[20/05/2023, 11:00:00] Alice: Christmas plans: December 25 at mine
+[20/05/2023, 11:05:00] John: I have 2 tickets for 3 people
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        events = whatsapp_parser.ChatParser.detect_shared_events(messages)
+        summary = [(e.referenced_date, e.message_count, e.participants) for e in events]
+        assert summary == [
+            ("2023-05-26", 2, ["Bob", "John"]),
+            ("2023-06-03", 1, ["Alice"]),
+            ("2023-12-25", 1, ["Alice"]),
+        ]
+    finally:
+        os.unlink(file_path)
+
+def test_parse_chat_empty_content():
+    """Test a line with nothing after the colon is kept as an empty text message."""
+    content = """[18/05/2023, 08:39:07] John: 
+[18/05/2023, 08:39:30] Alice:
+[18/05/2023, 08:40:15] Test User: Hello?
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert [(m.sender, m.content, m.message_type) for m in messages] == [
+            ("John", "", "text"),
+            ("Alice", "", "text"),
+            ("Test User", "Hello?", "text"),
+        ]
+    finally:
+        os.unlink(file_path)
+
+def test_style_metrics():
+    """Test per-sender style metrics, the baseline and the minimum-message filter."""
+    content = """[18/05/2023, 08:00:00] John: Hello there. How are you?
+[18/05/2023, 08:01:00] Alice: haha good 😂😂
+[18/05/2023, 08:02:00] John: Great. Lunch later?
+[18/05/2023, 08:03:00] Alice: jajaja sure
+[18/05/2023, 08:04:00] Bob: ok
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        per_sender, baseline = whatsapp_parser.style_metrics(messages, min_messages=2)
+        assert set(per_sender) == {"John", "Alice"}
+
+        john, alice = per_sender["John"], per_sender["Alice"]
+        assert john["capitalization_rate"] == 1.0
+        assert alice["capitalization_rate"] == 0.0
+        assert john["avg_words_per_message"] == 4.0
+        assert alice["emoji_per_message"] == 1.0
+        assert alice["laughter_rate"] == 100.0
+        assert john["laughter_rate"] == 0.0
+        assert alice["relative_response_length"] < john["relative_response_length"]
+        assert baseline["emoji_per_message"] == pytest.approx(0.4)
+    finally:
+        os.unlink(file_path)
+
+def test_heatmap():
+    """Test the weekday-by-hour activity grid."""
+    content = """[15/05/2023, 09:15:00] John: Monday morning
+[15/05/2023, 09:45:00] Alice: Still Monday morning
+[20/05/2023, 23:30:00] John: Saturday night
+[21/05/2023, 00:05:00] Alice: Sunday just after midnight
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        grid = whatsapp_parser.ChatParser.heatmap(messages)
+        assert len(grid) == 7 and all(len(row) == 24 for row in grid)
+        assert grid[0][9] == 2
+        assert grid[5][23] == 1
+        assert grid[6][0] == 1
+        assert sum(sum(row) for row in grid) == 4
+    finally:
+        os.unlink(file_path)
+
+def test_find_messages_by_content():
+    """Test substring search over message content."""
+    content = """[18/05/2023, 08:00:00] John: Pizza tonight?
+[18/05/2023, 08:01:00] Alice: I love pizza
+[18/05/2023, 08:02:00] John: Burgers then
+[18/05/2023, 08:03:00] Alice: ÉCOLE tomorrow, école today
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        find = whatsapp_parser.ChatParser.find_messages_by_content
+        assert find(messages, "pizza") == [0, 1]
+        assert find(messages, "pizza", case_sensitive=True) == [1]
+        assert find(messages, "Pizza", True) == [0]
+        assert find(messages, "école") == [3]
+        assert find(messages, "sushi") == []
+    finally:
+        os.unlink(file_path)
+
+def test_export_graph():
+    """Test DOT and GEXF interaction graph export, including awkward names."""
+    import xml.etree.ElementTree as ET
+
+    lines = []
+    for i in range(50):
+        lines.append(f"[18/05/2023, 10:{i:02d}:00] Person {i}: hello @Person {(i + 1) % 50}")
+    lines.append('[18/05/2023, 11:00:00] Jo "JJ" O\'Neil & Ünal <x>: hi all')
+    lines.append("[18/05/2023, 11:00:30] Person 0: welcome")
+    file_path = create_test_chat_file("\n".join(lines) + "\n")
+    dot_path = file_path + ".dot"
+    gexf_path = file_path + ".gexf"
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        whatsapp_parser.ChatParser.compute_sentiment_scores(messages)
+
+        whatsapp_parser.export_graph(messages, gexf_path, "gexf")
+        ns = {"g": "http://gexf.net/1.3"}
+        root = ET.parse(gexf_path).getroot()
+        nodes = root.findall("g:graph/g:nodes/g:node", ns)
+        edges = root.findall("g:graph/g:edges/g:edge", ns)
+        assert len(nodes) == 51
+        labels = {node.get("label") for node in nodes}
+        assert 'Jo "JJ" O\'Neil & Ünal <x>' in labels
+        node_ids = {node.get("id") for node in nodes}
+        assert all(edge.get("source") in node_ids and edge.get("target") in node_ids for edge in edges)
+        # Person 1 replied to Person 0 and mentioned Person 2
+        by_label = {node.get("label"): node.get("id") for node in nodes}
+        pairs = {(edge.get("source"), edge.get("target")): edge for edge in edges}
+        assert pairs[(by_label["Person 1"], by_label["Person 0"])].get("weight") == "1"
+        assert (by_label["Person 1"], by_label["Person 2"]) in pairs
+        assert pairs[(by_label["Person 0"], by_label['Jo "JJ" O\'Neil & Ünal <x>'])] is not None
+
+        whatsapp_parser.export_graph(messages, dot_path, "dot")
+        with open(dot_path, encoding="utf-8") as f:
+            dot = f.read()
+        assert dot.startswith("digraph chat {") and dot.rstrip().endswith("}")
+        assert '"Jo \\"JJ\\" O\'Neil & Ünal <x>"' in dot
+        assert '"Person 1" -> "Person 0"' in dot
+        assert "median_response_seconds=60" in dot
+
+        with pytest.raises(ValueError, match="graph format"):
+            whatsapp_parser.export_graph(messages, dot_path, "graphml")
+    finally:
+        for path in (file_path, dot_path, gexf_path):
+            if os.path.exists(path):
+                os.unlink(path)
+
+def test_compute_formality_score():
+    """Test the per-sender formality score."""
+    content = """[18/05/2023, 08:00:00] John: Good morning. Are you coming today?
+[18/05/2023, 08:01:00] Alice: lol u coming or not
+[18/05/2023, 08:02:00] John: Yes, I will be there at noon.
+[18/05/2023, 08:03:00] Alice: Sure, see you there
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        scores = whatsapp_parser.ChatParser.compute_formality_score(messages)
+        assert scores["John"] == pytest.approx(1.0)
+        # Alice: 1/2 capitalized, 0/2 punctuated, 1/2 without abbreviations
+        assert scores["Alice"] == pytest.approx(1.0 / 3.0)
+    finally:
+        os.unlink(file_path)
+
+def test_broadcast_marker():
+    """Test that marked broadcast-list messages are flagged and unmarked ones are not."""
+    content = """[18/05/2023, 08:00:00] John (Broadcast): Party on Saturday!
+[18/05/2023, 08:01:00] Alice: [Broadcast] Shop opens at 9
+[18/05/2023, 08:02:00] Alice: Just a normal message
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert [m.broadcast for m in messages] == [True, True, False]
+        assert messages[0].sender == "John"
+        assert messages[0].content == "Party on Saturday!"
+        assert messages[1].content == "Shop opens at 9"
+
+        dicts = whatsapp_parser.parse_whatsapp_chat(file_path, "Alice")
+        assert [m["broadcast"] for m in dicts] == [True, True, False]
+    finally:
+        os.unlink(file_path)
+
+def test_call_records_and_stats():
+    """Test call-log lines becoming call messages and the call_stats summary."""
+    content = """[18/05/2023, 08:00:00] John: Missed voice call
+[18/05/2023, 08:05:00] Alice: ‎Voice call, 12 min
+[18/05/2023, 20:00:00] John: Video call, 1 hr 5 min
+[19/05/2023, 20:30:00] Alice: Missed video call, Tap to call back
+[19/05/2023, 21:00:00] John: Llamada de voz, 45 s
+[19/05/2023, 21:10:00] Alice: Sprachanruf, 1,5 Min.
+[19/05/2023, 21:20:00] John: Voice call me later please
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert [m.message_type for m in messages] == ["call"] * 6 + ["text"]
+        assert messages[0].call == {"video": False, "missed": True, "duration_seconds": None}
+        assert messages[1].call["duration_seconds"] == 720
+        assert messages[2].call == {"video": True, "missed": False, "duration_seconds": 3900}
+        assert messages[4].call["duration_seconds"] == 45
+        assert messages[5].call["duration_seconds"] == 90
+        assert messages[6].call is None
+
+        stats = whatsapp_parser.call_stats(messages)
+        assert stats.total_calls == 6
+        assert stats.video_calls == 2
+        assert stats.missed_calls == 2
+        assert stats.missed_rate_by_caller == pytest.approx({"John": 1 / 3, "Alice": 1 / 3})
+        assert stats.total_duration_seconds == 720 + 3900 + 45 + 90
+        assert stats.longest_call_seconds == 3900
+        assert stats.longest_call_caller == "John"
+        assert stats.calls_by_hour[20] == 2 and sum(stats.calls_by_hour) == 6
+    finally:
+        os.unlink(file_path)
+
+def test_language_detection():
+    """Test per-message language detection and the language breakdown."""
+    content = """[18/05/2023, 08:00:00] John: I will meet you at the station tomorrow morning after breakfast
+[18/05/2023, 08:01:00] Alice: Mañana por la mañana vamos a la playa con mis hermanos y mis amigos
+[18/05/2023, 08:02:00] John: ok
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert whatsapp_parser.detect_language(messages[0].content) == "eng"
+        assert whatsapp_parser.detect_language(messages[1].content) == "spa"
+        assert whatsapp_parser.detect_language("ok") is None
+        assert whatsapp_parser.language_breakdown(messages) == {"eng": 1, "spa": 1}
+    finally:
+        os.unlink(file_path)
+
+def test_split_by_conversation_gap():
+    """Test splitting a chat into sessions at long silences."""
+    content = """[18/05/2023, 08:00:00] John: Morning
+[18/05/2023, 08:30:00] Alice: Morning!
+[18/05/2023, 14:00:00] John: Lunch was great
+[18/05/2023, 15:00:00] Alice: Agreed
+[20/05/2023, 09:00:00] John: Weekend plans?
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        sessions = whatsapp_parser.ChatParser.split_by_conversation_gap(messages, 4.0)
+        assert [len(session) for session in sessions] == [2, 2, 1]
+        assert sessions[1][0].content == "Lunch was great"
+        assert sessions[2][0].content == "Weekend plans?"
+
+        assert len(whatsapp_parser.ChatParser.split_by_conversation_gap(messages, 48.0)) == 1
+        assert whatsapp_parser.ChatParser.split_by_conversation_gap([], 4.0) == []
+    finally:
+        os.unlink(file_path)
+
+def test_find_first_occurrence_of_sender():
+    """Test finding a sender's first message by normalized name."""
+    content = """[18/05/2023, 08:00:00] John: Welcome everyone
+[18/05/2023, 08:01:00] John: Introductions please
+[18/05/2023, 08:05:00] Alice: Hi, I'm Alice
+[18/05/2023, 08:06:00] Alice: Glad to be here
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        find = whatsapp_parser.ChatParser.find_first_occurrence_of_sender
+        assert find(messages, "Alice") == 2
+        assert find(messages, "  ALICE ") == 2
+        assert find(messages, "john") == 0
+        assert find(messages, "Bob") is None
+    finally:
+        os.unlink(file_path)
+
+def test_seconds_less_timestamps():
+    """Test that timestamps without seconds parse (as :00) alongside full ones."""
+    content = """[18/05/2023, 08:39] John: No seconds here
+[18/05/2023, 08:40:15] Alice: With seconds
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert len(messages) == 2
+        assert datetime.fromisoformat(messages[0].timestamp).time().isoformat() == "08:39:00"
+        assert datetime.fromisoformat(messages[1].timestamp).time().isoformat() == "08:40:15"
+    finally:
+        os.unlink(file_path)
+
+def test_export_redacted_jsonl():
+    """Test that the redacted export keeps metadata but no message text."""
+    content = """[18/05/2023, 08:00:00] John: Meet me at the old boathouse
+[18/05/2023, 08:01:00] Alice: Bring the documents
+[18/05/2023, 08:02:00] Alice: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    out_path = file_path + ".jsonl"
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        whatsapp_parser.export_redacted_jsonl(messages, out_path)
+        with open(out_path, encoding="utf-8") as f:
+            output = f.read()
+        for word in ["boathouse", "documents", "Media omitted"]:
+            assert word not in output
+        records = [json.loads(line) for line in output.splitlines()]
+        assert [record["sender"] for record in records] == ["John", "Alice", "Alice"]
+        assert [record["type"] for record in records] == ["text", "text", "media"]
+        assert records[0]["content_length"] == len("Meet me at the old boathouse")
+        assert "content" not in records[0]
+    finally:
+        os.unlink(file_path)
+        if os.path.exists(out_path):
+            os.unlink(out_path)
+
+def test_compute_average_response_length():
+    """Test that one-word repliers score far below paragraph repliers."""
+    paragraph = "That is a really good question and I have thought about it a lot, so here is my long answer with every detail."
+    lines = []
+    for i in range(5):
+        lines.append(f"[18/05/2023, 10:{i * 2:02d}:00] John: {paragraph}")
+        lines.append(f"[18/05/2023, 10:{i * 2 + 1:02d}:00] Alice: ok")
+    # A follow-up to her own message is not a reply
+    lines.append(f"[18/05/2023, 10:59:00] Alice: {paragraph}")
+    file_path = create_test_chat_file("\n".join(lines) + "\n")
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        lengths = whatsapp_parser.ChatParser.compute_average_response_length(messages)
+        assert lengths["Alice"] == pytest.approx(2.0)
+        assert lengths["John"] == pytest.approx(len(paragraph))
+        assert lengths["John"] > 20 * lengths["Alice"]
+    finally:
+        os.unlink(file_path)
+
+def test_grouped_with_previous():
+    """Test that follow-ups from the same sender are grouped with the message before."""
+    content = """[18/05/2023, 08:00:00] John: First
+[18/05/2023, 08:00:20] John: Second
+[18/05/2023, 08:01:05] John: Third
+[18/05/2023, 08:02:00] Alice: Reply
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert [m.grouped_with_previous for m in messages] == [False, True, True, False]
+        dicts = whatsapp_parser.parse_whatsapp_chat(file_path, "Alice")
+        assert [m["grouped_with_previous"] for m in dicts] == [False, True, True, False]
+    finally:
+        os.unlink(file_path)
+
+def test_parse_whatsapp_zip_with_media():
+    """Test matching media files in a "with media" export ZIP to their messages."""
+    import zipfile
+
+    content = """[18/05/2023, 08:00:00] John: <Media omitted>
+[18/05/2023, 08:01:00] Alice: Nice photo!
+[18/05/2023, 08:02:00] Alice: <attached: 00000003-PHOTO-2023-05-18-08-02-00.jpg>
+"""
+    fd, zip_path = tempfile.mkstemp(suffix=".zip")
+    os.close(fd)
+    try:
+        with zipfile.ZipFile(zip_path, "w") as archive:
+            archive.writestr("_chat.txt", content)
+            archive.writestr("IMG-20230518-WA0001.jpg", b"\xff\xd8")
+            archive.writestr("00000003-PHOTO-2023-05-18-08-02-00.jpg", b"\xff\xd8")
+        messages = whatsapp_parser.parse_whatsapp_zip_with_media(zip_path, "Alice")
+        assert messages[0]["media_filename"] == "IMG-20230518-WA0001.jpg"
+        assert "media_filename" not in messages[1]
+        assert messages[2]["media_filename"] == "00000003-PHOTO-2023-05-18-08-02-00.jpg"
+        assert messages[2]["type"] == "media"
+        assert messages[2]["is_own"] is True
+    finally:
+        os.unlink(zip_path)
+
+def test_verify_export_detects_truncation_and_bit_flips():
+    """Test that verify_export reports truncated and bit-flipped JSONL exports."""
+    file_path = create_test_chat_file("".join(
+        f"[18/05/2023, 10:{i:02d}:00] {'John' if i % 2 else 'Alice'}: message number {i}\n" for i in range(20)
+    ))
+    out_path = file_path + ".jsonl"
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        whatsapp_parser.export_jsonl(messages, out_path, manifest=True)
+        assert whatsapp_parser.verify_export(out_path) == {"ok": True, "message_count": 20}
+        with open(out_path, "rb") as f:
+            data = f.read()
+
+        # Truncated mid-line
+        with open(out_path, "wb") as f:
+            f.write(data[: len(data) // 2])
+        report = whatsapp_parser.verify_export(out_path)
+        assert report["ok"] is False
+        assert report["line"] == data[: len(data) // 2].count(b"\n") + 1
+
+        # Truncated at a line boundary: only the missing manifest gives it away
+        lines = data.splitlines(keepends=True)
+        with open(out_path, "wb") as f:
+            f.write(b"".join(lines[:10]))
+        assert whatsapp_parser.verify_export(out_path)["ok"] is False
+        assert whatsapp_parser.verify_export(out_path, require_manifest=False) == {"ok": True, "message_count": 10}
+
+        # One flipped bit inside a message's content
+        flipped = bytearray(data)
+        flipped[data.index(b"message number 7")] ^= 0x20
+        with open(out_path, "wb") as f:
+            f.write(bytes(flipped))
+        report = whatsapp_parser.verify_export(out_path)
+        assert report["ok"] is False and "chunk 1" in report["detail"]
+        assert report["line"] == 1
+    finally:
+        os.unlink(file_path)
+        if os.path.exists(out_path):
+            os.unlink(out_path)
+
+def test_verify_export_names_the_corrupt_chunk():
+    """Test that a bit flip deep in a large export is traced to its chunk."""
+    file_path = create_test_chat_file("".join(
+        f"[{1 + i // 1440:02d}/05/2023, {i // 60 % 24:02d}:{i % 60:02d}:00] John: message number {i}\n" for i in range(2500)
+    ))
+    out_path = file_path + ".jsonl"
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        whatsapp_parser.export_jsonl(messages, out_path, manifest=True)
+        with open(out_path, "rb") as f:
+            data = f.read()
+
+        flipped = bytearray(data)
+        flipped[data.index(b"message number 1499")] ^= 0x20
+        with open(out_path, "wb") as f:
+            f.write(bytes(flipped))
+        report = whatsapp_parser.verify_export(out_path)
+        assert report["ok"] is False
+        assert report["line"] == 1001
+        assert "chunk 2 (lines 1001-2000)" in report["detail"]
+    finally:
+        os.unlink(file_path)
+        if os.path.exists(out_path):
+            os.unlink(out_path)
+
+def test_parse_many():
+    """Test parsing two files in one call with a reused parser."""
+    first = create_test_chat_file("""[18/05/2023, 08:00:00] John: Hello
+[18/05/2023, 08:01:00] Alice: Hi
+""")
+    second = create_test_chat_file("""[19/05/2023, 09:00:00] Bob: Morning
+""")
+    try:
+        with whatsapp_parser.ChatParser() as parser:
+            chats = parser.parse_many([first, second], user_identity="Alice")
+        assert set(chats) == {first, second}
+        assert [m.content for m in chats[first]] == ["Hello", "Hi"]
+        assert [m.is_own for m in chats[first]] == [False, True]
+        assert [m.sender for m in chats[second]] == ["Bob"]
+        with pytest.raises(IOError):
+            whatsapp_parser.ChatParser().parse_many([first, "/nonexistent/chat.txt"])
+    finally:
+        os.unlink(first)
+        os.unlink(second)
+
+def test_detect_topic_shifts():
+    """Test that the move from dinner plans to travel plans is detected once."""
+    fixture = TEST_DATA_DIR / "topic_shift_chat.txt"
+    messages = whatsapp_parser.ChatParser().parse_chat(str(fixture))
+    shifts = whatsapp_parser.ChatParser.detect_topic_shifts(messages, 0.8)
+    assert len(shifts) == 1
+    assert "flight" in messages[shifts[0]].content
+    assert whatsapp_parser.ChatParser.detect_topic_shifts(messages, 1.0) == []
+
+def test_find_messages_by_pattern_limits():
+    """Test that oversized patterns are rejected and a 1MB line doesn't stall the search."""
+    long_line = "a" * (1 << 20) + "b"
+    file_path = create_test_chat_file(f"""[18/05/2023, 08:00:00] John: {long_line}
+[18/05/2023, 08:01:00] Alice: see you at 10:30
+[18/05/2023, 08:02:00] John: ok
+""")
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        find = whatsapp_parser.ChatParser.find_messages_by_pattern
+        assert find(messages, r"\d{1,2}:\d{2}") == ([1], [])
+
+        with pytest.raises(ValueError, match=r"Pattern '\\w\{1000\}' is too large"):
+            find(messages, r"\w{1000}")
+        with pytest.raises(ValueError, match="Invalid pattern"):
+            find(messages, "(unclosed")
+
+        started = time.monotonic()
+        matches, skipped = find(messages, "(a+)+$")
+        assert time.monotonic() - started < 5
+        # The long line ends in "b": it fails to match in time or is skipped
+        assert matches == [] and skipped in ([], [0])
+    finally:
+        os.unlink(file_path)
+
+def test_compute_daily_active_users():
+    """Test that one sender sending 50 messages in a day counts once."""
+    lines = [f"[18/05/2023, 08:{i:02d}:00] John: message {i}" for i in range(50)]
+    lines += ["[19/05/2023, 09:00:00] John: hi", "[19/05/2023, 09:05:00] Alice: hello"]
+    file_path = create_test_chat_file("\n".join(lines) + "\n")
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        dau = whatsapp_parser.ChatParser.compute_daily_active_users(messages)
+        assert dau == [("2023-05-18", 1), ("2023-05-19", 2)]
+    finally:
+        os.unlink(file_path)
+
+def test_balance():
+    """Test conversation balance with a 3:1 message ratio and a system event."""
+    content = """[18/05/2023, 08:00:00] John created group "Plans"
+[18/05/2023, 08:01:00] John: One
+[18/05/2023, 08:02:00] John: Two
+[18/05/2023, 08:03:00] Alice: Three
+[18/05/2023, 08:04:00] John: Four
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        shares = whatsapp_parser.ChatParser.balance(messages)
+        assert shares == {"John": pytest.approx(0.75), "Alice": pytest.approx(0.25)}
+        assert sum(shares.values()) == pytest.approx(1.0)
+    finally:
+        os.unlink(file_path)
+
+def test_render_pdf():
+    """Test PDF export of a small chat, including page size validation."""
+    messages = whatsapp_parser.ChatParser().parse_chat(str(TEST_DATA_DIR / "late_night_chat.txt"))
+    with tempfile.NamedTemporaryFile(suffix=".pdf", delete=False) as f:
+        output_path = f.name
+    try:
+        whatsapp_parser.render_pdf(messages, output_path, page_size="letter", font_size=12.0)
+        with open(output_path, "rb") as f:
+            pdf = f.read()
+        assert pdf.startswith(b"%PDF-")
+        assert b"startxref" in pdf[-64:]
+
+        with pytest.raises(ValueError):
+            whatsapp_parser.render_pdf(messages, output_path, page_size="a5")
+    finally:
+        os.unlink(output_path)
+
+def test_parse_reply_previews():
+    """Test that a reply preview with a timestamp populates reply_to_timestamp."""
+    content = """[18/05/2023, 08:39:07] John: Good morning
+[18/05/2023, 08:45:00] Alice: > [18/05/2023, 08:39:07] John: Good morning
+Morning to you too
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        assert parser.parse_chat(file_path)[1].reply_to is None
+
+        messages = parser.parse_chat(file_path, parse_replies=True)
+        assert messages[1].reply_to == "[18/05/2023, 08:39:07] John: Good morning"
+        assert messages[1].reply_to_timestamp == messages[0].timestamp
+        assert messages[1].content == "Morning to you too"
+        assert messages[0].reply_to_timestamp is None
+    finally:
+        os.unlink(file_path)
+
+def test_set_type_rules():
+    """Test custom message types from user rules, and rule validation."""
+    content = """[18/05/2023, 08:39:07] Bot: [BOT] deploy finished
+[18/05/2023, 08:40:00] Alice: <Media omitted>
+[18/05/2023, 08:41:00] Alice: hello
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        parser.set_type_rules([("prefix", "[BOT]", "bot"), ("exact", "<Media omitted>", "text")])
+        messages = parser.parse_chat(file_path)
+        assert [m.message_type for m in messages] == ["bot", "text", "text"]
+
+        with pytest.raises(ValueError, match="Unknown rule kind"):
+            parser.set_type_rules([("suffix", "x", "bot")])
+        with pytest.raises(ValueError, match="Invalid pattern"):
+            parser.set_type_rules([("regex", "(unclosed", "bot")])
+        assert parser.parse_chat(file_path)[0].message_type == "bot"
+    finally:
+        os.unlink(file_path)
+
+def test_parse_partial_file():
+    """Test that a truncated final line is returned instead of parsed."""
+    content = """[18/05/2023, 08:39:07] John: Good morning
+[18/05/2023, 08:40:15] Alice: Morning!
+[18/05/2023, 08:41:00] John: I was about to"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages, incomplete = parser.parse_partial_file(file_path)
+        assert [m.content for m in messages] == ["Good morning", "Morning!"]
+        assert incomplete == "[18/05/2023, 08:41:00] John: I was about to"
+
+        with open(file_path, "a") as f:
+            f.write(" say\n")
+        messages, incomplete = parser.parse_partial_file(file_path)
+        assert len(messages) == 3 and messages[2].content == "I was about to say"
+        assert incomplete is None
+    finally:
+        os.unlink(file_path)
+
+def test_parse_whatsapp_chat_lenient():
+    """Test that lenient mode skips one bad timestamp and keeps its neighbours."""
+    content = """[18/05/2023, 08:39:07] John: Before
+[31/02/2023, 08:40:00] Alice: Impossible date
+[18/05/2023, 08:41:00] John: After
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        with pytest.raises(ValueError):
+            whatsapp_parser.parse_whatsapp_chat(file_path, "John")
+
+        with pytest.warns(UserWarning, match="line 2: Message skipped"):
+            messages = whatsapp_parser.parse_whatsapp_chat(file_path, "John", lenient=True)
+        assert [m["content"] for m in messages] == ["Before", "After"]
+    finally:
+        os.unlink(file_path)
+
+def test_chat_store_ingest_query_and_stats():
+    """Test re-ingesting an extended export only adds new messages, also from threads."""
+    content = """[18/05/2023, 08:00:00] John: Good morning
+[18/05/2023, 08:00:00] Alice: ok
+[18/05/2023, 08:00:00] Alice: ok
+"""
+    with tempfile.TemporaryDirectory() as directory:
+        database = os.path.join(directory, "chats.db")
+        chat = os.path.join(directory, "chat.txt")
+        with open(chat, "w") as file:
+            file.write(content)
+        store = whatsapp_parser.ChatStore(database)
+        assert store.schema_version == 1
+        assert store.ingest(chat, "family") == 3
+
+        with open(chat, "a") as file:
+            file.write("[19/05/2023, 21:00:00] Alice: Good night\n")
+        assert store.ingest(chat, "family") == 1
+
+        results = []
+        threads = [
+            threading.Thread(target=lambda: results.append(whatsapp_parser.ChatStore(database).ingest(chat, "copy")))
+            for _ in range(4)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        assert sorted(results) == [0, 0, 0, 4]
+
+        messages = store.query("family", sender="Alice", text="good")
+        assert [m.content for m in messages] == ["Good night"]
+        assert len(store.query("family", end="2023-05-19T00:00:00+00:00")) == 3
+
+        stats = store.stats("copy")
+        assert stats["message_count"] == 4
+        assert stats["senders"] == [("Alice", 3), ("John", 1)]
+        assert stats["message_types"] == {"text": 4}
+        with pytest.raises(ValueError):
+            store.stats("work")
+
+def test_chat_store_ingest_with_parser_and_identity():
+    """Test ingesting with a configured parser and a user identity."""
+    content = """[18/05/95, 08:00:00] John: [BOT] reminder
+[31/02/95, 08:01:00] Alice: Impossible date
+[18/05/95, 08:02:00] Alice: ok
+"""
+    with tempfile.TemporaryDirectory() as directory:
+        chat = os.path.join(directory, "chat.txt")
+        with open(chat, "w") as file:
+            file.write(content)
+        store = whatsapp_parser.ChatStore(os.path.join(directory, "chats.db"))
+        with pytest.raises(ValueError):
+            store.ingest(chat, "strict")
+
+        parser = whatsapp_parser.ChatParser()
+        parser.set_lenient(True)
+        parser.set_year_pivot(1950)
+        parser.set_type_rules([("prefix", "[BOT]", "bot")])
+        with pytest.warns(UserWarning, match="line 2"):
+            assert store.ingest(chat, "family", parser=parser, user_identity="Alice") == 2
+
+        messages = store.query("family")
+        assert [m.is_own for m in messages] == [False, True]
+        assert [m.message_type for m in messages] == ["bot", "text"]
+        assert datetime.fromisoformat(messages[0].timestamp).year == 1995
+
+def test_media_caption():
+    """Test that a caption after <Media omitted> is split into "caption"."""
+    content = """[18/05/2023, 08:39:07] John: <Media omitted>
+Sunset at the beach
+[18/05/2023, 08:40:00] Alice: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.parse_whatsapp_chat(file_path, "John")
+        assert messages[0]["type"] == "media"
+        assert messages[0]["content"] == "<Media omitted>"
+        assert messages[0]["caption"] == "Sunset at the beach"
+        assert "caption" not in messages[1]
+
+        parsed = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert parsed[0].caption == "Sunset at the beach"
+        assert parsed[1].caption is None
+    finally:
+        os.unlink(file_path)
+
+def test_compute_burst_score():
+    """Test that a sender whose messages all come in rapid bursts scores 1.0."""
+    content = """[18/05/2023, 08:00:00] John: wait
+[18/05/2023, 08:00:10] John: so
+[18/05/2023, 08:00:20] John: guess what
+[18/05/2023, 08:05:00] Alice: what?
+[18/05/2023, 08:20:00] Alice: hello?
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        scores = whatsapp_parser.ChatParser.compute_burst_score(messages)
+        assert scores == {"John": 1.0, "Alice": 0.0}
+    finally:
+        os.unlink(file_path)
+
+def test_fuzzy_search():
+    """Test that a misspelled query finds the right word with its span."""
+    content = """[18/05/2023, 08:00:00] John: See you Tomorrow!
+[18/05/2023, 09:00:00] Alice: sounds good
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        matches = whatsapp_parser.fuzzy_search(messages, "tommorow")
+        assert len(matches) == 1
+        found = matches[0]
+        assert (found.index, found.token, found.distance) == (0, "Tomorrow", 2)
+        assert messages[0].content[found.start:found.end] == "Tomorrow"
+
+        assert whatsapp_parser.search_messages(messages, "tomorrow") == ([0], [])
+        exact, fuzzy = whatsapp_parser.search_messages(messages, "tomorow")
+        assert exact == [] and [m.index for m in fuzzy] == [0]
+    finally:
+        os.unlink(file_path)
+
+def test_normalize_sender_names():
+    """Test that formatted and plain variants of one phone number merge."""
+    content = """[18/05/2023, 08:00:00] +1 (555) 123-4567: hi
+[18/05/2023, 08:01:00] +15551234567: again
+[18/05/2023, 08:02:00] Alice: hello
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        mapping = whatsapp_parser.ChatParser.normalize_sender_names(messages)
+        assert mapping == {"+1 (555) 123-4567": "+15551234567", "+15551234567": "+15551234567"}
+        assert [m.sender for m in messages] == ["+15551234567", "+15551234567", "Alice"]
+    finally:
+        os.unlink(file_path)
+
+def test_set_year_pivot():
+    """Test that a pivot of 1950 maps the two-digit year 95 to 1995."""
+    file_path = create_test_chat_file("[18/05/95, 08:39:07] John: Dial-up again\n")
+    try:
+        parser = whatsapp_parser.ChatParser()
+        assert parser.parse_chat(file_path)[0].timestamp.startswith("2095-05-18")
+        parser.set_year_pivot(1950)
+        assert parser.parse_chat(file_path)[0].timestamp.startswith("1995-05-18")
+    finally:
+        os.unlink(file_path)
+
+def test_version_build_info_and_supported_formats():
+    """Test the module reports its build and the formats its parser compiles."""
+    info = whatsapp_parser.build_info()
+    assert info["version"] == whatsapp_parser.__version__
+    assert set(info) == {"version", "git_hash", "target", "pyo3_version"}
+    assert info["pyo3_version"].startswith("0.18")
+
+    formats = whatsapp_parser.supported_formats()
+    assert [f["id"] for f in formats] == ["whatsapp_ios", "copied_text"]
+    example = formats[0]["example"] + "\n"
+    file_path = create_test_chat_file(example)
+    out_path = file_path + ".jsonl"
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert len(messages) == 1
+        whatsapp_parser.export_jsonl(messages, out_path, manifest=True)
+        with open(out_path) as f:
+            manifest = json.loads(f.read().splitlines()[-1])["manifest"]
+        assert manifest["parser_version"] == whatsapp_parser.__version__
+
+        # An export from another build still verifies, with a warning
+        with open(out_path) as f:
+            lines = f.read().splitlines()
+        manifest["parser_version"] = "0.0.1"
+        with open(out_path, "w") as f:
+            f.write("\n".join(lines[:-1] + [json.dumps({"manifest": manifest})]) + "\n")
+        with pytest.warns(UserWarning, match="0.0.1"):
+            assert whatsapp_parser.verify_export(out_path)["ok"] is True
+    finally:
+        os.unlink(file_path)
+        if os.path.exists(out_path):
+            os.unlink(out_path)
+
+def test_export_events_ics():
+    """Test that the ICS export has a VEVENT for a group-creation event."""
+    content = """[18/05/2023, 08:00:00] John created group "Trip"
+[18/05/2023, 08:01:00] John: Welcome
+[20/05/2023, 09:00:00] Alice: Hi
+"""
+    file_path = create_test_chat_file(content)
+    with tempfile.NamedTemporaryFile(suffix=".ics", delete=False) as f:
+        ics_path = f.name
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages = parser.parse_events(file_path) + parser.parse_chat(file_path)
+        whatsapp_parser.export_events_ics(messages, ics_path, include_longest_silence=True)
+        with open(ics_path, newline="") as f:
+            ics = f.read()
+        assert ics.startswith("BEGIN:VCALENDAR\r\n")
+        assert ics.count("BEGIN:VEVENT") == 2
+        assert 'SUMMARY:John created group "Trip"\r\n' in ics
+        assert "SUMMARY:Longest silence: 2 days\r\n" in ics
+    finally:
+        os.unlink(file_path)
+        os.unlink(ics_path)
+
+def test_parse_split_export():
+    """Test stitching split export parts, and the warning for a missing part."""
+    parts = TEST_DATA_DIR / "split_export"
+    parser = whatsapp_parser.ChatParser()
+    complete = parser.parse_chat(str(parts / "full.txt"))
+
+    stitched = parser.parse_split_export([str(parts / name) for name in ("part3.txt", "part1.txt", "part2.txt")])
+    assert [(m.id, m.timestamp, m.sender, m.content) for m in stitched] == \
+        [(m.id, m.timestamp, m.sender, m.content) for m in complete]
+
+    with pytest.warns(UserWarning, match="is a part missing"):
+        stitched = parser.parse_split_export([str(parts / "part3.txt"), str(parts / "part1.txt")])
+    assert len(stitched) == len(complete) - 5
+
+def test_detect_recurring_senders():
+    """Test counting the chats each sender appears in across three exports."""
+    contents = [
+        "[18/05/2023, 08:00:00] John: hi\n[18/05/2023, 08:01:00] Alice: hey\n[18/05/2023, 08:02:00] John: again\n",
+        "[19/05/2023, 08:00:00] John: hello\n[19/05/2023, 08:01:00] Bob: yo\n",
+        "[20/05/2023, 08:00:00] Carol: morning\n[20/05/2023, 08:01:00] John: morning\n",
+    ]
+    file_paths = [create_test_chat_file(content) for content in contents]
+    try:
+        parser = whatsapp_parser.ChatParser()
+        chats = [parser.parse_chat(file_path) for file_path in file_paths]
+        counts = whatsapp_parser.detect_recurring_senders(chats)
+        assert counts == {"John": 3, "Alice": 1, "Bob": 1, "Carol": 1}
+    finally:
+        for file_path in file_paths:
+            os.unlink(file_path)
+
+def test_message_constructor():
+    """Test building synthetic messages from Python."""
+    message = whatsapp_parser.Message("John", "https://example.com", "2023-05-18T08:00:00+00:00")
+    assert message.sender == "John"
+    assert message.message_type == "link"
+    assert message.sentiment_score is None
+    assert whatsapp_parser.Message("John", "<Media omitted>", "2023-05-18T08:00:00Z").message_type == "media"
+
+    with pytest.raises(ValueError):
+        whatsapp_parser.Message("John", "hi", "yesterday")
+
+def test_emoji_rate():
+    """Test the mean emoji count per message for each sender."""
+    content = """[18/05/2023, 08:00:00] John: 😂😂
+[18/05/2023, 08:01:00] John: no emoji
+[18/05/2023, 08:02:00] Alice: 👍
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        rates = whatsapp_parser.ChatParser.emoji_rate(parser.parse_chat(file_path))
+        assert rates == {"John": 1.0, "Alice": 1.0}
+    finally:
+        os.unlink(file_path)
+
+def test_sentence_sentiment():
+    """Test per-sentence sentiment scores and sentence splitting."""
+    content = "I love this place. The service was terrible! 今天很好。"
+    assert isinstance(whatsapp_parser.score_sentiment(content), float)
+
+    breakdown = whatsapp_parser.score_sentiment(content, granularity="sentence")
+    assert [s.text for s in breakdown.sentences] == ["I love this place.", "The service was terrible!", "今天很好。"]
+    worst = breakdown.most_negative
+    assert content[worst.start:worst.end] == "The service was terrible!"
+    assert breakdown.min == worst.score < 0 < breakdown.max
+    assert whatsapp_parser.split_sentences("ok.See you") == [(0, 3, "ok."), (3, 10, "See you")]
+
+    with pytest.raises(ValueError):
+        whatsapp_parser.score_sentiment(content, granularity="word")
+
+def test_compute_emoji_sentiment():
+    """Test the mean sentiment of messages containing frequently used emoji."""
+    content = "".join(f"[18/05/2023, 08:0{i}:00] John: {'love it' if i < 3 else 'hate it'} 😂\n" for i in range(6))
+    content += "[18/05/2023, 09:00:00] Alice: so happy 🎉\n"
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages = parser.parse_chat(file_path)
+        whatsapp_parser.ChatParser.compute_sentiment_scores(messages)
+        assert whatsapp_parser.ChatParser.emoji_frequencies(messages) == {"😂": 6, "🎉": 1}
+
+        sentiment = whatsapp_parser.ChatParser.compute_emoji_sentiment(messages)
+        assert list(sentiment) == ["😂"]
+        expected = sum(m.sentiment_score for m in messages[:6]) / 6
+        assert abs(sentiment["😂"] - expected) < 1e-6
+    finally:
+        os.unlink(file_path)
+
+def test_gap_markers():
+    """Test gap annotations and "N days later" separators."""
+    content = """[18/05/2023, 08:00:00] John: a
+[18/05/2023, 08:30:00] Alice: b
+[18/05/2023, 11:30:00] John: c
+[20/05/2023, 11:30:00] Alice: d
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages = parser.parse_chat(file_path)
+        assert [m.gap_before_seconds for m in messages] == [None, 1800, 10800, 172800]
+        assert whatsapp_parser.parse_whatsapp_chat(file_path, "John")[1]["gap_before_seconds"] == 1800
+
+        markers = whatsapp_parser.ChatParser.gap_markers(messages)
+        assert markers == [(2, 10800, "3 hours later"), (3, 172800, "2 days later")]
+        assert whatsapp_parser.ChatParser.gap_markers(messages, min_gap_seconds=86400) == [(3, 172800, "2 days later")]
+
+        johns = [m for m in messages if m.sender == "John"]
+        whatsapp_parser.ChatParser.annotate_gaps(johns)
+        assert [m.gap_before_seconds for m in johns] == [None, 12600]
+    finally:
+        os.unlink(file_path)
+
+def test_detect_shared_phone_numbers():
+    """Test finding phone numbers in messages, skipping URLs."""
+    content = """[18/05/2023, 08:00:00] John: call +1 (555) 123-4567
+[18/05/2023, 08:01:00] Alice: or 020 7946 0958, not https://example.com/5551234567
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        found = whatsapp_parser.ChatParser.detect_shared_phone_numbers(parser.parse_chat(file_path))
+        assert [(number, sender) for number, sender, _ in found] == [("+1 (555) 123-4567", "John"), ("020 7946 0958", "Alice")]
+        assert found[0][2].startswith("2023-05-18T08:00:00")
+    finally:
+        os.unlink(file_path)
+
+def test_interaction_matrix():
+    """Test directed reply counts between senders."""
+    content = """[18/05/2023, 08:00:00] John: a
+[18/05/2023, 08:01:00] Alice: b
+[18/05/2023, 08:02:00] Bob: c
+[18/05/2023, 08:03:00] John: d
+[18/05/2023, 08:04:00] John: e
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        matrix = whatsapp_parser.ChatParser.interaction_matrix(parser.parse_chat(file_path))
+        assert matrix == {("Alice", "John"): 1, ("Bob", "Alice"): 1, ("John", "Bob"): 1}
+    finally:
+        os.unlink(file_path)
+
+def test_sender_correction_warning():
+    """Test that a sender run into the content is re-split and reported."""
+    content = """[18/05/2023, 08:00:00] Alice: hi
+[18/05/2023, 08:01:00] Alice: agenda later
+[18/05/2023, 08:02:00] Alice - Meeting - Today: agenda
+[18/05/2023, 08:03:00] Alice: done
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        with pytest.warns(UserWarning, match="Sender corrected"):
+            messages = whatsapp_parser.parse_whatsapp_chat(file_path, "Bob", lenient=True)
+        assert (messages[2]["sender"], messages[2]["content"]) == ("Alice", "Meeting - Today: agenda")
+    finally:
+        os.unlink(file_path)
+
+def test_longest_message():
+    """Test picking the longest text message."""
+    content = """[18/05/2023, 08:00:00] John: short
+[18/05/2023, 08:01:00] Alice: a bit longer
+[18/05/2023, 08:02:00] John: not longer!
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        longest = whatsapp_parser.ChatParser.longest_message(parser.parse_chat(file_path))
+        assert (longest.sender, longest.content) == ("Alice", "a bit longer")
+        assert whatsapp_parser.ChatParser.longest_message([]) is None
+    finally:
+        os.unlink(file_path)
+
+def test_compute_average_daily_messages():
+    """Test messages per active day, ignoring days without messages."""
+    content = """[01/05/2023, 08:00:00] John: a
+[01/05/2023, 08:01:00] John: b
+[31/05/2023, 08:00:00] John: c
+[31/05/2023, 08:01:00] John: d
+[31/05/2023, 08:02:00] Alice: e
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        averages = whatsapp_parser.ChatParser.compute_average_daily_messages(parser.parse_chat(file_path))
+        assert averages == {"John": 2.0, "Alice": 1.0}
+    finally:
+        os.unlink(file_path)
+
+def test_chronotype():
+    """Test chronotype labels, including the midnight wraparound."""
+    content = "".join(
+        f"[{day:02d}/05/2023, 23:30:00] Owl: late\n[{day + 1:02d}/05/2023, 00:30:00] Owl: later\n"
+        for day in range(1, 26)
+    ) + "[27/05/2023, 12:00:00] Rare: hi\n"
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages = parser.parse_chat(file_path)
+        owl, rare = whatsapp_parser.ChatParser.chronotype(messages)
+        assert (owl.sender, owl.label, owl.message_count) == ("Owl", "night owl", 50)
+        assert min(owl.mean_hour, 24 - owl.mean_hour) < 1e-6
+        assert owl.hourly[23] == owl.hourly[0] == 25
+        assert rare.label == "not enough data"
+
+        with pytest.raises(ValueError):
+            whatsapp_parser.ChatParser.chronotype(messages, timezone="Mars/Olympus")
+    finally:
+        os.unlink(file_path)
+
+def test_export_to_parquet():
+    """Test writing messages to a Parquet file."""
+    content = """[18/05/2023, 08:00:00] John: hello
+[18/05/2023, 08:01:00] Alice: hi
+"""
+    file_path = create_test_chat_file(content)
+    output_path = file_path + ".parquet"
+    try:
+        parser = whatsapp_parser.ChatParser()
+        whatsapp_parser.export_to_parquet(parser.parse_chat(file_path), output_path)
+        with open(output_path, "rb") as output:
+            data = output.read()
+        assert data[:4] == b"PAR1" and data[-4:] == b"PAR1"
+        try:
+            import pyarrow.parquet
+        except ImportError:
+            return
+        table = pyarrow.parquet.read_table(output_path)
+        assert table.num_rows == 2
+        assert str(table.schema.field("timestamp").type) == "timestamp[us, tz=UTC]"
+    finally:
+        os.unlink(file_path)
+        if os.path.exists(output_path):
+            os.unlink(output_path)
+
+def test_word_counts_exclude_urls():
+    """Test that links contribute no words when excluded."""
+    content = """[18/05/2023, 08:00:00] John: https://example.com/path
+[18/05/2023, 08:01:00] John: see you there
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        messages = parser.parse_chat(file_path)
+        assert whatsapp_parser.ChatParser.avg_message_length(messages, exclude_urls=True) == {"John": 1.5}
+        assert whatsapp_parser.ChatParser.avg_message_length(messages) == {"John": 3.5}
+        words = dict(whatsapp_parser.ChatParser.word_frequency(messages, exclude_urls=True))
+        assert words == {"see": 1, "you": 1, "there": 1}
+    finally:
+        os.unlink(file_path)
+
+def test_parse_chat_batched():
+    """Test streaming a chat to a callback in fixed-size batches."""
+    content = "".join(f"[18/05/2023, 08:{minute:02d}:00] John: message {minute}\n" for minute in range(7))
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        batches = []
+        delivered = parser.parse_chat_batched(file_path, 3, lambda batch, progress: batches.append((batch, progress)))
+        assert delivered == 7
+        assert [len(batch) for batch, _ in batches] == [3, 3, 1]
+        assert [progress["messages_delivered"] for _, progress in batches] == [3, 6, 7]
+        assert batches[-1][1]["bytes_read"] == batches[-1][1]["total_bytes"]
+        assert batches[1][0][0].content == "message 3"
+
+        assert parser.parse_chat_batched(file_path, 2, lambda batch, progress: False) == 2
+
+        def failing(batch, progress):
+            if progress["messages_delivered"] > 3:
+                raise RuntimeError("sink full")
+        try:
+            parser.parse_chat_batched(file_path, 3, failing)
+            assert False, "callback error should propagate"
+        except RuntimeError as error:
+            assert error.messages_delivered == 3
+    finally:
+        os.unlink(file_path)
+
+def test_icon_changed_event():
+    """Test group icon changes surface as icon_changed events."""
+    content = """[03/05/2023, 09:00:00] Bob changed this group's icon
+[03/05/2023, 09:05:00] Bob: New picture
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        events = whatsapp_parser.ChatParser().parse_events(file_path)
+        assert events[0].event == {"kind": "icon_changed", "actor": "Bob"}
+    finally:
+        os.unlink(file_path)
+
+def test_export_events_ics_milestones():
+    """Test the anniversary and busiest-day events in the ICS export."""
+    content = """[18/05/2023, 08:00:00] John: Welcome, all
+[18/05/2023, 08:01:00] Alice: Hi
+[20/05/2023, 09:00:00] Alice: Back again
+"""
+    file_path = create_test_chat_file(content)
+    with tempfile.NamedTemporaryFile(suffix=".ics", delete=False) as f:
+        ics_path = f.name
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        whatsapp_parser.export_events_ics(messages, ics_path, top_busiest_days=10, include_anniversary=True)
+        with open(ics_path, newline="") as f:
+            ics = f.read().replace("\r\n ", "")
+        assert ics.count("BEGIN:VEVENT") == 3
+        assert "RRULE:FREQ=YEARLY\r\nSUMMARY:Chat anniversary (since 2023)\r\n" in ics
+        assert "SUMMARY:Busiest day: 2 messages\r\nDESCRIPTION:2 messages from Alice\\, John\\nJohn: Welcome\\, all\r\n" in ics
+        assert "SUMMARY:#2 busiest day: 1 message\r\n" in ics
+    finally:
+        os.unlink(file_path)
+        os.unlink(ics_path)
+
+def test_detect_unanswered_questions():
+    """Test a trailing question with no reply counts as unanswered."""
+    content = """[18/05/2023, 08:00:00] John: hi
+[18/05/2023, 08:01:00] Alice: are you coming?
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        unanswered = whatsapp_parser.ChatParser.detect_unanswered_questions(messages, 2.0)
+        assert [message.content for message in unanswered] == ["are you coming?"]
+    finally:
+        os.unlink(file_path)
+
+def test_compute_longest_word():
+    """Test each sender's longest word."""
+    content = """[18/05/2023, 08:00:00] John: that is antidisestablishmentarianism!
+[18/05/2023, 08:01:00] Alice: ok sure
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert whatsapp_parser.ChatParser.compute_longest_word(messages) == {
+            "John": "antidisestablishmentarianism",
+            "Alice": "sure",
+        }
+    finally:
+        os.unlink(file_path)
+
+def test_by_sender():
+    """Test partitioning messages by sender."""
+    content = """[18/05/2023, 08:00:00] John: a
+[18/05/2023, 08:01:00] Alice: b
+[18/05/2023, 08:02:00] John: c
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        partitions = whatsapp_parser.ChatParser.by_sender(messages)
+        assert {sender: [m.content for m in group] for sender, group in partitions.items()} == {
+            "John": ["a", "c"],
+            "Alice": ["b"],
+        }
+    finally:
+        os.unlink(file_path)
+
+def test_merge_chats_across_unicode_compositions():
+    """Test iOS (decomposed) and Android (precomposed) exports merge as one sender."""
+    ios = create_test_chat_file("[18/05/2023, 08:00:00] Jose\u0301: hola\n[18/05/2023, 08:01:00] Alice: hi\n")
+    android = create_test_chat_file("[18/05/2023, 08:00:00] Jos\u00e9: hola\n[18/05/2023, 08:02:00] Jos\u00e9: bye\n")
+    try:
+        parser = whatsapp_parser.ChatParser()
+        merged = whatsapp_parser.merge_chats([parser.parse_chat(ios), parser.parse_chat(android)])
+        assert [m.content for m in merged] == ["hola", "hi", "bye"]
+        assert {m.sender for m in merged} == {"Jos\u00e9", "Alice"}
+    finally:
+        os.unlink(ios)
+        os.unlink(android)
+
+def test_block_events():
+    """Test block notices become events instead of messages."""
+    content = """[03/05/2023, 09:00:00] Bob: hi
+[03/05/2023, 09:05:00] You blocked this contact. Tap to unblock.
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        assert [m.content for m in parser.parse_chat(file_path)] == ["hi"]
+        events = parser.parse_events(file_path)
+        assert [event.event for event in events] == [{"kind": "contact_blocked"}]
+    finally:
+        os.unlink(file_path)
+
+def test_group_media_bursts():
+    """Test grouping a run of photos and collapsing it to one row."""
+    content = """[18/05/2023, 08:00:00] John: <Media omitted>
+[18/05/2023, 08:00:30] John: <Media omitted>
+[18/05/2023, 08:01:00] John: <Media omitted>
+day one
+[18/05/2023, 08:05:00] Alice: lovely
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        bursts = whatsapp_parser.ChatParser.group_media_bursts(messages)
+        assert [(b.sender, b.count, b.indices, b.caption) for b in bursts] == [("John", 3, [0, 1, 2], "day one")]
+        collapsed = whatsapp_parser.ChatParser.collapse_media_bursts(messages, bursts)
+        assert [m.content for m in collapsed] == ["3 media files", "lovely"]
+    finally:
+        os.unlink(file_path)
+
+def test_find_anniversary_messages():
+    """Test messages near each yearly anniversary of the first message."""
+    content = """[10/03/2021, 08:00:00] John: first!
+[12/03/2022, 08:00:00] Alice: a year already
+[01/07/2022, 08:00:00] John: summer
+[06/03/2023, 08:00:00] Alice: two years soon
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        found = whatsapp_parser.ChatParser.find_anniversary_messages(messages, 7)
+        assert [m.content for m in found] == ["a year already", "two years soon"]
+    finally:
+        os.unlink(file_path)
+
+def test_detect_inside_jokes():
+    """Test a repeated made-up phrase is reported as an inside joke."""
+    parser = whatsapp_parser.ChatParser()
+    messages = parser.parse_chat(str(TEST_DATA_DIR / "inside_jokes_chat.txt"))
+    jokes = whatsapp_parser.ChatParser.detect_inside_jokes(messages, min_occurrences=3, max_word_count=3)
+    assert jokes == [("wumbo goblin", 4)]
+
+def test_whitespace_normalization_on_crlf_export():
+    """Test a CRLF export parses without carriage returns, and trimming options."""
+    parser = whatsapp_parser.ChatParser()
+    path = str(TEST_DATA_DIR / "crlf_chat.txt")
+    assert all("\r" not in m.content for m in parser.parse_chat(path))
+    parser.set_whitespace_normalization("trim_lines")
+    assert parser.parse_chat(path)[0].content == "Shopping list:\nmilk\neggs"
+    try:
+        parser.set_whitespace_normalization("strip")
+        assert False, "unknown mode should raise"
+    except ValueError:
+        pass
+
+def test_pasted_headers_are_continuations():
+    """Test pasted timestamped lines stay in the message they were pasted into."""
+    parser = whatsapp_parser.ChatParser()
+    path = str(TEST_DATA_DIR / "pasted_log_chat.txt")
+    messages = parser.parse_chat(path)
+    assert [m.sender for m in messages] == ["Sam", "Lee", "Lee", "Sam"]
+    assert messages[0].content.endswith("FATAL: giving up after 3 attempts")
+    assert parser.detect_senders(path)["ERROR"] == 1
+    parser.set_known_senders(["Sam"])
+    assert len(parser.parse_chat(path)) == 2
+
+def test_questions_asked():
+    """Test questions are counted by a trailing question mark."""
+    content = """[18/05/2023, 08:00:00] John: are we still on for tonight??
+[18/05/2023, 08:01:00] Alice: why? it's been planned for weeks
+[18/05/2023, 08:02:00] John: just checking 😅
+[18/05/2023, 08:03:00] Alice: you bringing snacks? 🍿
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert whatsapp_parser.ChatParser.questions_asked(messages) == {"John": 1, "Alice": 1}
+    finally:
+        os.unlink(file_path)
+
+def test_cluster_similar():
+    """Test a forward copied around the chat is clustered, earliest first."""
+    forward = "the council says the bridge will be closed all weekend for repairs so plan ahead"
+    content = f"""[18/05/2023, 08:00:00] John: {forward}
+[18/05/2023, 08:05:00] Alice: ok
+[18/05/2023, 09:00:00] Alice: Forwarded: {forward.upper()}
+[18/05/2023, 09:30:00] Priya: I think we should book the restaurant for saturday night before it fills up
+[19/05/2023, 07:00:00] Tom: {forward}
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        clusters = whatsapp_parser.ChatParser.cluster_similar(messages, 0.7)
+        assert len(clusters) == 1
+        assert clusters[0].indices == [0, 2, 4]
+        assert clusters[0].canonical == 0
+        assert [sender for _, sender in clusters[0].timeline] == ["John", "Alice", "Tom"]
+    finally:
+        os.unlink(file_path)
+
+def test_compute_timing_irregularity():
+    """Test the coefficient of variation of each sender's message intervals."""
+    import math
+
+    content = """[18/05/2023, 08:00:00] John: a
+[18/05/2023, 08:10:00] John: b
+[18/05/2023, 08:20:00] John: c
+[18/05/2023, 08:21:00] Alice: hi
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        irregularity = whatsapp_parser.ChatParser.compute_timing_irregularity(messages)
+        assert irregularity["John"] == 0.0
+        assert math.isnan(irregularity["Alice"])
+    finally:
+        os.unlink(file_path)
+
+def test_parse_reactions():
+    """Test reaction notices become reaction messages and ReactionEvents."""
+    parser = whatsapp_parser.ChatParser()
+    messages = parser.parse_chat(str(TEST_DATA_DIR / "reactions_chat.txt"), skip_system_messages=False)
+    reactions = whatsapp_parser.ChatParser.parse_reactions(messages)
+    assert [(r.reactor, r.emoji, r.reacted_to_sender) for r in reactions] == [
+        ("Alice", "❤️", "You"),
+        ("You", "😂", "Alice"),
+        ("Bob", "👍", "Alice"),
+    ]
+    assert messages[1].message_type == "reaction"
+    assert messages[1].reaction == {"reactor": "Alice", "emoji": "❤️", "to_sender": "You"}
+
+def test_validate_chat_file():
+    """Test the sampling check of an upload before a full parse."""
+    content = """[31/02/2023, 08:00:00] John: bad date
+[18/05/2023, 08:01:00] Alice: hi
+[18/05/2023, 08:02:00] John: hello
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        report = whatsapp_parser.validate_chat_file(file_path)
+        assert report["acceptable"]
+        assert (report["container"], report["format"], report["encoding"]) == ("text", "whatsapp_ios", "utf-8")
+        assert report["estimated_message_count"] == 2
+        assert report["warnings"][0]["line_number"] == 1
+    finally:
+        os.unlink(file_path)
+
+def test_word_count():
+    """Test word counts are set for text messages and zero for media."""
+    content = """[18/05/2023, 08:00:00] John: see you at  the station
+[18/05/2023, 08:01:00] Alice: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        assert [message.word_count for message in messages] == [5, 0]
+    finally:
+        os.unlink(file_path)
+
+def test_person_profile():
+    """Test the per-person profile resolves aliases and serializes to JSON."""
+    import json
+
+    content = """[18/05/2023, 08:00:00] Vishnu: Morning Priya 😊 the trip photos are up
+[18/05/2023, 08:10:00] priya: Morning! The trip was amazing 😊
+[20/05/2023, 09:00:00] Priya: <Media omitted>
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        profile = whatsapp_parser.person_profile(messages, "Priya", "Vishnu")
+        assert sorted(profile["aliases"]) == ["Priya", "priya"]
+        assert (profile["messages_from_person"], profile["messages_from_user"]) == (2, 1)
+        assert profile["person_reply_seconds"] == 600.0
+        assert profile["top_shared_emoji"] == [["😊", 2]]
+        assert profile["first_message"]["timestamp"].startswith("2023-05-18T08:00:00")
+        json.dumps(profile)
+        with pytest.raises(ValueError):
+            whatsapp_parser.person_profile(messages, "Nobody", "Vishnu")
+    finally:
+        os.unlink(file_path)
+
+def test_rank_senders_by_engagement():
+    """Test the composite engagement ranking and its weights."""
+    content = """[18/05/2023, 08:00:00] Alice: how was the weekend away with everyone 😄
+[18/05/2023, 08:01:00] John: ok
+[18/05/2023, 08:02:00] Alice: we should plan the next one soon 🎉🎉
+[18/05/2023, 08:03:00] John: sure
+[18/05/2023, 08:04:00] John: fine
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        messages = whatsapp_parser.ChatParser().parse_chat(file_path)
+        ranked = whatsapp_parser.ChatParser.rank_senders_by_engagement(messages)
+        assert [sender for sender, _ in ranked] == ["Alice", "John"]
+        by_count = whatsapp_parser.ChatParser.rank_senders_by_engagement(
+            messages, message_count=1.0, message_length=0.0, response_rate=0.0, emoji_rate=0.0
+        )
+        assert by_count[0] == ("John", 1.0)
+    finally:
+        os.unlink(file_path)
+
+def test_detect_broadcast_messages():
+    """Test a message pasted into two chats is reported once."""
+    family = create_test_chat_file("[18/05/2023, 08:00:00] John: Dinner at mine on Saturday, bring a dish!\n")
+    friends = create_test_chat_file("[18/05/2023, 08:02:00] John: dinner at mine on saturday bring a dish\n")
+    try:
+        parser = whatsapp_parser.ChatParser()
+        chats = [parser.parse_chat(family), parser.parse_chat(friends)]
+        broadcasts = whatsapp_parser.detect_broadcast_messages(chats, "John")
+        assert len(broadcasts) == 1
+        assert broadcasts[0].content == "Dinner at mine on Saturday, bring a dish!"
+        assert broadcasts[0].chat_indices == [0, 1]
+        assert len(broadcasts[0].timestamps) == 2
+    finally:
+        os.unlink(family)
+        os.unlink(friends)
+
+def test_disappearing_messages_events():
+    """Test disappearing-messages notices become events with their state."""
+    content = """[03/05/2023, 09:00:00] Bob turned on disappearing messages. New messages will disappear from this chat 7 days after they're sent, except when kept.
+[03/05/2023, 09:05:00] Disappearing messages were turned off.
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        events = whatsapp_parser.ChatParser().parse_events(file_path)
+        assert [event.event for event in events] == [
+            {"kind": "disappearing_messages", "enabled": True, "actor": "Bob"},
+            {"kind": "disappearing_messages", "enabled": False, "actor": None},
+        ]
+    finally:
+        os.unlink(file_path)
+
+def test_interpolate_subminute():
+    """Test minute-precision runs are spread over the minute on request."""
+    content = """[18/05/2023, 08:00] John: are you coming
+[18/05/2023, 08:00] Alice: yes
+[18/05/2023, 08:02] Alice: leaving now
+"""
+    file_path = create_test_chat_file(content)
+    try:
+        parser = whatsapp_parser.ChatParser()
+        parser.set_interpolate_subminute(True)
+        messages = parser.parse_chat(file_path)
+        assert [m.timestamp_interpolated for m in messages] == [True, True, False]
+        assert messages[1].timestamp.startswith("2023-05-18T08:00:30")
+        assert messages[1].original_timestamp == "18/05/2023, 08:00"
+        assert messages[2].original_timestamp == "18/05/2023, 08:02"
+        times = whatsapp_parser.ChatParser.average_response_time(messages)
+        assert times["Alice"] == 30.0
+        assert whatsapp_parser.ChatParser.average_response_time(messages, use_interpolated=False)["Alice"] == 0.0
+
+        dicts = whatsapp_parser.parse_whatsapp_chat(file_path, "John", interpolate_subminute=True)
+        assert dicts[1]["timestamp_interpolated"] and dicts[1]["original_timestamp"] == "18/05/2023, 08:00"
+    finally:
+        os.unlink(file_path)
+
+def test_parse_sample():
+    """Test a preview parse returns exactly the first max_messages messages."""
+    parser = whatsapp_parser.ChatParser()
+    path = str(TEST_DATA_DIR / "topic_shift_chat.txt")
+    sample = parser.parse_sample(path, 5)
+    assert len(sample) == 5
+    assert [m.content for m in sample] == [m.content for m in parser.parse_chat(path)[:5]]